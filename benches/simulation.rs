@@ -0,0 +1,12 @@
+//! `cargo bench`: the timings of `rust-blaster --bench`, built with the bench profile and
+//! a fixed seed so runs compare. Prints the same table, there is nothing to filter.
+use rust_blaster::bench;
+use rust_blaster::launch_options::LaunchOptions;
+
+/// Same rocks and spawns on every run.
+const BENCH_SEED: u64 = 1;
+
+fn main() {
+    let options = LaunchOptions { seed: Some(BENCH_SEED), ..LaunchOptions::default() };
+    bench::run(&options);
+}
@@ -2,6 +2,9 @@ use ggez::graphics::Vector2;
 use ggez::nalgebra as na;
 use serde::{Deserialize, Serialize};
 
+use crate::tuning::Tuning;
+use crate::well::WellPull;
+
 /// *********************************************************************
 /// Now we define our Actor's.
 /// An Actor is anything in the game world.
@@ -10,15 +13,18 @@ use serde::{Deserialize, Serialize};
 /// real ECS, but for this it's enough to say that all our game objects
 /// contain pretty much the same data.
 /// **********************************************************************
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ActorType {
     Player,
     Rock,
     Shot,
+    Gem,
+    TimeSlow,
+    Well,
 }
 
 // Serialization for our non serializable types.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Vec2Serial {
     pub x: f32,
     pub y: f32,
@@ -41,13 +47,13 @@ impl Vec2Serial {
 }
 
 // Serialization for our non serializable types.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ActorSerialIntermediate {
     pub pos: Vec2Serial,
     pub vel: Vec2Serial,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Actor {
     pub tag: ActorType,
 
@@ -69,31 +75,115 @@ pub struct Actor {
 
     #[serde(skip, default)]
     pub kill: bool,
+
+    /// Index of the player that fired this shot, or in versus mode the player this rock is
+    /// assigned to.
+    pub owner: Option<usize>,
+
+    /// Bitmask of player indexes that already got a graze bonus from this rock.
+    #[serde(skip, default)]
+    pub grazed_by: u64,
+
+    /// Seconds since this actor was created. Only tracked on the server.
+    #[serde(skip, default)]
+    pub age: f32,
+
+    /// How far a rock past `GameSetup::rock_lifetime` has faded out, 1 when it's gone.
+    pub fade: f32,
+
+    /// Points for destroying this rock, carried over to its gem.
+    pub score_value: i32,
+
+    /// Look picked at spawn. The bits from `ROCK_SIZE_BITS` up select the sprite, the ones
+    /// below are kept for a rock size. Player actors carry their `Player::ship` here.
+    pub variant: u8,
+
+    /// How hard a well pulls.
+    pub pull: WellPull,
+
+    /// Shots a well has swallowed. Only tracked on the server.
+    #[serde(skip, default)]
+    pub absorbed: u32,
+
+    /// Client only. Rotation still owed to reach the server's facing, paid off over a few ticks.
+    #[serde(skip, default)]
+    facing_correction: f32,
+
+    /// Client only. Physics ticks since the last snapshot, to estimate the server's spin.
+    #[serde(skip, default)]
+    ticks_since_sync: u32,
+
+    /// Client only. Distance still to cover towards the server's position after a late snapshot.
+    #[serde(skip, default = "na::zero")]
+    pos_correction: Vector2,
+
+    /// Names this actor for as long as it lives, 0 until the server issues one, see
+    /// `MainState::issue_actor_ids`.
+    pub id: u32,
 }
 
-const PLAYER_BBOX: f32 = 12.0;
-const ROCK_BBOX: f32 = 12.0;
-const SHOT_BBOX: f32 = 6.0;
+/// Low bits of a rock's `variant` reserved for its size.
+pub const ROCK_SIZE_BITS: u8 = 2;
+/// Number of rock sprites, see `Assets::actor_image`.
+pub const ROCK_SPRITES: u8 = 3;
 
 const SHOT_ANG_VEL: f32 = 0.5;
-const MAX_PHYSICS_VEL: f32 = 950.0;
+const GEM_ANG_VEL: f32 = 0.03;
+const TIME_SLOW_ANG_VEL: f32 = -0.02;
+const WELL_ANG_VEL: f32 = 0.05;
+
+/// Fraction of the outstanding facing correction applied each tick.
+const FACING_CORRECTION_RATE: f32 = 0.2;
+/// Faster spin estimates mean the list slot now holds a different actor.
+const MAX_ESTIMATED_ANG_VEL: f32 = 0.05;
+/// Further jumps are another actor taking over the slot, those snap.
+const MAX_BLEND_DISTANCE: f32 = 300.0;
+
+/// Signed difference from `from` to `to` along the shorter way around, in (-PI, PI].
+pub fn angle_diff(from: f32, to: f32) -> f32 {
+    use std::f32::consts::PI;
+    let diff = (to - from) % (2.0 * PI);
+    if diff > PI {
+        diff - 2.0 * PI
+    } else if diff <= -PI {
+        diff + 2.0 * PI
+    } else {
+        diff
+    }
+}
 
 
 impl Actor {
     
-    pub fn post_deserialize_defaults(&mut self) {
+    /// Which of the sprites for this actor's kind to draw.
+    pub fn sprite(&self) -> u8 {
+        self.variant >> ROCK_SIZE_BITS
+    }
+
+    pub fn set_sprite(&mut self, sprite: u8) {
+        let size_mask = (1 << ROCK_SIZE_BITS) - 1;
+        self.variant = (sprite << ROCK_SIZE_BITS) | (self.variant & size_mask);
+    }
+
+    pub fn post_deserialize_defaults(&mut self, tuning: &Tuning) {
+        self.bbox_size = tuning.bbox(&self.tag);
         match self.tag {
-            ActorType::Player => {
-                self.bbox_size = PLAYER_BBOX;
-            }
+            ActorType::Player => {}
             ActorType::Rock => {
-                self.bbox_size = ROCK_BBOX;
                 self.ang_vel = 0.01;
             }
             ActorType::Shot => {
-                self.bbox_size = SHOT_BBOX;
                 self.ang_vel = SHOT_ANG_VEL;
             }
+            ActorType::Gem => {
+                self.ang_vel = GEM_ANG_VEL;
+            }
+            ActorType::TimeSlow => {
+                self.ang_vel = TIME_SLOW_ANG_VEL;
+            }
+            ActorType::Well => {
+                self.ang_vel = WELL_ANG_VEL;
+            }
         }
     }
 
@@ -102,87 +192,319 @@ impl Actor {
         self.serial_interm.vel = Vec2Serial::from_floats(self.velocity.x, self.velocity.y);
     }
 
-    pub fn post_deserialize(&mut self) {
+    pub fn post_deserialize(&mut self, tuning: &Tuning) {
         self.pos = Vector2::new(self.serial_interm.pos.x, self.serial_interm.pos.y);
         self.velocity = Vector2::new(self.serial_interm.vel.x, self.serial_interm.vel.y);  
-        self.post_deserialize_defaults();
+        self.post_deserialize_defaults(tuning);
+    }
+
+    /// Takes over everything the server replicates from a freshly received actor.
+    /// Client-only fields are left alone so they survive across snapshots.
+    ///
+    /// Spin isn't replicated, so it is estimated from how far the server's facing moved
+    /// since the previous snapshot. Our own facing then eases towards the server's instead
+    /// of jumping back, which is what over-rotating between snapshots used to look like.
+    ///
+    /// With `blend` the position moves to the server's one over `blend_position` calls instead of right away.
+    pub fn sync_from(&mut self, remote: Actor, blend: bool, tuning: &Tuning) {
+        let same_kind = self.tag == remote.tag;
+        let old_pos = self.pos;
+        let previous_remote = self.facing + self.facing_correction - self.ang_vel * self.ticks_since_sync as f32;
+        let estimate = if self.ticks_since_sync > 0 {
+            angle_diff(previous_remote, remote.facing) / self.ticks_since_sync as f32
+        } else {
+            self.ang_vel
+        };
+
+        self.tag = remote.tag;
+        self.serial_interm = remote.serial_interm;
+        self.owner = remote.owner;
+        self.fade = remote.fade;
+        self.score_value = remote.score_value;
+        self.variant = remote.variant;
+        self.pull = remote.pull;
+        self.id = remote.id;
+        self.kill = false;
+        let ang_vel = self.ang_vel;
+        self.post_deserialize(tuning);
+
+        if same_kind && estimate.abs() <= MAX_ESTIMATED_ANG_VEL {
+            self.ang_vel = if self.ticks_since_sync > 0 { estimate } else { ang_vel };
+            self.facing_correction = angle_diff(self.facing, remote.facing);
+        } else {
+            self.facing = remote.facing;
+            self.facing_correction = 0.0;
+        }
+        self.ticks_since_sync = 0;
+
+        self.pos_correction = na::zero();
+        if blend && same_kind && (self.pos - old_pos).norm() < MAX_BLEND_DISTANCE {
+            self.pos_correction = self.pos - old_pos;
+            self.pos = old_pos;
+        }
     }
 
-    pub fn create_player_actor() -> Actor {
+    /// Moves part of the way towards the server's position, most of it within `blend_secs`.
+    pub fn blend_position(&mut self, delta: f32, blend_secs: f32) {
+        if self.pos_correction == na::zero::<Vector2>() {
+            return;
+        }
+        let fraction = if blend_secs > 0.0 { 1.0 - (-3.0 * delta / blend_secs).exp() } else { 1.0 };
+        let step = self.pos_correction * fraction;
+        self.pos += step;
+        self.pos_correction -= step;
+        if self.pos_correction.norm_squared() < 0.01 {
+            self.pos += self.pos_correction;
+            self.pos_correction = na::zero();
+        }
+    }
+
+    pub fn create_player_actor(tuning: &Tuning) -> Actor {
         Actor {
             tag: ActorType::Player,
             pos: na::zero(),
             facing: 0.0,
             velocity: na::zero(),
             ang_vel: 0.0,
-            bbox_size: PLAYER_BBOX,
+            bbox_size: tuning.player_bbox,
             kill: false,
+            owner: None,
+            grazed_by: 0,
+            age: 0.0,
+            fade: 0.0,
+            score_value: 1,
+            variant: 0,
+            pull: WellPull::default(),
+            absorbed: 0,
+            facing_correction: 0.0,
+            ticks_since_sync: 0,
+            pos_correction: na::zero(),
+            id: 0,
             serial_interm: ActorSerialIntermediate::default(),
         }
     }
 
-    pub fn create_rock() -> Actor {
+    pub fn create_rock(tuning: &Tuning) -> Actor {
         Actor {
             tag: ActorType::Rock,
             pos: na::zero(),
             facing: 0.0,
             velocity: na::zero(),
             ang_vel: rand::random::<f32>() * 0.02,
-            bbox_size: ROCK_BBOX,
+            bbox_size: tuning.rock_bbox,
             kill: false,
+            owner: None,
+            grazed_by: 0,
+            age: 0.0,
+            fade: 0.0,
+            score_value: 1,
+            variant: (rand::random::<u8>() % ROCK_SPRITES) << ROCK_SIZE_BITS,
+            pull: WellPull::default(),
+            absorbed: 0,
+            facing_correction: 0.0,
+            ticks_since_sync: 0,
+            pos_correction: na::zero(),
+            id: 0,
             serial_interm: ActorSerialIntermediate::default(),
         }
     }
 
-    pub fn create_shot() -> Actor {
+    pub fn create_shot(tuning: &Tuning) -> Actor {
         Actor {
             tag: ActorType::Shot,
             pos: na::zero(),
             facing: 0.0,
             velocity: na::zero(),
             ang_vel: SHOT_ANG_VEL,
-            bbox_size: SHOT_BBOX,
+            bbox_size: tuning.shot_bbox,
+            kill: false,
+            owner: None,
+            grazed_by: 0,
+            age: 0.0,
+            fade: 0.0,
+            score_value: 1,
+            variant: 0,
+            pull: WellPull::default(),
+            absorbed: 0,
+            facing_correction: 0.0,
+            ticks_since_sync: 0,
+            pos_correction: na::zero(),
+            id: 0,
+            serial_interm: ActorSerialIntermediate::default(),
+        }
+    }
+
+    pub fn create_gem(tuning: &Tuning) -> Actor {
+        Actor {
+            tag: ActorType::Gem,
+            pos: na::zero(),
+            facing: 0.0,
+            velocity: na::zero(),
+            ang_vel: GEM_ANG_VEL,
+            bbox_size: tuning.gem_bbox,
+            kill: false,
+            owner: None,
+            grazed_by: 0,
+            age: 0.0,
+            fade: 0.0,
+            score_value: 1,
+            variant: 0,
+            pull: WellPull::default(),
+            absorbed: 0,
+            facing_correction: 0.0,
+            ticks_since_sync: 0,
+            pos_correction: na::zero(),
+            id: 0,
+            serial_interm: ActorSerialIntermediate::default(),
+        }
+    }
+
+    pub fn create_time_slow(tuning: &Tuning) -> Actor {
+        Actor {
+            tag: ActorType::TimeSlow,
+            pos: na::zero(),
+            facing: 0.0,
+            velocity: na::zero(),
+            ang_vel: TIME_SLOW_ANG_VEL,
+            bbox_size: tuning.time_slow_bbox,
+            kill: false,
+            owner: None,
+            grazed_by: 0,
+            age: 0.0,
+            fade: 0.0,
+            score_value: 1,
+            variant: 0,
+            pull: WellPull::default(),
+            absorbed: 0,
+            facing_correction: 0.0,
+            ticks_since_sync: 0,
+            pos_correction: na::zero(),
+            id: 0,
+            serial_interm: ActorSerialIntermediate::default(),
+        }
+    }
+
+    pub fn create_well(pull: WellPull, tuning: &Tuning) -> Actor {
+        Actor {
+            tag: ActorType::Well,
+            pos: na::zero(),
+            facing: 0.0,
+            velocity: na::zero(),
+            ang_vel: WELL_ANG_VEL,
+            bbox_size: tuning.well_bbox,
             kill: false,
+            owner: None,
+            grazed_by: 0,
+            age: 0.0,
+            fade: 0.0,
+            score_value: 1,
+            variant: 0,
+            pull,
+            absorbed: 0,
+            facing_correction: 0.0,
+            ticks_since_sync: 0,
+            pos_correction: na::zero(),
+            id: 0,
             serial_interm: ActorSerialIntermediate::default(),
         }
     }
 
-    pub fn tick_physics(&mut self, delta: f32) {
+    /// Slows down to `max` if faster.
+    pub fn clamp_velocity(&mut self, max: f32) {
         // Clamp the velocity to the max efficiently
         let norm_sq = self.velocity.norm_squared();
-        if norm_sq > MAX_PHYSICS_VEL.powi(2) {
-            self.velocity = self.velocity / norm_sq.sqrt() * MAX_PHYSICS_VEL;
+        if norm_sq > max.powi(2) {
+            self.velocity = self.velocity / norm_sq.sqrt() * max;
         }
+    }
+
+    pub fn tick_physics(&mut self, delta: f32, tuning: &Tuning) {
+        self.clamp_velocity(tuning.max_physics_vel);
         let dv = self.velocity * (delta);
         self.pos += dv;
-        self.facing += self.ang_vel;
+
+        let correction = self.facing_correction * FACING_CORRECTION_RATE;
+        self.facing_correction -= correction;
+        self.facing += self.ang_vel + correction;
+        self.ticks_since_sync = self.ticks_since_sync.saturating_add(1);
+        self.age += delta;
     }
 
     /// Takes an actor and wraps its position to the bounds of the
     /// screen, so if it goes off the left side of the screen it
-    /// will re-enter on the right side and so on.
+    /// will re-enter on the right side and so on. It wraps once all of
+    /// `bbox_size` is past the edge, and comes back in just as far outside
+    /// the other one, so the sprite doesn't clip or pop.
     pub fn wrap_position(&mut self, sx: f32, sy: f32) {
-        let screen_x_bounds = sx / 2.0;
-        let screen_y_bounds = sy / 2.0;
+        let (period_x, period_y) = self.wrap_period(sx, sy);
+        let screen_x_bounds = period_x / 2.0;
+        let screen_y_bounds = period_y / 2.0;
         if self.pos.x > screen_x_bounds {
-            self.pos.x -= sx;
+            self.pos.x -= period_x;
         } else if self.pos.x < -screen_x_bounds {
-            self.pos.x += sx;
+            self.pos.x += period_x;
         };
         if self.pos.y > screen_y_bounds {
-            self.pos.y -= sy;
+            self.pos.y -= period_y;
         } else if self.pos.y < -screen_y_bounds {
-            self.pos.y += sy;
+            self.pos.y += period_y;
         }
     }
 
+    /// How far `wrap_position` moves the actor across and up: the screen plus its whole
+    /// hitbox, since it goes out past one edge and comes in from as far past the other.
+    /// Distances to it are measured around this, see `toroidal_delta`.
+    pub fn wrap_period(&self, sx: f32, sy: f32) -> (f32, f32) {
+        let radius = self.bbox_size.max(0.0);
+        (sx + 2.0 * radius, sy + 2.0 * radius)
+    }
+
+    /// Height to spawn at so the actor sits just above the top edge, hidden until it moves in.
+    pub fn entry_height(&self, sy: f32) -> f32 {
+        sy / 2.0 + self.bbox_size.max(0.0)
+    }
+
+    /// Whether the actor has completely left the screen.
     pub fn is_out_of_bounds(&self, sx: f32, sy: f32) -> bool {
-        let screen_x_bounds = sx / 2.0;
-        let screen_y_bounds = sy / 2.0;
+        self.is_out_of_bounds_by(sx, sy, 0.0)
+    }
+
+    /// Whether all of the actor, `bbox_size` included, is more than `margin` past an edge.
+    pub fn is_out_of_bounds_by(&self, sx: f32, sy: f32, margin: f32) -> bool {
+        let radius = self.bbox_size.max(0.0);
+        let screen_x_bounds = sx / 2.0 + margin + radius;
+        let screen_y_bounds = sy / 2.0 + margin + radius;
 
         self.pos.x > screen_x_bounds 
             || self.pos.x < -screen_x_bounds 
             || self.pos.y > screen_y_bounds
             || self.pos.y < -screen_y_bounds
     }
-}
\ No newline at end of file
+}
+
+/// Shortest vector from `b` to `a` when one of them wraps around the edges, so points
+/// hugging opposite edges are considered close. `width` and `height` are the wrapping
+/// actor's `Actor::wrap_period`, not the world size: it's a bit more than a world away
+/// from itself across an edge, and the distance has to match what the next tick shows.
+pub fn toroidal_delta(a: &Vector2, b: &Vector2, width: f32, height: f32) -> Vector2 {
+    fn wrap_axis(d: f32, size: f32) -> f32 {
+        if size <= 0.0 {
+            return d;
+        }
+        let d = d % size;
+        if d > size / 2.0 {
+            d - size
+        } else if d < -size / 2.0 {
+            d + size
+        } else {
+            d
+        }
+    }
+
+    let d = *a - *b;
+    Vector2::new(wrap_axis(d.x, width), wrap_axis(d.y, height))
+}
+
+pub fn toroidal_distance(a: &Vector2, b: &Vector2, width: f32, height: f32) -> f32 {
+    toroidal_delta(a, b, width, height).norm()
+}
@@ -0,0 +1,89 @@
+//! Aim assist: turns a volley slightly toward the rock it is most nearly aimed at.
+use ggez::graphics::Vector2;
+
+use crate::actor::Actor;
+
+/// Strongest aim assist a player can ask for. Servers may allow less, see `GameSetup::max_aim_assist`.
+pub const MAX_AIM_ASSIST: f32 = 0.3;
+/// Half width of the cone, in radians around the shot's path, rocks are looked for in.
+pub const AIM_ASSIST_CONE: f32 = 0.5;
+/// Turn in radians at strength 1.0. Scaled by the strength, so 0.3 turns at most about 8.6 degrees.
+pub const MAX_CORRECTION_ANGLE: f32 = 0.5;
+
+/// Strength a player gets: what they asked for, within both the server's and the game's limit.
+pub fn granted_strength(requested: f32, server_max: f32) -> f32 {
+    requested.min(server_max).min(MAX_AIM_ASSIST).max(0.0)
+}
+
+/// Angle in radians to turn a shot fired from `pos` with `velocity` by. The target is the
+/// rock in the cone whose interception needs the smallest turn, and the turn is limited
+/// to `strength * MAX_CORRECTION_ANGLE`. Rocks the shot can't reach within `lifetime`
+/// seconds don't count. 0 when nothing qualifies.
+pub fn correction(pos: Vector2, velocity: Vector2, rocks: &[Actor], strength: f32, lifetime: f32) -> f32 {
+    let strength = strength.min(MAX_AIM_ASSIST).max(0.0);
+    let speed = velocity.norm();
+    if strength <= 0.0 || speed <= 0.0 {
+        return 0.0;
+    }
+
+    let best = rocks.iter()
+        .filter(|rock| !rock.kill)
+        .filter_map(|rock| intercept(pos, speed, rock, lifetime))
+        .map(|aim| signed_angle(velocity, aim))
+        .filter(|angle| angle.abs() <= AIM_ASSIST_CONE)
+        .min_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap_or(std::cmp::Ordering::Equal));
+
+    match best {
+        Some(angle) => {
+            let limit = strength * MAX_CORRECTION_ANGLE;
+            angle.max(-limit).min(limit)
+        }
+        None => 0.0,
+    }
+}
+
+/// Counter-clockwise in world coordinates, where y points up.
+pub fn rotate(v: Vector2, angle: f32) -> Vector2 {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Direction a shot at `speed` from `pos` must take to meet `rock`, if it gets there within `lifetime`.
+fn intercept(pos: Vector2, speed: f32, rock: &Actor, lifetime: f32) -> Option<Vector2> {
+    // |offset + velocity * t| = speed * t, solved for the earliest t > 0.
+    let offset = rock.pos - pos;
+    let a = rock.velocity.dot(&rock.velocity) - speed * speed;
+    let b = 2.0 * offset.dot(&rock.velocity);
+    let c = offset.dot(&offset);
+
+    let time = if a.abs() < std::f32::EPSILON {
+        if b >= 0.0 {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (t1, t2) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+        match (t1 > 0.0, t2 > 0.0) {
+            (true, true) => t1.min(t2),
+            (true, false) => t1,
+            (false, true) => t2,
+            (false, false) => return None,
+        }
+    };
+
+    if time > lifetime {
+        return None;
+    }
+    Some(offset + rock.velocity * time)
+}
+
+/// Turn from `from` to `to`, between -pi and pi.
+fn signed_angle(from: Vector2, to: Vector2) -> f32 {
+    let cross = from.x * to.y - from.y * to.x;
+    cross.atan2(from.dot(&to))
+}
@@ -0,0 +1,107 @@
+use crate::actor::{Actor, ROCK_SPRITES};
+use crate::game_structs::{MainState, NetRole};
+use crate::launch_options::LaunchOptions;
+use crate::codec::BincodeCodec;
+use crate::net_structs::{NetFromServer, VersionedSnapshot, SNAPSHOT_VERSION};
+use crate::rng::SimRng;
+
+use ggez::graphics::Vector2;
+
+use std::time::{Duration, Instant};
+
+const BENCH_SCREEN: u32 = 1080;
+const BENCH_ROCKS: usize = 1000;
+const BENCH_SHOTS: usize = 300;
+const BENCH_PLAYERS: usize = 4;
+const BENCH_ITERATIONS: u32 = 200;
+
+/// Times the simulation hot paths on headless states and prints the results.
+/// Run with `rust-blaster --bench` or `cargo bench`, no window or assets are needed.
+pub fn run(options: &LaunchOptions) {
+    println!("Rust-Blaster benchmarks ({} iterations each)", BENCH_ITERATIONS);
+
+    let mut heavy = heavy_state(options);
+
+    report("handle_collisions 1k rocks / 300 shots / 4 players", BENCH_ITERATIONS, || {
+        let mut state = heavy_state(options);
+        let timer = Instant::now();
+        state.handle_collisions();
+        timer.elapsed()
+    });
+
+    report("spawn_rocks over a 60s ramp", 1, || {
+        let mut state = MainState::headless(options, BENCH_SCREEN, BENCH_SCREEN);
+        let step = 1.0 / 144.0;
+        let timer = Instant::now();
+        while state.curr_time < 60.0 {
+            state.curr_time += step;
+            state.spawn_rocks(step);
+        }
+        let elapsed = timer.elapsed();
+        println!("    spawned {} rocks", state.rocks.len());
+        elapsed
+    });
+
+    report("NetFromServer::publish (pre_serialize + bincode)", BENCH_ITERATIONS, || {
+        let timer = Instant::now();
+        NetFromServer::publish(&mut heavy);
+        timer.elapsed()
+    });
+    let bin = heavy.snapshot.bytes(SNAPSHOT_VERSION);
+    println!("    snapshot size: {} bytes", bin.len());
+
+    let client_options = LaunchOptions { role: NetRole::Client, ..options.clone() };
+    let mut client = MainState::headless(&client_options, BENCH_SCREEN, BENCH_SCREEN);
+    client.local_player_index = Some(0);
+    report("bincode deserialize + update_main_state", BENCH_ITERATIONS, || {
+        let timer = Instant::now();
+        let snapshot = VersionedSnapshot::decode(SNAPSHOT_VERSION, &mut BincodeCodec, &mut &bin[..]).expect("Failed to deserialize.");
+        snapshot.update_main_state(&mut client);
+        timer.elapsed()
+    });
+}
+
+/// A busy server: rocks and shots spread over the whole world around a few players.
+/// Everything is drawn from the state's rng, so the same seed lays out the same world.
+fn heavy_state(options: &LaunchOptions) -> MainState {
+    let mut state = MainState::headless(options, BENCH_SCREEN, BENCH_SCREEN);
+    // Nobody should run out of lives and restart the game mid measurement.
+    state.game_setup.starting_lives = 1000;
+    while state.players.len() < BENCH_PLAYERS {
+        state.add_player();
+    }
+
+    let (w, h) = (state.world_width, state.world_height);
+    let random_pos = |rng: &mut SimRng| Vector2::new((rng.next_f32() - 0.5) * w, (rng.next_f32() - 0.5) * h);
+
+    for (i, player) in state.players.iter_mut().enumerate() {
+        player.lives = 1000;
+        player.actor.pos = Vector2::new(i as f32 * 100.0 - 150.0, -h / 3.0);
+    }
+    for _ in 0..BENCH_ROCKS {
+        let mut rock = Actor::create_rock(&state.tuning);
+        rock.pos = random_pos(&mut state.rng);
+        rock.ang_vel = state.rng.next_f32() * 0.02;
+        rock.set_sprite((state.rng.next_u64() % ROCK_SPRITES as u64) as u8);
+        state.rocks.push(rock);
+    }
+    for i in 0..BENCH_SHOTS {
+        let mut shot = Actor::create_shot(&state.tuning);
+        shot.pos = random_pos(&mut state.rng);
+        shot.owner = Some(i % BENCH_PLAYERS);
+        state.shots.push(shot);
+    }
+    state
+}
+
+fn report(name: &str, iterations: u32, mut f: impl FnMut() -> Duration) {
+    let mut total = Duration::from_secs(0);
+    let mut worst = Duration::from_secs(0);
+    for _ in 0..iterations {
+        let elapsed = f();
+        total += elapsed;
+        worst = worst.max(elapsed);
+    }
+    println!("{:<55} avg {:>9.1}us  worst {:>9.1}us",
+        name, total.as_micros() as f64 / iterations as f64, worst.as_micros() as f64);
+}
@@ -0,0 +1,187 @@
+//! Load-testing bot: connects windowless clients that fly around and shoot, through the
+//! same networking code as the game.
+//!
+//! `cargo run --release --bin bot -- --count 20 --addr localhost:9942`
+//!
+//! The port in `--addr` is the snapshot port and `--input-port` sets the other one, both
+//! default to net_setup.json. Ctrl+C, or the end of `--seconds`, disconnects every bot
+//! and prints what they received.
+use rust_blaster::game_structs::{InputState, NetRole, StatePtr};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::networking;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const BOT_SCREEN: u32 = 1080;
+const TICKS_PER_SECOND: u32 = 60;
+/// Seconds a bot keeps its course before picking a new one.
+const MIN_COURSE_TIME: f32 = 0.3;
+const MAX_COURSE_TIME: f32 = 1.5;
+const THRUST_CHANCE: f32 = 0.6;
+const FIRE_CHANCE: f32 = 0.4;
+/// Time the network threads get to close their streams before the process exits.
+const DISCONNECT_GRACE: Duration = Duration::from_millis(1500);
+
+struct BotArgs {
+    count: usize,
+    address: String,
+    snapshot_port: Option<u16>,
+    input_port: Option<u16>,
+    /// Run time, until Ctrl+C when missing.
+    seconds: Option<u64>,
+}
+
+impl BotArgs {
+    fn parse(args: &[String]) -> BotArgs {
+        let mut bot_args = BotArgs {
+            count: 1,
+            address: String::from("localhost"),
+            snapshot_port: None,
+            input_port: None,
+            seconds: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--count" => match args.next().map(|s| s.parse::<usize>()) {
+                    Some(Ok(count)) => bot_args.count = count,
+                    _ => println!("--count needs a number, starting one bot"),
+                },
+                "--addr" => match args.next() {
+                    Some(addr) => bot_args.set_address(addr),
+                    None => println!("--addr needs host or host:port, using localhost"),
+                },
+                "--input-port" => match args.next().map(|s| s.parse::<u16>()) {
+                    Some(Ok(port)) => bot_args.input_port = Some(port),
+                    _ => println!("--input-port needs a port, using net_setup.json's"),
+                },
+                "--seconds" => match args.next().map(|s| s.parse::<u64>()) {
+                    Some(Ok(seconds)) => bot_args.seconds = Some(seconds),
+                    _ => println!("--seconds needs a number, running until Ctrl+C"),
+                },
+                _ => println!("Ignoring unknown option: {}", arg),
+            }
+        }
+        bot_args
+    }
+
+    fn set_address(&mut self, addr: &str) {
+        let port = addr.rfind(':').map(|colon| (&addr[..colon], addr[colon + 1..].parse::<u16>()));
+        match port {
+            Some((host, Ok(port))) => {
+                self.address = host.to_string();
+                self.snapshot_port = Some(port);
+            }
+            Some((host, Err(_))) => {
+                println!("Ignoring the port in {}, using net_setup.json's", addr);
+                self.address = host.to_string();
+            }
+            None => self.address = addr.to_string(),
+        }
+    }
+}
+
+struct Bot {
+    ptr: StatePtr,
+    course_left: f32,
+}
+
+impl Bot {
+    /// Steers like a player would: holds a course for a while, firing some of the time.
+    fn tick(&mut self, seconds: f32) {
+        let mut state = self.ptr.lock();
+        self.course_left -= seconds;
+        if self.course_left <= 0.0 {
+            self.course_left = MIN_COURSE_TIME + rand::random::<f32>() * (MAX_COURSE_TIME - MIN_COURSE_TIME);
+            let was_firing = state.local_input.fire;
+            pick_course(&mut state.local_input);
+            if state.local_input.fire && !was_firing {
+                state.fire_latch.press();
+            }
+        }
+        state.curr_time += seconds;
+        state.step_client(seconds);
+    }
+}
+
+fn pick_course(input: &mut InputState) {
+    let turn = rand::random::<f32>();
+    input.up = rand::random::<f32>() < THRUST_CHANCE;
+    input.down = false;
+    input.left = turn < 0.3;
+    input.right = turn > 0.7;
+    input.fire = rand::random::<f32>() < FIRE_CHANCE;
+}
+
+fn print_summary(bots: &[Bot], elapsed: Duration) {
+    let seconds = elapsed.as_millis().max(1) as f64 / 1000.0;
+    let mut total_bytes = 0;
+    println!("{:<5}{:>8}{:>11}{:>11}{:>11}{:>11}", "bot", "player", "snapshots", "avg bytes", "avg gap", "max gap");
+    for (i, bot) in bots.iter().enumerate() {
+        let state = bot.ptr.lock();
+        let stats = &state.net_stats;
+        let player = state.local_player_index.map_or(String::from("-"), |index| format!("P{}", index + 1));
+        let avg_bytes = stats.snapshot_bytes / stats.snapshots_received.max(1);
+        let avg_gap = seconds * 1000.0 / stats.snapshots_received.max(1) as f64;
+        println!("{:<5}{:>8}{:>11}{:>11}{:>9.1}ms{:>9}ms",
+            i + 1, player, stats.snapshots_received, avg_bytes, avg_gap, stats.max_snapshot_gap.as_millis());
+        total_bytes += stats.snapshot_bytes;
+    }
+    println!("{} bots for {:.1}s received {} bytes of snapshots, {:.1} KB/s in total",
+        bots.len(), seconds, total_bytes, total_bytes as f64 / 1024.0 / seconds);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = BotArgs::parse(&args);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_stop.store(true, Ordering::Relaxed)) {
+        println!("Ctrl+C won't disconnect the bots cleanly: {}", e);
+    }
+
+    let options = LaunchOptions {
+        role: NetRole::Client,
+        server_address: args.address.clone(),
+        ..LaunchOptions::default()
+    };
+    let mut bots = Vec::with_capacity(args.count);
+    for i in 0..args.count {
+        let mut ptr = StatePtr::headless(&options, BOT_SCREEN, BOT_SCREEN);
+        match networking::connect_headless_client(&mut ptr, &args.address, (args.snapshot_port, args.input_port)) {
+            Ok(()) => bots.push(Bot { ptr, course_left: 0.0 }),
+            Err(e) => println!("Bot {} failed to connect: {}", i + 1, e),
+        }
+    }
+    if bots.is_empty() {
+        println!("No bot could connect.");
+        return;
+    }
+    println!("{} bots connected. Ctrl+C disconnects them.", bots.len());
+
+    let tick = Duration::from_micros(1_000_000 / TICKS_PER_SECOND as u64);
+    let tick_seconds = 1.0 / TICKS_PER_SECOND as f32;
+    let run_time = args.seconds.map(Duration::from_secs);
+    let started = Instant::now();
+    while !stop.load(Ordering::Relaxed) && run_time.map_or(true, |limit| started.elapsed() < limit) {
+        let tick_start = Instant::now();
+        for bot in &mut bots {
+            bot.tick(tick_seconds);
+        }
+        if let Some(left) = tick.checked_sub(tick_start.elapsed()) {
+            std::thread::sleep(left);
+        }
+    }
+    let elapsed = started.elapsed();
+
+    println!("Disconnecting {} bots...", bots.len());
+    for bot in &bots {
+        bot.ptr.request_shutdown();
+    }
+    std::thread::sleep(DISCONNECT_GRACE);
+    print_summary(&bots, elapsed);
+}
@@ -0,0 +1,84 @@
+use ggez::event::Keycode;
+use sdl2::keyboard::Scancode;
+
+use crate::config::KeyBindingSetup;
+
+/// Game actions a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+}
+
+/// How a binding recognizes its key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyMatch {
+    /// The key labelled with this symbol, wherever the layout puts it.
+    Key(Keycode),
+    /// The key in this physical position, whatever the layout labels it.
+    Scan(Scancode),
+}
+
+impl KeyMatch {
+    /// "sc:W" binds a physical position, a plain name such as "Up" binds a symbol.
+    /// Names are SDL's key and scancode names.
+    pub fn parse(text: &str) -> Option<KeyMatch> {
+        if text.starts_with("sc:") {
+            Scancode::from_name(&text[3..]).map(KeyMatch::Scan)
+        } else {
+            Keycode::from_name(text).map(KeyMatch::Key)
+        }
+    }
+
+    pub fn matches(&self, keycode: Keycode, scancode: Option<Scancode>) -> bool {
+        match *self {
+            KeyMatch::Key(key) => key == keycode,
+            KeyMatch::Scan(scan) => Some(scan) == scancode,
+        }
+    }
+}
+
+/// Lookup table from key events to game actions, built from input_setup.json.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(KeyMatch, Action)>,
+}
+
+impl KeyBindings {
+    pub fn from_setup(setup: &KeyBindingSetup) -> KeyBindings {
+        let mut bindings = Vec::new();
+        let lists = [
+            (&setup.up, Action::Up),
+            (&setup.down, Action::Down),
+            (&setup.left, Action::Left),
+            (&setup.right, Action::Right),
+            (&setup.fire, Action::Fire),
+        ];
+
+        for (names, action) in lists.iter() {
+            for name in names.iter() {
+                match KeyMatch::parse(name) {
+                    Some(key) => bindings.push((key, *action)),
+                    None => println!("Ignoring unknown key binding {:?} for {:?}", name, action),
+                }
+            }
+        }
+
+        KeyBindings {
+            bindings,
+        }
+    }
+
+    /// ggez only reports keycodes, so the physical position is the one SDL's current keymap
+    /// gives the keycode, not read off the key press. Needs SDL's video subsystem running,
+    /// before that only symbol bindings match.
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        let scancode = Scancode::from_keycode(keycode);
+        self.bindings.iter()
+            .find(|(key, _)| key.matches(keycode, scancode))
+            .map(|(_, action)| *action)
+    }
+}
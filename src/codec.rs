@@ -0,0 +1,168 @@
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use std::io::BufRead;
+
+/// Upper bound for a single message. Length prefixes and JSON lines read from the
+/// network are checked against this so a corrupt or truncated stream yields an
+/// error instead of a huge allocation, and nothing bigger is sent.
+pub const MAX_MESSAGE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The bincode settings for everything on the wire, handshakes included: little-endian,
+/// fixed-size integers and lengths, at most `MAX_MESSAGE_BYTES`. Spelled out instead of
+/// left to bincode's defaults, so a dependency update can't quietly change the layout.
+/// tests/wire_format.rs holds the bytes this has to keep producing.
+pub fn wire_config() -> bincode::Config {
+    let mut config = bincode::config();
+    config.little_endian().limit(MAX_MESSAGE_BYTES);
+    config
+}
+
+/// How snapshots and inputs are written on the wire, from net_setup.json.
+/// The handshake messages are always bincode, the format is negotiated in them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    Bincode,
+    /// One JSON document per line. Readable with netcat or tcpdump, far bigger and slower.
+    Json,
+}
+
+impl Default for WireFormat {
+    fn default() -> WireFormat {
+        WireFormat::Bincode
+    }
+}
+
+impl WireFormat {
+    /// JSON only when both ends asked for it, a single debugging peer doesn't force it on the other.
+    pub fn negotiate(self, other: WireFormat) -> WireFormat {
+        if self == other {
+            self
+        } else {
+            WireFormat::Bincode
+        }
+    }
+
+    pub fn codec(self) -> AnyCodec {
+        match self {
+            WireFormat::Bincode => AnyCodec::Bincode(BincodeCodec),
+            WireFormat::Json => AnyCodec::Json(JsonCodec::default()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Nothing arrived in time or the peer went away.
+    Io(std::io::Error),
+    /// Bytes arrived that don't decode as the expected message.
+    Malformed(String),
+}
+
+/// Turns messages into bytes on the wire and back. One decoder per connection,
+/// since a decoder may hold on to part of a message that arrived before a timeout.
+pub trait Codec {
+    fn encode<T: Serialize>(&self, data: &T, buf: &mut Vec<u8>);
+    fn decode<T: DeserializeOwned, R: BufRead>(&mut self, reader: &mut R) -> Result<T, DecodeError>;
+}
+
+/// Bincode needs no framing, every message knows its own length.
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    /// A message over the limit is dropped with a warning, the peer would refuse it anyway.
+    fn encode<T: Serialize>(&self, data: &T, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        if let Err(e) = wire_config().serialize_into(&mut *buf, data) {
+            println!("Not sending a message: {}", e);
+            buf.truncate(start);
+        }
+    }
+
+    fn decode<T: DeserializeOwned, R: BufRead>(&mut self, reader: &mut R) -> Result<T, DecodeError> {
+        wire_config().deserialize_from(reader).map_err(|error| match *error {
+            bincode::ErrorKind::Io(e) => DecodeError::Io(e),
+            other => DecodeError::Malformed(other.to_string()),
+        })
+    }
+}
+
+/// Newline terminated JSON. serde_json never writes a raw newline inside a document,
+/// so the newline alone frames it.
+#[derive(Default)]
+pub struct JsonCodec {
+    /// Start of a line cut off by a read timeout, the rest follows in a later read.
+    pending: Vec<u8>,
+}
+
+impl Codec for JsonCodec {
+    fn encode<T: Serialize>(&self, data: &T, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        serde_json::to_writer(&mut *buf, data).expect("Failed to serialize.");
+        if (buf.len() - start) as u64 > MAX_MESSAGE_BYTES {
+            println!("Not sending a message: {} bytes of JSON is over the limit", buf.len() - start);
+            buf.truncate(start);
+            return;
+        }
+        buf.push(b'\n');
+    }
+
+    fn decode<T: DeserializeOwned, R: BufRead>(&mut self, reader: &mut R) -> Result<T, DecodeError> {
+        loop {
+            let (found, used) = {
+                let available = reader.fill_buf().map_err(DecodeError::Io)?;
+                if available.is_empty() {
+                    return Err(DecodeError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+                match available.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        self.pending.extend_from_slice(&available[..end]);
+                        (true, end + 1)
+                    }
+                    None => {
+                        self.pending.extend_from_slice(available);
+                        (false, available.len())
+                    }
+                }
+            };
+            reader.consume(used);
+
+            if self.pending.len() as u64 > MAX_MESSAGE_BYTES {
+                // Skip to the end of the oversized line, the next one decodes normally.
+                if found {
+                    self.pending.clear();
+                    return Err(DecodeError::Malformed(format!("line longer than {} bytes", MAX_MESSAGE_BYTES)));
+                }
+                self.pending.truncate(MAX_MESSAGE_BYTES as usize);
+                continue;
+            }
+            if found {
+                let line = std::mem::replace(&mut self.pending, Vec::new());
+                return serde_json::from_slice(&line).map_err(|e| DecodeError::Malformed(e.to_string()));
+            }
+        }
+    }
+}
+
+/// The codec a connection negotiated.
+pub enum AnyCodec {
+    Bincode(BincodeCodec),
+    Json(JsonCodec),
+}
+
+impl Codec for AnyCodec {
+    fn encode<T: Serialize>(&self, data: &T, buf: &mut Vec<u8>) {
+        match self {
+            AnyCodec::Bincode(codec) => codec.encode(data, buf),
+            AnyCodec::Json(codec) => codec.encode(data, buf),
+        }
+    }
+
+    fn decode<T: DeserializeOwned, R: BufRead>(&mut self, reader: &mut R) -> Result<T, DecodeError> {
+        match self {
+            AnyCodec::Bincode(codec) => codec.decode(reader),
+            AnyCodec::Json(codec) => codec.decode(reader),
+        }
+    }
+}
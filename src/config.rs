@@ -0,0 +1,261 @@
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::aim;
+use crate::game_structs::{DEFAULT_SPREAD, DEFAULT_VOLLEY};
+use crate::well::WellPull;
+
+pub const GAME_FILENAME: &str = "game_setup.json";
+pub const INPUT_FILENAME: &str = "input_setup.json";
+pub const DISPLAY_FILENAME: &str = "display_setup.json";
+
+/// Reads a json config, creating it with the defaults when it doesn't exist.
+/// A malformed file is reported and ignored rather than overwritten.
+pub fn load_or_default<T: Serialize + DeserializeOwned + Default>(filename: &str) -> T {
+    match File::open(filename) {
+        Ok(file) => {
+            match serde_json::from_reader(BufReader::new(file)) {
+                Ok(data) => data,
+                Err(e) => {
+                    println!("Ignoring malformed {}: {}", filename, e);
+                    T::default()
+                }
+            }
+        }
+        Err(_) => {
+            let data = T::default();
+            if let Ok(file) = File::create(filename) {
+                // We don't care if this fails
+                let _ = serde_json::to_writer_pretty(file, &data);
+            }
+            data
+        }
+    }
+}
+
+/// Gameplay rules of the server. Clients learn the results through snapshots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSetup {
+    pub starting_lives: u32,
+    pub max_lives: u32,
+    /// Every player earns a life each time the score crosses a multiple of this. 0 disables it.
+    pub extra_life_every: i32,
+    /// Fixed update steps allowed per frame before the backlog is dropped. 0 catches up fully.
+    pub max_catch_up_steps: u32,
+    /// Longest delta in seconds `spawn_rocks` accepts in a single call.
+    pub max_spawn_delta: f32,
+    /// Scores announced with a banner the first time a run reaches them.
+    pub milestones: Vec<u32>,
+    /// Shots wrap around the edges like players instead of leaving, until they get too old.
+    pub shots_wrap: bool,
+    /// Pixels a rock may be past the world's edge before it is removed. Rocks may also spawn that far out.
+    pub rock_despawn_margin: f32,
+    /// Seconds until a rock that never left the screen fades out and vanishes without a gem. 0 keeps rocks forever.
+    pub rock_lifetime: f32,
+    /// Remote players that send no input for this many seconds are kicked. 0 never kicks.
+    pub idle_kick_seconds: f32,
+    /// Most aim assist players on this server get, whatever they ask for. 0 turns it off.
+    pub max_aim_assist: f32,
+    /// Shots each player fires at once, fanned out evenly around straight ahead.
+    pub volley: u8,
+    /// Sideways speed between neighbouring shots of a volley, in pixels per second.
+    pub spread: f32,
+    pub wells: WellSetup,
+}
+
+impl Default for GameSetup {
+    fn default() -> GameSetup {
+        GameSetup {
+            starting_lives: 1,
+            max_lives: 5,
+            extra_life_every: 50,
+            max_catch_up_steps: 8,
+            max_spawn_delta: 0.1,
+            milestones: vec![25, 50, 100, 200],
+            shots_wrap: false,
+            rock_despawn_margin: 64.0,
+            rock_lifetime: 45.0,
+            idle_kick_seconds: 30.0,
+            max_aim_assist: aim::MAX_AIM_ASSIST,
+            volley: DEFAULT_VOLLEY,
+            spread: DEFAULT_SPREAD,
+            wells: WellSetup::default(),
+        }
+    }
+}
+
+/// Balance of the gravity wells, the rare hazard that pulls shots and ships in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WellSetup {
+    /// Chance per second of a well appearing once the intensity allows it. 0 turns wells off.
+    pub spawn_chance: f32,
+    /// Difficulty clock times the difficulty multiplier needed before wells appear.
+    pub min_intensity: f32,
+    /// Wells spawn at the top like rocks but only creep down at this speed.
+    pub drift_speed: f32,
+    /// Seconds until a well collapses on its own.
+    pub lifetime: f32,
+    /// Shots that make a well collapse early.
+    pub max_shots: u32,
+    pub pull: WellPull,
+}
+
+impl Default for WellSetup {
+    fn default() -> WellSetup {
+        WellSetup {
+            spawn_chance: 0.015,
+            min_intensity: 60.0,
+            drift_speed: 25.0,
+            lifetime: 8.0,
+            max_shots: 5,
+            pull: WellPull::default(),
+        }
+    }
+}
+
+/// Local input preferences. Only `aim_assist` is sent, when joining a server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSetup {
+    /// Tapping fire toggles continuous firing instead of having to hold the key.
+    pub auto_fire: bool,
+    /// Turns shots toward the rock they are closest to hitting, from 0.0 to 0.3.
+    pub aim_assist: f32,
+    /// Pauses a game nobody else is playing in while the window doesn't have focus.
+    pub pause_on_focus_loss: bool,
+    pub bindings: KeyBindingSetup,
+}
+
+/// Keys for each action. "sc:W" names a physical key position (scancode) and
+/// suits movement, a plain name like "Up" names the key symbol (keycode).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindingSetup {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub fire: Vec<String>,
+}
+
+impl Default for KeyBindingSetup {
+    fn default() -> KeyBindingSetup {
+        let keys = |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        KeyBindingSetup {
+            up: keys(&["sc:Up", "sc:W"]),
+            down: keys(&["sc:Down", "sc:S"]),
+            left: keys(&["sc:Left", "sc:A"]),
+            right: keys(&["sc:Right", "sc:D"]),
+            fire: keys(&["sc:Space"]),
+        }
+    }
+}
+
+/// Window and frame pacing options, read before the window is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySetup {
+    pub vsync: bool,
+    /// Frames per second the draw loop is capped at. 0 leaves pacing to vsync.
+    pub max_fps: u32,
+    pub sprite_fit: SpriteFitSetup,
+    pub palette: Palette,
+    /// Color of HUD text as `[r, g, b]`. Left out, the palette picks it.
+    pub hud_color: Option<[u8; 3]>,
+    /// Draws a shape under every ship as well, so players can be told apart without color.
+    pub player_markers: bool,
+    /// Font faces by the name the HUD asks for them, see `Assets::font`.
+    pub fonts: BTreeMap<String, FontFace>,
+}
+
+impl Default for DisplaySetup {
+    fn default() -> DisplaySetup {
+        DisplaySetup {
+            vsync: true,
+            max_fps: 0,
+            sprite_fit: SpriteFitSetup::default(),
+            palette: Palette::Normal,
+            hud_color: None,
+            player_markers: false,
+            fonts: FontFace::defaults(),
+        }
+    }
+}
+
+/// A font file at one size. ggez rasterizes a font once, so every size is its own face.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontFace {
+    /// Path inside the resources folder.
+    pub file: String,
+    pub size: u32,
+}
+
+impl Default for FontFace {
+    fn default() -> FontFace {
+        FontFace {
+            file: String::from("/DejaVuSerif.ttf"),
+            size: 18,
+        }
+    }
+}
+
+impl FontFace {
+    fn defaults() -> BTreeMap<String, FontFace> {
+        let mut fonts = BTreeMap::new();
+        for &(name, size) in &[("hud", 18), ("banner", 48), ("small", 12)] {
+            fonts.insert(String::from(name), FontFace { size, ..FontFace::default() });
+        }
+        fonts
+    }
+}
+
+/// Colors used for players and HUD text. Only changes how this client draws,
+/// every client maps `color_index` through its own palette.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    Normal,
+    /// Avoids pairs that only differ in red and green.
+    Deuteranopia,
+    /// Saturated colors and yellow text, for bright rooms and low vision.
+    HighContrast,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::Normal
+    }
+}
+
+/// Sprites are scaled so their radius matches the actor's hitbox. These multiply that
+/// radius per kind, for art that leaves empty space around the shape or overhangs it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpriteFitSetup {
+    pub player: f32,
+    pub rock: f32,
+    pub shot: f32,
+    pub gem: f32,
+    pub time_slow: f32,
+    pub well: f32,
+}
+
+impl Default for SpriteFitSetup {
+    fn default() -> SpriteFitSetup {
+        SpriteFitSetup {
+            player: 1.0,
+            rock: 1.0,
+            shot: 1.0,
+            gem: 1.0,
+            time_slow: 1.0,
+            well: 1.0,
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Commands typed into the terminal a server runs in.
+use crate::game_structs::{StatePtr, MAX_TRANSFER_MS, MIN_TRANSFER_MS};
+
+use std::io::BufRead;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    /// `rate <ms>`: milliseconds between snapshots.
+    Rate(u64),
+}
+
+/// One line of input, or what's wrong with it.
+pub fn parse(line: &str) -> Result<ConsoleCommand, String> {
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("rate"), Some(ms), None) => {
+            let ms: u64 = ms.parse().map_err(|_| format!("Not a number of milliseconds: {}", ms))?;
+            if ms < MIN_TRANSFER_MS || ms > MAX_TRANSFER_MS {
+                return Err(format!("The rate must be between {} and {} ms", MIN_TRANSFER_MS, MAX_TRANSFER_MS));
+            }
+            Ok(ConsoleCommand::Rate(ms))
+        }
+        (Some("rate"), _, _) => Err(String::from("Usage: rate <ms>")),
+        (Some(other), _, _) => Err(format!("Unknown command {}, try: rate <ms>", other)),
+        (None, _, _) => Err(String::from("Commands: rate <ms>")),
+    }
+}
+
+/// Reads commands from stdin until it closes or the game quits.
+pub fn spawn(ptr: StatePtr) {
+    let spawned = std::thread::Builder::new().name("server console".into()).spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => return,
+            };
+            if ptr.shutdown.load(std::sync::atomic::Ordering::Relaxed) {
+                return;
+            }
+            match parse(&line) {
+                Ok(ConsoleCommand::Rate(ms)) => {
+                    let ms = ptr.lock().set_transfer_ms(ms);
+                    println!("Snapshot interval: {}ms", ms);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        println!("Failed to start the server console: {}", e);
+    }
+}
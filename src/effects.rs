@@ -0,0 +1,129 @@
+//! Cosmetic effects: the "1UP" popups, the event ticker, the banner and toasts. Each kind
+//! keeps a fixed number of entries and a flood of events overwrites the oldest, so a long
+//! chaotic run can't grow them. Updated, drawn and cleared together.
+use ggez::graphics::{self, Color, Point2};
+use ggez::{Context, GameResult};
+
+use crate::game_structs::Popup;
+use crate::hud::{Banner, Layout, Ticker, Toasts};
+
+use std::collections::vec_deque::{self, VecDeque};
+
+/// Most "1UP" popups on screen at once.
+pub const POPUP_CAPACITY: usize = 16;
+/// How fast popups float up, in world units per second.
+const POPUP_RISE_SPEED: f32 = 40.0;
+
+/// A fixed number of entries, oldest first. Pushing into a full ring drops the oldest.
+#[derive(Debug)]
+pub struct Ring<T> {
+    entries: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> Ring<T> {
+    pub fn new(capacity: usize) -> Ring<T> {
+        Ring { entries: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub fn push(&mut self, entry: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<T> {
+        self.entries.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> vec_deque::IterMut<T> {
+        self.entries.iter_mut()
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, keep: F) {
+        self.entries.retain(keep);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub struct Effects {
+    /// "1UP" floating up from where a player earned a life.
+    pub popups: Ring<Popup>,
+    pub ticker: Ticker,
+    pub banner: Banner,
+    /// Local notices, e.g. a saved screenshot. Network threads push through `Toasts::sender`.
+    pub toasts: Toasts,
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Effects {
+            popups: Ring::new(POPUP_CAPACITY),
+            ticker: Ticker::new(),
+            banner: Banner::new(),
+            toasts: Toasts::new(),
+        }
+    }
+
+    /// Ages everything by `seconds` and drops what has run out.
+    pub fn update(&mut self, seconds: f32) {
+        self.ticker.tick(seconds);
+        self.banner.tick(seconds);
+        self.toasts.tick(seconds);
+
+        for popup in self.popups.iter_mut() {
+            popup.time_left -= seconds;
+            popup.pos.y += POPUP_RISE_SPEED * seconds;
+        }
+        self.popups.retain(|p| p.time_left > 0.0);
+    }
+
+    /// Drops what belongs to the run or connection being left. The ticker stays, it follows
+    /// the game's events and a hard restart clears it on every machine at once.
+    pub fn clear(&mut self) {
+        self.popups.clear();
+        self.banner.clear();
+        self.toasts.clear();
+    }
+
+    /// Builds the Text of new ticker lines, banners and toasts.
+    pub fn refresh(&mut self, ctx: &mut Context, font: &graphics::Font, banner_font: &graphics::Font) -> GameResult<()> {
+        self.ticker.refresh(ctx, font)?;
+        self.banner.refresh(ctx, banner_font)?;
+        self.toasts.refresh(ctx, font)
+    }
+
+    /// The HUD part, in window pixels. `toasts_bottom` is kept clear below the toasts.
+    pub fn draw(&self, ctx: &mut Context, layout: &Layout, text_color: Color, toasts_bottom: f32) -> GameResult<()> {
+        self.ticker.draw(ctx, layout, text_color)?;
+        self.banner.draw(ctx, layout)?;
+        self.toasts.draw(ctx, layout, toasts_bottom)
+    }
+
+    /// The popups, in view coordinates. `to_screen` places a world position.
+    pub fn draw_popups(&self, ctx: &mut Context, text: &graphics::Text, to_screen: &dyn Fn(Point2) -> Point2) -> GameResult<()> {
+        for popup in self.popups.iter() {
+            graphics::draw(ctx, text, to_screen(Point2::new(popup.pos.x, popup.pos.y)), 0.0)?;
+        }
+        Ok(())
+    }
+}
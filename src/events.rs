@@ -0,0 +1,173 @@
+use serde::{Serialize, Deserialize};
+
+use crate::strings::Strings;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long the server keeps repeating an event in snapshots when no player
+/// is around to acknowledge it.
+const EVENT_RESEND_TIME: Duration = Duration::from_secs(2);
+
+/// Entries a player hasn't acknowledged are still dropped after this long.
+const EVENT_MAX_AGE: Duration = Duration::from_secs(5);
+
+/// Entries in a single snapshot. Anything past that follows in later snapshots once acked.
+pub const MAX_EVENTS_PER_SNAPSHOT: usize = 16;
+
+/// Entries kept at most, however young.
+pub const MAX_EVENT_HISTORY: usize = 256;
+
+/// Something notable that happened on the server, delivered to all clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    RockStreak { player: u32, count: u32 },
+    ExtraLife { player: u32 },
+    PlayerJoined { player: u32 },
+    GameOver { score: i32 },
+    DifficultyChanged { difficulty: f32 },
+    /// The shared score reached one of the configured thresholds.
+    Milestone(u32),
+    /// A remote player went silent for too long and was taken out of the game.
+    PlayerKicked { player: u32 },
+    /// A remote player's connection broke.
+    PlayerLeft { player: u32 },
+    /// Someone started or stopped watching.
+    SpectatorJoined,
+    SpectatorLeft,
+    /// A new run started, `round` counting from 1 at launch. Hard resets also went back
+    /// to the launch difficulty and cleared the ticker.
+    Restarted { round: u32, hard: bool },
+    /// The server now sends snapshots every `ms` milliseconds.
+    TransferRateChanged { ms: u32 },
+    /// Why the run ended, right before the `Restarted` that follows it.
+    RunEnded { reason: RestartReason },
+}
+
+/// What ended a run and started the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RestartReason {
+    /// `player` lost their last life.
+    PlayerDeath { player: u32 },
+    /// The host reset the game, F7.
+    AdminRestart,
+    /// Launching, or recording or playing back input, starts a run of its own.
+    ModeChange,
+    /// The warm-up before the first player joined is over.
+    WarmUpEnd,
+}
+
+impl RestartReason {
+    /// Shown as a banner and in the ticker when the run ends.
+    pub fn describe(&self, strings: &Strings) -> String {
+        match self {
+            RestartReason::PlayerDeath { player } => strings.tr_fmt("reason.player_death", &[("player", &(player + 1))]),
+            RestartReason::AdminRestart => strings.tr("reason.admin_restart").to_string(),
+            RestartReason::ModeChange => strings.tr("reason.mode_change").to_string(),
+            RestartReason::WarmUpEnd => strings.tr("reason.warm_up_end").to_string(),
+        }
+    }
+}
+
+impl GameEvent {
+    /// The line shown in the event ticker.
+    pub fn describe(&self, strings: &Strings) -> String {
+        let player = |key, player: &u32| strings.tr_fmt(key, &[("player", &(player + 1))]);
+        match self {
+            GameEvent::RockStreak { player, count } =>
+                strings.tr_fmt("event.rock_streak", &[("player", &(player + 1)), ("count", count)]),
+            GameEvent::ExtraLife { player: p } => player("event.extra_life", p),
+            GameEvent::PlayerJoined { player: p } => player("event.player_joined", p),
+            GameEvent::GameOver { score } => strings.tr_fmt("event.game_over", &[("score", score)]),
+            GameEvent::DifficultyChanged { difficulty } =>
+                strings.tr_fmt("event.difficulty_changed", &[("difficulty", &format!("{:.2}", difficulty))]),
+            GameEvent::Milestone(points) => strings.tr_fmt("event.milestone", &[("points", points)]),
+            GameEvent::PlayerKicked { player: p } => player("event.player_kicked", p),
+            GameEvent::PlayerLeft { player: p } => player("event.player_left", p),
+            GameEvent::SpectatorJoined => strings.tr("event.spectator_joined").to_string(),
+            GameEvent::SpectatorLeft => strings.tr("event.spectator_left").to_string(),
+            GameEvent::Restarted { round, hard: false } => strings.tr_fmt("event.round_started", &[("round", round)]),
+            GameEvent::Restarted { round, hard: true } => strings.tr_fmt("event.game_reset", &[("round", round)]),
+            GameEvent::TransferRateChanged { ms } => strings.tr_fmt("event.transfer_rate_changed", &[("ms", ms)]),
+            GameEvent::RunEnded { reason } => reason.describe(strings),
+        }
+    }
+
+    /// Someone came or went, which gets a chime.
+    pub fn is_arrival_or_departure(&self) -> bool {
+        match self {
+            GameEvent::PlayerJoined { .. }
+            | GameEvent::PlayerLeft { .. }
+            | GameEvent::PlayerKicked { .. }
+            | GameEvent::SpectatorJoined
+            | GameEvent::SpectatorLeft => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub seq: u64,
+    pub event: GameEvent,
+}
+
+/// Server side history of recent events, each with its own sequence number. A snapshot
+/// carries the oldest entries the seated players haven't acknowledged yet, at most
+/// `MAX_EVENTS_PER_SNAPSHOT`, and clients skip the sequence numbers they already applied.
+///
+/// Snapshots are shared by every connection, so the slowest player's ack decides what
+/// goes out. Spectators never ack and only see what the players leave in, with nobody
+/// but spectators around entries are repeated for `EVENT_RESEND_TIME` instead.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    next_seq: u64,
+    recent: VecDeque<(Instant, EventRecord)>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, event: GameEvent) {
+        self.next_seq += 1;
+        self.recent.push_back((Instant::now(), EventRecord { seq: self.next_seq, event }));
+        // A flood of events from a player that never acks mustn't grow the log forever.
+        while self.recent.len() > MAX_EVENT_HISTORY {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Forgets acknowledged entries and anything older than `EVENT_MAX_AGE`, or older than
+    /// `EVENT_RESEND_TIME` when nobody acks. `acked` is `MainState::event_ack`.
+    pub fn prune(&mut self, acked: Option<u64>) {
+        while let Some((at, record)) = self.recent.front() {
+            let keep = match acked {
+                Some(acked) => record.seq > acked && at.elapsed() < EVENT_MAX_AGE,
+                None => at.elapsed() < EVENT_RESEND_TIME,
+            };
+            if keep {
+                break;
+            }
+            self.recent.pop_front();
+        }
+    }
+
+    /// What the next snapshot carries: the oldest entries after `acked`, or the oldest
+    /// ones kept when no player acks.
+    pub fn unacked(&self, acked: Option<u64>) -> Vec<EventRecord> {
+        let acked = acked.unwrap_or(0);
+        self.recent.iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.seq > acked)
+            .take(MAX_EVENTS_PER_SNAPSHOT)
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len()
+    }
+
+    /// Sequence number of the last event pushed, 0 before the first one.
+    pub fn last_seq(&self) -> u64 {
+        self.next_seq
+    }
+}
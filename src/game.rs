@@ -0,0 +1,2462 @@
+//! The game itself: simulation, input handling and drawing of a `MainState`.
+use ggez::graphics;
+use ggez::event::{EventHandler, Keycode, Mod, LCTRLMOD, RCTRLMOD};
+use ggez::graphics::{Vector2, Point2};
+use ggez::nalgebra as na;
+use ggez::timer;
+use ggez::{Context, GameResult};
+
+use crate::aim;
+use crate::sfx;
+use crate::actor::{Actor, ActorType, Vec2Serial, ROCK_SPRITES, toroidal_delta, toroidal_distance};
+use crate::game_structs::*;
+use crate::launch_options::LaunchOptions;
+use crate::stats::{self, DailyAttempt, RunLog, RunSummary};
+use crate::highscores;
+use crate::leaderboard::{self, Leaderboard, LeaderboardSetup};
+use crate::config::{self, GameSetup, InputSetup, KeyBindingSetup, DisplaySetup};
+use crate::events::{GameEvent, EventLog, RestartReason};
+use crate::hud::{self, Anchor, Severity, TOAST_LONG_TIME, TOAST_TIME};
+use crate::effects::Effects;
+use crate::bindings::{Action, KeyBindings};
+use crate::profiler::{FrameProfiler, Phase};
+use crate::rng::SimRng;
+use crate::patterns::{self, PatternScheduler, SpawnPattern, SpawnQueue};
+use crate::net_structs::{NetClientInput, NetFromServer, SnapshotCache};
+use crate::savegame::{self, SaveGame};
+use crate::replay::{self, InputLogHeader, InputReplay};
+use crate::input_timeline::InputTimeline;
+use crate::tuning::{self, Tuning};
+use crate::strings::Strings;
+
+/// A rock passing within this multiple of the combined bboxes counts as a graze.
+const GRAZE_RANGE_MULT: f32 = 1.5;
+const GRAZE_BONUS: f32 = 0.25;
+
+const GEM_LIFETIME: f32 = 5.0;
+/// Only matters with `shots_wrap`, otherwise shots leave the world long before.
+const SHOT_LIFETIME: f32 = 1.5;
+const GEM_FALL_SPEED: f32 = 60.0;
+const GEM_MAGNET_RADIUS: f32 = 80.0;
+const GEM_MAGNET_ACCEL: f32 = 1400.0;
+
+pub(crate) const EXTRA_LIFE_POPUP_TIME: f32 = 1.2;
+
+/// Chance that a destroyed rock also drops a time-slow pickup.
+const TIME_SLOW_DROP_CHANCE: f32 = 0.02;
+const POWERUP_LIFETIME: f32 = 8.0;
+/// Seconds a rock past `GameSetup::rock_lifetime` takes to fade out.
+const ROCK_FADE_TIME: f32 = 1.0;
+/// Blue wash over the screen while rocks are slowed, fading out over the last second.
+const TIME_SLOW_TINT_ALPHA: f32 = 0.12;
+const TIME_SLOW_TINT_FADE: f32 = 1.0;
+
+/// Wells swell and shrink by this fraction of their size, this many radians per second.
+const WELL_PULSE: f32 = 0.15;
+const WELL_PULSE_RATE: f32 = 6.0;
+
+/// Rocks at least this fast, relative to the current base speed, are worth 2 and 3 points.
+/// Random rocks roll between 0.5 and 1.5, so the top tenth is worth 3.
+const ROCK_VALUE_2_SPEED: f32 = 1.0;
+const ROCK_VALUE_3_SPEED: f32 = 1.4;
+/// Steep rocks count as faster by this much per radian.
+const ROCK_ANGLE_DANGER: f32 = 0.4;
+
+/// Announce a streak in the ticker every this many rocks.
+const STREAK_ANNOUNCE_EVERY: u32 = 10;
+
+const DIFFICULTY_STEP: f32 = 0.25;
+const MIN_DIFFICULTY: f32 = 0.25;
+const MAX_DIFFICULTY: f32 = 10.0;
+
+/// Named difficulties on the number keys.
+const DIFFICULTY_PRESETS: [(&str, f32); 4] = [
+    ("Easy", 0.5),
+    ("Normal", 1.0),
+    ("Hard", 2.0),
+    ("Nightmare", 4.0),
+];
+/// The presets pick the difficulty of the coming run, they're still taken this long into it,
+/// while the game over banner is up.
+const PRESET_TIME: f32 = 2.0;
+
+const PRACTICE_FLASH_TIME: f32 = 1.0;
+
+/// How rocks spawn, relative to the difficulty ramp. See `spawn_rocks`.
+struct SpawnParams {
+    chance: f32,
+    speed: f32,
+    /// Rock patterns and wells.
+    extras: bool,
+}
+
+const RUN_SPAWN: SpawnParams = SpawnParams { chance: 1.0, speed: 1.0, extras: true };
+/// Half the rocks of a fresh run at 60% of the speed, nothing else.
+const WARM_UP_SPAWN: SpawnParams = SpawnParams { chance: 0.5, speed: 0.6, extras: false };
+/// How far below the top edge telegraphs are drawn, so they show even with the view against the edge.
+const TELEGRAPH_INSET: f32 = 30.0;
+/// Rocks pass through a freshly respawned player for this long.
+const RESPAWN_SHIELD_TIME: f32 = 1.5;
+/// Overlapping ships drift apart at this many pixels per second for every pixel of overlap,
+/// but never faster than `MAX_SEPARATION_SPEED`.
+const SEPARATION_STIFFNESS: f32 = 8.0;
+const MAX_SEPARATION_SPEED: f32 = 200.0;
+/// The death animation: how much the ship swells, and its debris.
+const DEATH_SWELL: f32 = 1.5;
+const DEATH_PARTICLES: usize = 12;
+const DEATH_PARTICLE_RANGE: f32 = 60.0;
+/// How far Page Up and Page Down move the difficulty clock with `--debug`.
+const DEBUG_CLOCK_STEP: f32 = 30.0;
+/// Milliseconds Ctrl+Page Up and Ctrl+Page Down change the snapshot interval by.
+const DEBUG_TRANSFER_STEP: u64 = 10;
+
+/// What the world is drawn in, back to front. The HUD goes on top of all of them.
+#[derive(Debug, Clone, Copy)]
+enum DrawLayer {
+    Rocks,
+    Pickups,
+    Shots,
+    Players,
+    Effects,
+}
+
+const DRAW_LAYERS: [DrawLayer; 5] = [
+    DrawLayer::Rocks,
+    DrawLayer::Pickups,
+    DrawLayer::Shots,
+    DrawLayer::Players,
+    DrawLayer::Effects,
+];
+
+/// How far [ and ] move the difficulty timer in practice mode.
+const PRACTICE_SCRUB_SECONDS: u64 = 10;
+
+const WINDOW_TITLE_INTERVAL: Duration = Duration::from_secs(1);
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+
+/// Create a unit vector representing the
+/// given angle (in radians)
+fn vec_from_angle(angle: f32) -> Vector2 {
+    let vx = angle.sin();
+    let vy = angle.cos();
+    Vector2::new(vx, vy)
+}
+
+
+
+/// Distance between a shot and a rock. Wrapping shots can hit across the edges.
+fn shot_distance(shot: &Actor, rock: &Vector2, shots_wrap: bool, world_width: f32, world_height: f32) -> f32 {
+    if shots_wrap {
+        let (width, height) = shot.wrap_period(world_width, world_height);
+        toroidal_distance(&shot.pos, rock, width, height)
+    } else {
+        (shot.pos - *rock).norm()
+    }
+}
+
+/// Translates the world coordinate system, which
+/// has Y pointing up and the origin at the center,
+/// to the screen coordinate system, which has Y
+/// pointing downward and the origin at the top-left,
+/// with the camera position in the middle of the screen.
+/// The world is drawn with `view_coordinates`, so the screen here is `VIEW_SIZE` square.
+fn world_to_screen_coords(screen_width: u32, screen_height: u32, camera: &Vector2, point: Point2) -> Point2 {
+    let width = screen_width as f32;
+    let height = screen_height as f32;
+    let x = point.x - camera.x + width / 2.0;
+    let y = height - (point.y - camera.y + height / 2.0);
+    Point2::new(x, y)
+}
+
+/// The inverse of `world_to_screen_coords`.
+fn screen_to_world_coords(screen_width: u32, screen_height: u32, camera: &Vector2, point: Point2) -> Vector2 {
+    let width = screen_width as f32;
+    let height = screen_height as f32;
+    let x = point.x + camera.x - width / 2.0;
+    let y = height / 2.0 - point.y + camera.y;
+    Vector2::new(x, y)
+}
+
+/// The part of a window the view gets drawn into: the biggest centered square, with black
+/// bars on the longer side.
+fn viewport(screen_width: u32, screen_height: u32) -> graphics::Rect {
+    let (width, height) = (screen_width.max(1) as f32, screen_height.max(1) as f32);
+    let side = width.min(height);
+    graphics::Rect::new((width - side) / 2.0, (height - side) / 2.0, side, side)
+}
+
+/// Screen coordinates that fit `VIEW_SIZE` world units into the `viewport`, so the world
+/// is drawn the same way at any window size.
+fn view_coordinates(screen_width: u32, screen_height: u32) -> graphics::Rect {
+    let viewport = viewport(screen_width, screen_height);
+    let units_per_pixel = VIEW_SIZE as f32 / viewport.w;
+    graphics::Rect::new(
+        -viewport.x * units_per_pixel,
+        -viewport.y * units_per_pixel,
+        screen_width.max(1) as f32 * units_per_pixel,
+        screen_height.max(1) as f32 * units_per_pixel,
+    )
+}
+
+/// A point in window pixels, like the mouse cursor, in `view_coordinates`.
+fn pixel_to_view(screen_width: u32, screen_height: u32, point: Point2) -> Point2 {
+    let view = view_coordinates(screen_width, screen_height);
+    let units_per_pixel = view.w / screen_width.max(1) as f32;
+    Point2::new(view.x + point.x * units_per_pixel, view.y + point.y * units_per_pixel)
+}
+
+/// Typical rock speed once `MainState::difficulty_points` reaches `intensity`.
+fn rock_speed_mod(intensity: f32) -> f32 {
+    f32::powf(intensity * 4.0, 0.85) + 100.0
+}
+
+impl MainState {
+    pub(crate) fn new(ctx: &mut Context, options: &LaunchOptions, display: &DisplaySetup) -> MainState {
+        ctx.print_resource_stats();
+        graphics::set_background_color(ctx, (0, 0, 0, 255).into());
+
+        println!("Game resource path: {:?}", ctx.filesystem);
+
+        let strings = Strings::load(options.lang.as_ref().map(String::as_str));
+        print_instructions(&strings);
+
+        let render = RenderState::new(ctx, display, strings).expect("Failed to load assets. Terminating");
+
+        println!("Role: {:?} | Difficulty Multiplier: {:?}", options.role, options.difficulty_mult);
+
+        let mut s = MainState::headless(options, ctx.conf.window_mode.width, ctx.conf.window_mode.height);
+        s.render = Some(render);
+        s.run_log = RunLog::spawn(stats::RUNS_FILENAME, highscores::HIGHSCORES_FILENAME);
+        s.leaderboard = Leaderboard::spawn(config::load_or_default::<LeaderboardSetup>(leaderboard::LEADERBOARD_FILENAME));
+        s.game_setup = config::load_or_default::<GameSetup>(config::GAME_FILENAME);
+        s.set_tuning(Tuning::from_file(tuning::TUNING_FILENAME).unwrap_or_else(|_| Tuning::write_default(tuning::TUNING_FILENAME)));
+        s.input_setup = config::load_or_default::<InputSetup>(config::INPUT_FILENAME);
+        s.key_bindings = KeyBindings::from_setup(&s.input_setup.bindings);
+        s.aim_assist = aim::granted_strength(s.input_setup.aim_assist, s.game_setup.max_aim_assist);
+        s.patterns = PatternScheduler::new(SpawnPattern::load(patterns::PATTERNS_FILENAME));
+        for p in &mut s.players {
+            p.lives = s.game_setup.starting_lives;
+        }
+        if let Some(filename) = &options.play_input {
+            s.start_input_playback(filename);
+        } else if let Some(filename) = &options.record_input {
+            s.start_input_recording(filename);
+        }
+        if options.resume {
+            s.load_game();
+        }
+        s
+    }
+
+    /// Every run of the recording reuses the current seed, so the log alone says which rocks came.
+    fn start_input_recording(&mut self, filename: &str) {
+        let header = InputLogHeader {
+            version: replay::INPUT_LOG_VERSION,
+            seed: self.seed,
+            difficulty_mult: self.difficulty_mult,
+            practice: self.practice,
+            hardcore: self.hardcore,
+            world_width: self.world_width,
+            world_height: self.world_height,
+            aim_assist: self.aim_assist,
+            game_setup: self.game_setup.clone(),
+            tuning: self.tuning.clone(),
+            patterns: self.patterns.patterns().to_vec(),
+        };
+        match InputReplay::record(filename, &header) {
+            Ok(recording) => {
+                println!("Recording input to {}", filename);
+                self.fixed_seed = Some(self.seed);
+                self.input_replay = Some(recording);
+                self.soft_restart(RestartReason::ModeChange);
+            }
+            Err(e) => println!("Not recording input, failed to create {}: {}", filename, e),
+        }
+    }
+
+    /// Sets the run up the way the recording had it, local config files don't matter.
+    fn start_input_playback(&mut self, filename: &str) {
+        match InputReplay::play(filename) {
+            Ok((header, playback)) => {
+                println!("Playing back input from {}", filename);
+                self.fixed_seed = Some(header.seed);
+                self.difficulty_mult = header.difficulty_mult;
+                self.launch_difficulty = header.difficulty_mult;
+                self.practice = header.practice;
+                self.hardcore = header.hardcore;
+                self.world_width = header.world_width;
+                self.world_height = header.world_height;
+                self.aim_assist = header.aim_assist;
+                self.game_setup = header.game_setup;
+                self.set_tuning(header.tuning);
+                self.patterns = PatternScheduler::new(header.patterns);
+                self.input_replay = Some(playback);
+                self.soft_restart(RestartReason::ModeChange);
+            }
+            Err(e) => println!("Not playing back {}: {}", filename, e),
+        }
+    }
+
+    /// Marks where a recording ended, so its playback gives the keyboard back there.
+    pub fn finish_input_log(&mut self) {
+        if let Some(replay) = &mut self.input_replay {
+            replay.finish(self.sim_tick);
+        }
+    }
+
+    /// Input logs only hold movement and fire, so keys that change the game some other way
+    /// are off while one is recorded or played back.
+    fn blocked_by_input_log(&mut self) -> bool {
+        if self.input_replay.is_none() {
+            return false;
+        }
+        let text = self.tr("toast.blocked_by_input_log").to_string();
+        self.effects.toasts.push(text, TOAST_TIME, Severity::Warning);
+        true
+    }
+
+    /// A state without any graphics, audio or files attached. Only the simulation works.
+    pub fn headless(options: &LaunchOptions, screen_width: u32, screen_height: u32) -> MainState {
+        let players = Vec::new();
+        let rocks = Vec::new();
+
+        let diff_mult = options.difficulty_mult;
+
+        let mut s = MainState {
+            role: options.role,
+            local_player_index: None,
+            local_input: InputState::default(),
+            input_setup: InputSetup::default(),
+            auto_fire_engaged: false,
+            aim_assist: 0.0,
+            fire_latch: FireLatch::default(),
+            key_bindings: KeyBindings::from_setup(&KeyBindingSetup::default()),
+            local_ship: options.ship % SHIP_SKINS,
+            reconnect_token: rand::random(),
+            show_debug: false,
+            profiler: FrameProfiler::new(),
+            seed: 0,
+            fixed_seed: options.seed,
+            practice: options.practice,
+            warm_up: options.warm_up,
+            hardcore: options.hardcore,
+            versus: options.versus,
+            next_rock_owner: 0,
+            next_actor_id: 1,
+            debug: options.debug,
+            pending_debug: Vec::new(),
+            clock_frozen: false,
+            daily: options.daily.clone(),
+            rng: SimRng::new(0),
+            patterns: PatternScheduler::builtin(),
+            spawn_queue: SpawnQueue::default(),
+            telegraphs: Vec::new(),
+            players: players,
+            shots: Vec::new(),
+            rocks: rocks,
+            gems: Vec::new(),
+            powerups: Vec::new(),
+            wells: Vec::new(),
+            rock_time_scale: 1.0,
+            time_slow_left: 0.0,
+            time_slow_tick_step: std::u32::MAX,
+            score: 0.0,
+            render: None,
+            screen_width,
+            screen_height,
+            world_width: VIEW_SIZE as f32 * options.world_scale,
+            world_height: VIEW_SIZE as f32 * options.world_scale,
+            camera: na::zero(),
+            show_scoreboard: false,
+            show_minimap: true,
+            followed_player: None,
+            scrub: ScrubBuffer::default(),
+            start_time: std::time::Instant::now(),
+            curr_time: 0.0,
+            difficulty_mult: diff_mult,
+            launch_difficulty: diff_mult,
+            difficulty_points: 0.0,
+            round: 0,
+            play_sounds: PlaySounds::default(),
+            connections: 0,
+            spectators: 0,
+            net_stats: NetStats::default(),
+            extrapolation: ExtrapolationSetup::default(),
+            last_snapshot_at: None,
+            local_shots_made: Vec::new(),
+            input_timeline: InputTimeline::default(),
+            run_log: RunLog::discard(),
+            leaderboard: Leaderboard::disabled(),
+            game_setup: GameSetup::default(),
+            tuning: Tuning::default(),
+            event_log: EventLog::default(),
+            last_event_seq: 0,
+            effects: Effects::new(),
+            milestones_reached: Vec::new(),
+            snapshot: SnapshotCache::new(),
+            transfer_ms: Arc::new(AtomicU64::new(DEFAULT_TRANSFER_MS)),
+            input_queues: Vec::new(),
+            sim_tick: 0,
+            input_replay: None,
+            paused_at: None,
+            recovered_from: None,
+        };
+       
+        if options.local_play {
+            let index = s.add_player();
+            s.players[index].set_ship(s.local_ship);
+            s.local_player_index = Some(index);
+        }
+        s.soft_restart(RestartReason::ModeChange);
+        s
+    }
+
+    pub(crate) fn get_local_player(&self) -> Option<&Player> {
+        if let Some(index) = self.local_player_index {
+            if self.players.len() > index {
+                Some(&self.players[index])
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    fn get_local_player_mut(&mut self) -> Option<&mut Player> {
+        match self.local_player_index {
+            Some(index) => self.players.get_mut(index),
+            None => None,
+        }
+    }
+
+    /// Spectators only, the player picked with 1-4 while they are still in the game.
+    fn get_followed_player(&self) -> Option<&Player> {
+        if self.role != NetRole::Spectator {
+            return None;
+        }
+        self.followed_player.and_then(|index| self.players.get(index)).filter(|p| p.active)
+    }
+
+    /// Whether key presses steer a ship. Never for spectators, so their keys can't end up in `local_input`.
+    fn steers_a_ship(&self) -> bool {
+        self.role != NetRole::Spectator && self.local_player_index.is_some()
+    }
+
+    fn is_server(&self) -> bool {
+        self.role == NetRole::Server
+    }
+
+    /// Waiting for players, paused, or a run that only just started after a game over.
+    fn between_runs(&self) -> bool {
+        self.warm_up || self.paused_at.is_some() || self.curr_time < PRESET_TIME
+    }
+
+    /// Takes `tuning` from now on. Actors already in the game get its hitboxes.
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        for player in &mut self.players {
+            player.actor.bbox_size = tuning.bbox(&player.actor.tag);
+        }
+        for actor in self.rocks.iter_mut().chain(self.shots.iter_mut()).chain(self.gems.iter_mut()).chain(self.powerups.iter_mut()).chain(self.wells.iter_mut()) {
+            actor.bbox_size = tuning.bbox(&actor.tag);
+        }
+        self.tuning = tuning;
+    }
+
+    /// Gives every actor that doesn't have an `Actor::id` yet the next one. Done before
+    /// each snapshot, so it catches actors however they were spawned or loaded.
+    pub fn issue_actor_ids(&mut self) {
+        let mut next = self.next_actor_id;
+        for actor in self.rocks.iter_mut().chain(self.shots.iter_mut()).chain(self.gems.iter_mut()).chain(self.powerups.iter_mut()).chain(self.wells.iter_mut()) {
+            if actor.id == 0 {
+                actor.id = next;
+                // 0 means none, skip it if we ever come around.
+                next = next.wrapping_add(1).max(1);
+            }
+        }
+        self.next_actor_id = next;
+    }
+
+    pub fn add_player(&mut self) -> usize {
+        let index = self.players.len();
+        let new_player = self.new_player(index);
+        self.players.push(new_player);
+        index
+    }
+
+    fn new_player(&self, index: usize) -> Player {
+        let mut new_player = Player::create(&self.tuning);
+        new_player.index = index as u32;
+        new_player.lives = self.game_setup.starting_lives;
+        new_player.color_index = (index % hud::PLAYER_COLOR_COUNT) as u8;
+        new_player.volley = self.game_setup.volley;
+        new_player.spread = self.game_setup.spread;
+        new_player
+    }
+
+    /// Seats a remote player: the slot they had before if `token` matches an inactive one,
+    /// else any inactive slot, else a new one. Reused slots start out fresh.
+    pub(crate) fn join_player(&mut self, token: u64) -> usize {
+        let returning = self.players.iter().position(|p| !p.active && p.reconnect_token == token);
+        let index = if let Some(index) = returning {
+            self.players[index].active = true;
+            self.players[index].actor.pos = na::zero();
+            index
+        } else if let Some(index) = self.players.iter().position(|p| !p.active) {
+            self.players[index] = self.new_player(index);
+            index
+        } else {
+            self.add_player()
+        };
+        self.players[index].reconnect_token = token;
+        self.players[index].acked_event_seq = 0;
+        self.players[index].timeline = InputTimeline::default();
+        if let Some(queue) = self.input_queues.get_mut(index) {
+            queue.reset();
+        }
+        // Whoever warmed up waiting for this player starts the run with them.
+        self.end_warm_up();
+        // The idle kick counts from joining until the first input arrives.
+        self.players[index].last_input_at = Some(std::time::Instant::now());
+        // Someone else is playing now, they shouldn't find the game frozen.
+        self.resume();
+        self.difficulty_mult *= 2.0;
+        index
+    }
+
+    /// Parks a remote player that left or was kicked and gives back the difficulty they brought.
+    pub(crate) fn remove_player(&mut self, index: usize, event: GameEvent) {
+        match self.players.get_mut(index) {
+            Some(player) if player.active => player.park(),
+            _ => return,
+        }
+        if let Some(queue) = self.input_queues.get_mut(index) {
+            queue.reset();
+        }
+        self.difficulty_mult /= 2.0;
+        self.emit(event);
+    }
+
+    /// Fans `player.volley` shots out evenly around the way the ship faces.
+    fn spawn_shots(shots_ref: &mut Vec<Actor>, player: &Player, tuning: &Tuning) {
+        // `facing` is a draw rotation, clockwise on screen, and world y points up.
+        let heading = -player.actor.facing;
+        let middle = (player.volley as f32 - 1.0) / 2.0;
+        for i in 0..player.volley {
+            let mut shot = Actor::create_shot(tuning);
+            shot.pos = player.actor.pos;
+            shot.owner = Some(player.index as usize);
+
+            let sideways = (i as f32 - middle) * player.spread;
+            shot.velocity = aim::rotate(Vector2::new(sideways, tuning.shot_speed), heading);
+            shots_ref.push(shot);
+        }
+    }
+
+    /// `aim_assist` turns the whole volley toward a rock, see `aim::correction`.
+    fn fire_player_shot(shots_ref: &mut Vec<Actor>, player: &Player, rocks: &[Actor], aim_assist: f32, tuning: &Tuning) {
+        let first = shots_ref.len();
+        MainState::spawn_shots(shots_ref, player, tuning);
+
+        let forward = aim::rotate(Vector2::new(0.0, tuning.shot_speed), -player.actor.facing);
+        let turn = aim::correction(player.actor.pos, forward, rocks, aim_assist, SHOT_LIFETIME);
+        if turn != 0.0 {
+            for shot in &mut shots_ref[first..] {
+                shot.velocity = aim::rotate(shot.velocity, turn);
+            }
+        }
+    }
+
+    fn clear_dead_stuff(&mut self) {
+        self.shots.retain(|s| !s.kill);
+        self.rocks.retain(|r| !r.kill);
+        self.gems.retain(|g| !g.kill);
+        self.powerups.retain(|p| !p.kill);
+        self.wells.retain(|w| !w.kill);
+    }
+
+    fn update_time(&mut self) {
+        let now = std::time::Instant::now();
+        self.curr_time = now.duration_since(self.start_time).as_micros() as f32 / 1000000.0;
+    }
+
+    /// Recorded and replayed runs count steps instead of reading the clock, so a replay
+    /// sees the same times however fast it runs.
+    fn advance_time(&mut self, seconds: f32) {
+        if self.paused_at.is_some() {
+            return;
+        }
+        if self.input_replay.is_some() {
+            self.curr_time += seconds;
+        } else {
+            self.update_time();
+        }
+    }
+
+    fn reset_time(&mut self) {
+        self.start_time = std::time::Instant::now();
+        self.curr_time = 0.0;
+    }
+
+    /// Clears the field and starts the next run, after a game over. Connected players keep
+    /// their slots and ships, only their run is reset.
+    pub fn soft_restart(&mut self, reason: RestartReason) {
+        self.restart(false, reason);
+    }
+
+    /// Server only. Clears the warm-up field and starts the run that counts.
+    pub fn end_warm_up(&mut self) {
+        if !self.warm_up {
+            return;
+        }
+        // Still warming up during the restart, so the warm-up isn't recorded as a run.
+        self.soft_restart(RestartReason::WarmUpEnd);
+        self.warm_up = false;
+        let text = self.tr("banner.go").to_string();
+        self.effects.banner.show(text);
+    }
+
+    /// Like `soft_restart`, but also goes back to the launch difficulty and clears the ticker
+    /// everywhere. Server F7.
+    pub fn hard_reset(&mut self) {
+        self.restart(true, RestartReason::AdminRestart);
+    }
+
+    fn restart(&mut self, hard: bool, reason: RestartReason) {
+        println!("RUN ENDED ({}): Time: {:?} | Score: {:?} | On Difficulty: {:?}",
+            reason.describe(&Strings::default()), self.curr_time, self.score, self.difficulty_mult);
+        for p in &self.players {
+            println!("    P{}: Shots: {} | Rocks: {} | Accuracy: {:.1}%", 
+                p.index + 1, p.stats.shots_fired, p.stats.rocks_destroyed, p.stats.accuracy());
+        }
+        self.record_run(reason);
+        self.milestones_reached.clear();
+
+        if hard {
+            // Players still here doubled it when they joined, as they would joining now.
+            let remote_players = self.players.iter()
+                .filter(|p| p.active && Some(p.index as usize) != self.local_player_index)
+                .count();
+            self.difficulty_mult = self.launch_difficulty * 2f32.powi(remote_players as i32);
+            self.clock_frozen = false;
+        }
+
+        self.local_input = InputState::default();
+        self.auto_fire_engaged = false;
+        for p in &mut self.players {
+            p.last_shot_at = 0.0;
+            p.input = InputState::default();
+            p.stats = PlayerStats::default();
+            p.graze = 0.0;
+            p.deaths = 0;
+            p.lives = self.game_setup.starting_lives;
+            p.streak = 0;
+            p.dying_until = 0.0;
+            p.versus_score = 0;
+            p.volley = self.game_setup.volley;
+            p.spread = self.game_setup.spread;
+        }
+        self.next_rock_owner = 0;
+        self.effects.clear();
+        self.reset_time();
+        self.difficulty_points = 0.0;
+        self.score = 0.0;
+        self.seed = self.fixed_seed.unwrap_or_else(SimRng::random_seed);
+        self.rng = SimRng::new(self.seed);
+        self.patterns.reset();
+        self.spawn_queue.clear();
+        self.telegraphs.clear();
+
+        for shot in &mut self.shots {
+            shot.kill = true;
+        }
+        for rock in &mut self.rocks {
+            rock.kill = true;
+        }
+        for gem in &mut self.gems {
+            gem.kill = true;
+        }
+        for powerup in &mut self.powerups {
+            powerup.kill = true;
+        }
+        for well in &mut self.wells {
+            well.kill = true;
+        }
+        self.rock_time_scale = 1.0;
+        self.time_slow_left = 0.0;
+
+        self.round += 1;
+        // The first round starts with the process, nobody needs telling.
+        if self.round > 1 {
+            self.emit(GameEvent::RunEnded { reason });
+            self.emit(GameEvent::Restarted { round: self.round, hard });
+        }
+    }
+
+    /// Hands the run to the writer thread. Nothing here touches the disk, the state is locked.
+    fn record_run(&self, reason: RestartReason) {
+        // Nothing happened yet, e.g. the reset performed at startup.
+        let happened = self.curr_time > 0.0 && !self.practice && !self.warm_up && !self.debug;
+        let replayed = self.input_replay.as_ref().map_or(false, |replay| !replay.is_recording());
+        let counts = happened && !replayed;
+
+        if counts {
+            self.run_log.record(RunSummary {
+                timestamp: RunSummary::unix_now(),
+                survival_time: self.curr_time,
+                score: self.score,
+                difficulty_mult: self.difficulty_mult,
+                players: self.players.len(),
+                rocks_destroyed: self.players.iter().map(|p| p.stats.rocks_destroyed).sum(),
+                shots_fired: self.players.iter().map(|p| p.stats.shots_fired).sum(),
+                seed: Some(self.seed),
+                milestones: self.milestones_reached.clone(),
+                reason,
+            });
+            self.leaderboard.submit(self.score, self.curr_time, self.difficulty_mult, self.seed);
+        }
+
+        // Every restart shows the day's results, with this run in them if it counted.
+        if let Some(date) = &self.daily {
+            self.run_log.record_daily(DailyAttempt {
+                date: date.clone(),
+                score: if counts { Some(self.score) } else { None },
+                toasts: self.effects.toasts.sender(),
+                strings: self.strings(),
+            });
+        }
+    }
+
+    /// Gives every player a life when the score crosses an `extra_life_every` multiple.
+    fn award_extra_lives(&mut self, score_before: f32) {
+        let every = self.game_setup.extra_life_every;
+        if every <= 0 || (self.score / every as f32).floor() <= (score_before / every as f32).floor() {
+            return;
+        }
+
+        let mut events = Vec::new();
+        for player in &mut self.players {
+            if player.lives < self.game_setup.max_lives {
+                player.lives += 1;
+                self.effects.popups.push(Popup { pos: player.actor.pos, time_left: EXTRA_LIFE_POPUP_TIME });
+                self.play_sounds.play_extra_life = true;
+                events.push(GameEvent::ExtraLife { player: player.index });
+            }
+        }
+        for event in events {
+            self.emit(event);
+        }
+    }
+
+    /// Announces every configured milestone the score just reached for the first time this run.
+    fn announce_milestones(&mut self, score_before: f32) {
+        let mut reached: Vec<u32> = self.game_setup.milestones.iter().cloned()
+            .filter(|&m| score_before < m as f32 && self.score >= m as f32)
+            .filter(|m| !self.milestones_reached.contains(m))
+            .collect();
+        reached.sort();
+        for points in reached {
+            self.emit(GameEvent::Milestone(points));
+        }
+    }
+
+    /// Respawns players whose death animation is over. The first of them with no lives left,
+    /// whose death ends the run.
+    fn finish_deaths(&mut self) -> Option<u32> {
+        let mut game_over = None;
+        for player in &mut self.players {
+            if player.dying_until <= 0.0 || player.is_dying(self.curr_time) {
+                continue;
+            }
+            player.dying_until = 0.0;
+            if player.lives == 0 {
+                game_over = game_over.or(Some(player.index));
+            } else {
+                player.actor.pos = na::zero();
+                player.hit_flash = RESPAWN_SHIELD_TIME;
+            }
+        }
+        game_over
+    }
+
+    fn tick_popups(&mut self, seconds: f32) {
+        self.effects.update(seconds);
+
+        for player in &mut self.players {
+            player.hit_flash = (player.hit_flash - seconds).max(0.0);
+        }
+
+        // Counted in spawn time, like the queue they come from.
+        for telegraph in &mut self.telegraphs {
+            telegraph.time_left -= seconds * self.rock_time_scale;
+        }
+        self.telegraphs.retain(|t| t.time_left > 0.0);
+    }
+
+    pub(crate) fn handle_collisions(&mut self) {
+        let destroyed = self.finish_deaths();
+        if self.versus {
+            self.charge_escaped_rocks();
+        }
+        let score_before = self.score;
+        let mut events = Vec::new();
+        let mut explosions = Vec::new();
+        for rock in &mut self.rocks {
+            let was_killed = rock.kill;
+
+            for player_obj in &mut self.players {
+                // Exploding and freshly respawned ships are out of reach.
+                let forgiving = self.practice || self.warm_up;
+                let shielded = player_obj.hit_flash > 0.0 && !forgiving;
+                if player_obj.dying_until > 0.0 || shielded || !player_obj.active {
+                    continue;
+                }
+                let player = &player_obj.actor;
+                // Players wrap around the edges, so a rock hugging the opposite edge is right next to them.
+                let (width, height) = player.wrap_period(self.world_width, self.world_height);
+                let distance = toroidal_distance(&rock.pos, &player.pos, width, height);
+                let touch_distance = player.bbox_size + rock.bbox_size;
+                if distance < touch_distance && !rock.kill {
+                    if forgiving {
+                        player_obj.deaths += 1;
+                        player_obj.hit_flash = PRACTICE_FLASH_TIME;
+                        player_obj.streak = 0;
+                        rock.kill = true;
+                        self.play_sounds.play_death = true;
+                    } else {
+                        // The respawn, or the game over on the last life, waits for the animation.
+                        player_obj.lives = player_obj.lives.saturating_sub(1);
+                        player_obj.streak = 0;
+                        player_obj.dying_until = self.curr_time + DEATH_TIME;
+                        // Where the client put the ship before the hit doesn't count anymore.
+                        player_obj.timeline.clear();
+                        rock.kill = true;
+                        self.play_sounds.play_hit = true;
+                    }
+                } else if distance < touch_distance * GRAZE_RANGE_MULT && player_obj.index < 64 {
+                    let player_bit = 1u64 << player_obj.index;
+                    if rock.grazed_by & player_bit == 0 {
+                        rock.grazed_by |= player_bit;
+                        player_obj.graze += GRAZE_BONUS;
+                        self.play_sounds.play_graze = true;
+                    }
+                }
+            }
+            
+            for shot in &mut self.shots {
+                let distance = shot_distance(shot, &rock.pos, self.game_setup.shots_wrap, self.world_width, self.world_height);
+                if distance < (shot.bbox_size + rock.bbox_size) {
+                    shot.kill = true;
+                    if rock.kill {
+                        continue;
+                    }
+                    rock.kill = true;
+                    self.play_sounds.play_hit = true;
+
+                    // The point is only scored once someone picks up the gem.
+                    let mut gem = Actor::create_gem(&self.tuning);
+                    gem.pos = rock.pos;
+                    gem.score_value = rock.score_value;
+                    gem.velocity = Vector2::new(0.0, -GEM_FALL_SPEED);
+                    self.gems.push(gem);
+
+                    if self.rng.next_f32() < TIME_SLOW_DROP_CHANCE {
+                        let mut powerup = Actor::create_time_slow(&self.tuning);
+                        powerup.pos = rock.pos;
+                        powerup.velocity = Vector2::new(0.0, -GEM_FALL_SPEED);
+                        self.powerups.push(powerup);
+                    }
+
+                    if let Some(owner) = shot.owner {
+                        if let Some(player) = self.players.get_mut(owner) {
+                            if self.versus {
+                                // Rocks nobody was assigned to count as your own.
+                                player.versus_score += if rock.owner.map_or(true, |o| o == owner) {
+                                    VERSUS_OWN_ROCK_POINTS
+                                } else {
+                                    VERSUS_OTHER_ROCK_POINTS
+                                };
+                            }
+                            player.stats.rocks_destroyed += 1;
+                            player.streak += 1;
+                            if player.streak % STREAK_ANNOUNCE_EVERY == 0 {
+                                events.push(GameEvent::RockStreak { player: player.index, count: player.streak });
+                            }
+                        }
+                    }
+                }
+            }
+
+            if rock.kill && !was_killed {
+                explosions.push(rock.pos);
+            }
+        }
+        self.knock_back_rocks(&explosions);
+
+        for well in &mut self.wells {
+            for shot in &mut self.shots {
+                let distance = shot_distance(shot, &well.pos, self.game_setup.shots_wrap, self.world_width, self.world_height);
+                if !shot.kill && !well.kill && distance < (shot.bbox_size + well.bbox_size) {
+                    shot.kill = true;
+                    well.absorbed += 1;
+                    if well.absorbed >= self.game_setup.wells.max_shots {
+                        well.kill = true;
+                        self.play_sounds.play_hit = true;
+                    }
+                }
+            }
+        }
+
+        // Exploding and parked ships don't pick anything up.
+        for gem in &mut self.gems {
+            for player_obj in self.players.iter().filter(|p| p.active && p.dying_until <= 0.0) {
+                let player = &player_obj.actor;
+                let distance = gem.pos - player.pos;
+                if !gem.kill && distance.norm() < (player.bbox_size + gem.bbox_size) {
+                    gem.kill = true;
+                    if !self.warm_up {
+                        self.score += gem.score_value as f32;
+                    }
+                    self.play_sounds.play_pickup = true;
+                }
+            }
+        }
+
+        let mut slow_time = false;
+        for powerup in &mut self.powerups {
+            for player_obj in self.players.iter().filter(|p| p.active && p.dying_until <= 0.0) {
+                let distance = powerup.pos - player_obj.actor.pos;
+                if !powerup.kill && distance.norm() < (player_obj.actor.bbox_size + powerup.bbox_size) {
+                    powerup.kill = true;
+                    slow_time = true;
+                    self.play_sounds.play_pickup = true;
+                }
+            }
+        }
+        if slow_time {
+            self.start_time_slow();
+        }
+        for event in events {
+            self.emit(event);
+        }
+        self.award_extra_lives(score_before);
+        self.announce_milestones(score_before);
+        // Nobody can lose a life while warming up, but a death from before doesn't end it either.
+        if let Some(player) = destroyed.filter(|_| !self.warm_up) {
+            self.emit(GameEvent::GameOver { score: self.score as i32 });
+            self.soft_restart(RestartReason::PlayerDeath { player });
+            self.play_sounds.play_death = true;
+        }
+    }
+    
+    /// Pushes the rocks around each point in `explosions` away from it, harder the closer they are.
+    fn knock_back_rocks(&mut self, explosions: &[Vector2]) {
+        let tuning = &self.tuning;
+        if explosions.is_empty() || tuning.explosion_impulse <= 0.0 {
+            return;
+        }
+        // Rocks right on top of the explosion would otherwise fly off at the speed cap.
+        let closest = tuning.rock_bbox * 2.0;
+        for rock in self.rocks.iter_mut().filter(|r| !r.kill) {
+            for center in explosions {
+                let away = rock.pos - center;
+                let distance = away.norm();
+                if distance > 0.0 && distance < tuning.explosion_radius {
+                    rock.velocity += away / distance * (tuning.explosion_impulse / distance.max(closest));
+                }
+            }
+            rock.clamp_velocity(tuning.max_physics_vel);
+        }
+    }
+
+    /// Server only. Rocks `tick_physics` culled past the bottom edge this step cost the player they were assigned to.
+    fn charge_escaped_rocks(&mut self) {
+        let bottom = -self.world_height / 2.0 - self.game_setup.rock_despawn_margin;
+        for rock in self.rocks.iter().filter(|rock| rock.kill && rock.pos.y < bottom) {
+            if let Some(owner) = rock.owner {
+                if let Some(player) = self.players.get_mut(owner) {
+                    player.versus_score -= VERSUS_ESCAPE_PENALTY;
+                }
+            }
+        }
+    }
+
+    /// Server only. The next active player in turn, `None` when nobody plays.
+    fn assign_rock_owner(&mut self) -> Option<usize> {
+        let count = self.players.len();
+        for i in 0..count {
+            let index = (self.next_rock_owner + i) % count;
+            if self.players[index].active {
+                self.next_rock_owner = index + 1;
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn client_handle_sounds(&mut self) {
+        for gem in self.gems.iter().chain(self.powerups.iter()).filter(|g| !g.kill) {
+            for player_obj in self.players.iter().filter(|p| p.active && p.dying_until <= 0.0) {
+                let distance = gem.pos - player_obj.actor.pos;
+                if distance.norm() < (player_obj.actor.bbox_size + gem.bbox_size) {
+                    self.play_sounds.play_pickup = true;
+                }
+            }
+        }
+
+        for rock in self.rocks.iter().filter(|r| !r.kill) {
+            for shot in self.shots.iter().filter(|s| !s.kill) {
+                let distance = shot_distance(shot, &rock.pos, self.game_setup.shots_wrap, self.world_width, self.world_height);
+                if distance < (shot.bbox_size + rock.bbox_size) {
+                    self.play_sounds.play_hit = true;
+                    return
+                }
+            }
+        }
+    }
+
+    pub(crate) fn spawn_rocks(&mut self, delta: f32) {
+        // A single long tick must not spawn a wall of rocks.
+        let delta = delta.min(self.game_setup.max_spawn_delta);
+        let loops = (delta / 0.004).round() as i32;
+
+        // The warm-up keeps the difficulty clock stopped at the very start.
+        let spawn = if self.warm_up { &WARM_UP_SPAWN } else { &RUN_SPAWN };
+        if !self.clock_frozen && !self.warm_up {
+            self.difficulty_points += self.difficulty_mult * delta;
+        }
+        let intensity = if self.warm_up { 0.0 } else { self.difficulty_points };
+
+        let spawnpercent =  (intensity / 1600.0 + 0.01) * spawn.chance;
+        let speed_mod = rock_speed_mod(intensity) * spawn.speed;
+        let mut max_angle = intensity / 240.0;
+
+        if max_angle > 0.5 {
+            max_angle = 0.5;
+        }
+
+        for _ in 0..loops {
+            if self.rng.next_f32() < spawnpercent {
+                let mut angle = self.rng.next_f32() * max_angle;
+                if self.rng.next_bool() {
+                    angle = -angle;
+                }
+                let x_pos = (self.rng.next_f32() * self.world_width) - self.world_width / 2.0;
+                let speed = self.rng.next_f32() * speed_mod + speed_mod / 2.0;
+
+                self.spawn_rock(x_pos, angle, speed, speed_mod);
+            }
+        }
+
+        for due in self.spawn_queue.tick(delta) {
+            self.spawn_rock(due.x, due.angle, due.speed, due.base_speed);
+        }
+        if !spawn.extras {
+            self.telegraphs = self.spawn_queue.telegraphs();
+            return;
+        }
+        // Pattern rocks are announced first, see `SpawnQueue`.
+        let lead = patterns::telegraph_time(intensity);
+        for event in self.patterns.tick(delta, intensity, &mut self.rng) {
+            for i in 0..event.count {
+                let x_fraction = event.x + event.spread * i as f32;
+                let x_pos = x_fraction * self.world_width - self.world_width / 2.0;
+                self.spawn_queue.schedule(x_pos, event.angle, speed_mod * event.speed, speed_mod, lead);
+            }
+        }
+        self.telegraphs = self.spawn_queue.telegraphs();
+
+        let wells = &self.game_setup.wells;
+        if wells.spawn_chance > 0.0 && intensity >= wells.min_intensity
+            && self.rng.next_f32() < wells.spawn_chance * delta {
+            let mut well = Actor::create_well(wells.pull, &self.tuning);
+            let x_pos = (self.rng.next_f32() - 0.5) * self.world_width;
+            // Just inside the top edge, a well configured not to drift must still be seen.
+            well.pos = Vector2::new(x_pos, self.world_height / 2.0 - well.bbox_size);
+            well.velocity = Vector2::new(0.0, -wells.drift_speed);
+            self.wells.push(well);
+        }
+    }
+
+    /// Server only. Moves the ramp as if `seconds` passed at the current multiplier, back for negative ones.
+    fn shift_difficulty(&mut self, seconds: f32) {
+        self.difficulty_points = (self.difficulty_points + seconds * self.difficulty_mult).max(0.0);
+    }
+
+    /// Server only. Runs the debug commands queued by local keys and by clients.
+    fn apply_debug_commands(&mut self) {
+        for command in std::mem::replace(&mut self.pending_debug, Vec::new()) {
+            println!("Debug: {:?}", command);
+            match command {
+                DebugCommand::ToggleClockFreeze => self.clock_frozen = !self.clock_frozen,
+                DebugCommand::ShiftClock(seconds) => self.shift_difficulty(seconds),
+                DebugCommand::SpawnRock(pos) => {
+                    let base_speed = rock_speed_mod(self.difficulty_points);
+                    self.spawn_rock(pos.x, 0.0, base_speed, base_speed);
+                    if let Some(rock) = self.rocks.last_mut() {
+                        rock.pos.y = pos.y;
+                    }
+                }
+                DebugCommand::ClearRocks => {
+                    for rock in &mut self.rocks {
+                        rock.kill = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// `base_speed` is the current speed scale, so a rock's value follows how fast it is for its time.
+    fn spawn_rock(&mut self, x_pos: f32, angle: f32, speed: f32, base_speed: f32) {
+        let mut rock = Actor::create_rock(&self.tuning);
+        rock.ang_vel = self.rng.next_f32() * 0.02;
+        rock.set_sprite((self.rng.next_u64() % ROCK_SPRITES as u64) as u8);
+        // Anywhere within the despawn margin survives its first tick, e.g. pattern rocks spread past the edge.
+        let limit = self.world_width / 2.0 + self.game_setup.rock_despawn_margin;
+        rock.pos = Vector2::new(x_pos.max(-limit).min(limit), rock.entry_height(self.world_height));
+        rock.velocity = vec_from_angle(std::f32::consts::PI + angle) * speed;
+
+        let danger = speed / base_speed + angle.abs() * ROCK_ANGLE_DANGER;
+        rock.score_value = if danger >= ROCK_VALUE_3_SPEED {
+            3
+        } else if danger >= ROCK_VALUE_2_SPEED {
+            2
+        } else {
+            1
+        };
+        if self.versus {
+            rock.owner = self.assign_rock_owner();
+        }
+        self.rocks.push(rock);
+    }
+
+    fn update_ui(&mut self, ctx: &mut Context) {
+        let role = match (self.role, self.local_player_index) {
+            (NetRole::Server, _) => self.tr_fmt("hud.role.server",
+                &[("players", &self.active_player_count()), ("spectators", &self.spectator_count())]),
+            (NetRole::Client, Some(x)) => self.tr_fmt("hud.role.client", &[("id", &x)]),
+            (NetRole::Client, None) => self.tr("hud.role.connecting").to_string(),
+            (NetRole::Spectator, _) => self.tr("hud.role.spectator").to_string(),
+        };
+
+        let graze = self.players.iter().map(|p| p.graze).sum::<f32>().round();
+        let lives = self.get_local_player().map(|p| p.lives).unwrap_or(0);
+        let score = self.score_text();
+        let score_str = if self.warm_up {
+            self.tr_fmt("hud.score.warm_up", &[("role", &role)])
+        } else if self.daily.is_some() {
+            self.tr_fmt("hud.score.daily", &[("score", &score), ("graze", &graze), ("lives", &lives)])
+        } else if self.practice {
+            let deaths: u32 = match self.get_local_player() {
+                Some(player) => player.deaths,
+                None => self.players.iter().map(|p| p.deaths).sum(),
+            };
+            self.tr_fmt("hud.score.practice", &[("score", &score), ("graze", &graze), ("deaths", &deaths), ("role", &role)])
+        } else if self.versus {
+            self.tr_fmt("hud.score.versus",
+                &[("standings", &self.standings_text()), ("score", &score), ("lives", &lives), ("role", &role)])
+        } else if self.hardcore {
+            self.tr_fmt("hud.score.hardcore", &[("score", &score), ("graze", &graze), ("lives", &lives), ("role", &role)])
+        } else {
+            self.tr_fmt("hud.score", &[("score", &score), ("graze", &graze), ("lives", &lives), ("role", &role)])
+        };
+        let mut level_str = self.tr_fmt("hud.level", &[
+            ("time", &format!("{:.1}", self.curr_time)),
+            ("difficulty", &format!("{:.2}", self.difficulty_mult)),
+            ("intensity", &format!("{:.0}", self.difficulty_points)),
+        ]);
+        if self.debug && self.clock_frozen {
+            level_str.push_str(self.tr("hud.level.frozen"));
+        }
+
+        let attendance = self.attendance();
+        let title = match &self.render {
+            Some(render) if render.title_checked_at.elapsed() >= WINDOW_TITLE_INTERVAL => Some(match self.role {
+                NetRole::Server => self.tr_fmt("title.server", &[("attendance", &attendance), ("score", &score)]),
+                NetRole::Client if self.local_player_index.is_some() =>
+                    self.tr_fmt("title.client", &[("attendance", &attendance), ("score", &score)]),
+                NetRole::Client => self.tr("title.connecting").to_string(),
+                NetRole::Spectator => self.tr_fmt("title.spectator", &[("attendance", &attendance), ("score", &score)]),
+            }),
+            _ => None,
+        };
+
+        let render = match &mut self.render {
+            Some(render) => render,
+            None => return,
+        };
+
+        if let Some(title) = title {
+            render.title_checked_at = std::time::Instant::now();
+            if title != render.window_title {
+                let _ = graphics::get_window_mut(ctx).set_title(&title);
+                render.window_title = title;
+            }
+        }
+
+        for result in render.screenshots.finished() {
+            match result {
+                Ok(path) => self.effects.toasts.push(render.strings.tr_fmt("toast.screenshot_saved", &[("path", &path.display())]),
+                    TOAST_TIME, Severity::Info),
+                Err(_) => self.effects.toasts.push(render.tr("toast.screenshot_failed").to_string(), TOAST_LONG_TIME, Severity::Error),
+            }
+        }
+
+        render.score_display = graphics::Text::new(ctx, &score_str, render.assets.font(HUD_FONT)).unwrap();
+        render.level_display = graphics::Text::new(ctx, &level_str, render.assets.font(HUD_FONT)).unwrap();
+
+        let _ = self.effects.refresh(ctx, render.assets.font(HUD_FONT), render.assets.font(BANNER_FONT));
+
+        if self.show_scoreboard {
+            let mut lines = Vec::with_capacity(self.players.len() + 2);
+            lines.push(graphics::Text::new(ctx, render.strings.tr("hud.scoreboard"), render.assets.font(HUD_FONT)).unwrap());
+            for p in self.players.iter().filter(|p| p.active) {
+                let line = format!("P{:<7} {:>5}   {:>5}   {:>5}   {:>7.1}%   {:>5}", 
+                    p.index + 1, p.lives, p.stats.shots_fired, p.stats.rocks_destroyed, p.stats.accuracy(), p.graze.round());
+                lines.push(graphics::Text::new(ctx, &line, render.assets.font(HUD_FONT)).unwrap());
+            }
+            lines.push(graphics::Text::new(ctx, &attendance, render.assets.font(HUD_FONT)).unwrap());
+            render.scoreboard_display = lines;
+        }
+
+        render.follow_display.clear();
+        let followed = match self.followed_player.and_then(|index| self.players.get(index)) {
+            Some(p) if self.role == NetRole::Spectator && p.active => p,
+            _ => return,
+        };
+        let strings = &render.strings;
+        let lines = [
+            strings.tr_fmt("hud.follow.title", &[("player", &(followed.index + 1))]),
+            strings.tr_fmt("hud.follow.lives", &[("lives", &followed.lives)]),
+            strings.tr_fmt("hud.follow.rocks", &[("rocks", &followed.stats.rocks_destroyed), ("graze", &followed.graze.round())]),
+            strings.tr_fmt("hud.follow.accuracy", &[("accuracy", &format!("{:.1}", followed.stats.accuracy()))]),
+        ];
+        for line in &lines {
+            render.follow_display.push(graphics::Text::new(ctx, line, render.assets.font(HUD_FONT)).unwrap());
+        }
+    }
+
+    fn play_sounds(&mut self) {
+        if let Some(render) = &mut self.render {
+            let assets = &mut render.assets;
+            if self.play_sounds.play_hit && !assets.hit_sound.playing() {
+                let _ = assets.hit_sound.play();
+            }
+            for &player in &self.play_sounds.shots_by {
+                let jitter = rand::random::<f32>() * 2.0 - 1.0;
+                assets.shot_voices.play(player, sfx::shot_pitch(player, jitter));
+            }
+            if self.play_sounds.play_graze && !assets.graze_sound.playing() {
+                let _ = assets.graze_sound.play();
+            }
+            if self.play_sounds.play_pickup && !assets.pickup_sound.playing() {
+                let _ = assets.pickup_sound.play();
+            }
+            if self.play_sounds.play_extra_life && !assets.extra_life_sound.playing() {
+                let _ = assets.extra_life_sound.play();
+            }
+            if self.play_sounds.play_death && !assets.death_sound.playing() {
+                let _ = assets.death_sound.play();
+            }
+            if self.play_sounds.play_milestone && !assets.milestone_sound.playing() {
+                let _ = assets.milestone_sound.play();
+            }
+            if self.play_sounds.play_time_tick && !assets.time_tick_sound.playing() {
+                let _ = assets.time_tick_sound.play();
+            }
+            if self.play_sounds.play_chime && !assets.chime_sound.playing() {
+                let _ = assets.chime_sound.play();
+            }
+        }
+        self.clear_sounds();
+    }
+
+    fn clear_sounds(&mut self) {
+        self.play_sounds = PlaySounds::default();
+    }
+
+    fn tick_physics(&mut self, seconds: f32) {
+        // Tick shots
+        let shots_wrap = self.game_setup.shots_wrap;
+        for shot in &mut self.shots {
+            shot.tick_physics(seconds, &self.tuning);
+
+            if shots_wrap {
+                shot.wrap_position(self.world_width, self.world_height);
+                if shot.age > SHOT_LIFETIME {
+                    shot.kill = true;
+                }
+            } else if shot.is_out_of_bounds(self.world_width, self.world_height) {
+                shot.kill = true;
+            }
+        }
+
+        for shot in &mut self.local_shots_made {
+            shot.tick_physics(seconds, &self.tuning);
+            if shots_wrap {
+                shot.wrap_position(self.world_width, self.world_height);
+            }
+        }
+
+        // Tick rocks, slowed down while a time slow is active. Clients cull them the same way.
+        for rock in &mut self.rocks {
+            rock.tick_physics(seconds * self.rock_time_scale, &self.tuning);
+
+            if rock.is_out_of_bounds_by(self.world_width, self.world_height, self.game_setup.rock_despawn_margin) {
+                rock.kill = true;
+            }
+        }
+
+        // Tick gems, uncollected ones are simply lost
+        for gem in &mut self.gems {
+            gem.tick_physics(seconds, &self.tuning);
+
+            if gem.age > GEM_LIFETIME
+                || gem.is_out_of_bounds(self.world_width, self.world_height) {
+                gem.kill = true;
+            }
+        }
+
+        for powerup in &mut self.powerups {
+            powerup.tick_physics(seconds, &self.tuning);
+
+            if powerup.age > POWERUP_LIFETIME
+                || powerup.is_out_of_bounds(self.world_width, self.world_height) {
+                powerup.kill = true;
+            }
+        }
+
+        for well in &mut self.wells {
+            well.tick_physics(seconds, &self.tuning);
+
+            if well.age > self.game_setup.wells.lifetime
+                || well.is_out_of_bounds(self.world_width, self.world_height) {
+                well.kill = true;
+            }
+        }
+    }
+
+    /// Server only. Fades out rocks that outlived `rock_lifetime`, like ones crawling sideways
+    /// along the top, and removes them once faded. They leave no gem and score nothing.
+    fn expire_old_rocks(&mut self) {
+        let lifetime = self.game_setup.rock_lifetime;
+        if lifetime <= 0.0 {
+            return;
+        }
+        for rock in &mut self.rocks {
+            rock.fade = ((rock.age - lifetime) / ROCK_FADE_TIME).max(0.0).min(1.0);
+            if rock.fade >= 1.0 {
+                rock.kill = true;
+            }
+        }
+    }
+
+    /// Drags shots and ships toward every well. Clients run it too, their own ship's
+    /// position is what the server gets, and remote ships and shots would drift apart otherwise.
+    fn pull_into_wells(&mut self, seconds: f32) {
+        let (width, height) = (self.world_width, self.world_height);
+        let shots_wrap = self.game_setup.shots_wrap;
+        for well in self.wells.iter().filter(|w| !w.kill) {
+            for shot in self.shots.iter_mut().chain(self.local_shots_made.iter_mut()) {
+                let offset = if shots_wrap {
+                    let (width, height) = shot.wrap_period(width, height);
+                    toroidal_delta(&well.pos, &shot.pos, width, height)
+                } else {
+                    well.pos - shot.pos
+                };
+                shot.velocity += well.pull.shot_pull(offset) * seconds;
+            }
+
+            for player in self.players.iter_mut().filter(|p| p.active && p.dying_until <= 0.0) {
+                let (width, height) = player.actor.wrap_period(width, height);
+                let offset = toroidal_delta(&well.pos, &player.actor.pos, width, height);
+                player.actor.pos += well.pull.player_pull(offset) * seconds;
+            }
+        }
+    }
+
+    /// Nudges overlapping ships apart, so co-op players can tell who is who. Ships that are
+    /// exploding, parked or still shielded after a respawn are left alone. The server moves
+    /// every ship, a client only its own against where it last saw the others, whose own
+    /// clients push them the same way.
+    fn separate_players(&mut self, seconds: f32) {
+        let (width, height) = (self.world_width, self.world_height);
+        let movable = |p: &Player| p.active && p.dying_until <= 0.0 && p.hit_flash <= 0.0;
+        let mut pushes = vec![Vector2::new(0.0, 0.0); self.players.len()];
+        for i in 0..self.players.len() {
+            for j in i + 1..self.players.len() {
+                let (a, b) = (&self.players[i], &self.players[j]);
+                if !movable(a) || !movable(b) {
+                    continue;
+                }
+                // Every ship has the same hitbox, so either one's wrap will do.
+                let (width, height) = a.actor.wrap_period(width, height);
+                let offset = toroidal_delta(&a.actor.pos, &b.actor.pos, width, height);
+                let distance = offset.norm();
+                let overlap = a.actor.bbox_size + b.actor.bbox_size - distance;
+                if overlap <= 0.0 {
+                    continue;
+                }
+                // Ships on the very same spot split sideways, the lower index to the right.
+                let away = if distance > 0.0 { offset / distance } else { Vector2::new(1.0, 0.0) };
+                let push = away * (overlap * SEPARATION_STIFFNESS).min(MAX_SEPARATION_SPEED) * seconds;
+                pushes[i] += push;
+                pushes[j] -= push;
+            }
+        }
+
+        let server = self.is_server();
+        for (player, push) in self.players.iter_mut().zip(pushes) {
+            if server || self.local_player_index == Some(player.index as usize) {
+                player.actor.pos += push;
+                player.actor.wrap_position(width, height);
+            }
+        }
+    }
+
+    /// Pulls gems that are close to a player towards them.
+    fn attract_gems(&mut self, seconds: f32) {
+        for gem in &mut self.gems {
+            let nearest = self.players.iter()
+                .map(|p| p.actor.pos - gem.pos)
+                .filter(|d| d.norm() < GEM_MAGNET_RADIUS)
+                .min_by(|a, b| a.norm().partial_cmp(&b.norm()).unwrap_or(std::cmp::Ordering::Equal));
+
+            if let Some(direction) = nearest {
+                if direction.norm() > 0.0 {
+                    gem.velocity += direction.normalize() * GEM_MAGNET_ACCEL * seconds;
+                }
+            }
+        }
+    }
+
+    fn update_player_inputs(&mut self, seconds: f32) {
+        let mut local_input = self.local_input.clone();
+        local_input.fire_pressed = self.fire_latch.take_for_sim();
+        if let Some(replay) = &mut self.input_replay {
+            match replay.input(self.sim_tick, local_input.clone()) {
+                Some(input) => local_input = input,
+                None => {
+                    println!("Input playback finished at step {}.", self.sim_tick);
+                    let text = self.tr("toast.playback_finished").to_string();
+                    self.effects.toasts.push(text, TOAST_LONG_TIME, Severity::Info);
+                }
+            }
+        }
+        if self.role == NetRole::Client && self.local_player_index.is_some() {
+            self.input_timeline.record(&local_input, seconds);
+        }
+        if let Some(player) = self.get_local_player_mut() {
+            player.input = local_input;
+        }
+
+        let server = self.is_server();
+        for player in &mut self.players {
+            // Exploding ships stay where they were hit, parked ones must not wrap back in.
+            if player.dying_until > 0.0 || !player.active {
+                continue;
+            }
+            if server && player.timeline.has_pending() {
+                // Moves like it did on the client, one input packet behind.
+                for step in player.timeline.advance(seconds) {
+                    player.input = step.input;
+                    player.tick_input(step.seconds, &self.tuning);
+                    if let Some(pos) = step.checkpoint {
+                        let correction = (pos - player.actor.pos).norm();
+                        if let Some(queue) = self.input_queues.get_mut(player.index as usize) {
+                            queue.stats.record_correction(correction);
+                        }
+                        player.actor.pos = pos;
+                    }
+                }
+            } else {
+                player.tick_input(seconds, &self.tuning);
+            }
+            player.actor.wrap_position(self.world_width, self.world_height);
+        }
+    
+        let mut local_volleys = 0;
+        for player in &mut self.players {
+
+            let wants_fire = (player.input.fire || player.input.fire_pressed) && player.dying_until <= 0.0 && player.active;
+            if wants_fire && player.last_shot_at <= self.curr_time - self.tuning.shot_cooldown {
+                player.last_shot_at = self.curr_time;
+
+                match (self.role, self.local_player_index) {
+                    (NetRole::Server, local) => {
+                        // Remote players' shots arrive through their NetClientInput.
+                        if local == Some(player.index as usize) {
+                            MainState::fire_player_shot(&mut self.shots, player, &self.rocks, self.aim_assist, &self.tuning);
+                            player.stats.shots_fired += 1;
+                            local_volleys += 1;
+                        }
+                    }
+                    (_, None) => {
+                        MainState::fire_player_shot(&mut self.shots, player, &self.rocks, 0.0, &self.tuning);
+                    }
+                    (_, Some(x)) => {
+                        if x == player.index as usize {
+                            let mut new_shots = Vec::new();
+                            MainState::fire_player_shot(&mut new_shots, player, &self.rocks, self.aim_assist, &self.tuning);
+                            self.local_shots_made.append(&mut new_shots.clone());
+                            self.shots.append(&mut new_shots);
+                        }
+                        else {
+                            MainState::fire_player_shot(&mut self.shots, player, &self.rocks, 0.0, &self.tuning);
+                        }
+                    }
+                }
+                
+                if !self.play_sounds.shots_by.contains(&(player.index as usize)) {
+                    self.play_sounds.shots_by.push(player.index as usize);
+                }
+            }
+        }
+        self.charge_volleys(local_volleys);
+    }
+
+    /// One fixed simulation step of the authoritative game. Needs no Context.
+    pub fn step_server(&mut self, seconds: f32) {
+        if !self.pending_debug.is_empty() {
+            self.apply_debug_commands();
+        }
+
+        let t = self.profiler.start();
+        self.update_player_inputs(seconds);
+        self.profiler.record(Phase::Input, t);
+
+        let t = self.profiler.start();
+        self.tick_time_slow(seconds);
+        self.attract_gems(seconds);
+        self.pull_into_wells(seconds);
+        self.separate_players(seconds);
+        self.tick_physics(seconds);
+        self.expire_old_rocks();
+        self.profiler.record(Phase::Physics, t);
+
+        let t = self.profiler.start();
+        self.handle_collisions();
+        self.clear_dead_stuff();
+        self.profiler.record(Phase::Collisions, t);
+
+        self.tick_popups(seconds);
+        let acked = self.event_ack();
+        self.event_log.prune(acked);
+
+        let t = self.profiler.start();
+        self.spawn_rocks(seconds * self.rock_time_scale);
+        self.profiler.record(Phase::Spawn, t);
+
+        if self.connections > 0 && self.snapshot.is_due() {
+            let t = self.profiler.start();
+            NetFromServer::publish(self);
+            self.profiler.record(Phase::Snapshot, t);
+        }
+        self.sim_tick += 1;
+    }
+
+    /// Perform interpolation & "prediction"
+    pub fn step_client(&mut self, seconds: f32) {
+        let t = self.profiler.start();
+        self.update_player_inputs(seconds);
+        self.profiler.record(Phase::Input, t);
+
+        let t = self.profiler.start();
+        if self.snapshots_stale() {
+            // Guessing any further would only make the snap back worse. Our own shots still fly.
+            for shot in &mut self.local_shots_made {
+                shot.tick_physics(seconds, &self.tuning);
+            }
+        } else {
+            self.tick_time_slow(seconds);
+            self.attract_gems(seconds);
+            self.pull_into_wells(seconds);
+            self.separate_players(seconds);
+            self.tick_physics(seconds);
+        }
+        let blend_secs = self.extrapolation.blend_ms as f32 / 1000.0;
+        for actor in self.rocks.iter_mut().chain(self.shots.iter_mut()).chain(self.gems.iter_mut()).chain(self.powerups.iter_mut()).chain(self.wells.iter_mut()) {
+            actor.blend_position(seconds, blend_secs);
+        }
+        self.profiler.record(Phase::Physics, t);
+
+        let t = self.profiler.start();
+        self.client_handle_sounds();
+        self.profiler.record(Phase::Collisions, t);
+
+        self.tick_popups(seconds);
+    }
+
+    fn real_update_server(&mut self, ctx: &mut Context, seconds: f32) -> GameResult<()> {
+        if self.paused_at.is_none() {
+            self.apply_queued_inputs();
+            self.kick_silent_players();
+            self.step_server(seconds);
+        }
+        let t = self.profiler.start();
+        self.update_ui(ctx);
+        self.profiler.record(Phase::Ui, t);
+        Ok(())
+    }
+
+    fn real_update_client(&mut self, ctx: &mut Context, seconds: f32) -> GameResult<()> {
+        self.step_client(seconds);
+        let t = self.profiler.start();
+        self.update_ui(ctx);
+        self.profiler.record(Phase::Ui, t);
+        Ok(())
+    }
+
+    /// Called by a player's receiver thread. The input waits for the next tick, see `apply_queued_inputs`.
+    pub(crate) fn queue_input(&mut self, index: usize, input: NetClientInput) {
+        while self.input_queues.len() <= index {
+            self.input_queues.push(Default::default());
+        }
+        self.input_queues[index].push(input, std::time::Instant::now());
+    }
+
+    /// The one place remote input enters the simulation: every waiting input in the
+    /// order it arrived, so the newest one ends up as the held state.
+    fn apply_queued_inputs(&mut self) {
+        for index in 0..self.input_queues.len() {
+            for queued in self.input_queues[index].take() {
+                queued.input.update_main_state(index, queued.received_at, self);
+            }
+        }
+    }
+
+    /// Parks remote players that haven't sent input for `idle_kick_seconds`, e.g. a
+    /// crashed client whose connection never errored. Their receiver thread notices
+    /// and closes the stream.
+    fn kick_silent_players(&mut self) {
+        if self.game_setup.idle_kick_seconds <= 0.0 {
+            return;
+        }
+        let limit = Duration::from_millis((self.game_setup.idle_kick_seconds * 1000.0) as u64);
+        let silent: Vec<usize> = self.players.iter()
+            .filter(|p| p.active && p.last_input_at.map_or(false, |at| at.elapsed() > limit))
+            .map(|p| p.index as usize)
+            .collect();
+        for index in silent {
+            println!("Kicking P{}, no input for {:?}.", index + 1, limit);
+            self.remove_player(index, GameEvent::PlayerKicked { player: index as u32 });
+        }
+    }
+
+    /// Follows the local player, or the centroid of all players when we don't have one,
+    /// keeping the view inside the world.
+    fn update_camera(&mut self) {
+        let target = match self.get_local_player().or_else(|| self.get_followed_player()) {
+            Some(player) => player.actor.pos,
+            None => {
+                let active: Vec<&Player> = self.players.iter().filter(|p| p.active).collect();
+                if active.is_empty() {
+                    na::zero()
+                } else {
+                    let sum = active.iter().fold(Vector2::new(0.0, 0.0), |acc, p| acc + p.actor.pos);
+                    sum / active.len() as f32
+                }
+            }
+        };
+
+        let max_x = ((self.world_width - VIEW_SIZE as f32) / 2.0).max(0.0);
+        let max_y = ((self.world_height - VIEW_SIZE as f32) / 2.0).max(0.0);
+        self.camera = Vector2::new(target.x.max(-max_x).min(max_x), target.y.max(-max_y).min(max_y));
+    }
+
+    fn s_draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // A paused spectator sees a buffered frame, swapped in just for drawing.
+        if let Some(frame) = self.scrub.viewed().cloned() {
+            let live = frame.swap_into(self);
+            let result = self.draw_view(ctx);
+            live.swap_into(self);
+            return result;
+        }
+        self.draw_view(ctx)
+    }
+
+    fn draw_view(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.update_camera();
+
+        let render = match &mut self.render {
+            Some(render) => render,
+            None => return Ok(()),
+        };
+
+        // Loop over all objects drawing them...
+        let t = self.profiler.start();
+        let window = (self.screen_width, self.screen_height);
+        graphics::set_screen_coordinates(ctx, view_coordinates(window.0, window.1))?;
+        {
+            let assets = &mut render.assets;
+            let palette = render.palette;
+            let coords = (VIEW_SIZE, VIEW_SIZE);
+            let camera = &self.camera;
+            
+            // Within a layer actors go in list order, which is the server's, so the
+            // same snapshot always gives the same frame. Killed actors are gone even if
+            // clients only drop them with the next snapshot.
+            for layer in DRAW_LAYERS.iter() {
+                match layer {
+                    DrawLayer::Rocks => {
+                        // Just below the top edge, where the rocks will come in.
+                        let telegraph_y = self.world_height / 2.0 - TELEGRAPH_INSET;
+                        for telegraph in &self.telegraphs {
+                            let pos = world_to_screen_coords(coords.0, coords.1, camera, Point2::new(telegraph.x, telegraph_y));
+                            hud::draw_telegraph(ctx, pos, telegraph.time_left, hud::telegraph_color(palette))?;
+                        }
+                        for r in self.rocks.iter().filter(|r| !r.kill) {
+                            // Only versus mode assigns rocks, they take the color of their player.
+                            let owner = r.owner.and_then(|index| self.players.get(index));
+                            let tint = owner.map(|p| hud::player_color(palette, p.color_index));
+                            draw_actor(assets, ctx, r, tint, coords, camera)?;
+                        }
+                        for w in self.wells.iter().filter(|w| !w.kill) {
+                            draw_actor(assets, ctx, w, None, coords, camera)?;
+                        }
+                    }
+                    DrawLayer::Pickups => {
+                        for g in self.gems.iter().filter(|g| !g.kill) {
+                            draw_actor(assets, ctx, g, None, coords, camera)?;
+                        }
+                        for p in self.powerups.iter().filter(|p| !p.kill) {
+                            draw_actor(assets, ctx, p, None, coords, camera)?;
+                        }
+                    }
+                    DrawLayer::Shots => {
+                        // With company everyone's shots take their player's color.
+                        let multiplayer = self.players.iter().filter(|p| p.active).count() > 1;
+                        for s in self.shots.iter().filter(|s| !s.kill) {
+                            let owner = s.owner.filter(|_| multiplayer).and_then(|index| self.players.get(index));
+                            let tint = owner.map(|p| hud::player_color(palette, p.color_index));
+                            draw_actor(assets, ctx, s, tint, coords, camera)?;
+                        }
+                    }
+                    DrawLayer::Players => {
+                        for p_obj in self.players.iter().filter(|p| p.active && p.dying_until <= 0.0) {
+                            // Blink while showing a practice hit or the respawn shield.
+                            if p_obj.hit_flash > 0.0 && (p_obj.hit_flash * 10.0) as i32 % 2 == 0 {
+                                continue;
+                            }
+                            if render.player_markers {
+                                let pos = Point2::new(p_obj.actor.pos.x, p_obj.actor.pos.y - p_obj.actor.bbox_size - 10.0);
+                                let pos = world_to_screen_coords(coords.0, coords.1, camera, pos);
+                                hud::draw_player_marker(ctx, pos, p_obj.color_index, hud::player_color(palette, p_obj.color_index))?;
+                            }
+                            draw_actor(assets, ctx, &p_obj.actor, None, coords, camera)?;
+                        }
+                    }
+                    DrawLayer::Effects => {
+                        for p_obj in self.players.iter().filter(|p| p.active && p.dying_until > 0.0) {
+                            // Once the animation is over the ship stays hidden until the respawn arrives.
+                            let progress = 1.0 - (p_obj.dying_until - self.curr_time) / DEATH_TIME;
+                            if progress < 1.0 {
+                                let color = hud::player_color(palette, p_obj.color_index);
+                                draw_dying_player(assets, ctx, p_obj, color, progress.max(0.0), coords, camera)?;
+                            }
+                        }
+
+                        self.effects.draw_popups(ctx, &render.extra_life_display,
+                            &|pos| world_to_screen_coords(coords.0, coords.1, camera, Point2::new(pos.x, pos.y + 30.0)))?;
+
+                        if self.time_slow_left > 0.0 {
+                            let alpha = TIME_SLOW_TINT_ALPHA * (self.time_slow_left / TIME_SLOW_TINT_FADE).min(1.0);
+                            let tint = graphics::Color::new(0.3, 0.5, 1.0, alpha);
+                            hud::draw_tint(ctx, coords, tint)?;
+                        }
+
+                        let followed = self.followed_player.and_then(|index| self.players.get(index));
+                        if let Some(p_obj) = followed.filter(|p| self.role == NetRole::Spectator && p.active) {
+                            let pos = world_to_screen_coords(coords.0, coords.1, camera, Point2::new(p_obj.actor.pos.x, p_obj.actor.pos.y));
+                            hud::draw_follow_ring(ctx, pos, p_obj.actor.bbox_size * 2.0, hud::player_color(palette, p_obj.color_index))?;
+                        }
+
+                        if self.show_debug {
+                            let players = self.players.iter().filter(|p| p.active).map(|p| &p.actor);
+                            let actors = players.chain(&self.shots).chain(&self.rocks).chain(&self.gems).chain(&self.powerups).chain(&self.wells);
+                            for actor in actors {
+                                draw_hitbox(ctx, actor, coords, camera)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // The HUD goes by window pixels and may sit on the bars.
+        graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, window.0 as f32, window.1 as f32))?;
+        hud::draw_letterbox(ctx, window, viewport(window.0, window.1))?;
+        self.profiler.record(Phase::DrawActors, t);
+
+        // And draw the GUI elements in the right places.
+        let t = self.profiler.start();
+        let layout = render.layout;
+        let text_color = render.text_color;
+        graphics::set_color(ctx, text_color)?;
+        layout.draw_text_shadowed(ctx, &render.level_display, Anchor::TopLeft, (10.0, 10.0), text_color)?;
+        // Pushed right by a long level line instead of running into it.
+        let score_x = (30.0 + render.level_display.width() as f32).max(200.0);
+        layout.draw_text_shadowed(ctx, &render.score_display, Anchor::TopLeft, (score_x, 10.0), text_color)?;
+        if self.auto_fire_engaged {
+            layout.draw_text_shadowed(ctx, &render.auto_fire_display, Anchor::TopLeft, (10.0, 34.0), text_color)?;
+        }
+        if self.debug {
+            let scale = 2.0 * layout.scale;
+            let size = (render.debug_display.width() as f32 * 2.0, render.debug_display.height() as f32 * 2.0);
+            graphics::draw_ex(ctx, &render.debug_display, graphics::DrawParam {
+                dest: layout.place(Anchor::TopCenter, (0.0, 40.0), size),
+                scale: Point2::new(scale, scale),
+                color: Some(graphics::Color::new(1.0, 0.2, 0.2, 1.0)),
+                ..Default::default()
+            })?;
+        }
+        let toasts_bottom = if self.show_minimap { hud::MINIMAP_CLEARANCE + 10.0 } else { 10.0 };
+        self.effects.draw(ctx, &layout, text_color, toasts_bottom)?;
+
+        if self.snapshots_stale() {
+            let waiting = graphics::Text::new(ctx, render.tr("hud.waiting_for_server"), render.assets.font(HUD_FONT))?;
+            layout.draw_text(ctx, &waiting, Anchor::Center, (0.0, 0.0), None)?;
+        }
+        if self.warm_up {
+            let waiting = graphics::Text::new(ctx, render.tr("hud.waiting_for_players"), render.assets.font(HUD_FONT))?;
+            layout.draw_text(ctx, &waiting, Anchor::TopCenter, (0.0, 80.0), None)?;
+        }
+        if self.paused_at.is_some() {
+            let paused = graphics::Text::new(ctx, render.tr("hud.paused"), render.assets.font(BANNER_FONT))?;
+            layout.draw_text(ctx, &paused, Anchor::Center, (0.0, 0.0), None)?;
+        }
+        if self.recovered_from.is_some() {
+            let recovered = graphics::Text::new(ctx, render.tr("hud.recovered"), render.assets.font(HUD_FONT))?;
+            layout.draw_text(ctx, &recovered, Anchor::Center, (0.0, 60.0), None)?;
+        }
+        if self.scrub.is_paused() {
+            let text = render.strings.tr_fmt("hud.replay", &[("behind", &format!("{:.1}", self.scrub.behind()))]);
+            let replay = graphics::Text::new(ctx, &text, render.assets.font(HUD_FONT))?;
+            layout.draw_text(ctx, &replay, Anchor::Center, (0.0, 0.0), None)?;
+        }
+
+        if self.show_minimap {
+            let alpha = if self.show_scoreboard { 0.35 } else { 1.0 };
+            let view = hud::MinimapView {
+                world: (self.world_width, self.world_height),
+                players: &self.players,
+                rocks: &self.rocks,
+                highlighted: self.local_player_index.or(self.followed_player),
+            };
+            hud::draw_minimap(ctx, &layout, &view, render.palette, alpha)?;
+        }
+
+        // Fields only, `render` still borrows self.
+        let followed = self.followed_player.and_then(|index| self.players.get(index));
+        if let Some(followed) = followed.filter(|p| self.role == NetRole::Spectator && p.active) {
+            hud::draw_follow_panel(ctx, &layout, &render.follow_display, followed, render.palette, text_color)?;
+        }
+
+        // The minimap and panel put the color back to white.
+        graphics::set_color(ctx, text_color)?;
+        if self.show_scoreboard {
+            // Left aligned in a column centered on the screen.
+            for (i, line) in render.scoreboard_display.iter().enumerate() {
+                let margin = (line.width() as f32 / 2.0 - 180.0, 120.0 + 24.0 * i as f32);
+                layout.draw_text_shadowed(ctx, line, Anchor::TopCenter, margin, text_color)?;
+            }
+        }
+        self.profiler.record(Phase::DrawHud, t);
+
+        if self.show_debug {
+            self.profiler.draw(ctx, render.assets.font(SMALL_FONT), &layout, text_color)?;
+            graphics::set_color(ctx, text_color)?;
+            let net_line = format!("snapshots dropped {}  inputs coalesced {}  bad messages {}  unexpected actors {}  truncated {}",
+                self.net_stats.snapshots_dropped, self.net_stats.inputs_coalesced, self.net_stats.bad_messages,
+                self.net_stats.unexpected_actors, self.net_stats.truncated_snapshots);
+            let net_text = graphics::Text::new(ctx, &net_line, render.assets.font(SMALL_FONT))?;
+            layout.draw_text(ctx, &net_text, Anchor::BottomLeft, (10.0, 160.0 - net_text.height() as f32), None)?;
+
+            if self.is_server() {
+                // Stacked upwards from the net counters, so the first player ends up on top.
+                for (i, player) in self.players.iter().rev().enumerate() {
+                    let age = match player.last_input_at {
+                        Some(at) => format!("{} ms", at.elapsed().as_millis()),
+                        None => "local".to_string(),
+                    };
+                    let mut line = format!("P{} {:<6} input {}  at ({:.0}, {:.0})", player.index + 1, player.input.compact(),
+                        age, player.actor.pos.x, player.actor.pos.y);
+                    if let Some(queue) = self.input_queues.get(player.index as usize) {
+                        let stats = &queue.stats;
+                        line.push_str(&format!("  received {} lost {} merged {} max queued {}  corrected {:.0}/{:.0}px",
+                            stats.received, stats.lost, stats.merged, stats.max_depth, stats.last_correction, stats.max_correction));
+                    }
+                    let text = graphics::Text::new(ctx, &line, render.assets.font(SMALL_FONT))?;
+                    let margin = (10.0, 184.0 + 24.0 * i as f32 - text.height() as f32);
+                    layout.draw_text(ctx, &text, Anchor::BottomLeft, margin, None)?;
+                }
+            }
+        }
+        graphics::set_color(ctx, graphics::WHITE)?;
+        self.profiler.end_frame();
+
+        // Play our sound queue
+        self.play_sounds();
+
+        Ok(())
+    }
+
+    /// The view stays `VIEW_SIZE` units across and only gets scaled, `draw_view` sets the
+    /// coordinates each frame. The HUD is laid out again for the new size.
+    fn s_resize(&mut self, _ctx: &mut Context, width: u32, height: u32) {
+        self.screen_width = width;
+        self.screen_height = height;
+        if let Some(render) = &mut self.render {
+            render.layout = hud::Layout::new((width, height));
+        }
+    }
+
+    // Handle key events.  These just map keyboard events
+    // and alter our input state appropriately.
+    fn s_key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, keymod: Mod, repeat: bool) {
+        match keycode {
+            Keycode::Escape => {
+                ctx.quit().unwrap();
+                return;
+            }
+            Keycode::Tab => {
+                self.show_scoreboard = true;
+                return;
+            }
+            Keycode::Return if self.warm_up && self.is_server() && !repeat => {
+                self.end_warm_up();
+                return;
+            }
+            Keycode::F3 if !repeat => {
+                self.show_debug = !self.show_debug;
+                self.profiler.enabled = self.show_debug;
+                return;
+            }
+            Keycode::F5 | Keycode::F6 if self.is_server() => {
+                let step = if keycode == Keycode::F5 { -DIFFICULTY_STEP } else { DIFFICULTY_STEP };
+                let difficulty = self.difficulty_mult + step;
+                self.set_difficulty(difficulty);
+                return;
+            }
+            Keycode::Num1 | Keycode::Num2 | Keycode::Num3 | Keycode::Num4 if self.is_server() && !repeat => {
+                let preset = match keycode {
+                    Keycode::Num1 => 0,
+                    Keycode::Num2 => 1,
+                    Keycode::Num3 => 2,
+                    _ => 3,
+                };
+                self.pick_difficulty_preset(preset);
+                return;
+            }
+            Keycode::Num1 | Keycode::Num2 | Keycode::Num3 | Keycode::Num4 if self.role == NetRole::Spectator && !repeat => {
+                let index = match keycode {
+                    Keycode::Num1 => 0,
+                    Keycode::Num2 => 1,
+                    Keycode::Num3 => 2,
+                    _ => 3,
+                };
+                // Picking the same player again goes back to watching the whole field.
+                self.followed_player = if self.followed_player == Some(index) { None } else { Some(index) };
+                return;
+            }
+            Keycode::Space if self.role == NetRole::Spectator && !repeat => {
+                self.scrub.toggle_pause();
+                return;
+            }
+            Keycode::Comma | Keycode::Period if self.role == NetRole::Spectator => {
+                self.scrub.step(if keycode == Keycode::Comma { -1 } else { 1 });
+                return;
+            }
+            Keycode::LeftBracket | Keycode::RightBracket if self.practice && self.is_server() => {
+                if self.blocked_by_input_log() {
+                    return;
+                }
+                let scrub = Duration::from_secs(PRACTICE_SCRUB_SECONDS);
+                // The timer is measured from start_time, so moving it back moves the time forward.
+                if keycode == Keycode::RightBracket {
+                    if let Some(start) = self.start_time.checked_sub(scrub) {
+                        self.start_time = start;
+                    }
+                    self.shift_difficulty(PRACTICE_SCRUB_SECONDS as f32);
+                } else {
+                    let now = std::time::Instant::now();
+                    self.start_time = (self.start_time + scrub).min(now);
+                    self.shift_difficulty(-(PRACTICE_SCRUB_SECONDS as f32));
+                }
+                self.update_time();
+                println!("Practice time: {:.1}s", self.curr_time);
+                return;
+            }
+            Keycode::PageUp | Keycode::PageDown if self.debug && self.is_server() && keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                let current = self.transfer_ms.load(Ordering::Relaxed);
+                let ms = if keycode == Keycode::PageUp {
+                    current.saturating_sub(DEBUG_TRANSFER_STEP)
+                } else {
+                    current + DEBUG_TRANSFER_STEP
+                };
+                let ms = self.set_transfer_ms(ms);
+                println!("Snapshot interval: {}ms", ms);
+                return;
+            }
+            Keycode::F1 | Keycode::PageUp | Keycode::PageDown | Keycode::Insert | Keycode::Delete if self.debug => {
+                let command = match keycode {
+                    Keycode::F1 if !repeat => DebugCommand::ToggleClockFreeze,
+                    Keycode::PageUp => DebugCommand::ShiftClock(DEBUG_CLOCK_STEP),
+                    Keycode::PageDown => DebugCommand::ShiftClock(-DEBUG_CLOCK_STEP),
+                    Keycode::Insert if !repeat => {
+                        let cursor = ggez::mouse::get_position(ctx).unwrap_or(Point2::new(0.0, 0.0));
+                        let cursor = pixel_to_view(self.screen_width, self.screen_height, cursor);
+                        let pos = screen_to_world_coords(VIEW_SIZE, VIEW_SIZE, &self.camera, cursor);
+                        DebugCommand::SpawnRock(Vec2Serial::from_vec(&pos))
+                    }
+                    Keycode::Delete if !repeat => DebugCommand::ClearRocks,
+                    _ => return,
+                };
+                if !self.blocked_by_input_log() {
+                    self.pending_debug.push(command);
+                }
+                return;
+            }
+            Keycode::F7 if self.is_server() && !repeat => {
+                if !self.blocked_by_input_log() {
+                    self.hard_reset();
+                }
+                return;
+            }
+            Keycode::F9 if !repeat => {
+                self.save_game();
+                return;
+            }
+            Keycode::F10 if !repeat => {
+                self.load_game();
+                return;
+            }
+            Keycode::F8 if !repeat => {
+                self.cycle_ship();
+                return;
+            }
+            Keycode::F12 if !repeat => {
+                self.take_screenshot(ctx);
+                return;
+            }
+            Keycode::M => {
+                self.show_minimap = !self.show_minimap;
+                return;
+            }
+            Keycode::F if !repeat => {
+                self.input_setup.auto_fire = !self.input_setup.auto_fire;
+                self.set_auto_fire(false);
+                println!("Auto-fire: {}", if self.input_setup.auto_fire { "on" } else { "off" });
+                return;
+            }
+            _ => (),
+        }
+
+        // Spectators and refereeing servers have no ship to steer.
+        if !self.steers_a_ship() {
+            return;
+        }
+
+        let input_ref = &mut self.local_input;
+        match self.key_bindings.action(keycode) {
+            Some(Action::Up) => {
+                input_ref.up = true;
+            }
+            Some(Action::Down) => {
+                input_ref.down = true;
+            }
+            Some(Action::Left) => {
+                input_ref.left = true;
+            }
+            Some(Action::Right) => {
+                input_ref.right = true;
+            }
+            Some(Action::Fire) => {
+                if !self.input_setup.auto_fire {
+                    input_ref.fire = true;
+                    if !repeat {
+                        self.fire_latch.press();
+                    }
+                } else if !repeat {
+                    let engaged = !self.auto_fire_engaged;
+                    self.set_auto_fire(engaged);
+                }
+            }
+            _ => (), // Do nothing
+        }
+    }
+
+    fn s_key_up_event(&mut self, _ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool) {
+        if keycode == Keycode::Tab {
+            self.show_scoreboard = false;
+            return;
+        }
+
+        if !self.steers_a_ship() {
+            return;
+        }
+
+        let input_ref = &mut self.local_input;
+        match self.key_bindings.action(keycode) {
+            Some(Action::Up) => {
+                input_ref.up = false;
+            }
+            Some(Action::Down) => {
+                input_ref.down = false;
+            }
+            Some(Action::Left) => {
+                input_ref.left = false;
+            }
+            Some(Action::Right) => {
+                input_ref.right = false;
+            }
+            Some(Action::Fire) => {
+                // With auto-fire only the next tap stops shooting.
+                if !self.input_setup.auto_fire {
+                    input_ref.fire = false;
+                }
+            }
+            _ => (), // Do nothing
+        }
+    }
+
+    /// Key-ups go to whichever window has focus, so keys held while switching away would
+    /// stay pressed. Everything is released instead, clients send that with their next input.
+    fn s_focus_event(&mut self, gained: bool) {
+        if gained {
+            self.resume();
+            return;
+        }
+        self.local_input = InputState::default();
+        self.fire_latch.clear();
+        self.auto_fire_engaged = false;
+        self.show_scoreboard = false;
+
+        let others_playing = self.players.iter()
+            .any(|p| p.active && Some(p.index as usize) != self.local_player_index);
+        if self.input_setup.pause_on_focus_loss && self.is_server() && self.steers_a_ship() && !others_playing {
+            self.paused_at = Some(std::time::Instant::now());
+        }
+    }
+
+    /// Ends a pause, moving the start of the run up so the pause doesn't count as play time.
+    /// A game recovered from a panic stays paused.
+    fn resume(&mut self) {
+        if self.recovered_from.is_some() {
+            return;
+        }
+        if let Some(paused_at) = self.paused_at.take() {
+            self.start_time += paused_at.elapsed();
+        }
+    }
+
+    /// After a panic left this state half updated: freezes the simulation for good and drops
+    /// what players were holding. `cause` says where the panic happened.
+    pub(crate) fn enter_safe_state(&mut self, cause: String) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(std::time::Instant::now());
+        }
+        self.local_input = InputState::default();
+        self.fire_latch.clear();
+        self.auto_fire_engaged = false;
+        let text = self.tr_fmt("toast.internal_error", &[("cause", &cause)]);
+        self.effects.toasts.push(text, TOAST_LONG_TIME, Severity::Error);
+        self.recovered_from = Some(cause);
+    }
+
+    /// Server only. Sets the difficulty to `DIFFICULTY_PRESETS[preset]`, unless a run is
+    /// underway: mid-run only F5/F6 nudge it. Whether the preset was taken.
+    pub fn pick_difficulty_preset(&mut self, preset: usize) -> bool {
+        if !self.is_server() || !self.between_runs() {
+            return false;
+        }
+        let (name, difficulty) = match DIFFICULTY_PRESETS.get(preset) {
+            Some(&preset) => preset,
+            None => return false,
+        };
+        println!("Difficulty preset: {}", name);
+        self.set_difficulty(difficulty);
+        true
+    }
+
+    /// Server only. Rock spawning reads the multiplier every tick, so it takes effect right away.
+    fn set_difficulty(&mut self, difficulty: f32) {
+        if self.blocked_by_input_log() {
+            return;
+        }
+        let difficulty = difficulty.max(MIN_DIFFICULTY).min(MAX_DIFFICULTY);
+        if difficulty == self.difficulty_mult {
+            return;
+        }
+        self.difficulty_mult = difficulty;
+        println!("Difficulty Multiplier: {:?}", difficulty);
+        self.emit(GameEvent::DifficultyChanged { difficulty });
+    }
+
+    /// Picks the next ship skin. Clients tell the server when joining, so theirs can only change before that.
+    fn cycle_ship(&mut self) {
+        if self.role != NetRole::Server {
+            let text = self.tr("toast.ship_when_joining").to_string();
+            self.effects.toasts.push(text, TOAST_TIME, Severity::Warning);
+            return;
+        }
+        self.local_ship = (self.local_ship + 1) % SHIP_SKINS;
+        let ship = self.local_ship;
+        if let Some(player) = self.get_local_player_mut() {
+            player.set_ship(ship);
+        }
+    }
+
+    /// Saves only offline games. Remote players couldn't be brought back on load, kicked ones
+    /// keep their slot but are gone.
+    fn can_save_or_load(&mut self) -> bool {
+        let others_playing = self.players.iter()
+            .any(|p| p.active && Some(p.index as usize) != self.local_player_index);
+        let reason = if !self.is_server() {
+            Some("toast.cant_save.not_server")
+        } else if others_playing {
+            Some("toast.cant_save.others_connected")
+        } else if self.daily.is_some() {
+            Some("toast.cant_save.daily")
+        } else if self.input_replay.is_some() {
+            Some("toast.cant_save.input_log")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                let text = self.tr_fmt("toast.cant_save", &[("reason", &self.tr(reason))]);
+                self.effects.toasts.push(text, TOAST_TIME, Severity::Warning);
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn save_game(&mut self) {
+        if !self.can_save_or_load() {
+            return;
+        }
+        let (message, severity) = match SaveGame::capture(self).write(savegame::SAVE_FILENAME) {
+            Ok(()) => (self.tr_fmt("toast.saved", &[("file", &savegame::SAVE_FILENAME)]), Severity::Info),
+            Err(e) => (self.tr_fmt("toast.save_failed", &[("file", &savegame::SAVE_FILENAME), ("error", &e)]), Severity::Error),
+        };
+        println!("{}", message);
+        self.effects.toasts.push(message, TOAST_TIME, severity);
+    }
+
+    fn load_game(&mut self) {
+        if !self.can_save_or_load() {
+            return;
+        }
+        let (message, severity) = match SaveGame::read(savegame::SAVE_FILENAME) {
+            Ok(save) => {
+                save.restore(self);
+                self.set_auto_fire(false);
+                self.local_input = InputState::default();
+                let time = format!("{:.1}", self.curr_time);
+                (self.tr_fmt("toast.resumed", &[("time", &time), ("score", &self.score_text())]), Severity::Info)
+            }
+            Err(e) => (self.tr_fmt("toast.load_failed", &[("file", &savegame::SAVE_FILENAME), ("error", &e)]), Severity::Error),
+        };
+        println!("{}", message);
+        self.effects.toasts.push(message, TOAST_TIME, severity);
+    }
+
+    /// Grabs the frame here, the writer thread does the encoding and the disk.
+    fn take_screenshot(&mut self, ctx: &mut Context) {
+        let render = match &self.render {
+            Some(render) => render,
+            None => return,
+        };
+        let grabbed = graphics::screenshot(ctx)
+            .and_then(|image| Ok((image.to_rgba8(ctx)?, image.width(), image.height())));
+        match grabbed {
+            Ok((pixels, width, height)) => render.screenshots.save(pixels, width, height),
+            Err(e) => println!("Failed to take screenshot: {}", e),
+        }
+    }
+
+    /// Auto-fire drives `local_input.fire` directly, so nothing past the input layer knows about it.
+    /// Anything that takes the keyboard away from the game should disengage it.
+    fn set_auto_fire(&mut self, engaged: bool) {
+        self.auto_fire_engaged = engaged;
+        self.local_input.fire = engaged;
+        if engaged {
+            self.fire_latch.press();
+        }
+    }
+
+}
+
+fn print_instructions(strings: &Strings) {
+    println!();
+    println!("{}", strings.tr("instructions.welcome"));
+    println!();
+}
+
+/// `tint` replaces the color the actor would get otherwise.
+fn draw_actor(
+    assets: &mut Assets,
+    ctx: &mut Context,
+    actor: &Actor,
+    tint: Option<graphics::Color>,
+    world_coords: (u32, u32),
+    camera: &Vector2,
+) -> GameResult<()> {
+    const CULL_MARGIN: f32 = 64.0;
+
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, camera, Point2::new(actor.pos.x, actor.pos.y));
+    if pos.x < -CULL_MARGIN || pos.y < -CULL_MARGIN
+        || pos.x > screen_w as f32 + CULL_MARGIN || pos.y > screen_h as f32 + CULL_MARGIN {
+        return Ok(());
+    }
+
+    // Rocks worth more, and their gems, are warmer and a bit larger. Wells throb.
+    let (scale, color) = if actor.tag == ActorType::Well {
+        (1.0 + WELL_PULSE * (actor.age * WELL_PULSE_RATE).sin(), None)
+    } else {
+        match actor.score_value {
+            v if v >= 3 => (1.2, Some(graphics::Color::new(1.0, 0.55, 0.4, 1.0))),
+            2 => (1.1, Some(graphics::Color::new(1.0, 0.85, 0.55, 1.0))),
+            _ => (1.0, None),
+        }
+    };
+
+    let mut color = tint.or(color);
+    if actor.fade > 0.0 {
+        let mut faded = color.unwrap_or(graphics::WHITE);
+        faded.a *= 1.0 - actor.fade;
+        color = Some(faded);
+    }
+    let scale = scale * assets.actor_scale(actor);
+    let image = assets.actor_image(actor);
+    let drawparams = graphics::DrawParam {
+        dest: pos,
+        rotation: actor.facing as f32,
+        offset: graphics::Point2::new(0.5, 0.5),
+        scale: graphics::Point2::new(scale, scale),
+        color,
+        ..Default::default()
+    };
+    graphics::draw_ex(ctx, image, drawparams)
+}
+
+/// Debug overlay outline of the circle collisions are tested against.
+fn draw_hitbox(ctx: &mut Context, actor: &Actor, world_coords: (u32, u32), camera: &Vector2) -> GameResult<()> {
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, camera, Point2::new(actor.pos.x, actor.pos.y));
+    graphics::set_color(ctx, graphics::Color::new(0.2, 1.0, 0.2, 0.8))?;
+    graphics::circle(ctx, graphics::DrawMode::Line(1.0), pos, actor.bbox_size, 0.5)?;
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// The ship swells and fades while debris in the player's color flies off. `progress` runs from 0 to 1.
+fn draw_dying_player(
+    assets: &mut Assets,
+    ctx: &mut Context,
+    player: &Player,
+    color: graphics::Color,
+    progress: f32,
+    world_coords: (u32, u32),
+    camera: &Vector2,
+) -> GameResult<()> {
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, camera, Point2::new(player.actor.pos.x, player.actor.pos.y));
+    let alpha = 1.0 - progress;
+    let scale = (1.0 + progress * DEATH_SWELL) * assets.actor_scale(&player.actor);
+
+    let image = assets.actor_image(&player.actor);
+    graphics::draw_ex(ctx, image, graphics::DrawParam {
+        dest: pos,
+        rotation: player.actor.facing,
+        offset: graphics::Point2::new(0.5, 0.5),
+        scale: graphics::Point2::new(scale, scale),
+        color: Some(graphics::Color::new(1.0, 1.0, 1.0, alpha)),
+        ..Default::default()
+    })?;
+
+    let mut color = color;
+    color.a = alpha;
+    graphics::set_color(ctx, color)?;
+    for i in 0..DEATH_PARTICLES {
+        let angle = (i as f32 / DEATH_PARTICLES as f32) * 2.0 * std::f32::consts::PI + player.index as f32;
+        // Uneven distances so the debris doesn't fly as a perfect ring.
+        let distance = progress * DEATH_PARTICLE_RANGE * (0.6 + 0.1 * (i * 7 % 5) as f32);
+        let particle = Point2::new(pos.x + angle.cos() * distance, pos.y + angle.sin() * distance);
+        graphics::circle(ctx, graphics::DrawMode::Fill, particle, 2.0, 0.5)?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+impl EventHandler for StatePtr {
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx);
+        let r = self.lock().s_draw(ctx);
+        graphics::present(ctx);
+
+        self.limiter.wait();
+        r
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+
+        const DESIRED_FPS: u32 = 144;
+
+        // A capped frame rate needs several steps every frame, that's not a stall.
+        let max_steps = match self.lock().game_setup.max_catch_up_steps {
+            0 => 0,
+            configured => configured.max(self.limiter.steps_per_frame(DESIRED_FPS)),
+        };
+        let mut steps = 0;
+        
+        while timer::check_update_time(ctx, DESIRED_FPS) {
+            if max_steps > 0 && steps >= max_steps {
+                // We were stalled (sleep, window drag...), drop the backlog instead of fast-forwarding.
+                let mut skipped = 1;
+                while timer::check_update_time(ctx, DESIRED_FPS) {
+                    skipped += 1;
+                }
+                println!("Frame stalled: ran {} update steps and skipped {}", steps, skipped);
+                break;
+            }
+            steps += 1;
+
+            let seconds = 1.0 / (DESIRED_FPS as f32);
+
+            let mut locked_state = self.lock();          
+            
+            if locked_state.is_server() {
+                locked_state.advance_time(seconds);
+                locked_state.real_update_server(ctx, seconds)?;
+            }
+            else {
+                locked_state.curr_time += seconds;
+                locked_state.real_update_client(ctx, seconds)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool) {
+        self.lock().s_key_down_event(ctx, keycode, _keymod, _repeat)
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool) {
+        self.lock().s_key_up_event(_ctx, keycode, _keymod, _repeat)
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: u32, height: u32) {
+        self.lock().s_resize(ctx, width, height);
+    }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        self.lock().s_focus_event(gained);
+    }
+}
@@ -7,26 +7,150 @@ use ggez::graphics;
 use ggez::{Context, GameResult};
 
 use crate::actor;
-use actor::Actor;
+use actor::{Actor, Vec2Serial};
+use crate::launch_options::LaunchOptions;
+use crate::stats::RunLog;
+use crate::leaderboard::Leaderboard;
+use crate::config::{GameSetup, InputSetup, DisplaySetup, FontFace, SpriteFitSetup, Palette};
+use crate::limiter::FrameLimiter;
+use crate::events::{GameEvent, EventLog};
+use crate::hud::{self, Layout};
+use crate::effects::Effects;
+use crate::bindings::KeyBindings;
+use crate::screenshot::ScreenshotWriter;
+use crate::profiler::FrameProfiler;
+use crate::rng::SimRng;
+use crate::patterns::{PatternScheduler, SpawnQueue, Telegraph};
+use crate::net_structs::{InputQueue, SnapshotCache};
+use crate::replay::InputReplay;
+use crate::scrub::ScrubBuffer;
+use crate::input_timeline::InputTimeline;
+use crate::tuning::Tuning;
+use crate::sfx::ShotVoices;
+use crate::recovery;
+use crate::strings::{self, Strings};
 
-use std::sync::{Mutex, Arc};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::sync::{Mutex, MutexGuard, Arc};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
+/// Shots spawned every time a player fires, and the sideways speed between neighbouring
+/// ones, unless game_setup.json says otherwise. The outer shots fly at a third of the shot speed.
+pub const DEFAULT_VOLLEY: u8 = 3;
+pub const DEFAULT_SPREAD: f32 = 1100.0 / 3.0;
+/// Points a volley costs in hardcore mode. A rock is still worth 1.
+pub const HARDCORE_VOLLEY_COST: f32 = 0.2;
+/// Versus points for destroying a rock assigned to yourself, and to another player.
+pub const VERSUS_OWN_ROCK_POINTS: i32 = 1;
+pub const VERSUS_OTHER_ROCK_POINTS: i32 = 2;
+/// Versus points a player loses when a rock assigned to them escapes off the bottom.
+pub const VERSUS_ESCAPE_PENALTY: i32 = 1;
 
-const PLAYER_SPEED: f32 = 500.0;
+/// Rocks move at this fraction of their speed while a time-slow pickup is active.
+const TIME_SLOW_SCALE: f32 = 0.4;
+const TIME_SLOW_DURATION: f32 = 5.0;
+/// The last seconds of a time slow tick every `TIME_SLOW_TICK_EVERY` as a warning.
+const TIME_SLOW_WARN_TIME: f32 = 1.5;
+const TIME_SLOW_TICK_EVERY: f32 = 0.5;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Number of ship skins players pick from, see `Player::ship`.
+pub const SHIP_SKINS: u8 = 3;
+
+/// Seconds a hit player spends exploding before respawning or ending the run.
+pub const DEATH_TIME: f32 = 1.0;
+
+/// Milliseconds between snapshots unless net_setup.json says otherwise.
+pub const DEFAULT_TRANSFER_MS: u64 = 33;
+/// Limits for changing the snapshot interval while the server runs, see `MainState::set_transfer_ms`.
+pub const MIN_TRANSFER_MS: u64 = 10;
+pub const MAX_TRANSFER_MS: u64 = 500;
+/// Clients keep extrapolating for at least this many snapshot intervals before calling snapshots stale.
+const STALE_AFTER_INTERVALS: u64 = 3;
+
+/// World units across the view, whatever the window's size, and the size of the square
+/// playfield before `LaunchOptions::world_scale`. Windows of another shape get letterboxed.
+pub const VIEW_SIZE: u32 = 1080;
+
+/// Inactive players are parked this far out on both axes, well clear of anything.
+const PARKED_COORD: f32 = 1.0e6;
+
+/// Per-player shooting statistics. A whole volley counts as a single shot.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub shots_fired: u32,
+    pub rocks_destroyed: u32,
+}
+
+impl PlayerStats {
+    /// Rocks destroyed per volley as a percentage, capped at 100.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 0.0;
+        }
+        (self.rocks_destroyed as f32 / self.shots_fired as f32 * 100.0).min(100.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Player {
     pub actor: Actor,
     pub input: InputState,
     pub index: u32,
+    pub stats: PlayerStats,
+    pub lives: u32,
+    pub color_index: u8,
+    /// Seconds left of the blinking that shows a practice hit, or of the shield after a respawn.
+    pub hit_flash: f32,
+    /// Game time the death animation ends at, 0 when alive. The player can't act until then.
+    pub dying_until: f32,
+    /// Points earned by rocks passing close by.
+    pub graze: f32,
+    /// Hits taken in practice mode, where they don't cost lives.
+    pub deaths: u32,
+
+    #[serde(skip)]
+    pub last_shot_at: f32,
+
+    /// Rocks destroyed since this player last got hit. Only tracked on the server.
+    #[serde(skip)]
+    pub streak: u32,
+
+    /// Skin chosen by the player, see `set_ship`.
+    pub ship: u8,
+
+    /// When the server last accepted a `NetClientInput` from this player. Never set for local players.
+    #[serde(skip)]
+    pub last_input_at: Option<std::time::Instant>,
+
+    /// False once the player disconnected or was kicked. The slot stays so indices don't shift.
+    pub active: bool,
 
+    /// Sent by the client when joining, a reconnect with the same token gets this slot back. Server only.
     #[serde(skip)]
-    pub last_shot_at: f32
+    pub reconnect_token: u64,
+
+    /// Highest event sequence number this player's client says it applied. Server only,
+    /// see `MainState::event_ack`.
+    #[serde(skip)]
+    pub acked_event_seq: u64,
+
+    /// Points in versus mode, see `VERSUS_OWN_ROCK_POINTS`. Can go below zero.
+    pub versus_score: i32,
+
+    /// Shots in each of this player's volleys, and the sideways speed between neighbouring ones.
+    /// Start out as `GameSetup::volley` and `spread`. Sent to clients so they predict the same shots.
+    pub volley: u8,
+    pub spread: f32,
+
+    /// Server only. A remote player's keys over time, replayed across the server's steps.
+    #[serde(skip)]
+    pub timeline: InputTimeline,
 }
 
 impl Player {
-    pub fn create() -> Player {
-        Player::from_actor(Actor::create_player_actor())
+    pub fn create(tuning: &Tuning) -> Player {
+        Player::from_actor(Actor::create_player_actor(tuning))
     }
 
     pub fn from_actor(actor: Actor) -> Player {
@@ -34,11 +158,49 @@ impl Player {
             actor: actor,
             input: InputState::default(),
             last_shot_at: 0.0,
-            index: 0
+            index: 0,
+            stats: PlayerStats::default(),
+            lives: 1,
+            color_index: 0,
+            hit_flash: 0.0,
+            dying_until: 0.0,
+            graze: 0.0,
+            deaths: 0,
+            streak: 0,
+            ship: 0,
+            last_input_at: None,
+            active: true,
+            reconnect_token: 0,
+            acked_event_seq: 0,
+            versus_score: 0,
+            volley: DEFAULT_VOLLEY,
+            spread: DEFAULT_SPREAD,
+            timeline: InputTimeline::default(),
         }
     }
+
+    /// Still playing the death animation at game time `now`.
+    pub fn is_dying(&self, now: f32) -> bool {
+        self.dying_until > now
+    }
+
+    /// Takes a player that left out of the game until their slot is reused.
+    pub fn park(&mut self) {
+        self.active = false;
+        self.actor.pos = Vector2::new(PARKED_COORD, PARKED_COORD);
+        self.actor.velocity = Vector2::new(0.0, 0.0);
+        self.input = InputState::default();
+        self.dying_until = 0.0;
+        self.hit_flash = 0.0;
+    }
+
+    /// Also marks the actor, since images are looked up by actor.
+    pub fn set_ship(&mut self, ship: u8) {
+        self.ship = ship % SHIP_SKINS;
+        self.actor.set_sprite(self.ship);
+    }
     
-    pub fn tick_input(&mut self, delta: f32) {
+    pub fn tick_input(&mut self, delta: f32, tuning: &Tuning) {
         //actor.facing += dt * PLAYER_TURN_RATE * input.xaxis;
         fn bool_to_f(v: bool) -> f32 {
             if v { 1.0 } else { 0.0 }
@@ -52,106 +214,746 @@ impl Player {
         + bool_to_f(self.input.down) * -1.0
         );
 
-        self.actor.pos += point * delta * PLAYER_SPEED;
+        self.actor.pos += point * delta * tuning.player_speed;
     }
 
 }
 
+/// Short-lived marker floating up from a world position, e.g. "1UP".
+#[derive(Debug, Clone)]
+pub struct Popup {
+    pub pos: Vector2,
+    pub time_left: f32,
+}
+
 /// **********************************************************************
 /// The `InputState` is exactly what it sounds like, it just keeps track of
 /// the user's input state so that we turn keyboard events into something
 /// state-based and device-independent.
 /// **********************************************************************
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct InputState {
     pub fire: bool,
     pub up: bool,
     pub down: bool,
     pub right: bool,
-    pub left: bool
+    pub left: bool,
+    /// Fire was pressed since the last time this input was consumed, even if already released.
+    pub fire_pressed: bool,
+}
+
+impl InputState {
+    /// Held keys as arrows plus F for fire, for the debug overlay.
+    pub fn compact(&self) -> String {
+        let mut s = String::new();
+        for &(held, arrow) in &[(self.left, '←'), (self.up, '↑'), (self.down, '↓'), (self.right, '→')] {
+            if held {
+                s.push(arrow);
+            }
+        }
+        if self.fire {
+            s.push_str(" F");
+        }
+        s
+    }
+}
+
+/// Balancing tools behind `--debug`. Clients send them with their input,
+/// the server only carries them out when it runs with `--debug` too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DebugCommand {
+    /// Stops or restarts the clock rock spawning reads the difficulty from.
+    ToggleClockFreeze,
+    /// Moves the difficulty clock by this many seconds.
+    ShiftClock(f32),
+    SpawnRock(Vec2Serial),
+    ClearRocks,
+}
+
+/// Remembers a fire key press until both the simulation and the outgoing
+/// input packet have seen it, so taps shorter than a tick still shoot.
+#[derive(Debug, Clone, Default)]
+pub struct FireLatch {
+    pending_sim: bool,
+    pending_net: bool,
+}
+
+impl FireLatch {
+    pub fn press(&mut self) {
+        self.pending_sim = true;
+        self.pending_net = true;
+    }
+
+    pub fn take_for_sim(&mut self) -> bool {
+        std::mem::replace(&mut self.pending_sim, false)
+    }
+
+    pub fn take_for_net(&mut self) -> bool {
+        std::mem::replace(&mut self.pending_net, false)
+    }
+
+    /// Forgets a press neither the simulation nor the server has seen yet.
+    pub fn clear(&mut self) {
+        *self = FireLatch::default();
+    }
 }
 
 // TODO: refactor
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct PlaySounds {
     pub play_hit: bool,
-    pub play_shot: bool,
+    /// Players whose volleys went off this frame, each is heard at their own pitch.
+    pub shots_by: Vec<usize>,
+    pub play_graze: bool,
+    pub play_pickup: bool,
+    pub play_extra_life: bool,
+    pub play_death: bool,
+    pub play_milestone: bool,
+    pub play_time_tick: bool,
+    pub play_chime: bool,
 }
 
 /// Assets
 
 pub struct Assets {
-    pub player_image: graphics::Image,
+    /// Indexed by `Actor::sprite`, one per ship skin.
+    pub player_images: Vec<graphics::Image>,
     pub shot_image: graphics::Image,
-    pub rock_image: graphics::Image,
-    pub font: graphics::Font,
-    pub shot_sound: audio::Source,
+    /// Indexed by `Actor::sprite`, `ROCK_SPRITES` of them.
+    pub rock_images: Vec<graphics::Image>,
+    pub gem_image: graphics::Image,
+    pub time_slow_image: graphics::Image,
+    pub well_image: graphics::Image,
+    pub sprite_fit: SpriteFitSetup,
+    /// The built-in 18pt face, for any name that isn't configured or failed to load.
+    pub default_font: graphics::Font,
+    /// Faces from `DisplaySetup::fonts` by name.
+    pub fonts: HashMap<String, graphics::Font>,
+    pub shot_voices: ShotVoices,
     pub hit_sound: audio::Source,
+    pub graze_sound: audio::Source,
+    pub pickup_sound: audio::Source,
+    pub extra_life_sound: audio::Source,
+    pub death_sound: audio::Source,
+    pub milestone_sound: audio::Source,
+    pub time_tick_sound: audio::Source,
+    pub chime_sound: audio::Source,
+}
+
+/// A few samples of 16 bit mono silence, stands in for sounds that fail to load.
+const SILENT_WAV: [u8; 48] = [
+    b'R', b'I', b'F', b'F', 40, 0, 0, 0, b'W', b'A', b'V', b'E',
+    b'f', b'm', b't', b' ', 16, 0, 0, 0, 1, 0, 1, 0,
+    0x40, 0x1f, 0, 0, 0x80, 0x3e, 0, 0, 2, 0, 16, 0,
+    b'd', b'a', b't', b'a', 4, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Loads the first of `paths` that decodes, warming it up on the way.
+/// A missing or broken sound only costs a warning and plays silence.
+fn load_sound(ctx: &mut Context, paths: &[&str]) -> GameResult<audio::Source> {
+    let data = load_sound_data(ctx, paths);
+    audio::Source::from_data(ctx, data)
+}
+
+/// The data `load_sound` plays, for sounds played some other way.
+fn load_sound_data(ctx: &mut Context, paths: &[&str]) -> audio::SoundData {
+    for path in paths {
+        let loaded = audio::SoundData::new(ctx, path)
+            .and_then(|data| warm_up_sound(ctx, &data).map(|_| data));
+        match loaded {
+            Ok(data) => return data,
+            Err(e) => println!("Warning: could not load sound {}: {}", path, e),
+        }
+    }
+    audio::SoundData::from_bytes(&SILENT_WAV)
+}
+
+/// The first play of a sound builds its decoder and wakes up the output
+/// device, which is audible as a lag on the first shot. Do that once at
+/// zero volume on a throwaway sink: the real source is never touched, so
+/// its `playing()` stays false, and a stopped rodio sink can't be restarted.
+fn warm_up_sound(ctx: &mut Context, data: &audio::SoundData) -> GameResult<()> {
+    let mut primer = audio::Source::from_data(ctx, data.clone())?;
+    primer.set_volume(0.0);
+    primer.play()
+    // Dropping the primer stops it.
 }
 
+/// Font names the HUD asks for, see `Assets::font`.
+pub const HUD_FONT: &str = "hud";
+pub const BANNER_FONT: &str = "banner";
+pub const SMALL_FONT: &str = "small";
+
 impl Assets {
-    pub fn new(ctx: &mut Context) -> GameResult<Assets> {
-        let player_image = graphics::Image::new(ctx, "/player.png")?;
+    pub fn new(ctx: &mut Context, sprite_fit: &SpriteFitSetup, font_faces: &BTreeMap<String, FontFace>) -> GameResult<Assets> {
+        let player_images = vec![
+            graphics::Image::new(ctx, "/player.png")?,
+            graphics::Image::new(ctx, "/player2.png")?,
+            graphics::Image::new(ctx, "/player3.png")?,
+        ];
         let shot_image = graphics::Image::new(ctx, "/shot.png")?;
-        let rock_image = graphics::Image::new(ctx, "/rock.png")?;
-        let font = graphics::Font::new(ctx, "/DejaVuSerif.ttf", 18)?;
+        let rock_images = vec![
+            graphics::Image::new(ctx, "/rock.png")?,
+            graphics::Image::new(ctx, "/rock2.png")?,
+            graphics::Image::new(ctx, "/rock3.png")?,
+        ];
+        let gem_image = graphics::Image::new(ctx, "/gem.png")?;
+        let time_slow_image = graphics::Image::new(ctx, "/timeslow.png")?;
+        let well_image = graphics::Image::new(ctx, "/well.png")?;
+        let default_font = graphics::Font::new(ctx, "/DejaVuSerif.ttf", 18)?;
+        let mut fonts = HashMap::new();
+        for (name, face) in font_faces {
+            match graphics::Font::new(ctx, &face.file, face.size) {
+                Ok(font) => {
+                    fonts.insert(name.clone(), font);
+                }
+                Err(e) => println!("Warning: could not load font {} from {}: {}", name, face.file, e),
+            }
+        }
 
-        let shot_sound = audio::Source::new(ctx, "/pew.ogg")?;
-        let hit_sound = audio::Source::new(ctx, "/boom.ogg")?;
+        let shot_voices = ShotVoices::new(load_sound_data(ctx, &["/pew.ogg"]));
+        let hit_sound = load_sound(ctx, &["/boom.ogg"])?;
+        let mut graze_sound = load_sound(ctx, &["/pew.ogg"])?;
+        graze_sound.set_volume(0.25);
+        let pickup_sound = load_sound(ctx, &["/pickup.wav"])?;
+        let extra_life_sound = load_sound(ctx, &["/oneup.wav"])?;
+        // Older resource folders don't have a death sound, the explosion will do.
+        let mut death_sound = load_sound(ctx, &["/death.wav", "/boom.ogg"])?;
+        death_sound.set_volume(1.5);
+        let milestone_sound = load_sound(ctx, &["/milestone.wav", "/oneup.wav"])?;
+        let time_tick_sound = load_sound(ctx, &["/tick.wav"])?;
+        let chime_sound = load_sound(ctx, &["/chime.wav"])?;
         Ok(Assets {
-            player_image,
+            player_images,
             shot_image,
-            rock_image,
-            font,
-            shot_sound,
+            rock_images,
+            gem_image,
+            time_slow_image,
+            well_image,
+            sprite_fit: sprite_fit.clone(),
+            default_font,
+            fonts,
+            shot_voices,
             hit_sound,
+            graze_sound,
+            pickup_sound,
+            extra_life_sound,
+            death_sound,
+            milestone_sound,
+            time_tick_sound,
+            chime_sound,
         })
     }
 
+    /// The face registered as `name`, or the 18pt default.
+    pub fn font(&self, name: &str) -> &graphics::Font {
+        self.fonts.get(name).unwrap_or(&self.default_font)
+    }
+
     pub fn actor_image(&mut self, actor: &Actor) -> &mut graphics::Image {
         use actor::ActorType;
         match actor.tag {
-            ActorType::Player => &mut self.player_image,
-            ActorType::Rock => &mut self.rock_image,
+            ActorType::Player => {
+                let sprite = actor.sprite() as usize % self.player_images.len();
+                &mut self.player_images[sprite]
+            }
+            ActorType::Rock => {
+                let sprite = actor.sprite() as usize % self.rock_images.len();
+                &mut self.rock_images[sprite]
+            }
             ActorType::Shot => &mut self.shot_image,
+            ActorType::Gem => &mut self.gem_image,
+            ActorType::TimeSlow => &mut self.time_slow_image,
+            ActorType::Well => &mut self.well_image,
         }
     }
+
+    /// Scale that makes the actor's sprite as wide as its hitbox, see `SpriteFitSetup`.
+    pub fn actor_scale(&mut self, actor: &Actor) -> f32 {
+        use actor::ActorType;
+        let fit = match actor.tag {
+            ActorType::Player => self.sprite_fit.player,
+            ActorType::Rock => self.sprite_fit.rock,
+            ActorType::Shot => self.sprite_fit.shot,
+            ActorType::Gem => self.sprite_fit.gem,
+            ActorType::TimeSlow => self.sprite_fit.time_slow,
+            ActorType::Well => self.sprite_fit.well,
+        };
+        let image = self.actor_image(actor);
+        let size = image.width().max(image.height()).max(1) as f32;
+        2.0 * actor.bbox_size * fit / size
+    }
+}
+
+/// Everything that needs a graphics context: assets and the cached HUD texts.
+/// Headless states (benchmarks) simply don't have one.
+pub struct RenderState {
+    pub assets: Assets,
+    pub score_display: graphics::Text,
+    pub level_display: graphics::Text,
+    pub scoreboard_display: Vec<graphics::Text>,
+    /// Lines of the spectator's panel about the followed player.
+    pub follow_display: Vec<graphics::Text>,
+    pub extra_life_display: graphics::Text,
+    pub auto_fire_display: graphics::Text,
+    pub debug_display: graphics::Text,
+    pub palette: Palette,
+    /// Plain HUD text, see `DisplaySetup::hud_color`.
+    pub text_color: graphics::Color,
+    pub player_markers: bool,
+    /// Where HUD elements go for the current window size.
+    pub layout: Layout,
+    /// Last title given to the window and when it was last reconsidered.
+    pub window_title: String,
+    pub title_checked_at: std::time::Instant,
+    pub screenshots: ScreenshotWriter,
+    /// On-screen text in the language picked with `--lang`.
+    pub strings: Strings,
+}
+
+impl RenderState {
+    pub fn new(ctx: &mut Context, display: &DisplaySetup, strings: Strings) -> GameResult<RenderState> {
+        let assets = Assets::new(ctx, &display.sprite_fit, &display.fonts)?;
+        let score_display = graphics::Text::new(ctx, "score", assets.font(HUD_FONT))?;
+        let level_display = graphics::Text::new(ctx, "level", assets.font(HUD_FONT))?;
+        let extra_life_display = graphics::Text::new(ctx, strings.tr("hud.extra_life"), assets.font(HUD_FONT))?;
+        let auto_fire_display = graphics::Text::new(ctx, strings.tr("hud.auto_fire"), assets.font(HUD_FONT))?;
+        let debug_display = graphics::Text::new(ctx, strings.tr("hud.debug"), assets.font(HUD_FONT))?;
+        Ok(RenderState {
+            assets,
+            score_display,
+            level_display,
+            scoreboard_display: Vec::new(),
+            follow_display: Vec::new(),
+            extra_life_display,
+            auto_fire_display,
+            debug_display,
+            palette: display.palette,
+            text_color: match display.hud_color {
+                Some([r, g, b]) => graphics::Color::from_rgb(r, g, b),
+                None => hud::text_color(display.palette),
+            },
+            player_markers: display.player_markers,
+            layout: Layout::new(graphics::get_size(ctx)),
+            window_title: String::new(),
+            title_checked_at: std::time::Instant::now(),
+            screenshots: ScreenshotWriter::spawn(),
+            strings,
+        })
+    }
+
+    /// The text for `key` in the current language, see `Strings::tr`.
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.tr(key)
+    }
+}
+
+/// What this process is responsible for in a networked session.
+/// The server is authoritative regardless of whether it also controls a player.
+/// How far a client moves remote actors on its own while snapshots are late. From net_setup.json.
+#[derive(Debug, Clone)]
+pub struct ExtrapolationSetup {
+    /// Remote actors keep their last velocity this long after the latest snapshot, then freeze.
+    pub max_extrapolation_ms: u64,
+    /// Time taken to glide back to the server's positions once snapshots resume.
+    pub blend_ms: u64,
+}
+
+impl Default for ExtrapolationSetup {
+    fn default() -> ExtrapolationSetup {
+        ExtrapolationSetup {
+            max_extrapolation_ms: 250,
+            blend_ms: 150,
+        }
+    }
+}
+
+/// Local network counters, shown in the F3 overlay.
+#[derive(Debug, Clone, Default)]
+pub struct NetStats {
+    /// Snapshots replaced by a newer one before a slow connection could take them.
+    pub snapshots_dropped: u64,
+    /// Input ticks skipped because the previous input was still being written.
+    pub inputs_coalesced: u64,
+    /// Received messages that failed to decode, over all connections.
+    pub bad_messages: u64,
+    /// Snapshots received and their total size on the wire.
+    pub snapshots_received: u64,
+    pub snapshot_bytes: u64,
+    /// Longest wait between two received snapshots.
+    pub max_snapshot_gap: std::time::Duration,
+    /// Actors in snapshots that no list takes, skipped. Only a broken server sends them.
+    pub unexpected_actors: u64,
+    /// Snapshots the server cut down to its actor budget, see `SnapshotCache::max_actors`.
+    pub truncated_snapshots: u64,
+}
+
+impl NetStats {
+    /// `previous` is when the snapshot before this one arrived.
+    pub fn record_snapshot(&mut self, bytes: usize, previous: Option<std::time::Instant>) {
+        self.snapshots_received += 1;
+        self.snapshot_bytes += bytes as u64;
+        if let Some(previous) = previous {
+            self.max_snapshot_gap = self.max_snapshot_gap.max(previous.elapsed());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetRole {
+    Server,
+    Client,
+    Spectator,
 }
 
 pub struct MainState {
+    pub role: NetRole,
     pub local_player_index: Option<usize>,
     pub local_input: InputState,
+    pub input_setup: InputSetup,
+    /// Auto-fire currently shooting. Only meaningful with `input_setup.auto_fire`.
+    pub auto_fire_engaged: bool,
+    /// Aim assist for our own shots, within what the server allows.
+    pub aim_assist: f32,
+    pub fire_latch: FireLatch,
+    pub key_bindings: KeyBindings,
+    /// Skin for our own ship. Clients send it when joining.
+    pub local_ship: u8,
+    /// Sent when joining so the server can recognise us if we connect again.
+    pub reconnect_token: u64,
+    pub show_debug: bool,
+    /// Seed of the current run and, when set, the seed every run starts from.
+    pub seed: u64,
+    pub fixed_seed: Option<u64>,
+    /// Collisions are counted instead of ending the run, nothing is recorded.
+    pub practice: bool,
+    /// Server only. Until Enter is pressed or a player joins, rocks come slow and sparse,
+    /// hits cost nothing and no score is kept. Ending it starts the real run, see `end_warm_up`.
+    pub warm_up: bool,
+    /// Every volley costs `HARDCORE_VOLLEY_COST` points.
+    pub hardcore: bool,
+    /// Rocks are assigned to players in turn, and players score against each other for them.
+    pub versus: bool,
+    /// Server only. Active player the next rock is assigned to in versus mode, or the one after.
+    pub next_rock_owner: usize,
+    /// Server only. `Actor::id` the next actor without one gets.
+    pub next_actor_id: u32,
+    /// Launched with `--debug`: the debug keys work and nothing is recorded.
+    pub debug: bool,
+    /// Debug commands for the next server step, or on clients for the next input message.
+    pub pending_debug: Vec<DebugCommand>,
+    /// Server only. The debug freeze holds `difficulty_points` where it is.
+    pub clock_frozen: bool,
+    pub daily: Option<String>,
+    pub rng: SimRng,
+    pub patterns: PatternScheduler,
+    /// Server only. Pattern rocks waiting out their telegraph.
+    pub spawn_queue: SpawnQueue,
+    /// Rocks about to appear at the top edge. Servers take them from `spawn_queue`, clients
+    /// from snapshots, and both count them down between updates.
+    pub telegraphs: Vec<Telegraph>,
+    pub profiler: FrameProfiler,
     pub players: Vec<Player>,
     pub shots: Vec<Actor>,
     pub rocks: Vec<Actor>,
-    pub score: i32,
-    pub assets: Assets,
+    pub gems: Vec<Actor>,
+    pub powerups: Vec<Actor>,
+    /// Gravity wells, see `GameSetup::wells`.
+    pub wells: Vec<Actor>,
+    /// Multiplies the time rocks simulate and spawn with. Below 1 while a time slow is active.
+    pub rock_time_scale: f32,
+    /// Seconds until `rock_time_scale` goes back to 1.
+    pub time_slow_left: f32,
+    /// Local only. Last warning tick played, counted in `TIME_SLOW_TICK_EVERY` steps before the end.
+    pub time_slow_tick_step: u32,
+    /// Whole points unless in hardcore mode, where volleys cost a fraction.
+    pub score: f32,
+    pub render: Option<RenderState>,
+    /// Size of the window in pixels. Only drawing looks at it, see `VIEW_SIZE`.
     pub screen_width: u32,
     pub screen_height: u32,
-    pub score_display: graphics::Text,
-    pub level_display: graphics::Text,
+    /// Size of the simulated playfield. `VIEW_SIZE` square unless running a large world.
+    pub world_width: f32,
+    pub world_height: f32,
+    /// Center of the view in world coordinates. Local only, never serialized.
+    pub camera: Vector2,
+    pub show_scoreboard: bool,
+    pub show_minimap: bool,
+    /// Spectators only. Index of the player picked with 1-4, the camera and side panel follow them.
+    pub followed_player: Option<usize>,
+    /// Spectators only. Recent snapshots to pause on and step through with Space, `,` and `.`.
+    pub scrub: ScrubBuffer,
     pub start_time: std::time::Instant,
     pub curr_time: f32,
     pub difficulty_mult: f32,
+    /// Server only. The multiplier the game was launched with, `hard_reset` goes back to it.
+    pub launch_difficulty: f32,
+    /// How far the run has ramped up: every second of spawning adds the multiplier of that moment.
+    /// Rock spawning scales with this, so a new multiplier only changes the pace from then on.
+    pub difficulty_points: f32,
+    /// Runs started since launch, the first one included. Clients only learn it from the `NetWelcome`.
+    pub round: u32,
     pub play_sounds: PlaySounds,
+    /// Snapshot streams being served. Clients learn the server's count from snapshots.
     pub connections: u32,
+    /// Clients only, the server's `spectator_count`.
+    pub spectators: u32,
+    pub net_stats: NetStats,
+    pub extrapolation: ExtrapolationSetup,
+    /// When the latest snapshot arrived. Clients only.
+    pub last_snapshot_at: Option<std::time::Instant>,
     pub local_shots_made: Vec<Actor>,
+    /// Clients only. Our keys since the last `NetClientInput`, which carries them to the server.
+    pub input_timeline: InputTimeline,
+    pub run_log: RunLog,
+    pub leaderboard: Leaderboard,
+    pub game_setup: GameSetup,
+    /// Speeds, fire rate and hitboxes. Clients take the server's when joining.
+    pub tuning: Tuning,
+    pub event_log: EventLog,
+    pub last_event_seq: u64,
+    /// Popups, ticker, banner and toasts.
+    pub effects: Effects,
+    /// Milestones the current run has announced, in order.
+    pub milestones_reached: Vec<u32>,
+    pub snapshot: SnapshotCache,
+    /// Server only. Milliseconds between snapshots, read by every sender thread on each tick.
+    pub transfer_ms: Arc<AtomicU64>,
+    /// Server only. Inputs received from each remote player, indexed like `players`.
+    pub input_queues: Vec<InputQueue>,
+    /// Server steps taken since the process started. Input logs count in these.
+    pub sim_tick: u64,
+    /// Set with `--record-input` or `--play-input`.
+    pub input_replay: Option<InputReplay>,
+    /// Server only. When the window lost focus, if that paused the game. See `InputSetup::pause_on_focus_loss`.
+    pub paused_at: Option<std::time::Instant>,
+    /// Set once a thread panicked while holding the state lock: where it panicked, if known.
+    /// The game stays paused and offline from then on, see `StatePtr::lock`.
+    pub recovered_from: Option<String>,
+}
+
+impl MainState {
+    /// The server hasn't been heard from for longer than remote actors may be extrapolated.
+    pub fn snapshots_stale(&self) -> bool {
+        let limit = std::time::Duration::from_millis(self.extrapolation.max_extrapolation_ms);
+        self.role != NetRole::Server && self.last_snapshot_at.map_or(false, |at| at.elapsed() > limit)
+    }
+
+    /// Server only. Sends snapshots every `ms`, kept within `MIN_TRANSFER_MS` and `MAX_TRANSFER_MS`.
+    /// Connections pick it up on their next tick and clients hear about it in the ticker.
+    /// Returns the interval actually set.
+    pub fn set_transfer_ms(&mut self, ms: u64) -> u64 {
+        let ms = ms.max(MIN_TRANSFER_MS).min(MAX_TRANSFER_MS);
+        if self.transfer_ms.swap(ms, Ordering::Relaxed) != ms {
+            // Publish twice per transfer period so every sender tick finds a fresh snapshot.
+            self.snapshot.interval = std::time::Duration::from_millis(ms) / 2;
+            self.emit(GameEvent::TransferRateChanged { ms: ms as u32 });
+        }
+        ms
+    }
+
+    /// Snapshot readers without a ship: every connection that doesn't belong to a remote player.
+    pub fn spectator_count(&self) -> u32 {
+        if self.role != NetRole::Server {
+            return self.spectators;
+        }
+        let remote_players = self.players.iter()
+            .filter(|p| p.active && self.local_player_index != Some(p.index as usize))
+            .count() as u32;
+        self.connections.saturating_sub(remote_players)
+    }
+
+    /// Players still in the game, kicked ones don't count.
+    pub fn active_player_count(&self) -> usize {
+        self.players.iter().filter(|p| p.active).count()
+    }
+
+    /// "3 players, 1 spectator" for the scoreboard and the window title.
+    pub fn attendance(&self) -> String {
+        let count = |n: usize, one: &str, many: &str| {
+            if n == 1 { self.tr(one).to_string() } else { self.tr_fmt(many, &[("count", &n)]) }
+        };
+        let players = count(self.active_player_count(), "hud.attendance.player", "hud.attendance.players");
+        let spectators = count(self.spectator_count() as usize, "hud.attendance.spectator", "hud.attendance.spectators");
+        self.tr_fmt("hud.attendance", &[("players", &players), ("spectators", &spectators)])
+    }
+
+    /// On-screen text for `key`. States without a window have no language loaded and use English.
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        match &self.render {
+            Some(render) => render.tr(key),
+            None => strings::default_text(key).unwrap_or(key),
+        }
+    }
+
+    /// `tr` with its placeholders filled in.
+    pub fn tr_fmt(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        strings::fill(self.tr(key), args)
+    }
+
+    /// A copy of the loaded language, for threads that report through a toast sender.
+    pub fn strings(&self) -> Strings {
+        self.render.as_ref().map(|render| render.strings.clone()).unwrap_or_default()
+    }
+
+    /// Score as shown to players. Hardcore scores have a fractional part.
+    pub fn score_text(&self) -> String {
+        if self.hardcore {
+            format!("{:.1}", self.score)
+        } else {
+            format!("{}", self.score as i32)
+        }
+    }
+
+    /// "P2 7  P1 3" for the HUD: active players by versus score, best first.
+    pub fn standings_text(&self) -> String {
+        let mut standings: Vec<&Player> = self.players.iter().filter(|p| p.active).collect();
+        standings.sort_by(|a, b| b.versus_score.cmp(&a.versus_score).then(a.index.cmp(&b.index)));
+        standings.iter()
+            .map(|p| format!("P{} {}", p.index + 1, p.versus_score))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// Server only. Takes the hardcore price of fired volleys off the score.
+    pub fn charge_volleys(&mut self, volleys: u32) {
+        if self.hardcore && volleys > 0 {
+            self.score = (self.score - HARDCORE_VOLLEY_COST * volleys as f32).max(0.0);
+        }
+    }
+
+    /// Server only. A time-slow pickup was collected. Another one restarts the timer
+    /// instead of slowing rocks down further.
+    pub fn start_time_slow(&mut self) {
+        self.rock_time_scale = TIME_SLOW_SCALE;
+        self.time_slow_left = TIME_SLOW_DURATION;
+    }
+
+    /// Counts the time slow down. Clients run this between snapshots too, so the
+    /// warning ticks and the end of the slow line up with the server.
+    pub fn tick_time_slow(&mut self, seconds: f32) {
+        if self.time_slow_left <= 0.0 {
+            self.rock_time_scale = 1.0;
+            return;
+        }
+        self.time_slow_left = (self.time_slow_left - seconds).max(0.0);
+
+        let step = (self.time_slow_left / TIME_SLOW_TICK_EVERY).ceil() as u32;
+        if self.time_slow_left >= TIME_SLOW_WARN_TIME {
+            self.time_slow_tick_step = std::u32::MAX;
+        } else if step < self.time_slow_tick_step {
+            // Only ever counts down, so a snapshot moving the timer back a bit doesn't tick twice.
+            self.time_slow_tick_step = step;
+            self.play_sounds.play_time_tick = true;
+        }
+        if self.time_slow_left <= 0.0 {
+            self.rock_time_scale = 1.0;
+        }
+    }
+
+    /// Records a server-side event. It shows up in our own ticker right away
+    /// and reaches clients through the next snapshots.
+    pub fn emit(&mut self, event: GameEvent) {
+        self.present_event(&event);
+        self.event_log.push(event);
+    }
+
+    /// The last event every seated remote player acknowledged, or `None` when nobody but
+    /// spectators and the local player are around to ack anything.
+    pub fn event_ack(&self) -> Option<u64> {
+        self.players.iter()
+            .filter(|p| p.active && Some(p.index as usize) != self.local_player_index)
+            .map(|p| p.acked_event_seq)
+            .min()
+    }
+
+    /// Shows an event locally: a ticker line, plus the banner and sound for milestones
+    /// and a chime when someone comes or goes.
+    pub fn present_event(&mut self, event: &GameEvent) {
+        if let GameEvent::Restarted { hard: true, .. } = *event {
+            self.effects.ticker.clear();
+        }
+        let english = Strings::default();
+        let strings = self.render.as_ref().map(|render| &render.strings).unwrap_or(&english);
+        self.effects.ticker.push(event.describe(strings));
+        if event.is_arrival_or_departure() {
+            self.play_sounds.play_chime = true;
+        }
+        if let GameEvent::TransferRateChanged { ms } = *event {
+            // Slower snapshots mustn't freeze remote actors between two of them.
+            if self.role != NetRole::Server {
+                let limit = &mut self.extrapolation.max_extrapolation_ms;
+                *limit = (*limit).max(ms as u64 * STALE_AFTER_INTERVALS);
+            }
+        }
+        if let GameEvent::RunEnded { reason } = *event {
+            self.effects.banner.show(reason.describe(strings));
+        }
+        if let GameEvent::Milestone(points) = *event {
+            self.effects.banner.show(strings.tr_fmt("banner.milestone", &[("points", &points)]));
+            self.play_sounds.play_milestone = true;
+            self.milestones_reached.push(points);
+        }
+    }
 }
 
 pub struct StatePtr {
-    pub state: Arc<Mutex<MainState>>
+    pub state: Arc<Mutex<MainState>>,
+    /// Only used by the pointer driving the event loop, references get an unlimited one.
+    pub limiter: FrameLimiter,
+    /// Set when the game is quitting. Shared by every reference.
+    pub shutdown: Arc<AtomicBool>,
 }
 
 impl StatePtr {
-    pub fn new(ctx: &mut Context) -> StatePtr {
+    pub fn new(ctx: &mut Context, options: &LaunchOptions, display: &DisplaySetup) -> StatePtr {
+        recovery::install_panic_hook();
         StatePtr {
-            state: Arc::new(Mutex::new(MainState::new(ctx))),
+            state: Arc::new(Mutex::new(MainState::new(ctx, options, display))),
+            limiter: FrameLimiter::new(display.max_fps),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// A state without a window, for tools like the load-testing bot.
+    pub fn headless(options: &LaunchOptions, screen_width: u32, screen_height: u32) -> StatePtr {
+        recovery::install_panic_hook();
+        StatePtr {
+            state: Arc::new(Mutex::new(MainState::headless(options, screen_width, screen_height))),
+            limiter: FrameLimiter::new(0),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Locks the state, recovering it if a thread panicked while holding the lock.
+    /// That state may be halfway through an update, but carrying on beats having every
+    /// other thread panic on the poisoned lock and freezing the game. The first lock after
+    /// the panic pauses the game and disconnects every peer, so nobody plays on garbage.
+    pub fn lock(&self) -> MutexGuard<MainState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => {
+                let mut state = poisoned.into_inner();
+                if state.recovered_from.is_none() {
+                    let cause = recovery::last_panic().unwrap_or_else(|| String::from("unknown location"));
+                    println!("Recovered the game state after a panic at {}", cause);
+                    state.enter_safe_state(cause);
+                    self.request_shutdown();
+                }
+                state
+            }
         }
     }
 
     pub fn get_ref(&mut self) -> StatePtr {
         StatePtr {
-            state: self.state.clone()
+            state: self.state.clone(),
+            limiter: FrameLimiter::new(0),
+            shutdown: self.shutdown.clone(),
         }
     }
+
+    pub fn request_shutdown(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use serde::{Serialize, Deserialize};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+pub const HIGHSCORES_FILENAME: &str = "highscores.json";
+
+/// Daily challenge results for one date.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub best: f32,
+    pub last: f32,
+    pub attempts: u32,
+}
+
+/// Best results kept across sessions. Only daily challenges for now, keyed by YYYY-MM-DD.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub daily: BTreeMap<String, DailyResult>,
+}
+
+impl HighScores {
+    /// A missing file is an empty table. A malformed one is reported and
+    /// left alone so a typo doesn't wipe everyone's scores.
+    pub fn load(filename: &str) -> Option<HighScores> {
+        match File::open(filename) {
+            Ok(file) => match serde_json::from_reader(BufReader::new(file)) {
+                Ok(scores) => Some(scores),
+                Err(e) => {
+                    println!("Not using malformed {}: {}", filename, e);
+                    None
+                }
+            },
+            Err(_) => Some(HighScores::default()),
+        }
+    }
+
+    /// Adds a daily attempt and writes the file. Does nothing when the file couldn't be read.
+    pub fn record_daily(filename: &str, date: &str, score: f32) {
+        let mut scores = match HighScores::load(filename) {
+            Some(scores) => scores,
+            None => return,
+        };
+
+        let result = scores.daily.entry(date.to_string()).or_insert_with(|| DailyResult {
+            best: score,
+            ..DailyResult::default()
+        });
+        result.best = result.best.max(score);
+        result.last = score;
+        result.attempts += 1;
+
+        let written = File::create(filename)
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer_pretty(BufWriter::new(file), &scores));
+        if let Err(e) = written {
+            println!("Failed to write {}: {}", filename, e);
+        }
+    }
+
+    pub fn daily_result(filename: &str, date: &str) -> Option<DailyResult> {
+        HighScores::load(filename).and_then(|scores| scores.daily.get(date).cloned())
+    }
+}
@@ -0,0 +1,625 @@
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point2};
+use ggez::{Context, GameResult};
+
+use crate::actor::Actor;
+use crate::config::Palette;
+use crate::effects::Ring;
+use crate::game_structs::{InputState, Player};
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// How many players get a color of their own before colors repeat.
+pub const PLAYER_COLOR_COUNT: usize = 6;
+
+/// Colors players are told apart by, indexed by `Player::color_index`.
+const NORMAL_COLORS: [(u8, u8, u8); PLAYER_COLOR_COUNT] = [
+    (80, 200, 255),
+    (255, 120, 80),
+    (120, 230, 100),
+    (240, 220, 80),
+    (220, 110, 240),
+    (250, 250, 250),
+];
+
+/// Okabe-Ito colors, which stay distinct with red-green color blindness.
+const DEUTERANOPIA_COLORS: [(u8, u8, u8); PLAYER_COLOR_COUNT] = [
+    (86, 180, 233),
+    (230, 159, 0),
+    (0, 158, 115),
+    (240, 228, 66),
+    (204, 121, 167),
+    (250, 250, 250),
+];
+
+const HIGH_CONTRAST_COLORS: [(u8, u8, u8); PLAYER_COLOR_COUNT] = [
+    (0, 255, 255),
+    (255, 128, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (255, 0, 255),
+    (255, 255, 255),
+];
+
+pub fn player_color(palette: Palette, color_index: u8) -> Color {
+    let colors = match palette {
+        Palette::Normal => &NORMAL_COLORS,
+        Palette::Deuteranopia => &DEUTERANOPIA_COLORS,
+        Palette::HighContrast => &HIGH_CONTRAST_COLORS,
+    };
+    let (r, g, b) = colors[color_index as usize % PLAYER_COLOR_COUNT];
+    Color::from_rgb(r, g, b)
+}
+
+/// Color of plain HUD and debug text, unless display_setup.json sets `hud_color`.
+pub fn text_color(palette: Palette) -> Color {
+    match palette {
+        Palette::Normal | Palette::Deuteranopia => graphics::WHITE,
+        Palette::HighContrast => Color::from_rgb(255, 255, 0),
+    }
+}
+
+/// How far text shadows fall down and to the right, in pixels of a 1080 high window.
+const TEXT_SHADOW_OFFSET: f32 = 2.0;
+
+/// The HUD is laid out for a window this tall and scaled for others.
+const REFERENCE_HEIGHT: f32 = 1080.0;
+/// Below this the text gets too small to read, so small windows get a cramped HUD instead.
+const MIN_UI_SCALE: f32 = 0.5;
+
+/// Screen edge or point a HUD element is placed relative to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+/// Places HUD elements by anchor and scales them with the window height.
+/// Sizes and margins are given in pixels of a 1080 high window.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub width: f32,
+    pub height: f32,
+    pub scale: f32,
+}
+
+impl Layout {
+    pub fn new(screen: (u32, u32)) -> Layout {
+        Layout {
+            width: screen.0 as f32,
+            height: screen.1 as f32,
+            scale: (screen.1 as f32 / REFERENCE_HEIGHT).max(MIN_UI_SCALE),
+        }
+    }
+
+    /// Top-left corner for an element of `size`, `margin` away from the anchor towards the
+    /// middle of the screen. Centered axes take the margin as a signed shift.
+    pub fn place(&self, anchor: Anchor, margin: (f32, f32), size: (f32, f32)) -> Point2 {
+        let s = self.scale;
+        let x = match anchor {
+            Anchor::TopLeft | Anchor::BottomLeft => margin.0 * s,
+            Anchor::TopCenter | Anchor::Center | Anchor::BottomCenter => (self.width - size.0 * s) / 2.0 + margin.0 * s,
+            Anchor::TopRight | Anchor::BottomRight => self.width - (margin.0 + size.0) * s,
+        };
+        let y = match anchor {
+            Anchor::TopLeft | Anchor::TopCenter | Anchor::TopRight => margin.1 * s,
+            Anchor::Center => (self.height - size.1 * s) / 2.0 + margin.1 * s,
+            Anchor::BottomLeft | Anchor::BottomCenter | Anchor::BottomRight => self.height - (margin.1 + size.1) * s,
+        };
+        Point2::new(x, y)
+    }
+
+    /// Draws `text` scaled, in `color` or else the current color.
+    pub fn draw_text(
+        &self,
+        ctx: &mut Context,
+        text: &graphics::Text,
+        anchor: Anchor,
+        margin: (f32, f32),
+        color: Option<Color>,
+    ) -> GameResult<()> {
+        let dest = self.place(anchor, margin, (text.width() as f32, text.height() as f32));
+        graphics::draw_ex(ctx, text, graphics::DrawParam {
+            dest,
+            scale: Point2::new(self.scale, self.scale),
+            color,
+            ..Default::default()
+        })
+    }
+
+    /// Draws `text` in `color` over a dark copy of itself, so it stays readable in front of
+    /// white rocks. Both go from the same `Text`, nothing is rasterized twice.
+    pub fn draw_text_shadowed(
+        &self,
+        ctx: &mut Context,
+        text: &graphics::Text,
+        anchor: Anchor,
+        margin: (f32, f32),
+        color: Color,
+    ) -> GameResult<()> {
+        let dest = self.place(anchor, margin, (text.width() as f32, text.height() as f32));
+        let offset = (TEXT_SHADOW_OFFSET * self.scale).max(1.0);
+        graphics::draw_ex(ctx, text, graphics::DrawParam {
+            dest: Point2::new(dest.x + offset, dest.y + offset),
+            scale: Point2::new(self.scale, self.scale),
+            color: Some(Color::new(0.0, 0.0, 0.0, 0.75 * color.a)),
+            ..Default::default()
+        })?;
+        graphics::draw_ex(ctx, text, graphics::DrawParam {
+            dest,
+            scale: Point2::new(self.scale, self.scale),
+            color: Some(color),
+            ..Default::default()
+        })
+    }
+}
+
+const MINIMAP_SIZE: f32 = 150.0;
+const MINIMAP_MARGIN: f32 = 10.0;
+const MINIMAP_ROCK_SIZE: f32 = 1.5;
+
+const FOLLOW_PANEL_X: f32 = 10.0;
+const FOLLOW_PANEL_Y: f32 = 120.0;
+const FOLLOW_PANEL_WIDTH: f32 = 220.0;
+const FOLLOW_LINE_HEIGHT: f32 = 24.0;
+/// Half the width of a player marker.
+const PLAYER_MARKER_SIZE: f32 = 5.0;
+/// Half the width of a telegraph chevron.
+const TELEGRAPH_SIZE: f32 = 12.0;
+/// Seconds a telegraph stays on, and then off.
+const TELEGRAPH_BLINK: f32 = 0.1;
+/// Side of one key in the input widget.
+const INPUT_KEY_SIZE: f32 = 18.0;
+
+pub const TICKER_LINES: usize = 4;
+const TICKER_LIFETIME: f32 = 5.0;
+const TICKER_FADE_TIME: f32 = 1.0;
+const TICKER_LINE_HEIGHT: f32 = 22.0;
+
+struct TickerEntry {
+    text: String,
+    age: f32,
+}
+
+/// Scrolling list of recent notable events in the top-right corner.
+/// Text objects are only rebuilt when the list of lines changes.
+pub struct Ticker {
+    entries: Ring<TickerEntry>,
+    texts: Vec<graphics::Text>,
+    dirty: bool,
+}
+
+impl Ticker {
+    pub fn new() -> Ticker {
+        Ticker {
+            entries: Ring::new(TICKER_LINES),
+            texts: Vec::with_capacity(TICKER_LINES),
+            dirty: false,
+        }
+    }
+
+    pub fn push(&mut self, text: String) {
+        self.entries.push(TickerEntry { text, age: 0.0 });
+        self.dirty = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dirty = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn tick(&mut self, seconds: f32) {
+        for entry in self.entries.iter_mut() {
+            entry.age += seconds;
+        }
+        let before = self.entries.len();
+        self.entries.retain(|e| e.age < TICKER_LIFETIME);
+        if self.entries.len() != before {
+            self.dirty = true;
+        }
+    }
+
+    pub fn refresh(&mut self, ctx: &mut Context, font: &graphics::Font) -> GameResult<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.texts.clear();
+        for entry in self.entries.iter() {
+            self.texts.push(graphics::Text::new(ctx, &entry.text, font)?);
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn draw(&self, ctx: &mut Context, layout: &Layout, color: Color) -> GameResult<()> {
+        for (i, (text, entry)) in self.texts.iter().zip(self.entries.iter()).enumerate() {
+            let alpha = ((TICKER_LIFETIME - entry.age) / TICKER_FADE_TIME).min(1.0).max(0.0);
+            let margin = (10.0, 10.0 + TICKER_LINE_HEIGHT * i as f32);
+            layout.draw_text(ctx, text, Anchor::TopRight, margin, Some(Color::new(color.r, color.g, color.b, alpha)))?;
+        }
+        Ok(())
+    }
+}
+
+/// How long routine toasts stay up. Errors and anything longer to read use `TOAST_LONG_TIME`.
+pub const TOAST_TIME: f32 = 3.0;
+pub const TOAST_LONG_TIME: f32 = 6.0;
+pub const TOASTS_VISIBLE: usize = 5;
+const TOAST_FADE_TIME: f32 = 0.5;
+const TOAST_LINE_HEIGHT: f32 = 22.0;
+const TOAST_MARGIN: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn color(self, alpha: f32) -> Color {
+        match self {
+            Severity::Info => Color::new(0.85, 0.9, 1.0, alpha),
+            Severity::Warning => Color::new(1.0, 0.8, 0.3, alpha),
+            Severity::Error => Color::new(1.0, 0.35, 0.3, alpha),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToastMessage {
+    pub text: String,
+    pub duration: f32,
+    pub severity: Severity,
+}
+
+/// Lets other threads show toasts. Messages are picked up on the next `Toasts::tick`.
+#[derive(Debug, Clone)]
+pub struct ToastSender(Sender<ToastMessage>);
+
+impl ToastSender {
+    pub fn push(&self, text: String, duration: f32, severity: Severity) {
+        // Only fails once the game is shutting down.
+        let _ = self.0.send(ToastMessage { text, duration, severity });
+    }
+}
+
+struct Toast {
+    message: ToastMessage,
+    text: Option<graphics::Text>,
+    age: f32,
+}
+
+/// Short local notices stacked in the bottom-right corner, newest at the bottom.
+/// Unlike the ticker they never leave this machine. Each gets its Text once.
+pub struct Toasts {
+    shown: Ring<Toast>,
+    sender: Sender<ToastMessage>,
+    receiver: Receiver<ToastMessage>,
+}
+
+impl Toasts {
+    pub fn new() -> Toasts {
+        let (sender, receiver) = mpsc::channel();
+        Toasts {
+            shown: Ring::new(TOASTS_VISIBLE),
+            sender,
+            receiver,
+        }
+    }
+
+    pub fn sender(&self) -> ToastSender {
+        ToastSender(self.sender.clone())
+    }
+
+    pub fn push(&mut self, text: String, duration: f32, severity: Severity) {
+        self.show(ToastMessage { text, duration, severity });
+    }
+
+    fn show(&mut self, message: ToastMessage) {
+        self.shown.push(Toast { message, text: None, age: 0.0 });
+    }
+
+    /// Only the toasts shown so far, messages still on their way from other threads come after.
+    pub fn clear(&mut self) {
+        self.shown.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.shown.len()
+    }
+
+    pub fn tick(&mut self, seconds: f32) {
+        while let Ok(message) = self.receiver.try_recv() {
+            self.show(message);
+        }
+        for toast in self.shown.iter_mut() {
+            toast.age += seconds;
+        }
+        self.shown.retain(|t| t.age < t.message.duration);
+    }
+
+    pub fn refresh(&mut self, ctx: &mut Context, font: &graphics::Font) -> GameResult<()> {
+        for toast in self.shown.iter_mut().filter(|t| t.text.is_none()) {
+            toast.text = Some(graphics::Text::new(ctx, &toast.message.text, font)?);
+        }
+        Ok(())
+    }
+
+    /// `bottom` is kept clear below the stack, so it can sit above the minimap.
+    pub fn draw(&self, ctx: &mut Context, layout: &Layout, bottom: f32) -> GameResult<()> {
+        let texts = self.shown.iter().rev().filter_map(|t| t.text.as_ref().map(|text| (t, text)));
+        for (i, (toast, text)) in texts.enumerate() {
+            let alpha = ((toast.message.duration - toast.age) / TOAST_FADE_TIME).min(1.0).max(0.0);
+            let margin = (TOAST_MARGIN, bottom + TOAST_LINE_HEIGHT * i as f32);
+            layout.draw_text(ctx, text, Anchor::BottomRight, margin, Some(toast.message.severity.color(alpha)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Room the minimap takes up from the bottom edge, for things stacked above it.
+pub const MINIMAP_CLEARANCE: f32 = MINIMAP_SIZE + MINIMAP_MARGIN;
+
+const BANNER_TIME: f32 = 2.0;
+const BANNER_FADE_TIME: f32 = 0.3;
+const BANNER_SCALE: f32 = 2.0;
+/// Drawn this far down the screen, well above where players usually fly.
+const BANNER_HEIGHT_FRACTION: f32 = 0.25;
+
+/// Short centered announcement, e.g. a score milestone. A new one replaces the old.
+pub struct Banner {
+    message: String,
+    text: Option<graphics::Text>,
+    time_left: f32,
+    dirty: bool,
+}
+
+impl Banner {
+    pub fn new() -> Banner {
+        Banner {
+            message: String::new(),
+            text: None,
+            time_left: 0.0,
+            dirty: false,
+        }
+    }
+
+    pub fn show(&mut self, message: String) {
+        self.message = message;
+        self.time_left = BANNER_TIME;
+        self.dirty = true;
+    }
+
+    pub fn tick(&mut self, seconds: f32) {
+        self.time_left = (self.time_left - seconds).max(0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.time_left = 0.0;
+    }
+
+    pub fn refresh(&mut self, ctx: &mut Context, font: &graphics::Font) -> GameResult<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.text = Some(graphics::Text::new(ctx, &self.message, font)?);
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn draw(&self, ctx: &mut Context, layout: &Layout) -> GameResult<()> {
+        let text = match &self.text {
+            Some(text) if self.time_left > 0.0 => text,
+            _ => return Ok(()),
+        };
+        let fade_in = (BANNER_TIME - self.time_left) / BANNER_FADE_TIME;
+        let fade_out = self.time_left / BANNER_FADE_TIME;
+        let alpha = fade_in.min(fade_out).min(1.0).max(0.0);
+        let scale = BANNER_SCALE * layout.scale;
+        let dest = graphics::Point2::new(
+            (layout.width - text.width() as f32 * scale) / 2.0,
+            layout.height * BANNER_HEIGHT_FRACTION - text.height() as f32 * scale / 2.0,
+        );
+        graphics::draw_ex(ctx, text, graphics::DrawParam {
+            dest,
+            scale: graphics::Point2::new(scale, scale),
+            color: Some(graphics::Color::new(1.0, 0.85, 0.3, alpha)),
+            ..Default::default()
+        })
+    }
+}
+
+/// Washes the whole screen in a translucent color.
+pub fn draw_tint(ctx: &mut Context, screen: (u32, u32), color: Color) -> GameResult<()> {
+    graphics::set_color(ctx, color)?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(0.0, 0.0, screen.0 as f32, screen.1 as f32))?;
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Blacks out the window outside `viewport`, where actors past the view's edge would show.
+pub fn draw_letterbox(ctx: &mut Context, screen: (u32, u32), viewport: graphics::Rect) -> GameResult<()> {
+    let (width, height) = (screen.0 as f32, screen.1 as f32);
+    let bars = [
+        graphics::Rect::new(0.0, 0.0, viewport.x, height),
+        graphics::Rect::new(viewport.x + viewport.w, 0.0, width - viewport.x - viewport.w, height),
+        graphics::Rect::new(0.0, 0.0, width, viewport.y),
+        graphics::Rect::new(0.0, viewport.y + viewport.h, width, height - viewport.y - viewport.h),
+    ];
+    graphics::set_color(ctx, graphics::BLACK)?;
+    for bar in bars.iter().filter(|bar| bar.w > 0.0 && bar.h > 0.0) {
+        graphics::rectangle(ctx, DrawMode::Fill, *bar)?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// What the minimap shows.
+pub struct MinimapView<'a> {
+    /// Width and height of the world.
+    pub world: (f32, f32),
+    pub players: &'a [Player],
+    pub rocks: &'a [Actor],
+    /// Index of the player drawn larger, the local or followed one.
+    pub highlighted: Option<usize>,
+}
+
+/// Overview of the whole world in the bottom-right corner.
+/// All rocks go into a single mesh so the cost doesn't grow with draw calls.
+pub fn draw_minimap(ctx: &mut Context, layout: &Layout, view: &MinimapView, palette: Palette, alpha: f32) -> GameResult<()> {
+    let (world_w, world_h) = view.world;
+    let size = MINIMAP_SIZE * layout.scale;
+    let scale = size / world_w.max(world_h);
+    let origin = layout.place(Anchor::BottomRight, (MINIMAP_MARGIN, MINIMAP_MARGIN), (MINIMAP_SIZE, MINIMAP_SIZE));
+    let to_map = |x: f32, y: f32| {
+        Point2::new(
+            origin.x + size / 2.0 + x * scale,
+            origin.y + size / 2.0 - y * scale,
+        )
+    };
+
+    graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 0.6 * alpha))?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(origin.x, origin.y, size, size))?;
+    graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.5 * alpha))?;
+    graphics::rectangle(ctx, DrawMode::Line(1.0), graphics::Rect::new(origin.x, origin.y, size, size))?;
+
+    if !view.rocks.is_empty() {
+        let rock_size = MINIMAP_ROCK_SIZE * layout.scale;
+        let mut builder = graphics::MeshBuilder::new();
+        // Like the playfield, killed rocks aren't shown.
+        for rock in view.rocks.iter().filter(|rock| !rock.kill) {
+            let p = to_map(rock.pos.x, rock.pos.y);
+            builder.polygon(DrawMode::Fill, &[
+                Point2::new(p.x - rock_size, p.y - rock_size),
+                Point2::new(p.x + rock_size, p.y - rock_size),
+                Point2::new(p.x + rock_size, p.y + rock_size),
+                Point2::new(p.x - rock_size, p.y + rock_size),
+            ]);
+        }
+        let mesh = builder.build(ctx)?;
+        graphics::draw_ex(ctx, &mesh, graphics::DrawParam {
+            color: Some(Color::new(0.6, 0.6, 0.6, alpha)),
+            ..Default::default()
+        })?;
+    }
+
+    for player in view.players.iter().filter(|p| p.active) {
+        let mut color = player_color(palette, player.color_index);
+        color.a = alpha;
+        let radius = layout.scale * if view.highlighted == Some(player.index as usize) { 4.0 } else { 3.0 };
+        graphics::set_color(ctx, color)?;
+        graphics::circle(ctx, DrawMode::Fill, to_map(player.actor.pos.x, player.actor.pos.y), radius, 0.5)?;
+    }
+
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Shape drawn under a ship when `player_markers` is on: circle, triangle and square
+/// in turn by `color_index`, so neighbouring players differ in more than color.
+pub fn draw_player_marker(ctx: &mut Context, pos: Point2, color_index: u8, color: Color) -> GameResult<()> {
+    let size = PLAYER_MARKER_SIZE;
+    graphics::set_color(ctx, color)?;
+    match color_index % 3 {
+        0 => graphics::circle(ctx, DrawMode::Fill, pos, size, 0.5)?,
+        1 => graphics::polygon(ctx, DrawMode::Fill, &[
+            Point2::new(pos.x, pos.y - size),
+            Point2::new(pos.x + size, pos.y + size),
+            Point2::new(pos.x - size, pos.y + size),
+        ])?,
+        _ => graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(pos.x - size, pos.y - size, size * 2.0, size * 2.0))?,
+    }
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Color of the markers where pattern rocks are about to appear.
+pub fn telegraph_color(palette: Palette) -> Color {
+    match palette {
+        Palette::Normal => Color::from_rgb(255, 70, 50),
+        Palette::Deuteranopia => Color::from_rgb(213, 94, 0),
+        Palette::HighContrast => Color::from_rgb(255, 0, 0),
+    }
+}
+
+/// Downward chevron at screen position `pos` where a rock appears in `time_left` seconds.
+/// Blinks, and is off every other `TELEGRAPH_BLINK` seconds.
+pub fn draw_telegraph(ctx: &mut Context, pos: Point2, time_left: f32, color: Color) -> GameResult<()> {
+    if (time_left / TELEGRAPH_BLINK) as i32 % 2 == 1 {
+        return Ok(());
+    }
+    let size = TELEGRAPH_SIZE;
+    graphics::set_color(ctx, color)?;
+    for offset in &[0.0, size * 0.8] {
+        graphics::line(ctx, &[
+            Point2::new(pos.x - size, pos.y + offset - size * 0.5),
+            Point2::new(pos.x, pos.y + offset + size * 0.5),
+            Point2::new(pos.x + size, pos.y + offset - size * 0.5),
+        ], 3.0)?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Spectators only. Ring around the followed player at screen position `pos`.
+pub fn draw_follow_ring(ctx: &mut Context, pos: Point2, radius: f32, color: Color) -> GameResult<()> {
+    graphics::set_color(ctx, color)?;
+    graphics::circle(ctx, DrawMode::Line(2.0), pos, radius, 0.5)?;
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Spectators only. Describes the followed player on the left: `lines` from `update_ui`,
+/// then the keys they are holding, lit in their color.
+pub fn draw_follow_panel(
+    ctx: &mut Context,
+    layout: &Layout,
+    lines: &[graphics::Text],
+    player: &Player,
+    palette: Palette,
+    foreground: Color,
+) -> GameResult<()> {
+    let s = layout.scale;
+    let widget_height = INPUT_KEY_SIZE * 2.0 + 4.0;
+    let height = FOLLOW_LINE_HEIGHT * lines.len() as f32 + widget_height + 20.0;
+    let origin = layout.place(Anchor::TopLeft, (FOLLOW_PANEL_X, FOLLOW_PANEL_Y), (FOLLOW_PANEL_WIDTH, height));
+    graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 0.6))?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(origin.x, origin.y, FOLLOW_PANEL_WIDTH * s, height * s))?;
+    graphics::set_color(ctx, foreground)?;
+
+    for (i, line) in lines.iter().enumerate() {
+        let margin = (FOLLOW_PANEL_X + 8.0, FOLLOW_PANEL_Y + 6.0 + FOLLOW_LINE_HEIGHT * i as f32);
+        layout.draw_text(ctx, line, Anchor::TopLeft, margin, None)?;
+    }
+
+    let top = FOLLOW_PANEL_Y + 10.0 + FOLLOW_LINE_HEIGHT * lines.len() as f32;
+    let widget_origin = layout.place(Anchor::TopLeft, (FOLLOW_PANEL_X + 8.0, top), (0.0, 0.0));
+    draw_input_widget(ctx, widget_origin, s, &player.input, player_color(palette, player.color_index))
+}
+
+/// Arrow keys laid out like a keyboard with a wide fire key to their right.
+fn draw_input_widget(ctx: &mut Context, origin: Point2, scale: f32, input: &InputState, lit: Color) -> GameResult<()> {
+    let key = INPUT_KEY_SIZE * scale;
+    let step = key + 2.0 * scale;
+    let keys = [
+        (input.up, graphics::Rect::new(origin.x + step, origin.y, key, key)),
+        (input.left, graphics::Rect::new(origin.x, origin.y + step, key, key)),
+        (input.down, graphics::Rect::new(origin.x + step, origin.y + step, key, key)),
+        (input.right, graphics::Rect::new(origin.x + step * 2.0, origin.y + step, key, key)),
+        (input.fire, graphics::Rect::new(origin.x + step * 3.0 + 10.0 * scale, origin.y + step, key * 3.0, key)),
+    ];
+    for &(held, rect) in &keys {
+        if held {
+            graphics::set_color(ctx, lit)?;
+            graphics::rectangle(ctx, DrawMode::Fill, rect)?;
+        } else {
+            graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.3))?;
+            graphics::rectangle(ctx, DrawMode::Line(1.0), rect)?;
+        }
+    }
+    graphics::set_color(ctx, graphics::WHITE)
+}
@@ -0,0 +1,152 @@
+use ggez::nalgebra::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::game_structs::InputState;
+
+use std::collections::VecDeque;
+
+/// A server never replays more than this much of a player's input behind, a burst of late
+/// packets skips ahead instead.
+const MAX_REPLAY_BEHIND: f32 = 0.25;
+/// Gaps in the client clock shorter than this are rounding, not lost inputs.
+const LOST_INPUT_EPSILON: f64 = 0.001;
+
+/// One stretch of unchanged input on the client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeldInput {
+    pub input: InputState,
+    pub seconds: f32,
+}
+
+/// A slice of a simulation step the server moves a remote ship with, see `InputTimeline::advance`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStep {
+    pub input: InputState,
+    pub seconds: f32,
+    /// Where the client says the ship ended up after this step, from `NetClientInput::final_position`.
+    pub checkpoint: Option<Vector2<f32>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PendingInput {
+    held: HeldInput,
+    checkpoint: Option<Vector2<f32>>,
+}
+
+/// The keys a player held over time.
+///
+/// Clients `record` every simulation step and `take` the run-length list into each
+/// `NetClientInput`. The server `extend`s the player's timeline with it and `advance`s
+/// through it over its own steps, so the ship moves like it did on the client instead of
+/// holding the last keys until the next packet. That runs one packet behind the client.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputTimeline {
+    pending: VecDeque<PendingInput>,
+    /// Client only. Seconds recorded since connecting, sent as `NetClientInput::client_time`.
+    clock: f64,
+    /// Server only. `client_time` of the last input extended with, to notice lost ones.
+    last_client_time: Option<f64>,
+    /// Server only. Keys of the last step replayed, held on while the timeline is empty.
+    last_input: InputState,
+    /// Server only. A checkpoint skipped over by `MAX_REPLAY_BEHIND`, the ship jumps to it.
+    skipped_to: Option<Vector2<f32>>,
+}
+
+impl InputTimeline {
+    /// Client side. Adds one simulation step held with `input`.
+    pub fn record(&mut self, input: &InputState, seconds: f32) {
+        self.clock += seconds as f64;
+        if let Some(last) = self.pending.back_mut() {
+            if last.held.input == *input {
+                last.held.seconds += seconds;
+                return;
+            }
+        }
+        self.pending.push_back(PendingInput { held: HeldInput { input: input.clone(), seconds }, checkpoint: None });
+    }
+
+    /// Client side. Everything recorded since the last call, and the clock at its end.
+    pub fn take(&mut self) -> (Vec<HeldInput>, f64) {
+        let held = self.pending.drain(..).map(|pending| pending.held).collect();
+        (held, self.clock)
+    }
+
+    /// Server side. Queues the timeline of one `NetClientInput` that ends at `client_time`
+    /// with the ship at `checkpoint`. Time missing since the last one, from inputs that never
+    /// arrived, is filled in with the keys held before.
+    pub fn extend(&mut self, held: Vec<HeldInput>, client_time: f64, checkpoint: Vector2<f32>) {
+        let covered: f64 = held.iter().map(|h| h.seconds as f64).sum();
+        if let Some(last) = self.last_client_time {
+            let lost = client_time - covered - last;
+            if lost > LOST_INPUT_EPSILON {
+                let input = self.pending.back().map_or(self.last_input.clone(), |p| p.held.input.clone());
+                self.pending.push_back(PendingInput { held: HeldInput { input, seconds: lost as f32 }, checkpoint: None });
+            }
+        }
+        self.last_client_time = Some(client_time);
+
+        let count = held.len();
+        for (i, held) in held.into_iter().enumerate() {
+            let checkpoint = if i + 1 == count { Some(checkpoint) } else { None };
+            self.pending.push_back(PendingInput { held, checkpoint });
+        }
+
+        while self.behind() > MAX_REPLAY_BEHIND {
+            if let Some(skipped) = self.pending.pop_front() {
+                self.last_input = skipped.held.input;
+                if skipped.checkpoint.is_some() {
+                    self.skipped_to = skipped.checkpoint;
+                }
+            }
+        }
+    }
+
+    /// Server side. Splits a simulation step of `seconds` into the inputs held during it.
+    /// Runs on with the last keys once the timeline is empty.
+    pub fn advance(&mut self, seconds: f32) -> Vec<ReplayStep> {
+        let mut steps = Vec::new();
+        if let Some(pos) = self.skipped_to.take() {
+            steps.push(ReplayStep { input: self.last_input.clone(), seconds: 0.0, checkpoint: Some(pos) });
+        }
+
+        let mut left = seconds;
+        while left > 0.0 {
+            let front = match self.pending.front_mut() {
+                Some(front) => front,
+                None => {
+                    steps.push(ReplayStep { input: self.last_input.clone(), seconds: left, checkpoint: None });
+                    break;
+                }
+            };
+            if front.held.seconds > left {
+                front.held.seconds -= left;
+                steps.push(ReplayStep { input: front.held.input.clone(), seconds: left, checkpoint: None });
+                self.last_input = front.held.input.clone();
+                break;
+            }
+            let done = self.pending.pop_front().unwrap();
+            left -= done.held.seconds;
+            self.last_input = done.held.input.clone();
+            steps.push(ReplayStep { input: done.held.input, seconds: done.held.seconds, checkpoint: done.checkpoint });
+        }
+        steps
+    }
+
+    /// Seconds of input waiting to be replayed.
+    pub fn behind(&self) -> f32 {
+        self.pending.iter().map(|p| p.held.seconds).sum()
+    }
+
+    /// Server side. Whether `advance` has anything to replay. Without it the player's
+    /// input is simply held.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty() || self.skipped_to.is_some()
+    }
+
+    /// Server side. Drops what's waiting, e.g. once the ship was hit and the client's
+    /// positions from before don't apply anymore.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.skipped_to = None;
+    }
+}
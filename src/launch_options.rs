@@ -0,0 +1,205 @@
+use crate::game_structs::{NetRole, VIEW_SIZE};
+use crate::stats;
+
+use std::env;
+
+const LARGE_WORLD_SCALE: f32 = 3.0;
+
+/// Everything the command line decides about how this process runs.
+///
+/// Supported forms (positional arguments are kept for compatibility):
+///  * `rust-blaster [difficulty]`           - server with a local player
+///  * `rust-blaster c <address>`            - client
+///  * `rust-blaster s <address>`            - spectator
+///  * `rust-blaster --server --no-play`     - server that only referees
+///  * `rust-blaster --large-world`          - server with a playfield 3x the view
+///  * `rust-blaster --window 640x360`       - window size in pixels, the game plays the same at any size
+///  * `rust-blaster --bench`                - print simulation timings and exit
+///  * `rust-blaster --seed <n>`             - every run uses the same rock sequence
+///  * `rust-blaster --practice`             - hits are counted but never end the run
+///  * `rust-blaster --warm-up`              - sparse rocks and no score until Enter or the first player joins
+///  * `rust-blaster --daily`                - offline run with today's shared seed
+///  * `rust-blaster --resume`               - continue the run in savegame.bin
+///  * `rust-blaster --hardcore`             - every volley costs 0.2 points
+///  * `rust-blaster --versus`               - rocks are assigned to players, who score against each other
+///  * `rust-blaster --debug`                - difficulty clock keys, nothing is recorded
+///  * `rust-blaster --record-input <file>`  - offline run that logs every input change
+///  * `rust-blaster --play-input <file>`    - replays a logged run, then hands back the keyboard
+///  * `rust-blaster --lang de`              - on-screen text from strings.de.json
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    pub role: NetRole,
+    pub server_address: String,
+    pub difficulty_mult: f32,
+    pub local_play: bool,
+    pub world_scale: f32,
+    /// Initial window size in pixels. Only changes how big things are drawn.
+    pub window: (u32, u32),
+    pub bench: bool,
+    pub seed: Option<u64>,
+    pub practice: bool,
+    /// Server only. Starts in a warm-up that doesn't count, see `MainState::warm_up`.
+    pub warm_up: bool,
+    /// Date (YYYY-MM-DD, UTC) of the daily challenge being played.
+    pub daily: Option<String>,
+    pub resume: bool,
+    pub hardcore: bool,
+    pub versus: bool,
+    pub ship: u8,
+    pub debug: bool,
+    pub record_input: Option<String>,
+    /// Nothing is recorded for a run that was played back.
+    pub play_input: Option<String>,
+    /// Picks `strings.<lang>.json` for on-screen text, see `strings::Strings::load`.
+    pub lang: Option<String>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> LaunchOptions {
+        LaunchOptions {
+            role: NetRole::Server,
+            server_address: String::from("localhost"),
+            difficulty_mult: 1.0,
+            local_play: true,
+            world_scale: 1.0,
+            window: (VIEW_SIZE, VIEW_SIZE),
+            bench: false,
+            seed: None,
+            practice: false,
+            warm_up: false,
+            daily: None,
+            resume: false,
+            hardcore: false,
+            versus: false,
+            ship: 0,
+            debug: false,
+            record_input: None,
+            play_input: None,
+            lang: None,
+        }
+    }
+}
+
+impl LaunchOptions {
+    pub fn from_args() -> LaunchOptions {
+        let args: std::vec::Vec<String> = env::args().skip(1).collect();
+        LaunchOptions::parse(&args)
+    }
+
+    pub fn parse(args: &[String]) -> LaunchOptions {
+        let mut options = LaunchOptions::default();
+        let mut positional = Vec::new();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--server" => options.role = NetRole::Server,
+                "--no-play" => options.local_play = false,
+                "--large-world" => options.world_scale = LARGE_WORLD_SCALE,
+                "--bench" => options.bench = true,
+                "--practice" => options.practice = true,
+                "--warm-up" => options.warm_up = true,
+                "--daily" => options.daily = Some(stats::utc_today()),
+                "--resume" => options.resume = true,
+                "--hardcore" => options.hardcore = true,
+                "--versus" => options.versus = true,
+                "--debug" => options.debug = true,
+                "--ship" => match args.next().map(|s| s.parse::<u8>()) {
+                    Some(Ok(ship)) => options.ship = ship,
+                    _ => println!("--ship needs a number, using the default ship"),
+                },
+                "--record-input" => match args.next() {
+                    Some(file) => options.record_input = Some(file.clone()),
+                    None => println!("--record-input needs a file name, not recording"),
+                },
+                "--play-input" => match args.next() {
+                    Some(file) => options.play_input = Some(file.clone()),
+                    None => println!("--play-input needs a file name, playing normally"),
+                },
+                "--lang" => match args.next() {
+                    Some(lang) => options.lang = Some(lang.clone()),
+                    None => println!("--lang needs a language like de, using English"),
+                },
+                "--window" => match args.next().and_then(|s| parse_window(s)) {
+                    Some(window) => options.window = window,
+                    None => println!("--window needs a size like 640x360, using {}x{}", VIEW_SIZE, VIEW_SIZE),
+                },
+                "--seed" => match args.next().map(|s| s.parse::<u64>()) {
+                    Some(Ok(seed)) => options.seed = Some(seed),
+                    _ => println!("--seed needs a number, using random seeds"),
+                },
+                _ if arg.starts_with("--") => println!("Ignoring unknown option: {}", arg),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        if positional.len() >= 2 && positional[0].starts_with("s") {
+            options.role = NetRole::Spectator;
+            options.server_address = positional[1].clone();
+        } else if positional.len() >= 2 {
+            options.role = NetRole::Client;
+            options.server_address = positional[1].clone();
+        } else if let Some(diff) = positional.get(0) {
+            options.difficulty_mult = diff.parse().unwrap_or(1.0);
+        }
+
+        if options.role == NetRole::Spectator {
+            options.local_play = false;
+        }
+
+        if let Some(date) = &options.daily {
+            if options.role != NetRole::Server || !options.local_play || options.practice {
+                println!("--daily is a solo run and can't be combined with joining, --no-play or --practice. Ignoring it.");
+                options.daily = None;
+            } else {
+                // Everyone playing today gets the same rocks.
+                options.seed = Some(fnv1a(date.as_bytes()));
+                options.difficulty_mult = 1.0;
+            }
+        }
+
+        if options.versus && options.role != NetRole::Server {
+            println!("--versus is decided by the server, ignoring it.");
+            options.versus = false;
+        }
+
+        let logged = options.record_input.is_some() || options.play_input.is_some();
+        if options.warm_up && (options.role != NetRole::Server || options.daily.is_some() || options.resume || logged) {
+            println!("--warm-up is for a fresh server run and can't be combined with joining, --daily, --resume or input logs. Ignoring it.");
+            options.warm_up = false;
+        }
+
+        if options.record_input.is_some() || options.play_input.is_some() {
+            if options.role != NetRole::Server || !options.local_play || options.resume {
+                println!("--record-input and --play-input need a fresh offline run and can't be combined with joining, --no-play or --resume. Ignoring them.");
+                options.record_input = None;
+                options.play_input = None;
+            } else if options.record_input.is_some() && options.play_input.is_some() {
+                println!("--record-input can't be combined with --play-input, only playing back.");
+                options.record_input = None;
+            }
+        }
+        options
+    }
+}
+
+/// "640x360" into width and height, both at least 1.
+fn parse_window(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.splitn(2, 'x');
+    let width = parts.next()?.parse::<u32>().ok()?;
+    let height = parts.next()?.parse::<u32>().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// 64-bit FNV-1a. Stable across platforms and versions, unlike the std hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
@@ -0,0 +1,136 @@
+use serde::{Serialize, Deserialize};
+
+use std::sync::mpsc::{self, Sender};
+
+pub const LEADERBOARD_FILENAME: &str = "leaderboard_setup.json";
+
+/// Where finished runs are submitted. Nothing is sent while `leaderboard_url` is missing or blank.
+/// Submitting needs a build with the `online` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderboardSetup {
+    pub leaderboard_url: String,
+    /// Shown on the leaderboard for runs from this server.
+    pub name: String,
+    /// Sent along with every run so a self-hosted leaderboard can tell its servers apart from strangers.
+    pub secret: String,
+    pub timeout_ms: u64,
+}
+
+impl Default for LeaderboardSetup {
+    fn default() -> LeaderboardSetup {
+        LeaderboardSetup {
+            leaderboard_url: String::new(),
+            name: String::from("anonymous"),
+            secret: String::new(),
+            timeout_ms: 3000,
+        }
+    }
+}
+
+/// The JSON body POSTed to `leaderboard_url` when a run ends.
+///
+/// ```json
+/// {"name": "anonymous", "score": 812.0, "survival_time": 143.2, "difficulty": 1.0,
+///  "seed": 1234, "version": "0.1.0", "secret": ""}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: f32,
+    /// Seconds.
+    pub survival_time: f32,
+    /// The difficulty multiplier at the end of the run.
+    pub difficulty: f32,
+    pub seed: u64,
+    /// Version of the game that played the run.
+    pub version: String,
+    pub secret: String,
+}
+
+/// Submits runs from a background thread so a slow or dead leaderboard never stalls the game.
+pub struct Leaderboard {
+    /// None when submitting is off.
+    sender: Option<Sender<LeaderboardEntry>>,
+    setup: LeaderboardSetup,
+}
+
+impl Leaderboard {
+    pub fn spawn(setup: LeaderboardSetup) -> Leaderboard {
+        if setup.leaderboard_url.trim().is_empty() {
+            return Leaderboard::disabled();
+        }
+        if !cfg!(feature = "online") {
+            println!("Ignoring leaderboard_url, this build has no `online` feature.");
+            return Leaderboard::disabled();
+        }
+
+        let (sender, receiver) = mpsc::channel::<LeaderboardEntry>();
+        let url = setup.leaderboard_url.clone();
+        let timeout_ms = setup.timeout_ms;
+        let spawned = std::thread::Builder::new().name("leaderboard submitter".into())
+            .spawn(move || {
+                for entry in receiver {
+                    match post(&url, timeout_ms, &entry) {
+                        Ok(()) => println!("Submitted the run to the leaderboard."),
+                        Err(e) => println!("Failed to submit the run to {}: {}", url, e),
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            println!("Leaderboard disabled, failed to start its thread: {}", e);
+            return Leaderboard::disabled();
+        }
+
+        Leaderboard {
+            sender: Some(sender),
+            setup,
+        }
+    }
+
+    pub fn disabled() -> Leaderboard {
+        Leaderboard {
+            sender: None,
+            setup: LeaderboardSetup::default(),
+        }
+    }
+
+    /// Whether finished runs go anywhere. `submit` does nothing otherwise.
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    pub fn submit(&self, score: f32, survival_time: f32, difficulty: f32, seed: u64) {
+        if let Some(sender) = &self.sender {
+            // The submitter only goes away when the process is exiting.
+            let _ = sender.send(LeaderboardEntry {
+                name: self.setup.name.clone(),
+                score,
+                survival_time,
+                difficulty,
+                seed,
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                secret: self.setup.secret.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(feature = "online")]
+fn post(url: &str, timeout_ms: u64, entry: &LeaderboardEntry) -> Result<(), String> {
+    let body = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let response = ureq::post(url)
+        .set("Content-Type", "application/json")
+        .timeout(std::time::Duration::from_millis(timeout_ms))
+        .send_string(&body);
+    if response.ok() {
+        Ok(())
+    } else {
+        Err(format!("{} {}", response.status(), response.status_text()))
+    }
+}
+
+#[cfg(not(feature = "online"))]
+fn post(_url: &str, _timeout_ms: u64, _entry: &LeaderboardEntry) -> Result<(), String> {
+    Err(String::from("built without the `online` feature"))
+}
@@ -0,0 +1,42 @@
+//! Rust-Blaster as a library, for tools that want to run or talk to the game without
+//! its window: bots, load tests, referees and the integration tests.
+//!
+//! A headless `game_structs::MainState` runs the whole simulation. `net_structs` holds
+//! what goes over the wire and `networking` the threads that send and receive it.
+extern crate ggez;
+
+extern crate rand;
+
+pub mod actor;
+pub mod aim;
+pub mod bench;
+pub mod bindings;
+pub mod codec;
+pub mod console;
+pub mod config;
+pub mod effects;
+pub mod events;
+pub mod game;
+pub mod game_structs;
+pub mod highscores;
+pub mod hud;
+pub mod input_timeline;
+pub mod launch_options;
+pub mod leaderboard;
+mod limiter;
+pub mod networking;
+pub mod net_structs;
+pub mod patterns;
+mod profiler;
+mod recovery;
+mod replay;
+mod rng;
+pub mod savegame;
+pub mod snapshot_v1;
+mod screenshot;
+mod scrub;
+pub mod sfx;
+pub mod stats;
+pub mod strings;
+pub mod tuning;
+pub mod well;
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// Sleeping is only trusted up to this close to the deadline, the rest is spun.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Caps the frame rate by waiting out the rest of each frame's budget after present.
+pub struct FrameLimiter {
+    max_fps: u32,
+    last_frame: Instant,
+}
+
+impl FrameLimiter {
+    /// `max_fps` of 0 leaves pacing to vsync.
+    pub fn new(max_fps: u32) -> FrameLimiter {
+        FrameLimiter {
+            max_fps,
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Fixed update steps a frame has to fit so the accumulator never falls behind.
+    pub fn steps_per_frame(&self, update_fps: u32) -> u32 {
+        if self.max_fps == 0 {
+            return 1;
+        }
+        (update_fps + self.max_fps - 1) / self.max_fps + 1
+    }
+
+    pub fn wait(&mut self) {
+        if self.max_fps == 0 {
+            return;
+        }
+
+        let target = self.last_frame + Duration::from_secs(1) / self.max_fps;
+        let now = Instant::now();
+        if now >= target {
+            // Running behind, don't try to make up for it with a burst of short frames.
+            self.last_frame = now;
+            return;
+        }
+
+        let remaining = target - now;
+        if remaining > SPIN_MARGIN {
+            std::thread::sleep(remaining - SPIN_MARGIN);
+        }
+        while Instant::now() < target {
+            std::thread::yield_now();
+        }
+        self.last_frame = target;
+    }
+}
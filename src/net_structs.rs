@@ -1,39 +1,244 @@
 use crate::actor;
+use crate::aim;
 use crate::game_structs;
 use actor::{Actor, Vec2Serial};
 use ggez::nalgebra::Vector2;
-use game_structs::{MainState, InputState, Player};
+use game_structs::{MainState, InputState, NetRole, Player, Popup, DebugCommand, VIEW_SIZE};
+
+
+use serde::{Serialize, Serializer, Deserialize};
+use serde::ser::SerializeSeq;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::game::EXTRA_LIFE_POPUP_TIME;
+use crate::events::{EventRecord, GameEvent};
+use crate::codec::{BincodeCodec, Codec, DecodeError, WireFormat};
+use crate::scrub::ScrubFrame;
+use crate::snapshot_v1::NetFromServerV1;
+use crate::patterns::Telegraph;
+use crate::input_timeline::HeldInput;
+use crate::tuning::Tuning;
+
+
+/// Snapshot layout this build writes by default.
+///  * 1: `NetFromServerV1`, from before snapshots were versioned
+///  * 2: events, difficulty, world size, every actor kind and everything players and actors
+///    carry since then, truncated snapshots
+pub const SNAPSHOT_VERSION: u32 = 2;
+/// Oldest layout this build can still write and read.
+pub const MIN_SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_VERSION_COUNT: usize = (SNAPSHOT_VERSION - MIN_SNAPSHOT_VERSION + 1) as usize;
+
+/// First message on the snapshot stream, sent by both ends.
+/// Snapshots then use the newest layout both of them understand.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetSnapshotHello {
+    pub min_version: u32,
+    pub max_version: u32,
+}
 
+impl NetSnapshotHello {
+    pub fn ours() -> NetSnapshotHello {
+        NetSnapshotHello {
+            min_version: MIN_SNAPSHOT_VERSION,
+            max_version: SNAPSHOT_VERSION,
+        }
+    }
+
+    /// Both ends compute the same answer from the two hellos. `None` when there is no common version.
+    pub fn negotiate(&self, other: &NetSnapshotHello) -> Option<u32> {
+        let version = self.max_version.min(other.max_version);
+        if version >= self.min_version.max(other.min_version) {
+            Some(version)
+        } else {
+            None
+        }
+    }
+}
 
-use serde::{Serialize, Deserialize};
+/// Sent by both ends after the `NetSnapshotHello`s. Snapshots are then written in
+/// `WireFormat::negotiate` of the two.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetWireHello {
+    pub wire_format: WireFormat,
+}
 
+/// Sent by the receiving end after the `NetWireHello`s, so the server can announce spectators.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetViewerHello {
+    pub spectator: bool,
+}
 
 /// New Player "handsake". 
-/// Server sends this struct to the player that connects.
+/// Server sends this struct to the player that connects, followed by a `NetWelcome`.
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct NetPlayerConnected {
-    pub player_index: usize
+    pub player_index: usize,
+    pub practice: bool,
+    pub hardcore: bool,
+    /// Clients predict their own shots, which needs the server's `GameSetup::shots_wrap`.
+    pub shots_wrap: bool,
+    /// Format of the inputs that follow, negotiated from `NetPlayerHello::wire_format`.
+    pub wire_format: WireFormat,
+    /// `NetPlayerHello::aim_assist` within the server's limit. Clients aim their own shots with it.
+    pub aim_assist: f32,
+    /// Clients cull rocks like the server does, see `GameSetup::rock_despawn_margin`.
+    pub rock_despawn_margin: f32,
+    /// Clients show the standings of a versus game in their HUD.
+    pub versus: bool,
 }
 impl NetPlayerConnected {
-    pub fn make(player_index: usize) -> NetPlayerConnected {
+    /// Seats `hello`'s sender at `player_index`, inputs following in `wire_format`.
+    pub fn make(state: &MainState, player_index: usize, hello: &NetPlayerHello, wire_format: WireFormat) -> NetPlayerConnected {
         NetPlayerConnected {
-            player_index: player_index
+            player_index,
+            practice: state.practice,
+            hardcore: state.hardcore,
+            shots_wrap: state.game_setup.shots_wrap,
+            wire_format,
+            aim_assist: aim::granted_strength(hello.aim_assist, state.game_setup.max_aim_assist),
+            rock_despawn_margin: state.game_setup.rock_despawn_margin,
+            versus: state.versus,
+        }
+    }
+}
+
+/// Sent right after `NetPlayerConnected`, so a player joining mid-run knows the game it's in
+/// before the first snapshot is drawn.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetWelcome {
+    /// The server's `SNAPSHOT_VERSION`.
+    pub version: u32,
+    pub player_index: usize,
+    pub world_width: f32,
+    pub world_height: f32,
+    pub difficulty_mult: f32,
+    pub difficulty_points: f32,
+    pub practice: bool,
+    pub hardcore: bool,
+    pub versus: bool,
+    /// `MainState::round` on the server.
+    pub round: u32,
+    /// Seconds into the current run.
+    pub elapsed: f32,
+    pub players: Vec<NetWelcomePlayer>,
+}
+
+/// Everyone already seated when a player joins, the joining player included.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetWelcomePlayer {
+    pub index: u32,
+    pub color_index: u8,
+    pub ship: u8,
+    pub active: bool,
+}
+
+impl NetWelcome {
+    pub fn make(state: &MainState, player_index: usize) -> NetWelcome {
+        NetWelcome {
+            version: SNAPSHOT_VERSION,
+            player_index,
+            world_width: state.world_width,
+            world_height: state.world_height,
+            difficulty_mult: state.difficulty_mult,
+            difficulty_points: state.difficulty_points,
+            practice: state.practice,
+            hardcore: state.hardcore,
+            versus: state.versus,
+            round: state.round,
+            elapsed: state.curr_time,
+            players: state.players.iter().map(|p| NetWelcomePlayer {
+                index: p.index,
+                color_index: p.color_index,
+                ship: p.ship,
+                active: p.active,
+            }).collect(),
+        }
+    }
+
+    /// Runs on the client before any snapshot is applied.
+    pub fn update_main_state(self, state: &mut MainState) {
+        state.local_player_index = Some(self.player_index);
+        state.world_width = self.world_width;
+        state.world_height = self.world_height;
+        state.difficulty_mult = self.difficulty_mult;
+        state.difficulty_points = self.difficulty_points;
+        state.practice = self.practice;
+        state.hardcore = self.hardcore;
+        state.versus = self.versus;
+        state.round = self.round;
+        state.curr_time = self.elapsed;
+
+        while self.players.len() > state.players.len() {
+            state.add_player();
+        }
+        for welcomed in self.players {
+            if let Some(player) = state.players.get_mut(welcomed.index as usize) {
+                player.color_index = welcomed.color_index;
+                player.set_ship(welcomed.ship);
+                player.active = welcomed.active;
+            }
         }
     }
 }
 
+/// Sent right after the `NetWelcome`, so clients predict with the server's speeds and hitboxes.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetTuning {
+    /// `Tuning::to_fields` of the server.
+    pub fields: Vec<(String, f32)>,
+}
+
+impl NetTuning {
+    pub fn make(tuning: &Tuning) -> NetTuning {
+        NetTuning { fields: tuning.to_fields() }
+    }
+
+    /// The server's tuning, or the fields this build has that the server doesn't know about.
+    pub fn tuning(&self) -> Result<Tuning, Vec<String>> {
+        Tuning::from_fields(&self.fields)
+    }
+}
+
+/// First message a client sends on the input stream, answered with `NetPlayerConnected`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetPlayerHello {
+    pub ship: u8,
+    /// Random per client process. The server hands a returning token its old slot.
+    pub reconnect_token: u64,
+    /// Format the client would like its inputs in.
+    pub wire_format: WireFormat,
+    /// Aim assist strength the player asked for, from input_setup.json.
+    pub aim_assist: f32,
+}
+
 /// The struct that is transfered from the client to the server.
 /// 
 /// Just sending input state works ok only for very low latency and transfer rates.
 /// 
 /// 
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct NetClientInput {
+    /// Counts up from 0 on every connection, so the server can tell when inputs went missing.
+    pub seq: u64,
     pub input_state: InputState,
     pub final_position: Vec2Serial,
     pub shots_made: Vec<Actor>,
+    /// Only carried out when the server runs with `--debug` as well.
+    pub debug_commands: Vec<DebugCommand>,
+    /// The last event sequence number the client applied, see `EventLog`.
+    pub acked_event_seq: u64,
+    /// The keys held since the previous input, oldest first. The server replays them
+    /// instead of holding `input_state` until the next packet, see `InputTimeline`.
+    pub timeline: Vec<HeldInput>,
+    /// Seconds the client simulated since connecting, at the end of `timeline`.
+    pub client_time: f64,
 }
 
 impl NetClientInput {
@@ -43,25 +248,78 @@ impl NetClientInput {
     // normally you would want to ensure the data a client sends is valid.
     // For the purposes of this project and due to the game being co-op we suppose we can trust the client to not cheat.
     #[allow(unused_mut)]
-    pub fn update_main_state(mut self, player_id: usize, state: &mut MainState) {
-        if self.shots_made.len() > 0 {
-            state.play_sounds.play_shot = true;
+    pub fn update_main_state(mut self, player_id: usize, received_at: Instant, state: &mut MainState) {
+        let player_count = state.players.len();
+        let player = match state.players.get_mut(player_id) {
+            Some(player) => player,
+            None => {
+                println!("Ignoring input for P{}, there are only {} players.", player_id + 1, player_count);
+                return;
+            }
+        };
+        // A kicked player's late packets don't bring them back.
+        if !player.active {
+            return;
+        }
+
+        let per_volley = player.volley.max(1) as usize;
+        let volleys = (self.shots_made.len() + per_volley - 1) / per_volley;
+        player.stats.shots_fired += volleys as u32;
+        player.last_input_at = Some(received_at);
+        // Inputs can be applied out of order when a batch gets absorbed, acks only move forward.
+        player.acked_event_seq = player.acked_event_seq.max(self.acked_event_seq);
+        // The client may not have heard of the hit yet, the wreck stays where it was hit.
+        if player.dying_until <= 0.0 {
+            let final_position = Vector2::new(self.final_position.x, self.final_position.y);
+            if self.timeline.is_empty() {
+                player.input = self.input_state;
+                player.actor.pos = final_position;
+            } else {
+                // The ship reaches `final_position` when the replay does.
+                player.timeline.extend(self.timeline, self.client_time, final_position);
+            }
+        } else {
+            player.input = self.input_state;
+        }
+
+        if self.shots_made.len() > 0 && !state.play_sounds.shots_by.contains(&player_id) {
+            state.play_sounds.shots_by.push(player_id);
         }
+        state.charge_volleys(volleys as u32);
 
         for mut shot in self.shots_made {
-            shot.post_deserialize();
+            shot.post_deserialize(&state.tuning);
+            // Whoever sent the input fired the shot, whatever the client put in. Ids are ours to issue.
+            shot.owner = Some(player_id);
+            shot.id = 0;
             state.shots.push(shot);
         }
-        state.players[player_id].input = self.input_state;
-
-        
-        state.players[player_id].actor.pos = Vector2::new(self.final_position.x, self.final_position.y);
+        if state.debug {
+            state.pending_debug.extend(self.debug_commands);
+        }
     }
     
+    /// Takes over the shots and debug commands of an input that was never applied on its own.
+    fn absorb(&mut self, older: NetClientInput) {
+        let mut shots_made = older.shots_made;
+        shots_made.append(&mut self.shots_made);
+        self.shots_made = shots_made;
+        let mut debug_commands = older.debug_commands;
+        debug_commands.append(&mut self.debug_commands);
+        self.debug_commands = debug_commands;
+        let mut timeline = older.timeline;
+        timeline.append(&mut self.timeline);
+        self.timeline = timeline;
+    }
+
     /// Runs on client to prepare the struct for sending.
-    pub fn make_from_state(state: &mut MainState) -> NetClientInput {
-        let player = state.get_local_player().unwrap_or(&state.players[0]);
+    pub fn make_from_state(state: &mut MainState, seq: u64) -> NetClientInput {
+        // Before the first snapshot arrives the local player may not exist yet.
+        let final_position = state.get_local_player()
+            .map(|player| Vec2Serial::from_vec(&player.actor.pos))
+            .unwrap_or_default();
     
+        let (timeline, client_time) = state.input_timeline.take();
         let mut shots_made = Vec::with_capacity(state.local_shots_made.len());
 
         for shot in &state.local_shots_made {
@@ -70,10 +328,18 @@ impl NetClientInput {
             shots_made.push(shot);
         }
         
+        let mut input_state = state.local_input.clone();
+        input_state.fire_pressed = state.fire_latch.take_for_net();
+
         let r = NetClientInput {
-            input_state: state.local_input.clone(),
-            final_position: Vec2Serial::from_vec(&player.actor.pos),
+            seq,
+            input_state,
+            final_position: final_position,
             shots_made: shots_made,
+            debug_commands: std::mem::replace(&mut state.pending_debug, Vec::new()),
+            acked_event_seq: state.last_event_seq,
+            timeline,
+            client_time,
         };
 
         state.local_shots_made.clear();
@@ -82,60 +348,483 @@ impl NetClientInput {
 }
 
 
+/// Most inputs a player can have waiting. Beyond that the oldest is merged into the next one.
+const INPUT_QUEUE_LIMIT: usize = 8;
+
+pub struct QueuedInput {
+    pub input: NetClientInput,
+    pub received_at: Instant,
+}
+
+/// Counters of one player's `InputQueue`, shown in the F3 overlay.
+#[derive(Debug, Clone, Default)]
+pub struct InputQueueStats {
+    pub received: u64,
+    /// Inputs that never arrived, from gaps in `NetClientInput::seq`.
+    pub lost: u64,
+    /// Inputs merged into the next one because the queue was full. Their shots still count.
+    pub merged: u64,
+    /// Most inputs that were waiting at once.
+    pub max_depth: usize,
+    /// How far the replayed ship was from where the client put it, at the last checkpoint
+    /// and at worst. See `InputTimeline`.
+    pub last_correction: f32,
+    pub max_correction: f32,
+}
+
+impl InputQueueStats {
+    pub fn record_correction(&mut self, distance: f32) {
+        self.last_correction = distance;
+        self.max_correction = self.max_correction.max(distance);
+    }
+}
+
+/// Inputs from one remote player the simulation hasn't applied yet. The player's
+/// receiver thread fills it, every server tick drains it before simulating.
+#[derive(Default)]
+pub struct InputQueue {
+    pending: VecDeque<QueuedInput>,
+    last_seq: Option<u64>,
+    pub stats: InputQueueStats,
+}
+
+impl InputQueue {
+    pub fn push(&mut self, input: NetClientInput, received_at: Instant) {
+        if let Some(last) = self.last_seq {
+            self.stats.lost += input.seq.saturating_sub(last + 1);
+        }
+        self.last_seq = Some(input.seq);
+        self.stats.received += 1;
+
+        self.pending.push_back(QueuedInput { input, received_at });
+        if self.pending.len() > INPUT_QUEUE_LIMIT {
+            if let Some(oldest) = self.pending.pop_front() {
+                if let Some(next) = self.pending.front_mut() {
+                    next.input.absorb(oldest.input);
+                }
+            }
+            self.stats.merged += 1;
+        }
+        self.stats.max_depth = self.stats.max_depth.max(self.pending.len());
+    }
+
+    /// The waiting inputs, oldest first.
+    pub fn take(&mut self) -> VecDeque<QueuedInput> {
+        std::mem::replace(&mut self.pending, VecDeque::new())
+    }
+
+    /// Forgets everything, for a slot that is handed to someone else.
+    pub fn reset(&mut self) {
+        *self = InputQueue::default();
+    }
+}
+
+/// Borrowed twin of `NetFromServer` used for serializing without cloning the state.
+/// Field order and types must match it exactly, clients read these bytes as a `NetFromServer`.
+#[derive(Serialize)]
+struct NetFromServerRef<'a> {
+    players: &'a [Player],
+    actors: ActorChain<'a>,
+    score: f32,
+    server_time: f32,
+    events: &'a [EventRecord],
+    world_width: f32,
+    world_height: f32,
+    difficulty_mult: f32,
+    rock_time_scale: f32,
+    time_slow_left: f32,
+    connections: u32,
+    spectators: u32,
+    difficulty_points: f32,
+    truncated: bool,
+    telegraphs: &'a [Telegraph],
+    live_ids: &'a [Vec<u32>],
+}
+
+/// Rocks, shots, gems, power-ups and wells serialized as the single actor list of `NetFromServer`.
+/// Actors already marked `kill` are left out, the snapshot can be taken before `clear_dead_stuff` ran.
+#[derive(Clone, Copy)]
+struct ActorChain<'a> {
+    lists: [&'a [Actor]; 5],
+    /// Only these go out when the snapshot is over its budget, see `ActorBudget`.
+    picked: Option<&'a [PickedActor]>,
+}
+
+impl<'a> ActorChain<'a> {
+    fn iter(&self) -> Box<dyn Iterator<Item = &'a Actor> + 'a> {
+        let lists = self.lists;
+        match self.picked {
+            Some(picked) => Box::new(picked.iter().map(move |p| {
+                let list: &'a [Actor] = lists[p.list];
+                &list[p.index]
+            })),
+            None => Box::new(lists[0].iter().chain(lists[1]).chain(lists[2]).chain(lists[3]).chain(lists[4])
+                .filter(|actor| !actor.kill)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self.picked {
+            Some(picked) => picked.len(),
+            None => self.iter().count(),
+        }
+    }
+}
+
+/// Rocks this far from every player can't be on anyone's screen.
+const FAR_ROCK_DISTANCE: f32 = VIEW_SIZE as f32;
+/// Part of a truncated snapshot's budget kept for far rocks, so they all get refreshed eventually.
+const FAR_ROCK_SHARE: usize = 8;
+
+/// A live actor that goes out in a truncated snapshot.
+#[derive(Debug, Clone, Copy)]
+struct PickedActor {
+    /// Which list of the `ActorChain`.
+    list: usize,
+    /// Where in that list, counting actors marked `kill`.
+    index: usize,
+}
+
+/// The actors of a snapshot that has more of them than `SnapshotCache::max_actors`.
+struct ActorBudget {
+    picked: Vec<PickedActor>,
+    /// `Actor::id` of every live actor, list by list, for `NetFromServer::live_ids`.
+    live_ids: Vec<Vec<u32>>,
+}
+
+impl ActorBudget {
+    /// `None` when everything fits. Players always go out and count against `max_actors`,
+    /// the other actors by rank: rocks near a player, nearest first, then shots, then gems,
+    /// power-ups and wells, then far rocks. A share of the budget is kept for far rocks,
+    /// which take turns starting at `cursor`. Also returns where the next snapshot's turn starts.
+    fn pick(state: &MainState, max_actors: usize, cursor: usize) -> Option<(ActorBudget, usize)> {
+        let lists = [&state.rocks[..], &state.shots[..], &state.gems[..], &state.powerups[..], &state.wells[..]];
+        let live = |list: usize| lists[list].iter().enumerate()
+            .filter(|(_, actor)| !actor.kill)
+            .map(move |(index, _)| PickedActor { list, index });
+        let mut left = max_actors.saturating_sub(state.players.len());
+        if (0..lists.len()).map(|list| live(list).count()).sum::<usize>() <= left {
+            return None;
+        }
+
+        let players: Vec<&Actor> = state.players.iter().filter(|p| p.active).map(|p| &p.actor).collect();
+        let nearest = |rock: &Actor| players.iter()
+            .map(|player| {
+                let (width, height) = player.wrap_period(state.world_width, state.world_height);
+                actor::toroidal_distance(&rock.pos, &player.pos, width, height)
+            })
+            .fold(std::f32::INFINITY, f32::min);
+        let (mut near, far): (Vec<_>, Vec<_>) = live(0)
+            .map(|p| (nearest(&lists[0][p.index]), p))
+            .partition(|(distance, _)| *distance < FAR_ROCK_DISTANCE);
+        near.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let reserved = if far.is_empty() { 0 } else { (left / FAR_ROCK_SHARE).max(1).min(far.len()).min(left) };
+        let ranked = near.into_iter().map(|(_, p)| p).chain(live(1)).chain(live(2)).chain(live(3)).chain(live(4));
+        let mut picked: Vec<PickedActor> = ranked.take(left - reserved).collect();
+        left -= picked.len();
+        let start = if far.is_empty() { 0 } else { cursor % far.len() };
+        let taken = left.min(far.len());
+        picked.extend(far.iter().cycle().skip(start).take(taken).map(|(_, p)| *p));
+
+        // Clients keep each list in the server's order.
+        picked.sort_by_key(|p| (p.list, p.index));
+        let live_ids = (0..lists.len()).map(|list| live(list).map(|p| lists[list][p.index].id).collect()).collect();
+        Some((ActorBudget { picked, live_ids }, start + taken))
+    }
+}
+
+impl<'a> Serialize for ActorChain<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_seq(serializer, self.len(), self.iter())
+    }
+}
+
+/// Like `Serializer::collect_seq`, but with the length given. Bincode needs it up front
+/// and can't take it from a filtered iterator.
+fn serialize_seq<S: Serializer, T: Serialize, I: Iterator<Item = T>>(serializer: S, len: usize, items: I) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(len))?;
+    for item in items {
+        seq.serialize_element(&item)?;
+    }
+    seq.end()
+}
+
+/// The latest serialized snapshot, shared by every server sender thread.
+/// Older layouts are only encoded while a connected peer reads them.
+pub struct SnapshotCache {
+    /// Indexed by version - `MIN_SNAPSHOT_VERSION`.
+    encoded: [Arc<Vec<u8>>; SNAPSHOT_VERSION_COUNT],
+    readers: [u32; SNAPSHOT_VERSION_COUNT],
+    /// Increases with every new snapshot so senders can skip ones they already sent.
+    pub seq: u64,
+    pub interval: Duration,
+    built_at: Instant,
+    /// Actors, players included, a snapshot holds at most. 0 sends them all. See `ActorBudget`.
+    pub max_actors: usize,
+    /// Where the next truncated snapshot starts picking far rocks.
+    far_rock_cursor: usize,
+}
+
+impl SnapshotCache {
+    pub fn new() -> SnapshotCache {
+        SnapshotCache {
+            encoded: Default::default(),
+            readers: [0; SNAPSHOT_VERSION_COUNT],
+            seq: 0,
+            interval: Duration::from_millis(0),
+            built_at: Instant::now(),
+            max_actors: 0,
+            far_rock_cursor: 0,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.built_at.elapsed() >= self.interval
+    }
+
+    /// The latest snapshot in a negotiated version.
+    pub fn bytes(&self, version: u32) -> Arc<Vec<u8>> {
+        self.encoded[(version - MIN_SNAPSHOT_VERSION) as usize].clone()
+    }
+
+    pub fn add_reader(&mut self, version: u32) {
+        self.readers[(version - MIN_SNAPSHOT_VERSION) as usize] += 1;
+    }
+
+    pub fn remove_reader(&mut self, version: u32) {
+        let readers = &mut self.readers[(version - MIN_SNAPSHOT_VERSION) as usize];
+        *readers = readers.saturating_sub(1);
+    }
+
+    /// The current version is always kept fresh, older ones only while someone reads them.
+    fn wanted_versions(&self) -> Vec<u32> {
+        (MIN_SNAPSHOT_VERSION..=SNAPSHOT_VERSION)
+            .filter(|&v| v == SNAPSHOT_VERSION || self.readers[(v - MIN_SNAPSHOT_VERSION) as usize] > 0)
+            .collect()
+    }
+
+    /// Reuses the previous buffer when no sender is still writing it.
+    fn take_buffer(&mut self, version: u32) -> Vec<u8> {
+        let slot = &mut self.encoded[(version - MIN_SNAPSHOT_VERSION) as usize];
+        let old = std::mem::replace(slot, Arc::new(Vec::new()));
+        match Arc::try_unwrap(old) {
+            Ok(mut buf) => {
+                buf.clear();
+                buf
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn store(&mut self, version: u32, buf: Vec<u8>) {
+        self.encoded[(version - MIN_SNAPSHOT_VERSION) as usize] = Arc::new(buf);
+    }
+
+    fn finish(&mut self) {
+        self.seq += 1;
+        self.built_at = Instant::now();
+    }
+}
+
 ///
 /// Networking struct that the client receives from the server.
 ///
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct NetFromServer {
     players: Vec<Player>,
     actors: Vec<Actor>,
-    score: i32,
+    score: f32,
     server_time: f32,
+    events: Vec<EventRecord>,
+    world_width: f32,
+    world_height: f32,
+    difficulty_mult: f32,
+    rock_time_scale: f32,
+    time_slow_left: f32,
+    /// Snapshot streams the server is serving, clients and spectators alike.
+    connections: u32,
+    spectators: u32,
+    /// `MainState::difficulty_points`, for the HUD.
+    difficulty_points: f32,
+    /// The server had more actors than `SnapshotCache::max_actors` and left some out. Clients
+    /// keep the ones they don't hear about instead of dropping them.
+    truncated: bool,
+    /// Pattern rocks about to appear, see `SpawnQueue`.
+    telegraphs: Vec<Telegraph>,
+    /// Only in truncated snapshots: `Actor::id` of every live rock, shot, gem, power-up and
+    /// well on the server, a list for each kind in the server's order. Clients keep the ones
+    /// left out of `actors` by it.
+    live_ids: Vec<Vec<u32>>,
 }
 
-impl NetFromServer {
-    pub fn make_from_state(state: &MainState) -> NetFromServer {
-        let mut actors = Vec::new();
-        let mut players = Vec::new();
+/// A received snapshot in whichever layout was negotiated.
+#[derive(Debug, PartialEq)]
+pub enum VersionedSnapshot {
+    V1(NetFromServerV1),
+    Current(NetFromServer),
+}
 
-        for player in &state.players {
-            let mut player_clone = player.clone();
-            player_clone.actor.pre_serialize();
-            players.push(player_clone);
+impl VersionedSnapshot {
+    pub fn decode<C: Codec, R: BufRead>(version: u32, codec: &mut C, reader: &mut R) -> Result<VersionedSnapshot, DecodeError> {
+        match version {
+            1 => codec.decode(reader).map(VersionedSnapshot::V1),
+            SNAPSHOT_VERSION => codec.decode(reader).map(VersionedSnapshot::Current),
+            _ => Err(DecodeError::Malformed(format!("unsupported snapshot version {}", version))),
         }
+    }
 
-        for rock in &state.rocks {
-            actors.push(rock.clone());
-        }
+    pub fn update_main_state(self, state: &mut MainState) {
+        let snapshot = match self {
+            VersionedSnapshot::V1(old) => NetFromServer::from_v1(old, state),
+            VersionedSnapshot::Current(snapshot) => snapshot,
+        };
+        snapshot.update_main_state(state);
+    }
+}
+
+/// Clients sort the actor list back into `MainState`'s lists by tag, so an actor in another
+/// kind's list would move lists, and a player in one would be dropped.
+fn actor_lists_hold_their_kind(state: &MainState) -> bool {
+    use actor::ActorType;
+    let lists = [
+        (&state.rocks, ActorType::Rock),
+        (&state.shots, ActorType::Shot),
+        (&state.gems, ActorType::Gem),
+        (&state.powerups, ActorType::TimeSlow),
+        (&state.wells, ActorType::Well),
+    ];
+    lists.iter().all(|(list, tag)| list.iter().all(|actor| actor.tag == *tag))
+}
 
-        for shot in &state.shots {
-            actors.push(shot.clone());
+impl NetFromServer {
+    /// Rewrites a cached snapshot for a connection that negotiated another wire format.
+    pub fn reencode<C: Codec>(version: u32, bytes: &[u8], codec: &C) -> Result<Vec<u8>, DecodeError> {
+        let mut buf = Vec::with_capacity(bytes.len() * 4);
+        match VersionedSnapshot::decode(version, &mut BincodeCodec, &mut &bytes[..])? {
+            VersionedSnapshot::V1(snapshot) => codec.encode(&snapshot, &mut buf),
+            VersionedSnapshot::Current(snapshot) => codec.encode(&snapshot, &mut buf),
         }
+        Ok(buf)
+    }
 
-        for actor in &mut actors {
+    /// Serializes the state into the shared snapshot cache. Runs on the simulation
+    /// thread once per snapshot interval, sender threads only copy the `Arc`.
+    pub fn publish(state: &mut MainState) {
+        debug_assert!(actor_lists_hold_their_kind(state), "an actor is in another kind's list");
+        state.issue_actor_ids();
+        for player in &mut state.players {
+            player.actor.pre_serialize();
+        }
+        for actor in state.rocks.iter_mut().chain(state.shots.iter_mut()).chain(state.gems.iter_mut()).chain(state.powerups.iter_mut()).chain(state.wells.iter_mut()) {
             actor.pre_serialize();
         }
-        
-        NetFromServer {
-            players: players,
-            actors: actors,
+
+        let events = state.event_log.unacked(state.event_ack());
+        let budget = match state.snapshot.max_actors {
+            0 => None,
+            max => ActorBudget::pick(state, max, state.snapshot.far_rock_cursor).map(|(budget, cursor)| {
+                state.snapshot.far_rock_cursor = cursor;
+                budget
+            }),
+        };
+        for version in state.snapshot.wanted_versions() {
+            let mut buf = state.snapshot.take_buffer(version);
+            NetFromServer::encode(state, version, &events, budget.as_ref(), &mut buf);
+            state.snapshot.store(version, buf);
+        }
+        state.snapshot.finish();
+    }
+
+    /// Actors must already be pre-serialized.
+    /// Version 1 has neither events nor a way to leave actors out, it gets every rock and shot.
+    fn encode(state: &MainState, version: u32, events: &[EventRecord], budget: Option<&ActorBudget>, buf: &mut Vec<u8>) {
+        if version == 1 {
+            BincodeCodec.encode(&NetFromServerV1::make(state), buf);
+            return;
+        }
+        let lists = [&state.rocks[..], &state.shots[..], &state.gems[..], &state.powerups[..], &state.wells[..]];
+        let actors = ActorChain { lists, picked: budget.map(|budget| &budget.picked[..]) };
+        BincodeCodec.encode(&NetFromServerRef {
+            players: &state.players,
+            actors,
             score: state.score,
             server_time: state.curr_time,
+            events,
+            world_width: state.world_width,
+            world_height: state.world_height,
+            difficulty_mult: state.difficulty_mult,
+            rock_time_scale: state.rock_time_scale,
+            time_slow_left: state.time_slow_left,
+            connections: state.connections,
+            spectators: state.spectator_count(),
+            difficulty_points: state.difficulty_points,
+            truncated: budget.is_some(),
+            telegraphs: &state.telegraphs,
+            live_ids: budget.map_or(&[][..], |budget| &budget.live_ids[..]),
+        }, buf);
+    }
+
+    /// Fields version 1 lacks keep the value the client already has.
+    fn from_v1(old: NetFromServerV1, state: &mut MainState) -> NetFromServer {
+        while old.players.len() > state.players.len() {
+            state.add_player();
+        }
+        NetFromServer {
+            players: old.players.iter().zip(&state.players)
+                .map(|(player, base)| player.upgrade(base.clone(), &state.tuning))
+                .collect(),
+            actors: old.actors.iter().map(|actor| actor.upgrade(&state.tuning)).collect(),
+            score: old.score as f32,
+            server_time: old.server_time,
+            events: Vec::new(),
+            world_width: state.world_width,
+            world_height: state.world_height,
+            difficulty_mult: state.difficulty_mult,
+            rock_time_scale: state.rock_time_scale,
+            time_slow_left: state.time_slow_left,
+            connections: state.connections,
+            spectators: state.spectators,
+            difficulty_points: state.difficulty_points,
+            truncated: false,
+            telegraphs: Vec::new(),
+            live_ids: Vec::new(),
         }
     }
 
     pub fn update_main_state(self, state: &mut MainState) {
-        state.score = self.score;
-
-        state.rocks.clear();
-        state.shots.clear();
+        // Actors froze while we waited, glide them to where the server has them now.
+        let resuming = state.snapshots_stale();
+        state.last_snapshot_at = Some(Instant::now());
 
+        state.score = self.score;
+        state.world_width = self.world_width;
+        state.world_height = self.world_height;
+        state.difficulty_mult = self.difficulty_mult;
+        state.difficulty_points = self.difficulty_points;
+        state.rock_time_scale = self.rock_time_scale;
+        state.time_slow_left = self.time_slow_left;
+        state.connections = self.connections;
+        state.spectators = self.spectators;
+        state.telegraphs = self.telegraphs;
+
+        for record in &self.events {
+            if record.seq > state.last_event_seq {
+                if let GameEvent::GameOver { .. } = record.event {
+                    state.play_sounds.play_death = true;
+                }
+                state.present_event(&record.event);
+                state.last_event_seq = record.seq;
+            }
+        }
 
         let time_diff = state.curr_time - self.server_time;
 
         state.curr_time = self.server_time;
 
+        let lives_before: Vec<u32> = state.players.iter().map(|p| p.lives).collect();
+
         // for now it is safe to assume all the indexes will be correct, 
         // it is impossible to 'delete' players currently.
         while self.players.len() > state.players.len() {
@@ -146,27 +835,123 @@ impl NetFromServer {
 
         for i in (0..remote_list.len()).rev() {
             if state.local_player_index == Some(i) {
-                let _ = remote_list.pop().unwrap();
+                // Keep our predicted ship but take the server's bookkeeping.
+                let mut remote = remote_list.pop().unwrap();
+                remote.actor.post_deserialize(&state.tuning);
+                if remote.graze > state.players[i].graze {
+                    state.play_sounds.play_graze = true;
+                }
+                if remote.deaths > state.players[i].deaths {
+                    state.play_sounds.play_death = true;
+                }
+                state.players[i].stats = remote.stats;
+                state.players[i].set_ship(remote.ship);
+                state.players[i].graze = remote.graze;
+                state.players[i].deaths = remote.deaths;
+                state.players[i].hit_flash = remote.hit_flash;
+                // We got hit, are exploding, just respawned or were kicked: the server decides where we are.
+                let dying = remote.dying_until > 0.0 || state.players[i].dying_until > 0.0;
+                if remote.lives < state.players[i].lives || dying || !remote.active {
+                    state.players[i].actor.pos = remote.actor.pos;
+                }
+                state.players[i].dying_until = remote.dying_until;
+                state.players[i].lives = remote.lives;
+                state.players[i].active = remote.active;
+                state.players[i].versus_score = remote.versus_score;
+                state.players[i].volley = remote.volley;
+                state.players[i].spread = remote.spread;
                 //state.players[i].actor = remote.actor;
                 //state.players[i].actor.post_deserialize();
                 //state.players[i].last_shot_at -= time_diff;
 
             } else {
                 state.players[i] = remote_list.pop().unwrap();
-                state.players[i].actor.post_deserialize();
+                state.players[i].actor.post_deserialize(&state.tuning);
+            }
+
+            // Players we haven't seen before don't get a popup.
+            if state.players[i].lives > lives_before.get(i).cloned().unwrap_or(std::u32::MAX) {
+                state.play_sounds.play_extra_life = true;
+                state.effects.popups.push(Popup { pos: state.players[i].actor.pos, time_left: EXTRA_LIFE_POPUP_TIME });
             }
             state.players[i].last_shot_at -= time_diff;
         }
 
 
-        for mut actor in self.actors {
-            actor.post_deserialize();
-            
-            match actor.tag {
-                actor::ActorType::Player => {},
-                actor::ActorType::Rock => state.rocks.push(actor),
-                actor::ActorType::Shot => state.shots.push(actor),
+        // Reconcile in place by index so the lists keep their capacity and per-actor state.
+        // A truncated snapshot only holds some of the actors, those are matched by `Actor::id`
+        // and the rest keep moving on their own.
+        let mut counts = [0usize; 5];
+        let mut known: Vec<HashMap<u32, Actor>> = Vec::new();
+        if self.truncated {
+            state.net_stats.truncated_snapshots += 1;
+            // Our own predicted shots have no id yet, the server's copy replaces them.
+            known = [&mut state.rocks, &mut state.shots, &mut state.gems, &mut state.powerups, &mut state.wells].iter_mut()
+                .map(|list| list.drain(..).filter(|actor| actor.id != 0).map(|actor| (actor.id, actor)).collect())
+                .collect();
+        }
+        for actor in self.actors {
+            let (kind, list) = match actor.tag {
+                // Players travel in `players`, one in here has no list to go to.
+                actor::ActorType::Player => {
+                    if state.net_stats.unexpected_actors == 0 {
+                        println!("Skipping a player actor in a snapshot's actor list.");
+                    }
+                    state.net_stats.unexpected_actors += 1;
+                    continue;
+                }
+                actor::ActorType::Rock => (0, &mut state.rocks),
+                actor::ActorType::Shot => (1, &mut state.shots),
+                actor::ActorType::Gem => (2, &mut state.gems),
+                actor::ActorType::TimeSlow => (3, &mut state.powerups),
+                actor::ActorType::Well => (4, &mut state.wells),
+            };
+
+            if self.truncated {
+                let id = actor.id;
+                let placed = match known[kind].remove(&id) {
+                    Some(mut existing) => {
+                        existing.sync_from(actor, resuming, &state.tuning);
+                        existing
+                    }
+                    None => {
+                        let mut actor = actor;
+                        actor.post_deserialize(&state.tuning);
+                        actor
+                    }
+                };
+                known[kind].entry(id).or_insert(placed);
+            } else {
+                let at = counts[kind];
+                match list.get_mut(at) {
+                    Some(existing) => existing.sync_from(actor, resuming, &state.tuning),
+                    None => {
+                        let mut actor = actor;
+                        actor.post_deserialize(&state.tuning);
+                        list.push(actor);
+                    }
+                }
+                counts[kind] += 1;
+            }
+        }
+        if self.truncated {
+            // Back in the server's order. What it has but never sent us stays out until it does.
+            let mut lists = [&mut state.rocks, &mut state.shots, &mut state.gems, &mut state.powerups, &mut state.wells];
+            for ((list, known), live_ids) in lists.iter_mut().zip(&mut known).zip(&self.live_ids) {
+                list.extend(live_ids.iter().filter_map(|id| known.remove(id)));
             }
+        } else {
+            state.rocks.truncate(counts[0]);
+            state.shots.truncate(counts[1]);
+            state.gems.truncate(counts[2]);
+            state.powerups.truncate(counts[3]);
+            state.wells.truncate(counts[4]);
+        }
+
+        // Spectators keep the last few seconds to pause on, see `scrub`.
+        if state.role == NetRole::Spectator {
+            let frame = ScrubFrame::capture(state);
+            state.scrub.record(frame);
         }
     }
 }
@@ -1,12 +1,17 @@
 
 use crate::game_structs;
-use game_structs::StatePtr;
+use game_structs::{StatePtr, NetRole, ExtrapolationSetup, DEFAULT_TRANSFER_MS};
+
+use crate::console;
+use crate::launch_options::LaunchOptions;
+use crate::events::GameEvent;
+use crate::hud::{Severity, TOAST_LONG_TIME, TOAST_TIME};
 
 use crate::net_structs;
 use net_structs::*;
+use crate::codec::{AnyCodec, Codec, DecodeError, WireFormat, wire_config};
 
-use std::env;
-use std::net::{TcpListener, TcpStream};
+use std::net::{TcpListener, TcpStream, SocketAddr, Shutdown};
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::time::{Duration, Instant};
@@ -16,9 +21,21 @@ use serde::de::DeserializeOwned;
 
 use std::path::Path;
 use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 const NET_FILENAME: &str = "net_setup.json";
 
+/// Leading bytes of a malformed message shown in the log.
+const HEXDUMP_BYTES: usize = 32;
+/// Malformed messages after the first are logged this far apart.
+const BAD_MESSAGE_LOG_EVERY: u64 = 100;
+
+/// How often idle listeners check for new connections and the shutdown flag.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct NetSetup {
     transfer_ms: u64,
@@ -26,9 +43,243 @@ struct NetSetup {
     packet_ttl: u32,
     non_blocking: bool,
     nodelay: bool,
+    /// Interface the server listens on.
+    #[serde(default = "NetSetup::default_bind_address")]
+    bind_address: String,
+    /// Port snapshots are served on. 0 lets the OS pick one (useful for tests).
+    #[serde(default = "NetSetup::default_snapshot_port")]
+    snapshot_port: u16,
+    /// Port client inputs are received on. 0 lets the OS pick one.
+    #[serde(default = "NetSetup::default_input_port")]
+    input_port: u16,
+    /// Players the server accepts before it stops listening for more. 0 means no limit.
+    #[serde(default)]
+    max_players: u32,
+    /// Clients and spectators receiving snapshots at once. 0 means no limit.
+    #[serde(default)]
+    max_connections: u32,
+    /// Malformed messages in a row after which a stream counts as desynchronized and is closed.
+    /// 0 never closes.
+    #[serde(default = "NetSetup::default_max_bad_messages")]
+    max_bad_messages: u32,
+    /// Clients keep moving remote actors this long after the latest snapshot, then freeze them.
+    #[serde(default = "NetSetup::default_max_extrapolation_ms")]
+    max_extrapolation_ms: u64,
+    /// Clients glide frozen actors back to the server's positions over this long.
+    #[serde(default = "NetSetup::default_blend_ms")]
+    blend_ms: u64,
+    /// Actors, players included, in a single snapshot. Past that the farthest rocks are left
+    /// out and take turns, so a slow link isn't stalled by a crowded field. 0 sends them all.
+    #[serde(default)]
+    max_actors_per_snapshot: u32,
+    #[serde(default)]
+    net_sim: NetSim,
+    /// "json" writes snapshots and inputs as readable lines, when the other end asks for it too.
+    #[serde(default)]
+    wire_format: WireFormat,
+}
+
+/// Artificial network conditions for testing interpolation and prediction on localhost.
+/// Applies to snapshots and inputs, never to the handshake.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct NetSim {
+    added_latency_ms: u64,
+    jitter_ms: u64,
+    /// Percentage of messages that are skipped entirely.
+    loss_percent: f32,
+}
+
+impl NetSim {
+    fn is_active(&self) -> bool {
+        self.added_latency_ms > 0 || self.jitter_ms > 0 || self.loss_percent > 0.0
+    }
+
+    fn roll_delay(&self) -> Duration {
+        let jitter = (rand::random::<f32>() * 2.0 - 1.0) * self.jitter_ms as f32;
+        let delay = (self.added_latency_ms as f32 + jitter).max(0.0);
+        Duration::from_micros((delay * 1000.0) as u64)
+    }
+}
+
+#[derive(Default)]
+struct WriterSlot {
+    pending: Option<Arc<Vec<u8>>>,
+    closed: bool,
+}
+
+/// Hands messages to a writer thread that holds at most one unwritten message.
+/// A newer message replaces the pending one, so a peer that can't keep up misses
+/// stale messages instead of stalling the caller. A failed write closes the
+/// connection, a message is never left half written.
+struct LatestWriter {
+    shared: Arc<(Mutex<WriterSlot>, Condvar)>,
+}
+
+impl LatestWriter {
+    fn spawn(mut stream: TcpStream) -> LatestWriter {
+        let shared = Arc::new((Mutex::new(WriterSlot::default()), Condvar::new()));
+        let thread_shared = shared.clone();
+
+        // Only this thread writes, so it may block. A non-blocking write could stop mid message.
+        let _ = stream.set_nonblocking(false);
+        let spawned = std::thread::Builder::new().name("net writer".into())
+            .spawn(move || {
+                let (slot, wakeup) = &*thread_shared;
+                loop {
+                    let bin = {
+                        let mut slot = slot.lock().unwrap();
+                        while slot.pending.is_none() && !slot.closed {
+                            slot = wakeup.wait(slot).unwrap();
+                        }
+                        match slot.pending.take() {
+                            Some(bin) if !slot.closed => bin,
+                            _ => return,
+                        }
+                    };
+                    if let Err(e) = stream.write_all(&bin[..]) {
+                        println!("Closing connection to {:?}: {}", stream.peer_addr(), e);
+                        let _ = stream.shutdown(Shutdown::Both);
+                        slot.lock().unwrap().closed = true;
+                        return;
+                    }
+                }
+            });
+
+        let writer = LatestWriter {
+            shared,
+        };
+        if spawned.is_err() {
+            writer.close();
+        }
+        writer
+    }
+
+    /// Queues the message. Returns true when it replaced one that was never written.
+    fn offer(&self, bin: Arc<Vec<u8>>) -> bool {
+        let (slot, wakeup) = &*self.shared;
+        let mut slot = slot.lock().unwrap();
+        if slot.closed {
+            return false;
+        }
+        let replaced = slot.pending.replace(bin).is_some();
+        wakeup.notify_one();
+        replaced
+    }
+
+    /// Nothing waiting to be written. A message may still be on its way out.
+    fn is_idle(&self) -> bool {
+        self.shared.0.lock().unwrap().pending.is_none()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.shared.0.lock().unwrap().closed
+    }
+
+    fn close(&self) {
+        let (slot, wakeup) = &*self.shared;
+        slot.lock().unwrap().closed = true;
+        wakeup.notify_one();
+    }
+}
+
+impl Drop for LatestWriter {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Sends structs on a stream, through the NetSim delay queue when it is active.
+struct SimulatedSender {
+    writer: Arc<LatestWriter>,
+    delayed: Option<Sender<(Instant, Arc<Vec<u8>>)>>,
+    sim: NetSim,
+    codec: AnyCodec,
+}
+
+impl SimulatedSender {
+    fn new(stream: TcpStream, sim: &NetSim, format: WireFormat) -> SimulatedSender {
+        let writer = Arc::new(LatestWriter::spawn(stream));
+        let mut delayed = None;
+
+        if sim.is_active() {
+            let (sender, receiver) = mpsc::channel::<(Instant, Arc<Vec<u8>>)>();
+            let delayed_writer = writer.clone();
+            let _ = std::thread::Builder::new().name("net sim delay".into())
+                .spawn(move || {
+                    for (due, bin) in receiver {
+                        let now = Instant::now();
+                        if due > now {
+                            std::thread::sleep(due - now);
+                        }
+                        delayed_writer.offer(bin);
+                    }
+                });
+            delayed = Some(sender);
+        }
+
+        SimulatedSender {
+            writer,
+            delayed,
+            sim: sim.clone(),
+            codec: format.codec(),
+        }
+    }
+
+    fn send<T: Serialize>(&mut self, data: T) {
+        let mut bin = Vec::new();
+        self.codec.encode(&data, &mut bin);
+        self.send_bytes(Arc::new(bin));
+    }
+
+    /// Sends an already serialized message. Returns true when an older message was dropped for it.
+    fn send_bytes(&mut self, bin: Arc<Vec<u8>>) -> bool {
+        let delayed = match &self.delayed {
+            Some(delayed) => delayed,
+            None => return self.writer.offer(bin),
+        };
+
+        // With TCP a lost message is one we never write, so the framing stays intact.
+        if rand::random::<f32>() * 100.0 >= self.sim.loss_percent {
+            let _ = delayed.send((Instant::now() + self.sim.roll_delay(), bin));
+        }
+        false
+    }
+
+    fn is_idle(&self) -> bool {
+        self.writer.is_idle()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.writer.is_closed()
+    }
 }
 
 impl NetSetup {
+    fn default_bind_address() -> String {
+        String::from("0.0.0.0")
+    }
+
+    fn default_snapshot_port() -> u16 {
+        9942
+    }
+
+    fn default_input_port() -> u16 {
+        9949
+    }
+
+    fn default_max_bad_messages() -> u32 {
+        10
+    }
+
+    fn default_max_extrapolation_ms() -> u64 {
+        ExtrapolationSetup::default().max_extrapolation_ms
+    }
+
+    fn default_blend_ms() -> u64 {
+        ExtrapolationSetup::default().blend_ms
+    }
+
     pub fn from_file<T: AsRef<Path>>(filename: T) -> std::io::Result<NetSetup> {
         let file = File::open(filename)?;
         let reader = BufReader::new(file);
@@ -70,11 +321,22 @@ impl NetSetup {
 impl Default for NetSetup {
     fn default() -> NetSetup {
         NetSetup {
-            transfer_ms: 33,
+            transfer_ms: DEFAULT_TRANSFER_MS,
             timeout_ms: 1000,
             packet_ttl: 60,
             non_blocking: false,
             nodelay: true,
+            bind_address: NetSetup::default_bind_address(),
+            snapshot_port: NetSetup::default_snapshot_port(),
+            input_port: NetSetup::default_input_port(),
+            max_players: 0,
+            max_connections: 0,
+            max_bad_messages: NetSetup::default_max_bad_messages(),
+            max_extrapolation_ms: NetSetup::default_max_extrapolation_ms(),
+            blend_ms: NetSetup::default_blend_ms(),
+            max_actors_per_snapshot: 0,
+            net_sim: NetSim::default(),
+            wire_format: WireFormat::default(),
         }
     }
 }
@@ -90,117 +352,459 @@ fn block_for_next(time: Instant, transfer_ms: u64) -> Instant {
     Instant::now()
 }
 
-pub fn network_main(stateptr: &mut StatePtr) { 
-    let mut is_server = false;
+pub fn network_main(stateptr: &mut StatePtr, mut options: LaunchOptions) { 
+    let net = NetSetup::from_file(NET_FILENAME).unwrap_or_else(|_| NetSetup::write_default(NET_FILENAME) );
 
-    let mut args: std::vec::Vec<String> = env::args().collect();
-    if args.len() <= 2 {
-        is_server = true;
+    if net.net_sim.is_active() {
+        println!("**************************************************************");
+        println!("WARNING: NETWORK SIMULATION ACTIVE: {:?}", net.net_sim);
+        println!("Outgoing snapshots/inputs are delayed and dropped on purpose.");
+        println!("Disable net_sim in {} for normal play.", NET_FILENAME);
+        println!("**************************************************************");
     }
-    let is_server = is_server;
 
-    let net = NetSetup::from_file(NET_FILENAME).unwrap_or_else(|_| NetSetup::write_default(NET_FILENAME) );
+    if options.daily.is_some() {
+        println!("Daily challenge: networking disabled, other players would change the rock spawns.");
+        return;
+    }
+    if options.record_input.is_some() || options.play_input.is_some() {
+        println!("Input recording: networking disabled, other players aren't recorded.");
+        return;
+    }
 
-    if !is_server && args[1].starts_with("s") {
-        stateptr.state.lock().unwrap().local_player_index = None;
-        spawn_observer_thread(stateptr, &mut args[2], &net).expect("Spectator thread paniced.");
-    } else if !is_server {
-        client_main(stateptr, &mut args[2], net).expect("Client thread paniced.");
-    } else {
-        server_main(stateptr, net).expect("Server thread paniced.");
+    match options.role {
+        NetRole::Spectator => {
+            let (recv_stream, version, wire_format) = connect_snapshots(stateptr, &options.server_address, &net)
+                .expect("Spectator thread paniced.");
+            spawn_observer_thread(stateptr, recv_stream, version, wire_format, &net).expect("Spectator thread paniced.");
+        }
+        NetRole::Client => {
+            client_main(stateptr, &mut options.server_address, net).expect("Client thread paniced.");
+        }
+        NetRole::Server => {
+            let (snapshot_addr, input_addr) = server_main(stateptr, net).expect("Server thread paniced.");
+            println!("Serving snapshots on {} and receiving inputs on {}", snapshot_addr, input_addr);
+        }
     }
 }
 
-/// Attempts to send the struct in the stream.
+/// Attempts to send the struct in the stream. Only for the handshake, which is always bincode.
 fn send_struct<T: Serialize>(stream: &mut TcpStream, data: T) -> usize {
-    let bin = bincode::serialize(&data).expect("Failed to serialize.");
+    let bin = wire_config().serialize(&data).expect("Failed to serialize.");
     let _ = stream.write_all(&bin[..]);
     bin.len()
 }
 
+#[derive(Debug)]
+enum RecvError {
+    /// Nothing arrived in time or the peer went away.
+    Io(std::io::Error),
+    /// Bytes arrived that don't decode as the expected message.
+    Malformed { error: String, head: Vec<u8> },
+}
+
+/// Counts the bytes taken out of a reader, whether read or consumed from its buffer.
+struct CountingReader<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.bytes += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+/// Reads the messages of one connection in its negotiated format. Bytes read ahead
+/// and a JSON line cut off by a timeout stay here for the next message.
+struct MessageReader {
+    reader: CountingReader<BufReader<TcpStream>>,
+    codec: AnyCodec,
+    /// Size on the wire of the last message decoded. Short for a JSON line split by a read timeout.
+    last_len: usize,
+}
+
+impl MessageReader {
+    fn new(stream: &TcpStream, format: WireFormat) -> std::io::Result<MessageReader> {
+        Ok(MessageReader {
+            reader: CountingReader { inner: BufReader::new(stream.try_clone()?), bytes: 0 },
+            codec: format.codec(),
+            last_len: 0,
+        })
+    }
+
+    /// Reads one message with `decode`, keeping its first bytes around in case it turns out malformed.
+    fn read<T>(
+        &mut self,
+        decode: impl FnOnce(&mut AnyCodec, &mut CountingReader<BufReader<TcpStream>>) -> Result<T, DecodeError>,
+    ) -> Result<T, RecvError> {
+        let head: Vec<u8> = self.reader.fill_buf().map_err(RecvError::Io)?
+            .iter().take(HEXDUMP_BYTES).cloned().collect();
+
+        let before = self.reader.bytes;
+        let result = decode(&mut self.codec, &mut self.reader).map_err(|error| match error {
+            DecodeError::Io(e) => RecvError::Io(e),
+            DecodeError::Malformed(error) => RecvError::Malformed { error, head },
+        });
+        if result.is_ok() {
+            self.last_len = (self.reader.bytes - before) as usize;
+        }
+        result
+    }
+}
 
 /// Runs the given Function with the Deserialized struct. 
 /// Intended to edit a mutable state capture.
-fn recv_update<T: DeserializeOwned>(stream: &mut TcpStream, function: impl Fn(T)) {
-    let read_buf = BufReader::new(stream.try_clone().expect("Failed to clone stream."));
-    
-    let data = bincode::deserialize_from::<_, T>(read_buf);
-    if let Ok(data) = data {
-        function(data);
+fn recv_update<T: DeserializeOwned>(reader: &mut MessageReader, function: impl Fn(T)) -> Result<(), RecvError> {
+    reader.read(|codec, read_buf| codec.decode::<T, _>(read_buf)).map(function)
+}
+
+/// `recv_update` for snapshots in the layout negotiated by `snapshot_handshake`.
+/// The function also gets the snapshot's size on the wire.
+fn recv_snapshot(reader: &mut MessageReader, version: u32, function: impl Fn(VersionedSnapshot, usize)) -> Result<(), RecvError> {
+    let snapshot = reader.read(|codec, read_buf| VersionedSnapshot::decode(version, codec, read_buf))?;
+    function(snapshot, reader.last_len);
+    Ok(())
+}
+
+/// Keeps count of malformed messages on one connection.
+struct RecvHealth {
+    peer: String,
+    total: u64,
+    consecutive: u32,
+    limit: u32,
+}
+
+impl RecvHealth {
+    fn new(stream: &TcpStream, limit: u32) -> RecvHealth {
+        RecvHealth {
+            peer: peer_name(stream),
+            total: 0,
+            consecutive: 0,
+            limit,
+        }
+    }
+
+    /// Logs the first malformed message and every 100th after it.
+    /// Returns `Some(true)` once the stream should be given up on, `None` for anything that wasn't malformed.
+    fn record(&mut self, result: Result<(), RecvError>) -> Option<bool> {
+        let (error, head) = match result {
+            Ok(()) => {
+                self.consecutive = 0;
+                return None;
+            }
+            Err(RecvError::Io(_)) => return None,
+            Err(RecvError::Malformed { error, head }) => (error, head),
+        };
+
+        self.total += 1;
+        self.consecutive += 1;
+        if self.total == 1 || self.total % BAD_MESSAGE_LOG_EVERY == 0 {
+            let hex: Vec<String> = head.iter().map(|b| format!("{:02x}", b)).collect();
+            println!("Malformed message #{} from {}: {}", self.total, self.peer, error);
+            println!("    first bytes: {}", hex.join(" "));
+        }
+
+        let desynced = self.limit > 0 && self.consecutive >= self.limit;
+        if desynced {
+            println!("Closing {} after {} malformed messages in a row, the stream is out of sync.", self.peer, self.consecutive);
+        }
+        Some(desynced)
     }
 }
 
-fn spawn_observer_thread(stateptr: &mut StatePtr, server_addres: &mut String, net: &NetSetup) -> std::io::Result<()> {
-    let mut recv_stream = TcpStream::connect(format!("{}:9942", server_addres))?;
+/// Exchanges `NetSnapshotHello`s, then `NetWireHello`s, and returns the snapshot version
+/// and wire format to use.
+/// The hellos are read straight from the stream, a buffered reader could swallow the first snapshot behind them.
+fn snapshot_handshake(stream: &mut TcpStream, wire_format: WireFormat) -> std::io::Result<(u32, WireFormat)> {
+    send_struct(stream, NetSnapshotHello::ours());
+    let theirs: NetSnapshotHello = wire_config().deserialize_from(&mut *stream)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("No snapshot hello from peer: {}", e)))?;
+
+    let version = NetSnapshotHello::ours().negotiate(&theirs).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+            "Incompatible game versions: peer sends snapshot layouts {}-{}, this build {}-{}",
+            theirs.min_version, theirs.max_version, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION))
+    })?;
+
+    send_struct(stream, NetWireHello { wire_format });
+    let theirs: NetWireHello = wire_config().deserialize_from(&mut *stream)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("No wire format hello from peer: {}", e)))?;
+    let negotiated = wire_format.negotiate(theirs.wire_format);
+    if negotiated != wire_format {
+        println!("Peer doesn't use the {:?} wire format, falling back to {:?}", wire_format, negotiated);
+    }
+    Ok((version, negotiated))
+}
+
+/// Whether a snapshot peer only watches.
+fn read_viewer_hello(stream: &mut TcpStream) -> std::io::Result<bool> {
+    let hello: NetViewerHello = wire_config().deserialize_from(&mut *stream)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("No viewer hello from peer: {}", e)))?;
+    Ok(hello.spectator)
+}
+
+/// Connects the snapshot stream and runs its handshake. Nothing reads snapshots from it
+/// until `spawn_observer_thread`.
+fn connect_snapshots(stateptr: &StatePtr, server_addres: &str, net: &NetSetup) -> std::io::Result<(TcpStream, u32, WireFormat)> {
+    let mut recv_stream = TcpStream::connect(format!("{}:{}", server_addres, net.snapshot_port))?;
     net.configure_stream(&mut recv_stream);
+    let (version, wire_format) = snapshot_handshake(&mut recv_stream, net.wire_format)?;
+    if version != SNAPSHOT_VERSION {
+        println!("Server is an older build, using snapshot version {}", version);
+    }
+    let spectator = stateptr.lock().role == NetRole::Spectator;
+    send_struct(&mut recv_stream, NetViewerHello { spectator });
+    Ok((recv_stream, version, wire_format))
+}
+
+fn spawn_observer_thread(stateptr: &mut StatePtr, recv_stream: TcpStream, version: u32, wire_format: WireFormat, net: &NetSetup) -> std::io::Result<()> {
+    let mut reader = MessageReader::new(&recv_stream, wire_format)?;
 
     let ptr = stateptr.get_ref();
+    ptr.lock().extrapolation = ExtrapolationSetup {
+        max_extrapolation_ms: net.max_extrapolation_ms,
+        blend_ms: net.blend_ms,
+    };
     let net_copy = net.clone();
     std::thread::spawn(move || {
         let net = net_copy;
         println!("Recv thread.");
+        let mut health = RecvHealth::new(&recv_stream, net.max_bad_messages);
         let mut timer = Instant::now();
         loop {
             timer = block_for_next(timer, net.transfer_ms);
+            if ptr.shutdown.load(Ordering::Relaxed) {
+                let _ = recv_stream.shutdown(Shutdown::Both);
+                return;
+            }
 
-            recv_update(&mut recv_stream, |data: NetFromServer| {
-                let mut state = ptr.state.lock().unwrap();
+            let result = recv_snapshot(&mut reader, version, |data, bytes| {
+                let mut state = ptr.lock();
+                let previous = state.last_snapshot_at;
+                state.net_stats.record_snapshot(bytes, previous);
                 data.update_main_state(&mut state);
             });
+            if let Some(desynced) = health.record(result) {
+                ptr.lock().net_stats.bad_messages += 1;
+                if desynced {
+                    let _ = recv_stream.shutdown(Shutdown::Both);
+                    return;
+                }
+            }
         }
     });
     Ok(())
 }
 
-fn client_sender_thread(ptr: StatePtr, mut send_stream: TcpStream, net: NetSetup) {
+/// Asks the server for a player slot on the input stream, then reads the `NetWelcome` and `NetTuning` after it.
+/// `None` when the server gave no slot or its tuning lacks fields of ours, the client then only watches.
+fn join_game(ptr: &StatePtr, send_stream: &mut TcpStream, net: &NetSetup) -> Option<NetPlayerConnected> {
+    let (hello, toasts, strings) = {
+        let state = ptr.lock();
+        let hello = NetPlayerHello {
+            ship: state.local_ship,
+            reconnect_token: state.reconnect_token,
+            wire_format: net.wire_format,
+            aim_assist: state.input_setup.aim_assist,
+        };
+        (hello, state.effects.toasts.sender(), state.strings())
+    };
+    send_struct(send_stream, hello);
 
-    recv_update(&mut send_stream, |x: NetPlayerConnected| {
-        let p_index = x.player_index;
-        ptr.state.lock().unwrap().local_player_index = Some(p_index);
-        println!("Assigned local player id: {}", p_index);
-    });
+    // Straight from the stream like the hellos, nothing else is read from it.
+    let joined: NetPlayerConnected = match wire_config().deserialize_from(&mut *send_stream) {
+        Ok(joined) => joined,
+        Err(e) => {
+            println!("No player slot from the server, playing as a spectator: {:?}", e);
+            toasts.push(strings.tr("toast.no_player_slot").to_string(), TOAST_LONG_TIME, Severity::Warning);
+            return None;
+        }
+    };
+    let welcome: Option<NetWelcome> = match wire_config().deserialize_from(&mut *send_stream) {
+        Ok(welcome) => Some(welcome),
+        Err(e) => {
+            println!("No welcome from the server, the first snapshot fills in the game: {:?}", e);
+            None
+        }
+    };
+    let tuning: Option<NetTuning> = match wire_config().deserialize_from(&mut *send_stream) {
+        Ok(tuning) => Some(tuning),
+        Err(e) => {
+            println!("No tuning from the server, playing with our own: {:?}", e);
+            None
+        }
+    };
+    // Predicting with numbers the server doesn't have would only fight its corrections.
+    let tuning = match tuning.map(|tuning| tuning.tuning()) {
+        Some(Ok(tuning)) => Some(tuning),
+        Some(Err(missing)) => {
+            println!("The server doesn't know the tuning fields {:?}, watching instead of joining.", missing);
+            toasts.push(strings.tr("toast.server_too_old").to_string(), TOAST_LONG_TIME, Severity::Warning);
+            return None;
+        }
+        None => None,
+    };
 
+    let p_index = joined.player_index;
+    let mut state = ptr.lock();
+    if let Some(tuning) = tuning {
+        state.set_tuning(tuning);
+    }
+    if let Some(welcome) = welcome {
+        println!("Joining round {} at {:.1}s, server version {}", welcome.round, welcome.elapsed, welcome.version);
+        welcome.update_main_state(&mut state);
+    }
+    state.local_player_index = Some(p_index);
+    state.practice = joined.practice;
+    state.hardcore = joined.hardcore;
+    state.versus = joined.versus;
+    state.game_setup.shots_wrap = joined.shots_wrap;
+    state.game_setup.rock_despawn_margin = joined.rock_despawn_margin;
+    state.aim_assist = joined.aim_assist;
+    let text = state.tr_fmt("toast.connected", &[("player", &(p_index + 1))]);
+    state.effects.toasts.push(text, TOAST_TIME, Severity::Info);
+    println!("Assigned local player id: {}", p_index);
+    Some(joined)
+}
+
+fn client_sender_thread(ptr: StatePtr, send_stream: TcpStream, net: NetSetup, joined: NetPlayerConnected) {
+    let (toasts, lost) = {
+        let state = ptr.lock();
+        (state.effects.toasts.sender(), state.tr("toast.connection_lost").to_string())
+    };
+    let mut sender = SimulatedSender::new(send_stream, &net.net_sim, joined.wire_format);
+    let mut seq = 0;
     let mut timer = Instant::now();    
     loop {
         timer = block_for_next(timer, net.transfer_ms);
 
+        // Dropping the sender closes the stream, the server sees the player leave.
+        if ptr.shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        if sender.is_closed() {
+            println!("Lost the connection to the server.");
+            ptr.lock().effects.clear();
+            toasts.push(lost, TOAST_LONG_TIME, Severity::Error);
+            return;
+        }
+
         let net_data: NetClientInput;
         {
-            let mut state = ptr.state.lock().unwrap();
-            net_data = NetClientInput::make_from_state(&mut state);
+            let mut state = ptr.lock();
+            // Shots and fire presses keep piling up in the state, the next message carries them all.
+            if !sender.is_idle() {
+                state.net_stats.inputs_coalesced += 1;
+                continue;
+            }
+            net_data = NetClientInput::make_from_state(&mut state, seq);
         }
+        seq += 1;
 
-        send_struct(&mut send_stream, net_data);
+        sender.send(net_data);
     }
 }
 
+/// Joins `server_address` as a player without a window, for the load-testing bot.
+/// `ports` replaces the snapshot and input ports from net_setup.json.
+/// Whatever drives the state's local input and `step_client` plays the ship.
+pub fn connect_headless_client(stateptr: &mut StatePtr, server_address: &str, ports: (Option<u16>, Option<u16>)) -> std::io::Result<()> {
+    let mut net = NetSetup::from_file(NET_FILENAME).unwrap_or_default();
+    if let Some(port) = ports.0 {
+        net.snapshot_port = port;
+    }
+    if let Some(port) = ports.1 {
+        net.input_port = port;
+    }
+    client_main(stateptr, &mut server_address.to_string(), net)
+}
+
+/// Serves the state on `bind_address` without a window, for tests and tools. `ports` replaces
+/// the snapshot and input ports from net_setup.json, 0 lets the OS pick one.
+/// Whatever calls `step_server` runs the game. Returns the addresses actually bound.
+pub fn serve_headless(stateptr: &mut StatePtr, bind_address: &str, ports: (u16, u16)) -> std::io::Result<(SocketAddr, SocketAddr)> {
+    let mut net = NetSetup::from_file(NET_FILENAME).unwrap_or_default();
+    net.bind_address = bind_address.to_string();
+    net.snapshot_port = ports.0;
+    net.input_port = ports.1;
+    server_main(stateptr, net)
+}
+
+/// Joins before reading any snapshot, so the welcome is applied first.
 fn client_main(stateptr: &mut StatePtr, server_addres: &mut String, net: NetSetup) -> std::io::Result<()> {
-    spawn_observer_thread(stateptr, server_addres, &net)?;
+    let (recv_stream, version, wire_format) = connect_snapshots(stateptr, server_addres, &net)?;
 
-    let mut send_stream = TcpStream::connect(format!("{}:9949", server_addres))?;
+    let mut send_stream = TcpStream::connect(format!("{}:{}", server_addres, net.input_port))?;
     net.configure_stream(&mut send_stream);
+    println!("Client connecting! Transfer rate: {:?}ms", net.transfer_ms);
+    let joined = join_game(stateptr, &mut send_stream, &net);
 
-    let ptr = stateptr.get_ref();
-    std::thread::spawn(move || {
-        println!("Client connecting! Transfer rate: {:?}ms", net.transfer_ms);
-        client_sender_thread(ptr, send_stream, net);
-    });  
+    spawn_observer_thread(stateptr, recv_stream, version, wire_format, &net)?;
+
+    if let Some(joined) = joined {
+        let ptr = stateptr.get_ref();
+        std::thread::spawn(move || {
+            client_sender_thread(ptr, send_stream, net, joined);
+        });
+    }
     Ok(())
 }
 
-fn server_sender(mut stream: TcpStream, stateptr: StatePtr, transfer_ms: u64) {
+/// `transfer_ms` is shared with the state, so a new rate applies from the next tick.
+fn server_sender(stream: TcpStream, stateptr: StatePtr, transfer_ms: Arc<AtomicU64>, net_sim: NetSim, version: u32, wire_format: WireFormat) {
+    let mut sender = SimulatedSender::new(stream, &net_sim, wire_format);
+    // The cache only holds bincode, other formats are converted per connection.
+    let converter = match wire_format {
+        WireFormat::Bincode => None,
+        other => Some(other.codec()),
+    };
     let mut timer = Instant::now();
     let mut max_packet = 0 as usize;
+    let mut last_seq = 0;
 
-    loop {
-        timer = block_for_next(timer, transfer_ms);
+    // Dropping the sender closes the stream once the game shuts down or went into its safe state.
+    while !sender.is_closed() && !stateptr.shutdown.load(Ordering::Relaxed) {
+        timer = block_for_next(timer, transfer_ms.load(Ordering::Relaxed));
 
-        let mut net_struct;
-        {
-            let state = stateptr.state.lock().unwrap();
-            net_struct = NetFromServer::make_from_state(&state);
+        // The simulation serializes the snapshot, we just pick up the latest one.
+        let (seq, bytes) = {
+            let state = stateptr.lock();
+            (state.snapshot.seq, state.snapshot.bytes(version))
+        };
+        if seq == last_seq {
+            continue;
+        }
+        last_seq = seq;
+        let bytes = match &converter {
+            None => bytes,
+            Some(codec) => match NetFromServer::reencode(version, &bytes, codec) {
+                Ok(converted) => Arc::new(converted),
+                Err(e) => {
+                    println!("Failed to convert a snapshot to {:?}: {:?}", wire_format, e);
+                    continue;
+                }
+            },
+        };
+        let size = bytes.len();
+
+        // Snapshots supersede each other, an unsent one is simply replaced.
+        if sender.send_bytes(bytes) {
+            stateptr.lock().net_stats.snapshots_dropped += 1;
         }
-        let size = send_struct(&mut stream, net_struct);
 
         if size > max_packet {
             println!("New max packet size: {}", size);
@@ -209,77 +813,277 @@ fn server_sender(mut stream: TcpStream, stateptr: StatePtr, transfer_ms: u64) {
     }
 }
 
-fn server_recver(mut stream: TcpStream, stateptr: StatePtr, transfer_ms: u64) -> std::io::Result<()> {
+/// Gives back what a snapshot connection held, after it closed or its thread panicked.
+fn release_connection(stateptr: &StatePtr, version: u32, spectator: bool, peer: &str) {
+    let mut state = stateptr.lock();
+    state.connections = state.connections.saturating_sub(1);
+    state.snapshot.remove_reader(version);
+    if spectator {
+        println!("Spectator {} disconnected.", peer);
+        state.emit(GameEvent::SpectatorLeft);
+    } else {
+        // The player's input stream announces them leaving.
+        println!("Snapshot stream of the player at {} closed.", peer);
+    }
+}
+
+fn server_recver(mut stream: TcpStream, stateptr: StatePtr, transfer_ms: u64, max_bad_messages: u32, wire_format: WireFormat) -> std::io::Result<()> {
+    // The hello comes first so a returning client can get its old slot back.
+    let hello: NetPlayerHello = match wire_config().deserialize_from(&mut stream) {
+        Ok(hello) => hello,
+        Err(e) => {
+            println!("Player never said hello, dropping the connection: {:?}", e);
+            let _ = stream.shutdown(Shutdown::Both);
+            return Ok(());
+        }
+    };
+
+    let wire_format = wire_format.negotiate(hello.wire_format);
     let player_index;
+    let joined;
+    let welcome;
+    let tuning;
     {
-        let mut state = stateptr.state.lock().unwrap();
-        player_index = state.add_player();
-        state.difficulty_mult *= 2.0;
+        let mut state = stateptr.lock();
+        player_index = state.join_player(hello.reconnect_token);
+        state.players[player_index].set_ship(hello.ship);
+        joined = NetPlayerConnected::make(&state, player_index, &hello, wire_format);
+        welcome = NetWelcome::make(&state, player_index);
+        tuning = NetTuning::make(&state.tuning);
+        state.emit(GameEvent::PlayerJoined { player: player_index as u32 });
     }
-    
-    send_struct(&mut stream, NetPlayerConnected::make(player_index));
+    let peer = peer_name(&stream);
+    println!("P{} joined from {}", player_index + 1, peer);
+
+    send_struct(&mut stream, joined);
+    send_struct(&mut stream, welcome);
+    send_struct(&mut stream, tuning);
 
+    let mut reader = MessageReader::new(&stream, wire_format)?;
+    let token = hello.reconnect_token;
+    let served = isolated(|| serve_player_inputs(&mut stream, &mut reader, &stateptr, player_index, token, transfer_ms, max_bad_messages));
+    if served.is_none() {
+        let _ = stream.shutdown(Shutdown::Both);
+        println!("P{} at {} left", player_index + 1, peer);
+        let mut state = stateptr.lock();
+        state.remove_player(player_index, GameEvent::PlayerLeft { player: player_index as u32 });
+    }
+    Ok(())
+}
+
+/// Applies a seated player's inputs until they leave, get kicked or desync.
+fn serve_player_inputs(
+    stream: &mut TcpStream,
+    reader: &mut MessageReader,
+    stateptr: &StatePtr,
+    player_index: usize,
+    reconnect_token: u64,
+    transfer_ms: u64,
+    max_bad_messages: u32,
+) {
+    let mut health = RecvHealth::new(stream, max_bad_messages);
     let mut timer = Instant::now();    
     loop {
         timer = block_for_next(timer, transfer_ms);
+
+        if stateptr.shutdown.load(Ordering::Relaxed) {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+
+        // Once kicked the slot may already belong to someone else.
+        let seated = {
+            let state = stateptr.lock();
+            state.players.get(player_index)
+                .map_or(false, |player| player.active && player.reconnect_token == reconnect_token)
+        };
+        if !seated {
+            println!("P{} at {} was kicked, closing their connection.", player_index + 1, peer_name(stream));
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
         
-        recv_update(&mut stream, |data: net_structs::NetClientInput| {
-            match stateptr.state.lock() {
-                Ok(ref mut state) => {
-                    data.update_main_state(player_index, state);
-                },
-                Err(_) => {},
-            }
+        let result = recv_update(reader, |data: net_structs::NetClientInput| {
+            stateptr.lock().queue_input(player_index, data);
         });
+        if let Some(desynced) = health.record(result) {
+            stateptr.lock().net_stats.bad_messages += 1;
+            if desynced {
+                let _ = stream.shutdown(Shutdown::Both);
+                println!("P{} at {} left", player_index + 1, peer_name(stream));
+                let mut state = stateptr.lock();
+                state.remove_player(player_index, GameEvent::PlayerLeft { player: player_index as u32 });
+                return;
+            }
+        }
+    }
+}
+
+/// Thread name for one direction of a connection, e.g. "send 192.168.1.5:54321".
+fn connection_thread_name(direction: &str, stream: &TcpStream) -> String {
+    format!("{} {}", direction, peer_name(stream))
+}
+
+/// The peer's address for log lines, e.g. "192.168.1.5:54321".
+fn peer_name(stream: &TcpStream) -> String {
+    stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|_| String::from("unknown peer"))
+}
+
+/// Runs `body` and logs a panic in it with the thread's name, instead of letting it end the thread
+/// silently. Returns None when it panicked so the caller can clean up after the connection.
+fn isolated<T>(body: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown cause"));
+            let thread = std::thread::current();
+            println!("Connection thread '{}' panicked: {}", thread.name().unwrap_or("unnamed"), message);
+            None
+        }
     }
 }
 
-fn server_main(stateptr: &mut StatePtr, net: NetSetup) -> std::io::Result<()> {
-    let send_lstener = TcpListener::bind("0.0.0.0:9942")?;
-    let recv_listener = TcpListener::bind("0.0.0.0:9949")?;
+/// Accepts connections until `shutdown` is set, polling so the flag is noticed even when nobody connects.
+/// While `is_full` holds nothing is accepted and new peers wait in the OS backlog.
+fn accept_loop(listener: TcpListener, shutdown: &AtomicBool, is_full: impl Fn() -> bool, mut on_accept: impl FnMut(TcpStream)) {
+    if let Err(e) = listener.set_nonblocking(true) {
+        println!("Listener can't poll, not accepting connections: {}", e);
+        return;
+    }
+
+    while !shutdown.load(Ordering::Relaxed) {
+        if is_full() {
+            std::thread::sleep(ACCEPT_POLL);
+            continue;
+        }
+        match listener.accept() {
+            // Some platforms hand out accepted sockets in the listener's non-blocking mode.
+            Ok((stream, _)) => {
+                let _ = stream.set_nonblocking(false);
+                on_accept(stream);
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(ACCEPT_POLL),
+            // E.g. out of file descriptors. Later connections may well succeed.
+            Err(e) => {
+                println!("Failed to accept a connection: {}", e);
+                std::thread::sleep(ACCEPT_POLL);
+            }
+        }
+    }
+}
+
+/// Starts the listener threads and returns the addresses actually bound,
+/// which differ from the configured ones when the ports are 0.
+fn server_main(stateptr: &mut StatePtr, net: NetSetup) -> std::io::Result<(SocketAddr, SocketAddr)> {
+    let send_lstener = TcpListener::bind((net.bind_address.as_str(), net.snapshot_port))?;
+    let recv_listener = TcpListener::bind((net.bind_address.as_str(), net.input_port))?;
+    let bound = (send_lstener.local_addr()?, recv_listener.local_addr()?);
 
     println!("Server!");
     println!("Listening for connections.... Transfer rate: {:?}ms", net.transfer_ms);
 
+    let transfer_ms = {
+        let mut state = stateptr.lock();
+        state.transfer_ms.store(net.transfer_ms, Ordering::Relaxed);
+        // Publish twice per transfer period so every sender tick finds a fresh snapshot.
+        state.snapshot.interval = Duration::from_millis(net.transfer_ms) / 2;
+        state.snapshot.max_actors = net.max_actors_per_snapshot as usize;
+        state.transfer_ms.clone()
+    };
+    console::spawn(stateptr.get_ref());
+
     let mut ptr = stateptr.get_ref();
     let net_copy = net.clone();
 
     let _ = std::thread::Builder::new().name("server listener sender".into())
         .spawn(move || {
-            
             let net = net_copy;
-        
-            for listen_result in send_lstener.incoming() {
-                let this_listen_ref = ptr.get_ref();
-                let mut stream = listen_result.expect("Server Sender Thread Failed.");
+            let shutdown = ptr.shutdown.clone();
+            let full_ptr = ptr.get_ref();
+            let max_connections = net.max_connections;
+            // Tolerates a poisoned lock, a connection thread's panic shouldn't stop the listener.
+            let is_full = move || max_connections > 0
+                && full_ptr.lock().connections >= max_connections;
+
+            accept_loop(send_lstener, &shutdown, is_full, |mut stream| {
+                let mut this_listen_ref = ptr.get_ref();
                 net.configure_stream(&mut stream);
 
-                let transfer_ms = net.transfer_ms;
+                let transfer_ms = transfer_ms.clone();
+                let net_sim = net.net_sim.clone();
+                let wire_format = net.wire_format;
                 
-                let _ = std::thread::Builder::new().name("server sender".into())
+                let spawned = std::thread::Builder::new().name(connection_thread_name("send", &stream))
                     .spawn(move || {
-                        this_listen_ref.state.lock().unwrap().connections += 1;
-                        println!("Client/Spectator Connected: {:?}", stream.peer_addr());
-                        server_sender(stream, this_listen_ref, transfer_ms);
+                        let handshake = isolated(|| -> std::io::Result<(u32, WireFormat, bool)> {
+                            let (version, wire_format) = snapshot_handshake(&mut stream, wire_format)?;
+                            let spectator = read_viewer_hello(&mut stream)?;
+                            Ok((version, wire_format, spectator))
+                        });
+                        let (version, wire_format, spectator) = match handshake {
+                            Some(Ok(negotiated)) => negotiated,
+                            Some(Err(e)) => {
+                                println!("Rejected {:?}: {}", stream.peer_addr(), e);
+                                let mut state = this_listen_ref.lock();
+                                let text = state.tr_fmt("toast.rejected", &[("error", &e)]);
+                                state.effects.toasts.push(text, TOAST_LONG_TIME, Severity::Warning);
+                                return;
+                            }
+                            None => return,
+                        };
+                        {
+                            let mut state = this_listen_ref.lock();
+                            state.connections += 1;
+                            state.snapshot.add_reader(version);
+                            // Players are announced once their input stream is seated.
+                            if spectator {
+                                state.emit(GameEvent::SpectatorJoined);
+                            }
+                        }
+                        let peer = peer_name(&stream);
+                        let kind = if spectator { "Spectator" } else { "Player" };
+                        println!("{} connected from {} (snapshot version {})", kind, peer, version);
+                        let sender_ref = this_listen_ref.get_ref();
+                        isolated(|| server_sender(stream, sender_ref, transfer_ms, net_sim, version, wire_format));
+                        release_connection(&this_listen_ref, version, spectator, &peer);
                     });
-            }
+                if let Err(e) = spawned {
+                    println!("Failed to start a snapshot thread: {}", e);
+                }
+            });
+            println!("Stopped accepting spectators and clients.");
         });
 
     let mut ptr = stateptr.get_ref();
     let _ = std::thread::Builder::new().name("server listener recver".into()).spawn(move || {
-        
-        for listen_result in recv_listener.incoming() {
+        let shutdown = ptr.shutdown.clone();
+        let full_ptr = ptr.get_ref();
+        let max_players = net.max_players as usize;
+        // Kicked players keep their slot in the list but don't count against the limit.
+        let is_full = move || max_players > 0
+            && full_ptr.lock().players.iter().filter(|p| p.active).count() >= max_players;
+
+        accept_loop(recv_listener, &shutdown, is_full, |mut stream| {
             let this_listen_ref = ptr.get_ref();
-            let mut stream = listen_result.expect("Server Recv Thread Failed.");
             net.configure_stream(&mut stream);
 
             let transfer_ms = net.transfer_ms;
-            let _ = std::thread::Builder::new().name("server sender".into())
+            let max_bad_messages = net.max_bad_messages;
+            let wire_format = net.wire_format;
+            let spawned = std::thread::Builder::new().name(connection_thread_name("recv", &stream))
                 .spawn(move || {
-                    server_recver(stream, this_listen_ref, transfer_ms).expect("Server Recv Thread Failed.");
+                    if let Err(e) = server_recver(stream, this_listen_ref, transfer_ms, max_bad_messages, wire_format) {
+                        println!("Player connection failed: {}", e);
+                    }
                 });
-        }
+            if let Err(e) = spawned {
+                println!("Failed to start an input thread: {}", e);
+            }
+        });
+        println!("Stopped accepting players.");
     });  
 
-    Ok(())
+    Ok(bound)
 }
\ No newline at end of file
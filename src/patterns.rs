@@ -0,0 +1,301 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::rng::SimRng;
+
+use std::fs::File;
+use std::io::BufReader;
+
+pub const PATTERNS_FILENAME: &str = "patterns.json";
+
+/// Chance per second of starting a pattern grows with intensity up to this.
+const MAX_PATTERN_CHANCE: f32 = 0.12;
+const PATTERN_CHANCE_RAMP: f32 = 1500.0;
+
+/// Seconds a pattern rock is announced before it appears, at the very start of a run.
+pub const TELEGRAPH_TIME: f32 = 0.6;
+/// The warning never gets shorter than this.
+pub const MIN_TELEGRAPH_TIME: f32 = 0.25;
+/// Intensity at which the warning has shrunk to half of `TELEGRAPH_TIME`.
+const TELEGRAPH_RAMP: f32 = 600.0;
+
+/// How long a pattern rock is announced before it appears at `intensity`. Shrinks as
+/// the run gets harder, so the warning never turns into a free pass.
+pub fn telegraph_time(intensity: f32) -> f32 {
+    (TELEGRAPH_TIME * TELEGRAPH_RAMP / (TELEGRAPH_RAMP + intensity.max(0.0))).max(MIN_TELEGRAPH_TIME)
+}
+
+/// One timed burst of rocks inside a pattern.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnEvent {
+    /// Seconds after the pattern started.
+    pub time: f32,
+    /// Horizontal position as a fraction of the world width, 0 is the left edge.
+    pub x: f32,
+    /// Radians away from straight down.
+    #[serde(default)]
+    pub angle: f32,
+    /// Multiplier on the current random rock speed.
+    #[serde(default = "SpawnEvent::default_speed")]
+    pub speed: f32,
+    #[serde(default = "SpawnEvent::default_count")]
+    pub count: u32,
+    /// Fraction of the width between consecutive rocks of the same event.
+    #[serde(default = "SpawnEvent::default_spread")]
+    pub spread: f32,
+}
+
+impl SpawnEvent {
+    fn default_speed() -> f32 {
+        1.0
+    }
+
+    fn default_count() -> u32 {
+        1
+    }
+
+    fn default_spread() -> f32 {
+        0.05
+    }
+
+    fn new(time: f32, x: f32, count: u32, spread: f32, speed: f32) -> SpawnEvent {
+        SpawnEvent { time, x, angle: 0.0, speed, count, spread }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnPattern {
+    pub name: String,
+    /// `curr_time * difficulty_mult` needed before the pattern can be picked.
+    #[serde(default)]
+    pub min_intensity: f32,
+    pub events: Vec<SpawnEvent>,
+}
+
+impl SpawnPattern {
+    fn validate(&self) -> Result<(), String> {
+        if self.events.is_empty() {
+            return Err(format!("no events"));
+        }
+        for event in &self.events {
+            if !(0.0..=1.0).contains(&event.x) || event.time < 0.0 || event.speed <= 0.0 {
+                return Err(format!("event at {}s is out of range", event.time));
+            }
+        }
+        Ok(())
+    }
+
+    fn defaults() -> Vec<SpawnPattern> {
+        let sweep = (0..12)
+            .map(|i| SpawnEvent::new(i as f32 * 0.2, 0.5 + 0.4 * (i as f32 * 0.6).sin(), 1, 0.05, 1.0))
+            .collect();
+        let mut v = vec![SpawnEvent::new(0.0, 0.5, 1, 0.05, 1.0)];
+        for i in 1..5 {
+            let offset = 0.06 * i as f32;
+            v.push(SpawnEvent::new(0.15 * i as f32, 0.5 - offset, 1, 0.05, 1.0));
+            v.push(SpawnEvent::new(0.15 * i as f32, 0.5 + offset, 1, 0.05, 1.0));
+        }
+
+        vec![
+            SpawnPattern {
+                name: String::from("wall with a gap"),
+                min_intensity: 60.0,
+                events: vec![
+                    SpawnEvent::new(0.0, 0.05, 4, 0.1, 0.8),
+                    SpawnEvent::new(0.0, 0.65, 4, 0.1, 0.8),
+                ],
+            },
+            SpawnPattern {
+                name: String::from("sine sweep"),
+                min_intensity: 30.0,
+                events: sweep,
+            },
+            SpawnPattern {
+                name: String::from("V formation"),
+                min_intensity: 90.0,
+                events: v,
+            },
+        ]
+    }
+
+    /// Reads the pattern file, writing the built-in patterns when it doesn't exist.
+    /// Entries that fail to parse or make no sense are reported by name and skipped.
+    pub fn load(filename: &str) -> Vec<SpawnPattern> {
+        let file = match File::open(filename) {
+            Ok(file) => file,
+            Err(_) => {
+                let patterns = SpawnPattern::defaults();
+                if let Ok(file) = File::create(filename) {
+                    // We don't care if this fails
+                    let _ = serde_json::to_writer_pretty(file, &patterns);
+                }
+                return patterns;
+            }
+        };
+
+        let entries = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(Value::Array(entries)) => entries,
+            Ok(_) => {
+                println!("Ignoring {}: expected a list of patterns", filename);
+                return Vec::new();
+            }
+            Err(e) => {
+                println!("Ignoring malformed {}: {}", filename, e);
+                return Vec::new();
+            }
+        };
+
+        let mut patterns = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.into_iter().enumerate() {
+            let name = entry.get("name").and_then(Value::as_str).map(String::from)
+                .unwrap_or_else(|| format!("#{}", i));
+            match serde_json::from_value::<SpawnPattern>(entry) {
+                Ok(pattern) => match pattern.validate() {
+                    Ok(()) => patterns.push(pattern),
+                    Err(e) => println!("Skipping spawn pattern {:?}: {}", name, e),
+                },
+                Err(e) => println!("Skipping spawn pattern {:?}: {}", name, e),
+            }
+        }
+        patterns
+    }
+}
+
+struct ActivePattern {
+    index: usize,
+    elapsed: f32,
+    next_event: usize,
+}
+
+/// Occasionally plays an authored pattern on top of the random trickle.
+pub struct PatternScheduler {
+    patterns: Vec<SpawnPattern>,
+    active: Option<ActivePattern>,
+}
+
+impl PatternScheduler {
+    pub fn new(mut patterns: Vec<SpawnPattern>) -> PatternScheduler {
+        // Events may be listed in any order in the file.
+        for pattern in &mut patterns {
+            pattern.events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        PatternScheduler {
+            patterns,
+            active: None,
+        }
+    }
+
+    pub fn builtin() -> PatternScheduler {
+        PatternScheduler::new(SpawnPattern::defaults())
+    }
+
+    pub fn reset(&mut self) {
+        self.active = None;
+    }
+
+    pub fn patterns(&self) -> &[SpawnPattern] {
+        &self.patterns
+    }
+
+    /// Advances the running pattern, possibly starting a new one, and returns the events now due.
+    pub fn tick(&mut self, delta: f32, intensity: f32, rng: &mut SimRng) -> Vec<SpawnEvent> {
+        if self.active.is_none() {
+            let chance = (intensity / PATTERN_CHANCE_RAMP).min(MAX_PATTERN_CHANCE) * delta;
+            if rng.next_f32() < chance {
+                self.start(intensity, rng);
+            }
+        }
+
+        let mut due = Vec::new();
+        let finished = match &mut self.active {
+            Some(active) => {
+                active.elapsed += delta;
+                let events = &self.patterns[active.index].events;
+                while active.next_event < events.len() && events[active.next_event].time <= active.elapsed {
+                    due.push(events[active.next_event].clone());
+                    active.next_event += 1;
+                }
+                active.next_event >= events.len()
+            }
+            None => false,
+        };
+        if finished {
+            self.active = None;
+        }
+        due
+    }
+
+    fn start(&mut self, intensity: f32, rng: &mut SimRng) {
+        let eligible: Vec<usize> = (0..self.patterns.len())
+            .filter(|&i| self.patterns[i].min_intensity <= intensity)
+            .collect();
+        if eligible.is_empty() {
+            return;
+        }
+        let pick = eligible[(rng.next_f32() * eligible.len() as f32) as usize % eligible.len()];
+        self.active = Some(ActivePattern { index: pick, elapsed: 0.0, next_event: 0 });
+    }
+}
+
+/// Where a rock is about to appear at the top edge, shown as a blinking marker.
+/// Servers send theirs in every snapshot, so everyone sees the same warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Telegraph {
+    /// World x the rock spawns at.
+    pub x: f32,
+    /// Seconds of spawn time until it does.
+    pub time_left: f32,
+}
+
+/// Server only. A rock a pattern decided on, waiting out its telegraph.
+#[derive(Debug, Clone)]
+struct PendingSpawn {
+    telegraph: Telegraph,
+    angle: f32,
+    speed: f32,
+    base_speed: f32,
+}
+
+/// A rock whose telegraph ran out, arguments for `MainState::spawn_rock`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DueSpawn {
+    pub x: f32,
+    pub angle: f32,
+    pub speed: f32,
+    pub base_speed: f32,
+}
+
+/// Server only. Rocks scheduled to appear once their telegraph has been shown, so the
+/// marker and the rock can't disagree about where and when.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnQueue {
+    pending: Vec<PendingSpawn>,
+}
+
+impl SpawnQueue {
+    pub fn schedule(&mut self, x: f32, angle: f32, speed: f32, base_speed: f32, lead: f32) {
+        let telegraph = Telegraph { x, time_left: lead };
+        self.pending.push(PendingSpawn { telegraph, angle, speed, base_speed });
+    }
+
+    /// Counts the telegraphs down by `delta` and returns the rocks now due, in the order they were scheduled.
+    pub fn tick(&mut self, delta: f32) -> Vec<DueSpawn> {
+        let mut due = Vec::new();
+        for spawn in &mut self.pending {
+            spawn.telegraph.time_left -= delta;
+            if spawn.telegraph.time_left <= 0.0 {
+                due.push(DueSpawn { x: spawn.telegraph.x, angle: spawn.angle, speed: spawn.speed, base_speed: spawn.base_speed });
+            }
+        }
+        self.pending.retain(|spawn| spawn.telegraph.time_left > 0.0);
+        due
+    }
+
+    pub fn telegraphs(&self) -> Vec<Telegraph> {
+        self.pending.iter().map(|spawn| spawn.telegraph).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
@@ -0,0 +1,163 @@
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point2};
+use ggez::{Context, GameResult};
+
+use crate::hud::{Anchor, Layout};
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+const HISTORY_FRAMES: usize = 240;
+const GRAPH_HEIGHT: f32 = 120.0;
+/// Vertical pixels per millisecond. 16.6ms, a 60Hz frame, is most of the graph.
+const PIXELS_PER_MS: f32 = 6.0;
+const TEXT_LINE_HEIGHT: f32 = 20.0;
+
+/// Parts of a frame that get their own color in the graph.
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Input,
+    Physics,
+    Collisions,
+    Spawn,
+    Snapshot,
+    Ui,
+    DrawActors,
+    DrawHud,
+}
+
+const PHASE_COUNT: usize = 8;
+
+const PHASE_NAMES: [&str; PHASE_COUNT] = [
+    "input", "physics", "collisions", "spawn", "snapshot", "ui", "draw actors", "draw hud",
+];
+
+const PHASE_COLORS: [(u8, u8, u8); PHASE_COUNT] = [
+    (120, 120, 255),
+    (80, 200, 255),
+    (255, 90, 90),
+    (240, 220, 80),
+    (200, 120, 240),
+    (120, 230, 100),
+    (255, 160, 60),
+    (200, 200, 200),
+];
+
+/// Milliseconds spent in each phase during one rendered frame.
+type FrameTimes = [f32; PHASE_COUNT];
+
+/// Frame time breakdown of the last few seconds, local only.
+/// While disabled `start` hands out `None` and nothing is measured.
+pub struct FrameProfiler {
+    pub enabled: bool,
+    current: FrameTimes,
+    history: VecDeque<FrameTimes>,
+}
+
+impl FrameProfiler {
+    pub fn new() -> FrameProfiler {
+        FrameProfiler {
+            enabled: false,
+            current: [0.0; PHASE_COUNT],
+            history: VecDeque::with_capacity(HISTORY_FRAMES),
+        }
+    }
+
+    pub fn start(&self) -> Option<Instant> {
+        if self.enabled {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    /// Adds the time since `started` to the phase. Update steps add up until the next frame is drawn.
+    pub fn record(&mut self, phase: Phase, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.current[phase as usize] += started.elapsed().as_micros() as f32 / 1000.0;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if self.history.len() >= HISTORY_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.current);
+        self.current = [0.0; PHASE_COUNT];
+    }
+
+    fn worst_frame(&self) -> Option<&FrameTimes> {
+        self.history.iter().max_by(|a, b| {
+            let (a, b): (f32, f32) = (a.iter().sum(), b.iter().sum());
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+
+    /// Stacked bar per frame, newest on the right, in the bottom-left corner.
+    pub fn draw(&self, ctx: &mut Context, font: &graphics::Font, layout: &Layout, text_color: Color) -> GameResult<()> {
+        let scale = layout.scale;
+        let origin = layout.place(Anchor::BottomLeft, (10.0, 10.0), (0.0, 0.0));
+
+        graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 0.6))?;
+        graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(
+            origin.x, origin.y - GRAPH_HEIGHT * scale, HISTORY_FRAMES as f32 * scale, GRAPH_HEIGHT * scale))?;
+
+        // Built relative to the graph's bottom-left corner and scaled when drawn.
+        for (phase, &(r, g, b)) in PHASE_COLORS.iter().enumerate() {
+            let mut builder = graphics::MeshBuilder::new();
+            let mut any = false;
+            for (i, frame) in self.history.iter().enumerate() {
+                let below: f32 = frame[..phase].iter().sum();
+                let bottom = (below * PIXELS_PER_MS).min(GRAPH_HEIGHT);
+                let top = ((below + frame[phase]) * PIXELS_PER_MS).min(GRAPH_HEIGHT);
+                if top - bottom < 0.5 {
+                    continue;
+                }
+                let x = (HISTORY_FRAMES - self.history.len() + i) as f32;
+                builder.polygon(DrawMode::Fill, &[
+                    Point2::new(x, -bottom),
+                    Point2::new(x + 1.0, -bottom),
+                    Point2::new(x + 1.0, -top),
+                    Point2::new(x, -top),
+                ]);
+                any = true;
+            }
+            if any {
+                let mesh = builder.build(ctx)?;
+                graphics::set_color(ctx, Color::from_rgb(r, g, b))?;
+                graphics::draw_ex(ctx, &mesh, graphics::DrawParam {
+                    dest: origin,
+                    scale: Point2::new(scale, scale),
+                    ..Default::default()
+                })?;
+            }
+        }
+        graphics::set_color(ctx, text_color)?;
+
+        if let Some(worst) = self.worst_frame() {
+            let total: f32 = worst.iter().sum();
+            let mut lines = vec![format!("worst frame {:.2}ms", total)];
+            for (name, ms) in PHASE_NAMES.iter().zip(worst.iter()) {
+                lines.push(format!("  {:<12}{:>6.2}ms", name, ms));
+            }
+            let x = origin.x + (HISTORY_FRAMES as f32 + 10.0) * scale;
+            for (i, line) in lines.iter().enumerate() {
+                let text = graphics::Text::new(ctx, line, font)?;
+                let y = origin.y - TEXT_LINE_HEIGHT * (lines.len() - i) as f32 * scale;
+                if i > 0 {
+                    let (r, g, b) = PHASE_COLORS[i - 1];
+                    graphics::set_color(ctx, Color::from_rgb(r, g, b))?;
+                }
+                graphics::draw_ex(ctx, &text, graphics::DrawParam {
+                    dest: Point2::new(x, y),
+                    scale: Point2::new(scale, scale),
+                    ..Default::default()
+                })?;
+            }
+        }
+        graphics::set_color(ctx, graphics::WHITE)
+    }
+}
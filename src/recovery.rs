@@ -0,0 +1,29 @@
+//! Remembers where the last panic happened, so a poisoned state lock can say what broke it
+//! instead of leaving only the backtraces of every thread that tripped over it afterwards.
+use std::panic;
+use std::sync::{Mutex, Once};
+
+static INSTALL: Once = Once::new();
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Adds recording the panic location in front of the current hook, which still prints as before.
+/// Installs once per process however often it's called.
+pub fn install_panic_hook() {
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let thread = std::thread::current();
+            let location = info.location()
+                .map(|l| format!("{}:{}", l.file(), l.line()))
+                .unwrap_or_else(|| String::from("unknown location"));
+            let described = format!("{} in thread '{}'", location, thread.name().unwrap_or("unnamed"));
+            *LAST_PANIC.lock().unwrap_or_else(|e| e.into_inner()) = Some(described);
+            previous(info);
+        }));
+    });
+}
+
+/// Where the most recent panic happened, e.g. "src/game.rs:812 in thread 'main'".
+pub fn last_panic() -> Option<String> {
+    LAST_PANIC.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
@@ -0,0 +1,132 @@
+//! Input logs: the local player's input recorded tick by tick, and played back in its place.
+//! With the seed and settings stored alongside, a replay runs exactly like the recording.
+use serde::{Serialize, Deserialize};
+
+use crate::config::GameSetup;
+use crate::game_structs::InputState;
+use crate::patterns::SpawnPattern;
+use crate::tuning::Tuning;
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Bump whenever `InputLogHeader` or `InputEvent` change shape.
+pub const INPUT_LOG_VERSION: u32 = 2;
+
+/// First line of an input log: everything besides the input that decides how the run goes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputLogHeader {
+    pub version: u32,
+    /// Every run of the recording uses this seed.
+    pub seed: u64,
+    pub difficulty_mult: f32,
+    pub practice: bool,
+    pub hardcore: bool,
+    pub world_width: f32,
+    pub world_height: f32,
+    pub aim_assist: f32,
+    pub game_setup: GameSetup,
+    pub tuning: Tuning,
+    pub patterns: Vec<SpawnPattern>,
+}
+
+/// Every other line: the input the local player holds from server step `tick` on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputEvent {
+    pub tick: u64,
+    pub input: InputState,
+}
+
+pub enum InputReplay {
+    Recording {
+        writer: BufWriter<File>,
+        last: Option<InputState>,
+    },
+    Playing {
+        events: VecDeque<InputEvent>,
+        current: InputState,
+    },
+    /// The log ran out and the keyboard steers again. Still counts as a replayed run.
+    Finished,
+}
+
+impl InputReplay {
+    pub fn record(filename: &str, header: &InputLogHeader) -> std::io::Result<InputReplay> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        write_line(&mut writer, header)?;
+        Ok(InputReplay::Recording { writer, last: None })
+    }
+
+    pub fn play(filename: &str) -> Result<(InputLogHeader, InputReplay), String> {
+        let file = File::open(filename).map_err(|e| e.to_string())?;
+        let mut lines = BufReader::new(file).lines();
+
+        let first = lines.next().ok_or("the file is empty")?.map_err(|e| e.to_string())?;
+        let header: InputLogHeader = serde_json::from_str(&first).map_err(|e| format!("bad header: {}", e))?;
+        if header.version != INPUT_LOG_VERSION {
+            return Err(format!("recorded by a different version (format {}, this build reads {})", header.version, INPUT_LOG_VERSION));
+        }
+
+        let mut events = VecDeque::new();
+        for (i, line) in lines.enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let event = serde_json::from_str(&line).map_err(|e| format!("bad event on line {}: {}", i + 2, e))?;
+            events.push_back(event);
+        }
+        Ok((header, InputReplay::Playing { events, current: InputState::default() }))
+    }
+
+    /// The input the simulation gets for step `tick`. Recording logs `live` when it changed,
+    /// playback replaces it. `None` when playback just ran out.
+    pub fn input(&mut self, tick: u64, live: InputState) -> Option<InputState> {
+        match self {
+            InputReplay::Recording { writer, last } => {
+                if last.as_ref() != Some(&live) {
+                    // Flushed right away, the run being recorded may well end in a crash.
+                    if let Err(e) = write_line(writer, &InputEvent { tick, input: live.clone() }) {
+                        println!("Failed to record input: {}", e);
+                    }
+                    *last = Some(live.clone());
+                }
+                Some(live)
+            }
+            InputReplay::Playing { events, current } => {
+                // The last event still gets its step, control comes back on the one after.
+                if events.is_empty() {
+                    *self = InputReplay::Finished;
+                    return None;
+                }
+                while events.front().map_or(false, |event| event.tick <= tick) {
+                    if let Some(event) = events.pop_front() {
+                        *current = event.input;
+                    }
+                }
+                Some(current.clone())
+            }
+            InputReplay::Finished => Some(live),
+        }
+    }
+
+    /// Ends a recording with every key released at `tick`, which is where its playback hands back control.
+    pub fn finish(&mut self, tick: u64) {
+        if let InputReplay::Recording { writer, .. } = self {
+            if let Err(e) = write_line(writer, &InputEvent { tick, input: InputState::default() }) {
+                println!("Failed to record input: {}", e);
+            }
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        match self {
+            InputReplay::Recording { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+fn write_line<T: Serialize>(writer: &mut BufWriter<File>, data: &T) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, data)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
@@ -0,0 +1,43 @@
+use serde::{Serialize, Deserialize};
+
+/// Small deterministic generator (xorshift64*) for everything that shapes a run.
+/// Same seed, same rocks, on any platform. Serializable so a run can be saved mid-way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        // Scramble the seed so small seeds (1, 2, 3...) don't start out similar.
+        // xorshift must never hold 0.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        SimRng {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    /// A seed for runs that didn't ask for a specific one.
+    pub fn random_seed() -> u64 {
+        rand::random()
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+}
@@ -0,0 +1,210 @@
+use serde::{Serialize, Deserialize};
+
+use crate::actor::Actor;
+use crate::game_structs::{MainState, Player};
+use crate::rng::SimRng;
+
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+pub const SAVE_FILENAME: &str = "savegame.bin";
+
+/// Written before the bincode body so foreign or outdated files are recognized.
+const SAVE_MAGIC: &[u8; 4] = b"RBSV";
+/// Bump whenever anything stored in `SaveGame` changes shape.
+///  * 2: fractional score
+///  * 3: time-slow pickups and the running time slow
+///  * 4: rock sprite variants
+///  * 5: player ships
+///  * 6: `Player::dying_until`
+///  * 7: gravity wells
+///  * 8: `MainState::difficulty_points`
+///  * 9: rock and gem score values
+///  * 10: actor ages and rock fades
+///  * 11: graze and practice deaths moved out of `Player::stats`
+///  * 12: actors and players store what snapshots replicate themselves
+const SAVE_VERSION: u32 = 12;
+const MAX_SAVE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    NotASave,
+    Version { found: u32 },
+    Corrupt(bincode::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{}", e),
+            SaveError::NotASave => write!(f, "not a Rust-Blaster save file"),
+            SaveError::Version { found } => write!(f, "saved by a different version (format {}, this build reads {})", found, SAVE_VERSION),
+            SaveError::Corrupt(e) => write!(f, "file is damaged ({})", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> SaveError {
+        SaveError::Io(e)
+    }
+}
+
+/// Everything needed to continue a single player run where it was left.
+/// Server-only bookkeeping that the actors don't serialize (graze marks, streaks, shots a
+/// well absorbed) starts over after loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    players: Vec<Player>,
+    shots: Vec<Actor>,
+    rocks: Vec<Actor>,
+    gems: Vec<Actor>,
+    powerups: Vec<Actor>,
+    wells: Vec<Actor>,
+    /// `Actor::age` of every rock, shot, gem, powerup and well, in that order. Lifetimes run
+    /// out at the same time after loading.
+    ages: Vec<f32>,
+    rock_time_scale: f32,
+    time_slow_left: f32,
+    score: f32,
+    curr_time: f32,
+    difficulty_mult: f32,
+    difficulty_points: f32,
+    world_width: f32,
+    world_height: f32,
+    seed: u64,
+    rng: SimRng,
+    /// `MainState::next_actor_id`, so actors spawned after loading don't take a saved actor's id.
+    next_actor_id: u32,
+}
+
+impl SaveGame {
+    pub fn capture(state: &mut MainState) -> SaveGame {
+        for actor in state.rocks.iter_mut()
+            .chain(state.shots.iter_mut())
+            .chain(state.gems.iter_mut())
+            .chain(state.powerups.iter_mut())
+            .chain(state.wells.iter_mut())
+            .chain(state.players.iter_mut().map(|p| &mut p.actor)) {
+            actor.pre_serialize();
+        }
+
+        SaveGame {
+            players: state.players.clone(),
+            shots: state.shots.clone(),
+            rocks: state.rocks.clone(),
+            gems: state.gems.clone(),
+            powerups: state.powerups.clone(),
+            wells: state.wells.clone(),
+            ages: state.rocks.iter()
+                .chain(&state.shots)
+                .chain(&state.gems)
+                .chain(&state.powerups)
+                .chain(&state.wells)
+                .map(|actor| actor.age)
+                .collect(),
+            rock_time_scale: state.rock_time_scale,
+            time_slow_left: state.time_slow_left,
+            score: state.score,
+            curr_time: state.curr_time,
+            difficulty_mult: state.difficulty_mult,
+            difficulty_points: state.difficulty_points,
+            world_width: state.world_width,
+            world_height: state.world_height,
+            seed: state.seed,
+            rng: state.rng.clone(),
+            next_actor_id: state.next_actor_id,
+        }
+    }
+
+    pub fn restore(mut self, state: &mut MainState) {
+        for actor in self.rocks.iter_mut()
+            .chain(self.shots.iter_mut())
+            .chain(self.gems.iter_mut())
+            .chain(self.powerups.iter_mut())
+            .chain(self.wells.iter_mut())
+            .chain(self.players.iter_mut().map(|p| &mut p.actor)) {
+            actor.post_deserialize(&state.tuning);
+        }
+        let aged = self.rocks.iter_mut()
+            .chain(self.shots.iter_mut())
+            .chain(self.gems.iter_mut())
+            .chain(self.powerups.iter_mut())
+            .chain(self.wells.iter_mut());
+        for (actor, age) in aged.zip(&self.ages) {
+            actor.age = *age;
+        }
+
+        state.players = self.players;
+        state.shots = self.shots;
+        state.rocks = self.rocks;
+        state.gems = self.gems;
+        state.powerups = self.powerups;
+        state.wells = self.wells;
+        state.rock_time_scale = self.rock_time_scale;
+        state.time_slow_left = self.time_slow_left;
+        state.score = self.score;
+        state.difficulty_mult = self.difficulty_mult;
+        state.difficulty_points = self.difficulty_points;
+        state.world_width = self.world_width;
+        state.world_height = self.world_height;
+        state.seed = self.seed;
+        state.rng = self.rng;
+        state.next_actor_id = self.next_actor_id;
+        state.patterns.reset();
+        state.spawn_queue.clear();
+        state.telegraphs.clear();
+        state.effects.popups.clear();
+        // Milestones below the saved score were announced before saving.
+        let score = state.score;
+        state.milestones_reached = state.game_setup.milestones.iter().cloned()
+            .filter(|&m| m as f32 <= score)
+            .collect();
+
+        if state.local_player_index.map_or(false, |i| i >= state.players.len()) {
+            state.local_player_index = None;
+        }
+
+        // The level timer runs off start_time, move it back so it reads curr_time again.
+        let elapsed = Duration::from_micros((self.curr_time.max(0.0) * 1_000_000.0) as u64);
+        let now = Instant::now();
+        state.start_time = now.checked_sub(elapsed).unwrap_or(now);
+        state.curr_time = self.curr_time;
+    }
+
+    pub fn write(&self, filename: &str) -> Result<(), SaveError> {
+        let body = bincode::serialize(self).map_err(SaveError::Corrupt)?;
+        // Write next to the old save first so a crash mid-write never loses it.
+        let temp = format!("{}.tmp", filename);
+        {
+            let mut file = File::create(&temp)?;
+            file.write_all(SAVE_MAGIC)?;
+            file.write_all(&SAVE_VERSION.to_le_bytes())?;
+            file.write_all(&body)?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&temp, filename)?;
+        Ok(())
+    }
+
+    pub fn read(filename: &str) -> Result<SaveGame, SaveError> {
+        let mut file = File::open(filename)?;
+
+        let mut magic = [0u8; 4];
+        let mut version = [0u8; 4];
+        file.read_exact(&mut magic).map_err(|_| SaveError::NotASave)?;
+        if &magic != SAVE_MAGIC {
+            return Err(SaveError::NotASave);
+        }
+        file.read_exact(&mut version).map_err(|_| SaveError::NotASave)?;
+        let found = u32::from_le_bytes(version);
+        if found != SAVE_VERSION {
+            return Err(SaveError::Version { found });
+        }
+
+        bincode::config().limit(MAX_SAVE_BYTES).deserialize_from(file).map_err(SaveError::Corrupt)
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::stats::{self, RunSummary};
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+struct Capture {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Encodes and writes screenshots on a background thread. Only grabbing the
+/// pixels happens on the render thread, a slow disk never hitches the game.
+pub struct ScreenshotWriter {
+    sender: Sender<Capture>,
+    results: Receiver<Result<PathBuf, String>>,
+}
+
+impl ScreenshotWriter {
+    pub fn spawn() -> ScreenshotWriter {
+        let (sender, receiver) = mpsc::channel::<Capture>();
+        let (result_sender, results) = mpsc::channel();
+
+        let _ = std::thread::Builder::new().name("screenshot writer".into())
+            .spawn(move || {
+                for capture in receiver {
+                    let result = ScreenshotWriter::write(&capture).map_err(|e| e.to_string());
+                    if let Err(e) = &result {
+                        println!("Failed to save screenshot: {}", e);
+                    }
+                    if result_sender.send(result).is_err() {
+                        return;
+                    }
+                }
+            });
+
+        ScreenshotWriter {
+            sender,
+            results,
+        }
+    }
+
+    /// Queues RGBA8 pixels, top row first, for saving.
+    pub fn save(&self, pixels: Vec<u8>, width: u32, height: u32) {
+        let _ = self.sender.send(Capture { pixels, width, height });
+    }
+
+    /// Results of finished saves since the last call.
+    pub fn finished(&self) -> Vec<Result<PathBuf, String>> {
+        self.results.try_iter().collect()
+    }
+
+    fn write(capture: &Capture) -> std::io::Result<PathBuf> {
+        fs::create_dir_all(SCREENSHOT_DIR)?;
+        let mut path = PathBuf::from(SCREENSHOT_DIR);
+        path.push(format!("blaster_{}.png", timestamp()));
+
+        let writer = BufWriter::new(File::create(&path)?);
+        image::png::PNGEncoder::new(writer)
+            .encode(&capture.pixels, capture.width, capture.height, image::ColorType::RGBA(8))?;
+        Ok(path)
+    }
+}
+
+/// Current UTC time as YYYYMMDD_HHMMSS.
+fn timestamp() -> String {
+    let secs = RunSummary::unix_now();
+    let (year, month, day) = stats::utc_date(secs);
+    let day_secs = secs % 86400;
+
+    format!("{:04}{:02}{:02}_{:02}{:02}{:02}",
+        year, month, day, day_secs / 3600, day_secs / 60 % 60, day_secs % 60)
+}
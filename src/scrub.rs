@@ -0,0 +1,117 @@
+//! Spectator playback controls: the last few seconds of snapshots, kept to pause on and
+//! step through. Only the spectator's own view changes, nothing is sent to the server.
+use crate::actor::Actor;
+use crate::game_structs::{MainState, Player};
+use crate::patterns::Telegraph;
+
+use std::collections::VecDeque;
+
+/// Seconds of server time a spectator can step back through.
+pub const SCRUB_SECONDS: f32 = 5.0;
+
+/// What a spectator sees of one applied snapshot.
+#[derive(Debug, Clone)]
+pub struct ScrubFrame {
+    pub server_time: f32,
+    pub score: f32,
+    pub players: Vec<Player>,
+    pub rocks: Vec<Actor>,
+    pub shots: Vec<Actor>,
+    pub gems: Vec<Actor>,
+    pub powerups: Vec<Actor>,
+    pub wells: Vec<Actor>,
+    pub telegraphs: Vec<Telegraph>,
+}
+
+impl ScrubFrame {
+    pub fn capture(state: &MainState) -> ScrubFrame {
+        ScrubFrame {
+            server_time: state.curr_time,
+            score: state.score,
+            players: state.players.clone(),
+            rocks: state.rocks.clone(),
+            shots: state.shots.clone(),
+            gems: state.gems.clone(),
+            powerups: state.powerups.clone(),
+            wells: state.wells.clone(),
+            telegraphs: state.telegraphs.clone(),
+        }
+    }
+
+    /// Puts this frame into `state` and gives back what was there, which swaps it back later.
+    pub fn swap_into(mut self, state: &mut MainState) -> ScrubFrame {
+        std::mem::swap(&mut self.server_time, &mut state.curr_time);
+        std::mem::swap(&mut self.score, &mut state.score);
+        std::mem::swap(&mut self.players, &mut state.players);
+        std::mem::swap(&mut self.rocks, &mut state.rocks);
+        std::mem::swap(&mut self.shots, &mut state.shots);
+        std::mem::swap(&mut self.gems, &mut state.gems);
+        std::mem::swap(&mut self.powerups, &mut state.powerups);
+        std::mem::swap(&mut self.wells, &mut state.wells);
+        std::mem::swap(&mut self.telegraphs, &mut state.telegraphs);
+        self
+    }
+}
+
+/// Recent frames, oldest first, and which one is shown while paused.
+#[derive(Debug, Default)]
+pub struct ScrubBuffer {
+    frames: VecDeque<ScrubFrame>,
+    /// Index into `frames` of the frame drawn instead of the live state. `None` while live.
+    viewing: Option<usize>,
+}
+
+impl ScrubBuffer {
+    /// Adds the newest frame and forgets the ones older than `SCRUB_SECONDS`.
+    pub fn record(&mut self, frame: ScrubFrame) {
+        // A restart sets the server clock back, the old frames belong to another run.
+        if self.frames.back().map_or(false, |last| frame.server_time < last.server_time) {
+            self.frames.clear();
+            self.viewing = None;
+        }
+        let oldest = frame.server_time - SCRUB_SECONDS;
+        self.frames.push_back(frame);
+        while self.frames.front().map_or(false, |first| first.server_time < oldest) {
+            self.frames.pop_front();
+            // The view stays on its frame, or the oldest one left once that is gone.
+            if let Some(index) = &mut self.viewing {
+                *index = index.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Freezes the view on the newest frame, or goes back to live.
+    pub fn toggle_pause(&mut self) {
+        self.viewing = match self.viewing {
+            Some(_) => None,
+            None => self.frames.len().checked_sub(1),
+        };
+    }
+
+    /// Moves the frozen view `steps` frames, negative ones back. Pauses first when live.
+    pub fn step(&mut self, steps: isize) {
+        let last = match self.frames.len().checked_sub(1) {
+            Some(last) => last,
+            None => return,
+        };
+        let current = self.viewing.unwrap_or(last) as isize;
+        self.viewing = Some((current + steps).max(0).min(last as isize) as usize);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.viewing.is_some()
+    }
+
+    /// The frame to draw instead of the live state, if paused.
+    pub fn viewed(&self) -> Option<&ScrubFrame> {
+        self.viewing.and_then(|index| self.frames.get(index))
+    }
+
+    /// Seconds the shown frame is behind the newest one.
+    pub fn behind(&self) -> f32 {
+        match (self.viewed(), self.frames.back()) {
+            (Some(viewed), Some(newest)) => newest.server_time - viewed.server_time,
+            _ => 0.0,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Shot sounds played at a different pitch for every player, so a crowded game doesn't
+//! sound like one gun. ggez can't change the pitch of a `Source`, this goes through rodio.
+use ggez::audio::SoundData;
+use rodio::Source;
+
+use std::io::Cursor;
+
+/// How far apart the pitches of neighbouring players are, as a playback rate.
+const PLAYER_PITCH_STEP: f32 = 0.05;
+/// Distinct pitches before players share one: the base and two steps up and down, ±10% at most.
+const PLAYER_PITCHES: usize = 5;
+/// Most a single volley strays from its player's pitch, so rapid fire isn't perfectly even.
+pub const VOLLEY_JITTER: f32 = 0.03;
+
+/// Playback rate of a volley by `player`. `jitter` between -1 and 1 nudges it by up to
+/// `VOLLEY_JITTER`. The first player gets the sound as recorded, the next ones alternate
+/// above and below.
+pub fn shot_pitch(player: usize, jitter: f32) -> f32 {
+    let slot = player % PLAYER_PITCHES;
+    let steps = ((slot + 1) / 2) as f32;
+    let sign = if slot % 2 == 1 { 1.0 } else { -1.0 };
+    1.0 + sign * steps * PLAYER_PITCH_STEP + jitter.max(-1.0).min(1.0) * VOLLEY_JITTER
+}
+
+/// One output per player, so everyone's shots can sound at once but a player's own
+/// volleys don't pile up on each other.
+pub struct ShotVoices {
+    data: SoundData,
+    /// None without a sound device, everything is silent then.
+    device: Option<rodio::Device>,
+    sinks: Vec<rodio::Sink>,
+}
+
+impl ShotVoices {
+    pub fn new(data: SoundData) -> ShotVoices {
+        ShotVoices { data, device: rodio::default_output_device(), sinks: Vec::new() }
+    }
+
+    /// Plays the sound for `player` at `pitch`, unless their last one is still playing.
+    pub fn play(&mut self, player: usize, pitch: f32) {
+        let device = match &self.device {
+            Some(device) => device,
+            None => return,
+        };
+        while self.sinks.len() <= player {
+            self.sinks.push(rodio::Sink::new(device));
+        }
+        let sink = &self.sinks[player];
+        if !sink.empty() {
+            return;
+        }
+        match rodio::Decoder::new(Cursor::new(self.data.clone())) {
+            Ok(decoder) => sink.append(decoder.speed(pitch)),
+            Err(e) => println!("Warning: could not play the shot sound: {:?}", e),
+        }
+    }
+}
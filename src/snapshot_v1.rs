@@ -0,0 +1,147 @@
+//! Snapshot version 1: `NetFromServer` as it was before snapshots were versioned, still
+//! written for and read from peers that negotiate it. The types here are frozen copies of
+//! what `Player` and `Actor` serialized to back then. They don't follow the current ones,
+//! changing them would break every peer on version 1.
+use ggez::graphics::Vector2;
+use serde::{Serialize, Deserialize};
+
+use crate::actor::{Actor, ActorType};
+use crate::game_structs::{InputState, MainState, Player};
+use crate::tuning::Tuning;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetFromServerV1 {
+    pub players: Vec<PlayerV1>,
+    /// Rocks and shots. Version 1 has no other kinds, they are left out.
+    pub actors: Vec<ActorV1>,
+    pub score: i32,
+    pub server_time: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerV1 {
+    pub actor: ActorV1,
+    pub input: InputStateV1,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorV1 {
+    pub tag: ActorTypeV1,
+    pub facing: f32,
+    pub serial_interm: SerialIntermediateV1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ActorTypeV1 {
+    Player,
+    Rock,
+    Shot,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SerialIntermediateV1 {
+    pub pos: Vec2V1,
+    pub vel: Vec2V1,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vec2V1 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputStateV1 {
+    pub fire: bool,
+    pub up: bool,
+    pub down: bool,
+    pub right: bool,
+    pub left: bool,
+}
+
+impl NetFromServerV1 {
+    pub fn make(state: &MainState) -> NetFromServerV1 {
+        NetFromServerV1 {
+            players: state.players.iter().map(PlayerV1::from_player).collect(),
+            actors: state.rocks.iter().chain(&state.shots)
+                .filter(|actor| !actor.kill)
+                .filter_map(ActorV1::from_actor)
+                .collect(),
+            score: state.score.floor() as i32,
+            server_time: state.curr_time,
+        }
+    }
+}
+
+impl PlayerV1 {
+    fn from_player(player: &Player) -> PlayerV1 {
+        PlayerV1 {
+            actor: ActorV1::with_tag(ActorTypeV1::Player, &player.actor),
+            input: InputStateV1 {
+                fire: player.input.fire,
+                up: player.input.up,
+                down: player.input.down,
+                right: player.input.right,
+                left: player.input.left,
+            },
+            index: player.index,
+        }
+    }
+
+    /// `base` has everything version 1 doesn't carry, the client's own copy of the player when it has one.
+    pub fn upgrade(&self, mut base: Player, tuning: &Tuning) -> Player {
+        base.actor = self.actor.upgrade(tuning);
+        base.set_ship(base.ship);
+        base.input = InputState {
+            fire: self.input.fire,
+            up: self.input.up,
+            down: self.input.down,
+            right: self.input.right,
+            left: self.input.left,
+            ..InputState::default()
+        };
+        base.index = self.index;
+        base
+    }
+}
+
+impl ActorV1 {
+    /// `None` for the kinds version 1 doesn't have.
+    fn from_actor(actor: &Actor) -> Option<ActorV1> {
+        let tag = match actor.tag {
+            ActorType::Player => ActorTypeV1::Player,
+            ActorType::Rock => ActorTypeV1::Rock,
+            ActorType::Shot => ActorTypeV1::Shot,
+            ActorType::Gem | ActorType::TimeSlow | ActorType::Well => return None,
+        };
+        Some(ActorV1::with_tag(tag, actor))
+    }
+
+    fn with_tag(tag: ActorTypeV1, actor: &Actor) -> ActorV1 {
+        ActorV1 {
+            tag,
+            facing: actor.facing,
+            serial_interm: SerialIntermediateV1 {
+                pos: Vec2V1 { x: actor.pos.x, y: actor.pos.y },
+                vel: Vec2V1 { x: actor.velocity.x, y: actor.velocity.y },
+            },
+        }
+    }
+
+    /// A current actor, pre-serialized like one that came in a current snapshot.
+    pub fn upgrade(&self, tuning: &Tuning) -> Actor {
+        let mut actor = match self.tag {
+            ActorTypeV1::Player => Actor::create_player_actor(tuning),
+            ActorTypeV1::Rock => Actor::create_rock(tuning),
+            ActorTypeV1::Shot => Actor::create_shot(tuning),
+        };
+        // Version 1 has no sprites, a random one would change with every snapshot.
+        actor.variant = 0;
+        actor.facing = self.facing;
+        actor.pos = Vector2::new(self.serial_interm.pos.x, self.serial_interm.pos.y);
+        actor.velocity = Vector2::new(self.serial_interm.vel.x, self.serial_interm.vel.y);
+        actor.pre_serialize();
+        actor
+    }
+}
@@ -0,0 +1,163 @@
+use serde::Serialize;
+
+use crate::events::RestartReason;
+use crate::highscores::HighScores;
+use crate::hud::{Severity, ToastSender, TOAST_LONG_TIME};
+use crate::strings::Strings;
+
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const RUNS_FILENAME: &str = "runs.jsonl";
+
+/// One line of the runs file, written every game over.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub timestamp: u64,
+    pub survival_time: f32,
+    pub score: f32,
+    pub difficulty_mult: f32,
+    pub players: usize,
+    pub rocks_destroyed: u32,
+    pub shots_fired: u32,
+    pub seed: Option<u64>,
+    /// Score milestones reached during the run, in the order they were reached.
+    pub milestones: Vec<u32>,
+    /// What ended the run.
+    pub reason: RestartReason,
+}
+
+impl RunSummary {
+    pub fn unix_now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+}
+
+/// (year, month, day) in UTC of a unix timestamp.
+pub fn utc_date(unix_secs: u64) -> (i64, i64, i64) {
+    let days = (unix_secs / 86400) as i64;
+
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Today's UTC date as YYYY-MM-DD.
+pub fn utc_today() -> String {
+    let (year, month, day) = utc_date(RunSummary::unix_now());
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// A daily challenge run for the high score table. Once it's written the date's results
+/// are shown in a toast.
+pub struct DailyAttempt {
+    /// YYYY-MM-DD.
+    pub date: String,
+    /// None for runs that don't count, e.g. the reset at startup: the results are only shown.
+    pub score: Option<f32>,
+    pub toasts: ToastSender,
+    pub strings: Strings,
+}
+
+impl DailyAttempt {
+    fn write(self, filename: &str) {
+        if let Some(score) = self.score {
+            HighScores::record_daily(filename, &self.date, score);
+        }
+        if let Some(result) = HighScores::daily_result(filename, &self.date) {
+            let text = self.strings.tr_fmt("toast.daily_result",
+                &[("last", &result.last), ("best", &result.best), ("attempts", &result.attempts)]);
+            self.toasts.push(text, TOAST_LONG_TIME, Severity::Info);
+        }
+    }
+}
+
+/// What the writer thread is handed, written in order.
+enum Record {
+    Run(RunSummary),
+    Daily(DailyAttempt),
+}
+
+/// Appends run summaries as JSON lines and daily attempts to the high score table from a
+/// background thread so a slow disk never stalls the simulation.
+pub struct RunLog {
+    sender: Sender<Record>,
+}
+
+impl RunLog {
+    pub fn spawn<T: AsRef<Path>>(filename: T, highscores_filename: &str) -> RunLog {
+        let (sender, receiver) = mpsc::channel::<Record>();
+        let path = filename.as_ref().to_path_buf();
+        let highscores_filename = highscores_filename.to_string();
+
+        let _ = std::thread::Builder::new().name("run log writer".into())
+            .spawn(move || {
+                for record in receiver {
+                    match record {
+                        Record::Run(summary) => {
+                            if let Err(e) = RunLog::append(&path, &summary) {
+                                println!("Failed to write run summary to {:?}: {}", path, e);
+                            }
+                        }
+                        Record::Daily(attempt) => attempt.write(&highscores_filename),
+                    }
+                }
+            });
+
+        RunLog {
+            sender,
+        }
+    }
+
+    /// A log that drops every record, for states that aren't real games.
+    pub fn discard() -> RunLog {
+        let (sender, _) = mpsc::channel::<Record>();
+        RunLog {
+            sender,
+        }
+    }
+
+    pub fn record(&self, summary: RunSummary) {
+        // The writer only goes away when the process is exiting.
+        let _ = self.sender.send(Record::Run(summary));
+    }
+
+    pub fn record_daily(&self, attempt: DailyAttempt) {
+        let _ = self.sender.send(Record::Daily(attempt));
+    }
+
+    fn append(path: &PathBuf, summary: &RunSummary) -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).read(true).open(path)?;
+        let line = serde_json::to_string(summary)?;
+
+        // Never glue our record onto a truncated or hand-edited last line.
+        if !RunLog::ends_with_newline(&mut file)? {
+            file.write_all(b"\n")?;
+        }
+        file.write_all(line.as_bytes())?;
+        file.write_all(b"\n")
+    }
+
+    fn ends_with_newline(file: &mut File) -> std::io::Result<bool> {
+        let len = file.metadata()?.len();
+        if len == 0 {
+            return Ok(true);
+        }
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::Start(len - 1))?;
+        file.read_exact(&mut last)?;
+        Ok(last[0] == b'\n')
+    }
+}
@@ -0,0 +1,187 @@
+//! On-screen text by key, so the game can be played in another language. The English
+//! table is built in, `strings.json` can change single entries and `--lang de` swaps in
+//! `strings.de.json`. Placeholders like `{score}` are filled in by `Strings::tr_fmt`.
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufReader;
+
+pub const STRINGS_FILENAME: &str = "strings.json";
+
+/// Every key the game uses, with its English text.
+pub const DEFAULT_STRINGS: &[(&str, &str)] = &[
+    ("instructions.welcome", "Welcome to Rust-Blaster"),
+
+    ("hud.role.server", "Server | Players: {players} | Spectators: {spectators}"),
+    ("hud.role.client", "Client | Player Id: {id}"),
+    ("hud.role.connecting", "Client | Connecting"),
+    ("hud.role.spectator", "Spectator"),
+    ("hud.score", "Score: {score} (+{graze} graze)  Lives: {lives}  {role}"),
+    ("hud.score.warm_up", "WARM-UP  Enter starts the run  {role}"),
+    ("hud.score.daily", "Daily Challenge  Score: {score} (+{graze} graze)  Lives: {lives}"),
+    ("hud.score.practice", "PRACTICE  Score: {score} (+{graze} graze)  Deaths: {deaths}  {role}"),
+    ("hud.score.versus", "VERSUS  {standings}  Score: {score}  Lives: {lives}  {role}"),
+    ("hud.score.hardcore", "HARDCORE  Score: {score} (+{graze} graze)  Lives: {lives}  {role}"),
+    ("hud.level", "Time: {time}  Difficulty: {difficulty}x  Intensity: {intensity}"),
+    ("hud.level.frozen", " (frozen)"),
+    ("hud.extra_life", "1UP"),
+    ("hud.auto_fire", "AUTO"),
+    ("hud.debug", "DEBUG"),
+    ("hud.waiting_for_server", "Waiting for the server..."),
+    ("hud.waiting_for_players", "Waiting for players \u{2014} warm-up"),
+    ("hud.paused", "Paused"),
+    ("hud.recovered", "Recovered from an internal error, disconnected. Restart the game to play on."),
+    ("hud.replay", "Replay -{behind}s  Space: live  , .: step"),
+    ("hud.scoreboard", "Player   Lives   Shots   Rocks   Accuracy   Graze"),
+    ("hud.attendance", "{players}, {spectators}"),
+    ("hud.attendance.player", "1 player"),
+    ("hud.attendance.players", "{count} players"),
+    ("hud.attendance.spectator", "1 spectator"),
+    ("hud.attendance.spectators", "{count} spectators"),
+    ("hud.follow.title", "Following P{player}"),
+    ("hud.follow.lives", "Lives: {lives}"),
+    ("hud.follow.rocks", "Rocks: {rocks}  Graze: {graze}"),
+    ("hud.follow.accuracy", "Accuracy: {accuracy}%"),
+
+    ("title.server", "Rust Blaster - Server - {attendance} - Score {score}"),
+    ("title.client", "Rust Blaster - Client (connected) - {attendance} - Score {score}"),
+    ("title.connecting", "Rust Blaster - Client (connecting)"),
+    ("title.spectator", "Rust Blaster - Spectator - {attendance} - Score {score}"),
+
+    ("banner.go", "GO!"),
+    ("banner.milestone", "{points} POINTS!"),
+
+    ("event.rock_streak", "P{player} destroyed {count} rocks in a row"),
+    ("event.extra_life", "P{player} earned an extra life"),
+    ("event.player_joined", "P{player} joined"),
+    ("event.game_over", "Game over with {score} points"),
+    ("event.difficulty_changed", "Difficulty set to {difficulty}x"),
+    ("event.milestone", "Reached {points} points"),
+    ("event.player_kicked", "P{player} stopped responding and was removed"),
+    ("event.player_left", "P{player} left"),
+    ("event.spectator_joined", "A spectator is watching"),
+    ("event.spectator_left", "A spectator stopped watching"),
+    ("event.round_started", "Round {round} started"),
+    ("event.game_reset", "Game reset, round {round} started"),
+    ("event.transfer_rate_changed", "Server sends snapshots every {ms} ms"),
+    ("reason.player_death", "P{player} was destroyed!"),
+    ("reason.admin_restart", "Server restarted the match"),
+    ("reason.mode_change", "The game mode changed"),
+    ("reason.warm_up_end", "Warm-up over, here we go!"),
+
+    ("toast.blocked_by_input_log", "Not while input is recorded or played back"),
+    ("toast.daily_result", "Today's last attempt: {last} (best {best}, {attempts} tries)"),
+    ("toast.screenshot_saved", "Saved screenshot {path}"),
+    ("toast.screenshot_failed", "Failed to save screenshot"),
+    ("toast.playback_finished", "Playback finished, the keyboard has control"),
+    ("toast.internal_error", "Internal error at {cause}"),
+    ("toast.ship_when_joining", "Pick a ship with --ship when joining"),
+    ("toast.cant_save", "Can't save or load: {reason}"),
+    ("toast.cant_save.not_server", "only the server has the whole game"),
+    ("toast.cant_save.others_connected", "other players are connected"),
+    ("toast.cant_save.daily", "daily challenges are played in one go"),
+    ("toast.cant_save.input_log", "input is being recorded or played back"),
+    ("toast.saved", "Saved to {file}"),
+    ("toast.save_failed", "Failed to save {file}: {error}"),
+    ("toast.resumed", "Resumed at {time}s, score {score}"),
+    ("toast.load_failed", "Failed to load {file}: {error}"),
+    ("toast.no_player_slot", "No player slot, watching as a spectator"),
+    ("toast.server_too_old", "The server is too old to play with, watching as a spectator"),
+    ("toast.connected", "Connected as P{player}"),
+    ("toast.connection_lost", "Lost the connection to the server"),
+    ("toast.rejected", "Rejected a connection: {error}"),
+];
+
+/// English text of `key`, `None` for keys the game doesn't know.
+pub fn default_text(key: &str) -> Option<&'static str> {
+    DEFAULT_STRINGS.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+}
+
+/// Replaces every `{name}` in `template` with its value from `args`. Unknown
+/// placeholders are left as they are.
+pub fn fill(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in args {
+        let placeholder = format!("{{{}}}", name);
+        if text.contains(&placeholder) {
+            text = text.replace(&placeholder, &value.to_string());
+        }
+    }
+    text
+}
+
+#[derive(Debug, Clone)]
+pub struct Strings {
+    table: HashMap<String, String>,
+    /// Whether keys missing from `table` get their English text.
+    english: bool,
+}
+
+impl Default for Strings {
+    /// The built-in English table.
+    fn default() -> Strings {
+        Strings { table: HashMap::new(), english: true }
+    }
+}
+
+impl Strings {
+    /// `strings.<lang>.json` when `lang` is given and the file exists, else `strings.json`
+    /// over the English defaults. A language file stands on its own, so untranslated
+    /// entries show up as their key instead of slipping through in English.
+    pub fn load(lang: Option<&str>) -> Strings {
+        if let Some(lang) = lang {
+            let filename = format!("strings.{}.json", lang);
+            match read_table(&filename) {
+                Some(table) => {
+                    let strings = Strings::language(table);
+                    let missing = strings.missing_keys();
+                    if !missing.is_empty() {
+                        println!("{} has no text for {} keys, showing them by name: {:?}", filename, missing.len(), missing);
+                    }
+                    return strings;
+                }
+                None => println!("No language file {}, using {}", filename, STRINGS_FILENAME),
+            }
+        }
+        Strings { table: read_table(STRINGS_FILENAME).unwrap_or_default(), english: true }
+    }
+
+    /// A whole language, nothing falls back to English.
+    pub fn language(table: HashMap<String, String>) -> Strings {
+        Strings { table, english: false }
+    }
+
+    /// The text for `key`, or the key itself when there is none.
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        match self.table.get(key) {
+            Some(text) => text,
+            None if self.english => default_text(key).unwrap_or(key),
+            None => key,
+        }
+    }
+
+    /// `tr` with its placeholders filled in, see `fill`.
+    pub fn tr_fmt(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        fill(self.tr(key), args)
+    }
+
+    /// Keys the game uses that have no text here.
+    pub fn missing_keys(&self) -> Vec<&'static str> {
+        DEFAULT_STRINGS.iter()
+            .map(|(k, _)| *k)
+            .filter(|k| !self.english && !self.table.contains_key(*k))
+            .collect()
+    }
+}
+
+/// A flat json object of key to text. A missing file is fine, a malformed one is reported.
+fn read_table(filename: &str) -> Option<HashMap<String, String>> {
+    let file = File::open(filename).ok()?;
+    match serde_json::from_reader(BufReader::new(file)) {
+        Ok(table) => Some(table),
+        Err(e) => {
+            println!("Ignoring malformed {}: {}", filename, e);
+            None
+        }
+    }
+}
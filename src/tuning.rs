@@ -0,0 +1,144 @@
+//! Numbers that decide how the game feels: ship and shot speeds, the fire rate and hitboxes.
+//! Read from `tuning.json`, so play-testing a change doesn't take a rebuild.
+use serde::{Serialize, Deserialize};
+
+use crate::actor::ActorType;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+pub const TUNING_FILENAME: &str = "tuning.json";
+
+/// The server's tuning is the one that counts, clients get it with `NetTuning` when joining.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tuning {
+    /// Ship speed in world units per second.
+    pub player_speed: f32,
+    pub shot_speed: f32,
+    /// Seconds between two shots of the same ship.
+    pub shot_cooldown: f32,
+    /// Nothing moves faster than this, however hard wells pull.
+    pub max_physics_vel: f32,
+    /// Hitbox radii.
+    pub player_bbox: f32,
+    pub rock_bbox: f32,
+    pub shot_bbox: f32,
+    pub gem_bbox: f32,
+    pub time_slow_bbox: f32,
+    pub well_bbox: f32,
+    /// Rocks within `explosion_radius` of one that is destroyed get pushed away from it, by
+    /// `explosion_impulse` divided by their distance.
+    pub explosion_impulse: f32,
+    pub explosion_radius: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            player_speed: 500.0,
+            shot_speed: 1100.0,
+            shot_cooldown: 0.2,
+            max_physics_vel: 950.0,
+            player_bbox: 12.0,
+            rock_bbox: 12.0,
+            shot_bbox: 6.0,
+            gem_bbox: 8.0,
+            time_slow_bbox: 10.0,
+            well_bbox: 20.0,
+            explosion_impulse: 4000.0,
+            explosion_radius: 120.0,
+        }
+    }
+}
+
+impl Tuning {
+    pub fn from_file<T: AsRef<Path>>(filename: T) -> std::io::Result<Tuning> {
+        let file = File::open(filename)?;
+        let reader = BufReader::new(file);
+        let data: Tuning = serde_json::from_reader(reader)?;
+        Ok(data.clamped())
+    }
+
+    pub fn write_default<T: AsRef<Path>>(filename: T) -> Tuning {
+        match File::create(filename) {
+            Ok(file) => {
+                let tuning = Tuning::default();
+                // We don't care if this fails
+                let _ = serde_json::to_writer_pretty(file, &tuning);
+                tuning
+            }
+            _ => Tuning::default()
+        }
+    }
+
+    /// Every field by name, sorted by name. This is what goes over the wire, so builds
+    /// with more or fewer fields can tell what they disagree on.
+    pub fn to_fields(&self) -> Vec<(String, f32)> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) => map
+                .into_iter()
+                .filter_map(|(name, value)| value.as_f64().map(|v| (name, v as f32)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The tuning from another build's `to_fields`. Fields only the other build has are
+    /// ignored. Errs with the names of fields this build has that the other one didn't send.
+    pub fn from_fields(fields: &[(String, f32)]) -> Result<Tuning, Vec<String>> {
+        let missing: Vec<String> = Tuning::default()
+            .to_fields()
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| !fields.iter().any(|(other, _)| other == name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(missing);
+        }
+
+        let map = fields
+            .iter()
+            .filter_map(|(name, value)| {
+                serde_json::Number::from_f64(*value as f64).map(|v| (name.clone(), serde_json::Value::Number(v)))
+            })
+            .collect();
+        serde_json::from_value::<Tuning>(serde_json::Value::Object(map))
+            .map(Tuning::clamped)
+            .map_err(|e| vec![e.to_string()])
+    }
+
+    /// Hitbox radius of an actor of kind `tag`.
+    pub fn bbox(&self, tag: &ActorType) -> f32 {
+        match tag {
+            ActorType::Player => self.player_bbox,
+            ActorType::Rock => self.rock_bbox,
+            ActorType::Shot => self.shot_bbox,
+            ActorType::Gem => self.gem_bbox,
+            ActorType::TimeSlow => self.time_slow_bbox,
+            ActorType::Well => self.well_bbox,
+        }
+    }
+
+    /// Negative speeds and sizes only make for confusing bugs.
+    fn clamped(mut self) -> Tuning {
+        for value in &mut [
+            &mut self.player_speed,
+            &mut self.shot_speed,
+            &mut self.shot_cooldown,
+            &mut self.max_physics_vel,
+            &mut self.player_bbox,
+            &mut self.rock_bbox,
+            &mut self.shot_bbox,
+            &mut self.gem_bbox,
+            &mut self.time_slow_bbox,
+            &mut self.well_bbox,
+            &mut self.explosion_impulse,
+            &mut self.explosion_radius,
+        ] {
+            **value = (**value).max(0.0);
+        }
+        self
+    }
+}
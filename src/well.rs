@@ -0,0 +1,60 @@
+//! Gravity wells: a rare hazard that drags shots and ships toward itself.
+use ggez::graphics::Vector2;
+use serde::{Serialize, Deserialize};
+
+/// How hard a well pulls. Copied from `WellSetup::pull` when the well spawns and sent to
+/// clients with it, so their prediction bends shots and ships the way the server does.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WellPull {
+    /// Within this distance the pull is at full strength, further out it falls off with the square of the distance.
+    pub core: f32,
+    /// Nothing further away than this is pulled.
+    pub reach: f32,
+    /// Acceleration on shots at full strength, in pixels per second squared.
+    pub shot_accel: f32,
+    /// Speed ships drift at at full strength. Ships have no momentum, so this moves them directly.
+    pub player_drift: f32,
+}
+
+impl Default for WellPull {
+    fn default() -> WellPull {
+        WellPull {
+            core: 60.0,
+            reach: 350.0,
+            shot_accel: 2500.0,
+            player_drift: 260.0,
+        }
+    }
+}
+
+impl WellPull {
+    /// Fraction of full strength at `distance`: 1 inside `core`, 0 beyond `reach`.
+    pub fn falloff(&self, distance: f32) -> f32 {
+        if distance > self.reach {
+            0.0
+        } else if distance <= self.core {
+            1.0
+        } else {
+            (self.core / distance).powi(2)
+        }
+    }
+
+    /// Acceleration of a shot `offset` away from the well, `offset` pointing at the well.
+    pub fn shot_pull(&self, offset: Vector2) -> Vector2 {
+        self.towards(offset) * self.shot_accel
+    }
+
+    /// Velocity a ship `offset` away from the well drifts at, `offset` pointing at the well.
+    pub fn player_pull(&self, offset: Vector2) -> Vector2 {
+        self.towards(offset) * self.player_drift
+    }
+
+    fn towards(&self, offset: Vector2) -> Vector2 {
+        let distance = offset.norm();
+        if distance <= 0.0 {
+            return Vector2::new(0.0, 0.0);
+        }
+        offset / distance * self.falloff(distance)
+    }
+}
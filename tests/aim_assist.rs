@@ -0,0 +1,105 @@
+//! Aim assist picks the rock needing the smallest turn and never turns further than its strength allows.
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::Actor;
+use rust_blaster::aim::{self, AIM_ASSIST_CONE, MAX_AIM_ASSIST, MAX_CORRECTION_ANGLE};
+use rust_blaster::tuning::Tuning;
+
+const SPEED: f32 = 1000.0;
+const LIFETIME: f32 = 1.5;
+
+fn rock_at(x: f32, y: f32) -> Actor {
+    let mut rock = Actor::create_rock(&Tuning::default());
+    rock.pos = Vector2::new(x, y);
+    rock.velocity = Vector2::new(0.0, 0.0);
+    rock
+}
+
+fn up() -> Vector2 {
+    Vector2::new(0.0, SPEED)
+}
+
+fn origin() -> Vector2 {
+    Vector2::new(0.0, 0.0)
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn no_strength_no_turn() {
+    let rocks = vec![rock_at(50.0, 500.0)];
+    assert_eq!(aim::correction(origin(), up(), &rocks, 0.0, LIFETIME), 0.0);
+}
+
+#[test]
+fn small_misses_are_corrected_exactly() {
+    // 0.1 rad to the right, clockwise, within the 0.15 limit at strength 0.3.
+    let angle: f32 = 0.1;
+    let rocks = vec![rock_at(500.0 * angle.sin(), 500.0 * angle.cos())];
+    assert_close(aim::correction(origin(), up(), &rocks, MAX_AIM_ASSIST, LIFETIME), -angle);
+}
+
+#[test]
+fn large_misses_are_clamped() {
+    let angle: f32 = 0.4;
+    let rocks = vec![rock_at(-500.0 * angle.sin(), 500.0 * angle.cos())];
+    let strength = 0.2;
+    assert_close(aim::correction(origin(), up(), &rocks, strength, LIFETIME), strength * MAX_CORRECTION_ANGLE);
+}
+
+#[test]
+fn strength_is_capped_at_the_maximum() {
+    let angle: f32 = 0.4;
+    let rocks = vec![rock_at(500.0 * angle.sin(), 500.0 * angle.cos())];
+    assert_close(aim::correction(origin(), up(), &rocks, 1.0, LIFETIME), -MAX_AIM_ASSIST * MAX_CORRECTION_ANGLE);
+}
+
+#[test]
+fn smallest_turn_wins_over_nearest_rock() {
+    let near: f32 = 0.3;
+    let far: f32 = 0.05;
+    let rocks = vec![
+        rock_at(200.0 * near.sin(), 200.0 * near.cos()),
+        rock_at(-900.0 * far.sin(), 900.0 * far.cos()),
+    ];
+    assert_close(aim::correction(origin(), up(), &rocks, MAX_AIM_ASSIST, LIFETIME), far);
+}
+
+#[test]
+fn rocks_outside_the_cone_or_behind_are_ignored() {
+    let wide = AIM_ASSIST_CONE + 0.2;
+    let rocks = vec![
+        rock_at(500.0 * wide.sin(), 500.0 * wide.cos()),
+        rock_at(10.0, -400.0),
+    ];
+    assert_eq!(aim::correction(origin(), up(), &rocks, MAX_AIM_ASSIST, LIFETIME), 0.0);
+}
+
+#[test]
+fn rocks_out_of_range_are_ignored() {
+    let rocks = vec![rock_at(20.0, SPEED * LIFETIME + 100.0)];
+    assert_eq!(aim::correction(origin(), up(), &rocks, MAX_AIM_ASSIST, LIFETIME), 0.0);
+}
+
+#[test]
+fn moving_rocks_are_led() {
+    // Straight ahead but drifting left: the shot has to go left of it to meet it.
+    let mut rock = rock_at(0.0, 500.0);
+    rock.velocity = Vector2::new(-100.0, 0.0);
+    let turn = aim::correction(origin(), up(), &[rock], MAX_AIM_ASSIST, LIFETIME);
+    assert!(turn > 0.0, "expected a turn to the left, got {}", turn);
+
+    let aimed = aim::rotate(up(), turn);
+    let time = 500.0 / aimed.y;
+    let rock_x = -100.0 * time;
+    assert!((aimed.x * time - rock_x).abs() < 0.5, "shot at {}, rock at {}", aimed.x * time, rock_x);
+}
+
+#[test]
+fn servers_can_lower_the_strength() {
+    assert_close(aim::granted_strength(0.3, 0.1), 0.1);
+    assert_close(aim::granted_strength(0.8, 1.0), MAX_AIM_ASSIST);
+    assert_close(aim::granted_strength(-1.0, 0.3), 0.0);
+}
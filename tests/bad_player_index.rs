@@ -0,0 +1,75 @@
+//! Input for a player slot that doesn't exist, or a thread dying with the state locked,
+//! must not take the whole game down.
+mod common;
+
+use common::{SCREEN, STEP};
+use rust_blaster::actor::{Actor, Vec2Serial};
+use rust_blaster::game_structs::{InputState, MainState, StatePtr};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::NetClientInput;
+use rust_blaster::tuning::Tuning;
+
+use std::time::Instant;
+
+fn options() -> LaunchOptions {
+    LaunchOptions { seed: Some(11), ..LaunchOptions::default() }
+}
+
+/// A local player and a remote one.
+fn server() -> MainState {
+    common::server_with(&options(), 1)
+}
+
+fn input_with_a_volley() -> NetClientInput {
+    let mut shot = Actor::create_shot(&Tuning::default());
+    shot.pre_serialize();
+    NetClientInput {
+        seq: 0,
+        input_state: InputState { fire: true, ..InputState::default() },
+        final_position: Vec2Serial::default(),
+        shots_made: vec![shot],
+        debug_commands: Vec::new(),
+        acked_event_seq: 0,
+        timeline: Vec::new(),
+        client_time: 0.0,
+    }
+}
+
+#[test]
+fn input_for_a_missing_player_is_dropped() {
+    let mut state = server();
+
+    input_with_a_volley().update_main_state(7, Instant::now(), &mut state);
+
+    assert_eq!(state.players.len(), 2);
+    assert!(state.shots.is_empty());
+    assert!(state.players.iter().all(|p| p.stats.shots_fired == 0));
+
+    // And the game carries on.
+    common::run(&mut state, 1.0);
+}
+
+#[test]
+fn input_for_a_seated_player_still_applies() {
+    let mut state = server();
+
+    input_with_a_volley().update_main_state(1, Instant::now(), &mut state);
+
+    assert_eq!(state.shots.len(), 1);
+    assert!(state.players[1].input.fire);
+}
+
+#[test]
+fn a_panic_while_locked_doesnt_freeze_the_state() {
+    let ptr = StatePtr::headless(&options(), SCREEN, SCREEN);
+    let state = ptr.state.clone();
+    let crashed = std::thread::spawn(move || {
+        let _locked = state.lock().unwrap();
+        panic!("a network thread died holding the lock");
+    }).join();
+    assert!(crashed.is_err());
+    assert!(ptr.state.is_poisoned());
+
+    let mut state = ptr.lock();
+    state.step_server(STEP);
+}
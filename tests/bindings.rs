@@ -0,0 +1,60 @@
+//! Bindings name either a key's symbol or its physical position, and names SDL doesn't
+//! know are left out instead of binding something else. A pressed key finds its action
+//! by either.
+use ggez::event::Keycode;
+use sdl2::keyboard::Scancode;
+
+use rust_blaster::bindings::{Action, KeyBindings, KeyMatch};
+use rust_blaster::config::KeyBindingSetup;
+
+#[test]
+fn plain_names_are_symbols() {
+    assert_eq!(KeyMatch::parse("W"), Some(KeyMatch::Key(Keycode::W)));
+    assert_eq!(KeyMatch::parse("Up"), Some(KeyMatch::Key(Keycode::Up)));
+    assert_eq!(KeyMatch::parse("Space"), Some(KeyMatch::Key(Keycode::Space)));
+}
+
+#[test]
+fn sc_names_are_positions() {
+    assert_eq!(KeyMatch::parse("sc:W"), Some(KeyMatch::Scan(Scancode::W)));
+    assert_eq!(KeyMatch::parse("sc:Up"), Some(KeyMatch::Scan(Scancode::Up)));
+    assert_eq!(KeyMatch::parse("sc:Space"), Some(KeyMatch::Scan(Scancode::Space)));
+}
+
+#[test]
+fn unknown_names_bind_nothing() {
+    for name in &["", "Nope", "sc:", "sc:Nope", "SC:W", "scancode:W"] {
+        assert_eq!(KeyMatch::parse(name), None, "{:?}", name);
+    }
+}
+
+#[test]
+fn positions_ignore_the_layout() {
+    // On AZERTY the key where QWERTY has W is labelled Z.
+    let position = KeyMatch::parse("sc:W").unwrap();
+    let symbol = KeyMatch::parse("W").unwrap();
+    assert!(position.matches(Keycode::Z, Some(Scancode::W)));
+    assert!(!symbol.matches(Keycode::Z, Some(Scancode::W)));
+    assert!(symbol.matches(Keycode::W, Some(Scancode::Z)));
+    assert!(!position.matches(Keycode::W, Some(Scancode::Z)));
+    // Without a known position only symbols match.
+    assert!(!position.matches(Keycode::W, None));
+    assert!(symbol.matches(Keycode::W, None));
+}
+
+#[test]
+fn pressed_keys_find_their_action() {
+    // Positions come from SDL's keymap, which the video subsystem sets up. The dummy driver
+    // has the US layout, so every position maps back to the key of the same name.
+    std::env::set_var("SDL_VIDEODRIVER", "dummy");
+    let sdl = sdl2::init().expect("SDL init failed");
+    let _video = sdl.video().expect("no dummy video driver");
+
+    let setup = KeyBindingSetup { fire: vec!["sc:Space".to_string(), "Return".to_string()], ..KeyBindingSetup::default() };
+    let bindings = KeyBindings::from_setup(&setup);
+    assert_eq!(bindings.action(Keycode::W), Some(Action::Up));
+    assert_eq!(bindings.action(Keycode::Left), Some(Action::Left));
+    assert_eq!(bindings.action(Keycode::Space), Some(Action::Fire));
+    assert_eq!(bindings.action(Keycode::Return), Some(Action::Fire));
+    assert_eq!(bindings.action(Keycode::Q), None);
+}
@@ -0,0 +1,126 @@
+//! Actors wrap and get culled only once all of their hitbox is past an edge, however
+//! large they are, and rocks spawn hidden just above the top. Distances across an edge
+//! agree with where wrapping puts an actor.
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::{toroidal_delta, toroidal_distance, Actor};
+use rust_blaster::tuning::Tuning;
+
+const WIDTH: f32 = 1000.0;
+const HEIGHT: f32 = 800.0;
+const RADII: [f32; 2] = [6.0, 120.0];
+/// Unit vectors towards the right, left, top and bottom edges.
+const EDGES: [(f32, f32); 4] = [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)];
+/// Towards the top right, top left, bottom right and bottom left corners.
+const CORNERS: [(f32, f32); 4] = [(1.0, 1.0), (-1.0, 1.0), (1.0, -1.0), (-1.0, -1.0)];
+
+fn actor(radius: f32, edge: (f32, f32), past_edge: f32) -> Actor {
+    let mut actor = Actor::create_rock(&Tuning::default());
+    actor.bbox_size = radius;
+    let (bound_x, bound_y) = (WIDTH / 2.0 + past_edge, HEIGHT / 2.0 + past_edge);
+    actor.pos = Vector2::new(edge.0 * bound_x, edge.1 * bound_y);
+    actor
+}
+
+#[test]
+fn nothing_wraps_while_part_of_it_is_on_screen() {
+    for &radius in &RADII {
+        for &edge in &EDGES {
+            let mut partly = actor(radius, edge, radius - 1.0);
+            let before = partly.pos;
+            partly.wrap_position(WIDTH, HEIGHT);
+            assert_eq!(partly.pos, before, "radius {} at edge {:?}", radius, edge);
+        }
+    }
+}
+
+#[test]
+fn wrapping_comes_back_in_as_far_outside_the_other_edge() {
+    for &radius in &RADII {
+        for &edge in &EDGES {
+            let mut gone = actor(radius, edge, radius + 1.0);
+            gone.wrap_position(WIDTH, HEIGHT);
+            // 1 past the point of leaving one edge is 1 into entering from the other.
+            let expected = actor(radius, (-edge.0, -edge.1), radius - 1.0).pos;
+            assert!((gone.pos - expected).norm() < 1e-3, "radius {} at edge {:?} ended at {:?}", radius, edge, gone.pos);
+        }
+    }
+}
+
+#[test]
+fn culling_waits_for_the_whole_actor_and_the_margin() {
+    let margin = 64.0;
+    for &radius in &RADII {
+        for &edge in &EDGES {
+            assert!(!actor(radius, edge, radius - 1.0).is_out_of_bounds(WIDTH, HEIGHT));
+            assert!(actor(radius, edge, radius + 1.0).is_out_of_bounds(WIDTH, HEIGHT));
+            assert!(!actor(radius, edge, radius + margin - 1.0).is_out_of_bounds_by(WIDTH, HEIGHT, margin));
+            assert!(actor(radius, edge, radius + margin + 1.0).is_out_of_bounds_by(WIDTH, HEIGHT, margin));
+        }
+    }
+}
+
+#[test]
+fn rocks_enter_from_just_above_the_top() {
+    for &radius in &RADII {
+        let mut rock = actor(radius, (0.0, 0.0), 0.0);
+        rock.pos.y = rock.entry_height(HEIGHT);
+        assert_eq!(rock.pos.y - radius, HEIGHT / 2.0);
+        // Not culled before it had a chance to move in, even without a margin.
+        assert!(!rock.is_out_of_bounds(WIDTH, HEIGHT));
+        rock.wrap_position(WIDTH, HEIGHT);
+        assert_eq!(rock.pos.y - radius, HEIGHT / 2.0);
+    }
+}
+
+#[test]
+fn a_step_across_an_edge_is_still_a_step_away() {
+    for &radius in &RADII {
+        for &edge in EDGES.iter().chain(&CORNERS) {
+            let before = actor(radius, edge, radius - 1.0);
+            let step = Vector2::new(edge.0, edge.1) * 3.0;
+            let mut after = before.clone();
+            after.pos += step;
+            after.wrap_position(WIDTH, HEIGHT);
+            assert!((after.pos - before.pos).norm() > WIDTH / 2.0, "radius {} at {:?} didn't wrap", radius, edge);
+
+            let (width, height) = after.wrap_period(WIDTH, HEIGHT);
+            let delta = toroidal_delta(&after.pos, &before.pos, width, height);
+            assert!((delta - step).norm() < 1e-3, "radius {} at {:?}: {:?}", radius, edge, delta);
+            let distance = toroidal_distance(&before.pos, &after.pos, width, height);
+            assert!((distance - step.norm()).abs() < 1e-3);
+        }
+    }
+}
+
+#[test]
+fn the_shorter_way_wins() {
+    let radius = RADII[1];
+    let (width, height) = actor(radius, (0.0, 0.0), 0.0).wrap_period(WIDTH, HEIGHT);
+    assert_eq!((width, height), (WIDTH + 2.0 * radius, HEIGHT + 2.0 * radius));
+
+    // Mid-field the direct way is shorter.
+    let a = Vector2::new(-100.0, 50.0);
+    let b = Vector2::new(100.0, -50.0);
+    assert_eq!(toroidal_delta(&a, &b, width, height), a - b);
+
+    // Hugging opposite edges, across the edge is shorter, and that's counted around the
+    // wrap period. The world size would leave the actor's whole hitbox out.
+    let left = Vector2::new(-width / 2.0 + 10.0, 0.0);
+    let right = Vector2::new(width / 2.0 - 10.0, 0.0);
+    assert!((toroidal_delta(&left, &right, width, height) - Vector2::new(20.0, 0.0)).norm() < 1e-3);
+    assert!((toroidal_delta(&right, &left, width, height) - Vector2::new(-20.0, 0.0)).norm() < 1e-3);
+    assert!((toroidal_distance(&left, &right, WIDTH, HEIGHT) - (2.0 * radius - 20.0)).abs() < 1e-3);
+
+    // In a corner both axes go across.
+    let bottom_left = Vector2::new(-width / 2.0 + 10.0, -height / 2.0 + 5.0);
+    let top_right = Vector2::new(width / 2.0 - 10.0, height / 2.0 - 5.0);
+    assert!((toroidal_delta(&bottom_left, &top_right, width, height) - Vector2::new(20.0, 10.0)).norm() < 1e-3);
+
+    // Exactly half a period apart either way is as short.
+    let half = toroidal_delta(&Vector2::new(width / 2.0, 0.0), &Vector2::new(0.0, 0.0), width, height);
+    assert!((half.x.abs() - width / 2.0).abs() < 1e-3);
+
+    // A world without a size doesn't wrap.
+    assert_eq!(toroidal_delta(&left, &right, 0.0, 0.0), left - right);
+}
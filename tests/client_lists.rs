@@ -0,0 +1,60 @@
+//! Clients reconcile their actor lists in place, so after a while snapshots stop
+//! allocating: the lists keep their buffers however the counts go up and down.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::receive;
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, SNAPSHOT_VERSION};
+
+const SNAPSHOTS: usize = 100;
+/// Snapshots in one cycle of rock counts, the lists have seen the largest after it.
+const CYCLE: usize = 20;
+
+/// A synthetic snapshot `i`: a rock count going up and down in cycles, every rock a bit further along.
+fn publish(server: &mut MainState, i: usize) {
+    let count = (i % CYCLE) * 5 + 1;
+    let tuning = server.tuning.clone();
+    server.rocks.truncate(count);
+    while server.rocks.len() < count {
+        server.rocks.push(Actor::create_rock(&tuning));
+    }
+    for (n, rock) in server.rocks.iter_mut().enumerate() {
+        rock.pos = Vector2::new(n as f32 * 10.0 - 250.0, i as f32);
+    }
+    server.curr_time += common::STEP;
+    NetFromServer::publish(server);
+}
+
+/// Capacity and address of the client's rock buffer after each snapshot.
+fn buffers(max_actors: usize) -> Vec<(usize, *const Actor)> {
+    let mut server = common::server(97, 1);
+    server.snapshot.max_actors = max_actors;
+    let mut client = common::client();
+    (0..SNAPSHOTS).map(|i| {
+        publish(&mut server, i);
+        receive(&server, &mut client, SNAPSHOT_VERSION);
+        (client.rocks.capacity(), client.rocks.as_ptr())
+    }).collect()
+}
+
+fn assert_settled(buffers: &[(usize, *const Actor)]) {
+    let settled = buffers[CYCLE];
+    for (i, buffer) in buffers.iter().enumerate().skip(CYCLE) {
+        assert_eq!(*buffer, settled, "snapshot {} reallocated the rocks", i);
+    }
+}
+
+#[test]
+fn full_snapshots_settle() {
+    let buffers = buffers(0);
+    assert!(buffers[CYCLE].0 >= (CYCLE - 1) * 5 + 1);
+    assert_settled(&buffers);
+}
+
+#[test]
+fn truncated_snapshots_settle() {
+    assert_settled(&buffers(30));
+}
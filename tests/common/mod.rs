@@ -0,0 +1,57 @@
+//! Fixtures shared by the integration tests: headless servers and clients, stepping them
+//! and passing snapshots between them. Each test file only uses some of them.
+#![allow(dead_code)]
+
+use rust_blaster::codec::BincodeCodec;
+use rust_blaster::game_structs::{MainState, NetRole};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::VersionedSnapshot;
+
+/// Size of the square window the headless games are made for.
+pub const SCREEN: u32 = 1080;
+/// One frame at 144 fps.
+pub const STEP: f32 = 1.0 / 144.0;
+
+/// A refereeing server without a local player, seeded with `seed`, with `players` remote
+/// players seated.
+pub fn server(seed: u64, players: usize) -> MainState {
+    let options = LaunchOptions { seed: Some(seed), local_play: false, ..LaunchOptions::default() };
+    server_with(&options, players)
+}
+
+/// A server launched with `options`, with `players` remote players seated after whoever
+/// plays locally.
+pub fn server_with(options: &LaunchOptions, players: usize) -> MainState {
+    let mut state = MainState::headless(options, SCREEN, SCREEN);
+    for _ in 0..players {
+        state.add_player();
+    }
+    state
+}
+
+/// A client that hasn't heard from its server yet.
+pub fn client() -> MainState {
+    let options = LaunchOptions { role: NetRole::Client, local_play: false, ..LaunchOptions::default() };
+    MainState::headless(&options, SCREEN, SCREEN)
+}
+
+/// One server frame, the clock moving with it.
+pub fn step(state: &mut MainState) {
+    state.curr_time += STEP;
+    state.step_server(STEP);
+}
+
+/// Runs the server for `seconds`, in frames.
+pub fn run(state: &mut MainState, seconds: f32) {
+    for _ in 0..(seconds / STEP).round() as u32 {
+        step(state);
+    }
+}
+
+/// Hands the server's last published snapshot, in `version`'s layout, to `client`.
+pub fn receive(server: &MainState, client: &mut MainState, version: u32) {
+    let bytes = server.snapshot.bytes(version);
+    VersionedSnapshot::decode(version, &mut BincodeCodec, &mut &bytes[..])
+        .expect("Failed to deserialize.")
+        .update_main_state(client);
+}
@@ -0,0 +1,56 @@
+//! Daily challenge attempts are written by the run log's thread, never the one holding the
+//! game state, and the day's results come back as a toast.
+use rust_blaster::highscores::HighScores;
+use rust_blaster::hud::Toasts;
+use rust_blaster::stats::{DailyAttempt, RunLog};
+use rust_blaster::strings::Strings;
+
+use std::time::{Duration, Instant};
+
+const DATE: &str = "2026-10-16";
+
+fn temp_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rust_blaster_daily_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_str().unwrap().to_string()
+}
+
+fn attempt(score: Option<f32>, toasts: &Toasts) -> DailyAttempt {
+    DailyAttempt { date: DATE.to_string(), score, toasts: toasts.sender(), strings: Strings::default() }
+}
+
+/// Ticks `toasts` until the writer thread's toast shows up, for a few seconds at most.
+fn wait_for_toast(toasts: &mut Toasts) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        toasts.tick(0.0);
+        if toasts.len() > 0 {
+            toasts.clear();
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn attempts_are_written_in_the_background_and_toasted() {
+    let runs = temp_file("runs.jsonl");
+    let scores = temp_file("highscores.json");
+    let log = RunLog::spawn(&runs, &scores);
+    let mut toasts = Toasts::new();
+
+    for &score in &[120.0, 80.0] {
+        log.record_daily(attempt(Some(score), &toasts));
+        assert!(wait_for_toast(&mut toasts), "no results after scoring {}", score);
+    }
+    let result = HighScores::daily_result(&scores, DATE).unwrap();
+    assert_eq!((result.best, result.last, result.attempts), (120.0, 80.0, 2));
+
+    // A run that doesn't count only shows the results.
+    log.record_daily(attempt(None, &toasts));
+    assert!(wait_for_toast(&mut toasts));
+    assert_eq!(HighScores::daily_result(&scores, DATE).unwrap().attempts, 2);
+
+    let _ = std::fs::remove_file(&scores);
+}
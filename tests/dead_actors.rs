@@ -0,0 +1,64 @@
+//! Actors killed this tick but not swept yet never reach clients.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION};
+use rust_blaster::well::WellPull;
+use rust_blaster::tuning::Tuning;
+
+fn rock(x: f32, score_value: i32, kill: bool) -> Actor {
+    let mut rock = Actor::create_rock(&Tuning::default());
+    rock.pos = Vector2::new(x, -100.0);
+    rock.score_value = score_value;
+    rock.kill = kill;
+    rock
+}
+
+/// A server halfway through a tick: live and dead actors side by side.
+fn server() -> MainState {
+    let mut state = common::server(9, 0);
+    state.rocks = vec![rock(-50.0, 3, true), rock(0.0, 5, false), rock(50.0, 7, true), rock(100.0, 9, false)];
+
+    let mut dead_shot = Actor::create_shot(&Tuning::default());
+    dead_shot.kill = true;
+    state.shots = vec![dead_shot, Actor::create_shot(&Tuning::default())];
+
+    let mut dead_well = Actor::create_well(WellPull { reach: 100.0, ..WellPull::default() }, &Tuning::default());
+    dead_well.kill = true;
+    let live_well = Actor::create_well(WellPull { reach: 500.0, ..WellPull::default() }, &Tuning::default());
+    state.wells = vec![dead_well, live_well];
+    state
+}
+
+fn client_view(version: u32) -> MainState {
+    let mut state = server();
+    state.snapshot.add_reader(version);
+    NetFromServer::publish(&mut state);
+
+    let mut client = common::client();
+    common::receive(&state, &mut client, version);
+    client
+}
+
+#[test]
+fn snapshots_leave_out_killed_actors() {
+    for &version in &[MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION] {
+        let client = client_view(version);
+        assert_eq!(client.rocks.len(), 2, "version {}", version);
+        assert_eq!(client.shots.len(), 1, "version {}", version);
+        assert!(client.rocks.iter().chain(&client.shots).chain(&client.wells).all(|actor| !actor.kill));
+    }
+    // Version 1 has no wells.
+    assert_eq!(client_view(SNAPSHOT_VERSION).wells.len(), 1);
+}
+
+#[test]
+fn live_actors_keep_their_own_values() {
+    let client = client_view(SNAPSHOT_VERSION);
+    let score_values: Vec<i32> = client.rocks.iter().map(|rock| rock.score_value).collect();
+    assert_eq!(score_values, vec![5, 9]);
+    assert_eq!(client.wells[0].pull.reach, 500.0);
+}
@@ -0,0 +1,62 @@
+//! The difficulty ramp adds up the multiplier over time, so changing it never makes the ramp jump.
+mod common;
+
+use common::{run, STEP};
+use rust_blaster::events::RestartReason;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, SNAPSHOT_VERSION};
+
+/// A refereeing server, nobody can die and restart the run.
+fn server() -> MainState {
+    common::server(3, 0)
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 0.1, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn doubling_the_multiplier_mid_run_has_no_cliff() {
+    let mut state = server();
+    run(&mut state, 60.0);
+    let before = state.difficulty_points;
+    assert_close(before, 60.0);
+
+    state.difficulty_mult *= 2.0;
+    common::step(&mut state);
+    let step = state.difficulty_points - before;
+    assert!(step <= 2.0 * STEP * 1.01, "one step after doubling moved the ramp by {}", step);
+
+    // From here on it climbs twice as fast, the first minute still counts once.
+    run(&mut state, 10.0);
+    assert_close(state.difficulty_points, before + 2.0 * STEP + 20.0);
+}
+
+#[test]
+fn presets_only_pick_the_difficulty_between_runs() {
+    let mut state = server();
+    assert!(state.pick_difficulty_preset(2));
+    assert_eq!(state.difficulty_mult, 2.0);
+
+    run(&mut state, 5.0);
+    assert!(!state.pick_difficulty_preset(3));
+    assert_eq!(state.difficulty_mult, 2.0);
+
+    // The game over restarts the field, the next run can be picked.
+    state.soft_restart(RestartReason::PlayerDeath { player: 0 });
+    assert!(state.pick_difficulty_preset(0));
+    assert_eq!(state.difficulty_mult, 0.5);
+    assert!(!state.pick_difficulty_preset(4));
+}
+
+#[test]
+fn clients_get_the_ramp_with_the_snapshot() {
+    let mut state = server();
+    run(&mut state, 5.0);
+
+    NetFromServer::publish(&mut state);
+    let mut client = common::client();
+    common::receive(&state, &mut client, SNAPSHOT_VERSION);
+
+    assert_eq!(client.difficulty_points, state.difficulty_points);
+}
@@ -0,0 +1,78 @@
+//! Popups, ticker lines and toasts stay within their capacities however many events pile
+//! up, and `clear` empties what belongs to the run being left.
+use ggez::graphics::Vector2;
+
+use rust_blaster::effects::{Effects, Ring, POPUP_CAPACITY};
+use rust_blaster::game_structs::Popup;
+use rust_blaster::hud::{Severity, TICKER_LINES, TOASTS_VISIBLE};
+
+const EVENTS: usize = 100_000;
+
+fn popup(y: f32) -> Popup {
+    Popup { pos: Vector2::new(0.0, y), time_left: 1.0 }
+}
+
+#[test]
+fn a_full_ring_drops_its_oldest() {
+    let mut ring = Ring::new(3);
+    for i in 0..5 {
+        ring.push(i);
+    }
+    assert_eq!(ring.len(), ring.capacity());
+    assert_eq!(ring.iter().cloned().collect::<Vec<_>>(), vec![2, 3, 4]);
+
+    let mut none = Ring::new(0);
+    none.push(1);
+    assert!(none.is_empty());
+}
+
+#[test]
+fn a_flood_of_events_stays_within_capacity() {
+    let mut effects = Effects::new();
+    let sender = effects.toasts.sender();
+    for i in 0..EVENTS {
+        effects.popups.push(popup(i as f32));
+        effects.ticker.push(format!("event {}", i));
+        effects.toasts.push(format!("toast {}", i), 10.0, Severity::Info);
+        sender.push(format!("from another thread {}", i), 10.0, Severity::Warning);
+        effects.banner.show(format!("banner {}", i));
+        if i % 100 == 0 {
+            effects.update(0.001);
+        }
+        assert!(effects.popups.len() <= POPUP_CAPACITY);
+        assert!(effects.ticker.len() <= TICKER_LINES);
+        assert!(effects.toasts.len() <= TOASTS_VISIBLE);
+    }
+    effects.update(0.001);
+
+    assert_eq!(effects.popups.len(), POPUP_CAPACITY);
+    assert_eq!(effects.ticker.len(), TICKER_LINES);
+    assert_eq!(effects.toasts.len(), TOASTS_VISIBLE);
+    // The newest ones are kept.
+    assert_eq!(effects.popups.iter().last().map(|p| p.pos.y.round()), Some((EVENTS - 1) as f32));
+}
+
+#[test]
+fn clear_keeps_only_the_ticker() {
+    let mut effects = Effects::new();
+    effects.popups.push(popup(0.0));
+    effects.ticker.push(String::from("P1 joined"));
+    effects.toasts.push(String::from("Saved"), 10.0, Severity::Info);
+    effects.update(0.0);
+
+    effects.clear();
+    assert!(effects.popups.is_empty());
+    assert_eq!(effects.toasts.len(), 0);
+    // The ticker follows the game's events, a hard restart clears it everywhere at once.
+    assert_eq!(effects.ticker.len(), 1);
+}
+
+#[test]
+fn popups_float_up_and_expire() {
+    let mut effects = Effects::new();
+    effects.popups.push(popup(0.0));
+    effects.update(0.5);
+    assert!(effects.popups.iter().next().unwrap().pos.y > 0.0);
+    effects.update(0.6);
+    assert!(effects.popups.is_empty());
+}
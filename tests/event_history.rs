@@ -0,0 +1,68 @@
+//! Snapshots carry the events players haven't acknowledged yet, a few at a time, and the
+//! history stays bounded when somebody never acks.
+mod common;
+
+use rust_blaster::events::{GameEvent, MAX_EVENTS_PER_SNAPSHOT, MAX_EVENT_HISTORY};
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetClientInput, NetFromServer, SNAPSHOT_VERSION};
+
+use std::time::Instant;
+
+/// A refereeing server with one remote player.
+fn server() -> MainState {
+    common::server(13, 1)
+}
+
+/// Publishes a snapshot and returns its bytes.
+fn publish(server: &mut MainState) -> Vec<u8> {
+    let acked = server.event_ack();
+    server.event_log.prune(acked);
+    NetFromServer::publish(server);
+    server.snapshot.bytes(SNAPSHOT_VERSION).to_vec()
+}
+
+#[test]
+fn a_slow_acker_gets_everything_in_batches() {
+    let mut server = server();
+    let mut client = common::client();
+    for points in 0..40 {
+        server.emit(GameEvent::Milestone(points));
+    }
+
+    let mut acked = 0;
+    for snapshot in 0..30 {
+        publish(&mut server);
+        common::receive(&server, &mut client, SNAPSHOT_VERSION);
+        // Every snapshot picks up right after the last ack, so nothing in between is skipped.
+        let expected = (acked + MAX_EVENTS_PER_SNAPSHOT as u64).min(40);
+        assert_eq!(client.last_event_seq, expected, "snapshot {}", snapshot);
+
+        // Only every third snapshot gets an input back to the server.
+        if snapshot % 3 == 2 {
+            NetClientInput::make_from_state(&mut client, snapshot)
+                .update_main_state(0, Instant::now(), &mut server);
+            acked = client.last_event_seq;
+        }
+    }
+
+    assert_eq!(client.last_event_seq, 40);
+    assert_eq!(server.event_ack(), Some(40));
+    publish(&mut server);
+    assert_eq!(server.event_log.len(), 0);
+}
+
+#[test]
+fn a_client_that_never_acks_doesnt_grow_the_snapshots() {
+    let mut server = server();
+    let mut sizes = Vec::new();
+    for points in 0..1000 {
+        server.emit(GameEvent::Milestone(points));
+        sizes.push(publish(&mut server).len());
+    }
+
+    assert!(server.event_log.len() <= MAX_EVENT_HISTORY);
+    // Growing until the first snapshot is full, flat from there on.
+    let full = sizes[MAX_EVENTS_PER_SNAPSHOT - 1];
+    assert!(sizes[MAX_EVENTS_PER_SNAPSHOT - 2] < full);
+    assert!(sizes[MAX_EVENTS_PER_SNAPSHOT..].iter().all(|&size| size == full));
+}
@@ -0,0 +1,121 @@
+//! The server replays a remote player's keys as they were held, instead of holding the
+//! last ones until the next packet arrives.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::STEP;
+use rust_blaster::actor::Vec2Serial;
+use rust_blaster::game_structs::{InputState, MainState, Player};
+use rust_blaster::input_timeline::{HeldInput, InputTimeline};
+use rust_blaster::net_structs::NetClientInput;
+use rust_blaster::tuning::Tuning;
+
+use std::time::Instant;
+
+/// A refereeing server with one remote player.
+fn server() -> MainState {
+    let mut state = common::server(19, 1);
+    state.players[0].actor.pos = Vector2::new(0.0, -300.0);
+    state
+}
+
+fn step(server: &mut MainState) {
+    server.rocks.clear();
+    common::step(server);
+}
+
+/// A client tapping right for 3 steps out of every 14 and sending every 7.
+struct Client {
+    ship: Player,
+    timeline: InputTimeline,
+    seq: u64,
+}
+
+impl Client {
+    fn new(server: &MainState) -> Client {
+        let mut ship = Player::create(&Tuning::default());
+        ship.actor.pos = server.players[0].actor.pos;
+        Client { ship, timeline: InputTimeline::default(), seq: 0 }
+    }
+
+    fn step(&mut self, step: u32) {
+        let input = InputState { right: step % 14 >= 4 && step % 14 <= 6, ..InputState::default() };
+        self.ship.input = input.clone();
+        self.ship.tick_input(STEP, &Tuning::default());
+        self.timeline.record(&input, STEP);
+    }
+
+    fn input(&mut self) -> NetClientInput {
+        let (timeline, client_time) = self.timeline.take();
+        self.seq += 1;
+        NetClientInput {
+            seq: self.seq,
+            input_state: self.ship.input.clone(),
+            final_position: Vec2Serial::from_vec(&self.ship.actor.pos),
+            shots_made: Vec::new(),
+            debug_commands: Vec::new(),
+            acked_event_seq: 0,
+            timeline,
+            client_time,
+        }
+    }
+}
+
+#[test]
+fn released_keys_are_released_on_the_server_too() {
+    let mut server = server();
+    let mut client = Client::new(&server);
+
+    let mut furthest = std::f32::MIN;
+    for i in 0..80 {
+        client.step(i);
+        // Every other packet goes out while the key is still held, holding it until the next one overshoots.
+        if i % 7 == 6 {
+            client.input().update_main_state(0, Instant::now(), &mut server);
+        }
+        step(&mut server);
+        furthest = furthest.max(server.players[0].actor.pos.x);
+    }
+    client.input().update_main_state(0, Instant::now(), &mut server);
+    for _ in 0..20 {
+        step(&mut server);
+    }
+
+    let ship = client.ship.actor.pos;
+    assert!(furthest <= ship.x + 0.01, "server ship got to {} while the client stopped at {}", furthest, ship.x);
+    assert!((server.players[0].actor.pos - ship).norm() < 0.01);
+}
+
+#[test]
+fn a_lost_packet_keeps_the_keys_held_before() {
+    let held = |right: bool, seconds: f32| HeldInput { input: InputState { right, ..InputState::default() }, seconds };
+    let mut timeline = InputTimeline::default();
+    timeline.extend(vec![held(true, 0.05)], 0.05, Vector2::new(25.0, 0.0));
+    // 0.05s of holding right never arrived, then the key was let go.
+    timeline.extend(vec![held(false, 0.05)], 0.15, Vector2::new(50.0, 0.0));
+
+    let steps = timeline.advance(0.2);
+    let right: f32 = steps.iter().filter(|s| s.input.right).map(|s| s.seconds).sum();
+    assert!((right - 0.1).abs() < 1e-4, "held right for {}", right);
+    let checkpoints: Vec<Vector2> = steps.iter().filter_map(|s| s.checkpoint).collect();
+    assert_eq!(checkpoints, vec![Vector2::new(25.0, 0.0), Vector2::new(50.0, 0.0)]);
+}
+
+#[test]
+fn a_backlog_skips_ahead_to_the_latest_position() {
+    let mut timeline = InputTimeline::default();
+    for i in 0..10 {
+        let held = HeldInput { input: InputState { up: true, ..InputState::default() }, seconds: 0.05 };
+        timeline.extend(vec![held], 0.05 * (i + 1) as f64, Vector2::new(0.0, i as f32));
+    }
+
+    assert!(timeline.behind() <= 0.25);
+    // Jumps straight to the newest position that was skipped, then replays the rest.
+    let first = timeline.advance(STEP);
+    assert_eq!(first[0].seconds, 0.0);
+    let skipped_to = first[0].checkpoint.expect("no jump").y;
+    assert!(skipped_to >= 3.0 && skipped_to < 9.0, "jumped to {}", skipped_to);
+    let rest = timeline.advance(1.0);
+    assert_eq!(rest.iter().filter_map(|s| s.checkpoint).last(), Some(Vector2::new(0.0, 9.0)));
+}
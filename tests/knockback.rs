@@ -0,0 +1,62 @@
+//! Destroyed rocks push the rocks around them away.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::step;
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::tuning::Tuning;
+
+fn rock(state: &MainState, x: f32) -> Actor {
+    let mut rock = Actor::create_rock(&state.tuning);
+    rock.pos = Vector2::new(x, 200.0);
+    rock
+}
+
+/// A shot resting on a rock at x = 0, with more rocks `others` further right.
+fn server(tuning: Tuning, others: &[f32]) -> MainState {
+    let options = LaunchOptions { seed: Some(29), practice: true, ..LaunchOptions::default() };
+    let mut state = common::server_with(&options, 0);
+    state.set_tuning(tuning);
+    state.rocks = vec![rock(&state, 0.0)];
+    for &x in others {
+        let other = rock(&state, x);
+        state.rocks.push(other);
+    }
+    let mut shot = Actor::create_shot(&state.tuning);
+    shot.pos = Vector2::new(0.0, 200.0);
+    shot.owner = Some(0);
+    state.shots = vec![shot];
+    state
+}
+
+#[test]
+fn nearby_rocks_are_pushed_away() {
+    let mut state = server(Tuning::default(), &[-40.0, 80.0, 400.0]);
+    step(&mut state);
+
+    // The hit rock is gone, the ones spawned this step come after the survivors.
+    let velocity = |i: usize| state.rocks[i].velocity;
+    assert!(velocity(0).x < 0.0 && velocity(0).y.abs() < 1e-4);
+    assert!(velocity(1).x > 0.0);
+    // Closer rocks get the harder push.
+    assert!(velocity(0).x.abs() > velocity(1).x);
+    assert_eq!(velocity(2), Vector2::new(0.0, 0.0), "out of reach");
+}
+
+#[test]
+fn the_push_is_capped() {
+    let tuning = Tuning { explosion_impulse: 1.0e7, max_physics_vel: 300.0, ..Tuning::default() };
+    let mut state = server(tuning, &[30.0]);
+    step(&mut state);
+    assert!((state.rocks[0].velocity.norm() - 300.0).abs() < 0.01);
+}
+
+#[test]
+fn no_impulse_no_push() {
+    let mut state = server(Tuning { explosion_impulse: 0.0, ..Tuning::default() }, &[30.0]);
+    step(&mut state);
+    assert_eq!(state.rocks[0].velocity, Vector2::new(0.0, 0.0));
+}
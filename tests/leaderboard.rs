@@ -0,0 +1,48 @@
+//! What a finished run is submitted as, and that nothing is submitted without a url.
+use rust_blaster::leaderboard::{Leaderboard, LeaderboardEntry, LeaderboardSetup};
+
+#[test]
+fn entries_keep_their_field_names() {
+    let entry = LeaderboardEntry {
+        name: String::from("anonymous"),
+        score: 812.0,
+        survival_time: 143.25,
+        difficulty: 1.5,
+        seed: 1234,
+        version: String::from("0.1.0"),
+        secret: String::from("hunter2"),
+    };
+    let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&entry).unwrap()).unwrap();
+    // Leaderboards parse these names, renaming a field breaks every one of them.
+    let expected = serde_json::json!({
+        "name": "anonymous",
+        "score": 812.0,
+        "survival_time": 143.25,
+        "difficulty": 1.5,
+        "seed": 1234,
+        "version": "0.1.0",
+        "secret": "hunter2",
+    });
+    assert_eq!(json, expected);
+}
+
+#[test]
+fn no_url_no_submissions() {
+    assert!(!Leaderboard::spawn(LeaderboardSetup::default()).is_enabled());
+    assert!(!Leaderboard::disabled().is_enabled());
+
+    // A setup file without the url, or with a blank one.
+    for json in &[r#"{"name": "server"}"#, r#"{"leaderboard_url": ""}"#, r#"{"leaderboard_url": "  "}"#] {
+        let setup: LeaderboardSetup = serde_json::from_str(json).unwrap();
+        let leaderboard = Leaderboard::spawn(setup);
+        assert!(!leaderboard.is_enabled(), "{}", json);
+        // Does nothing rather than fail.
+        leaderboard.submit(10.0, 5.0, 1.0, 7);
+    }
+}
+
+#[test]
+fn a_url_needs_the_online_feature() {
+    let setup = LeaderboardSetup { leaderboard_url: String::from("http://127.0.0.1:9/runs"), ..LeaderboardSetup::default() };
+    assert_eq!(Leaderboard::spawn(setup).is_enabled(), cfg!(feature = "online"));
+}
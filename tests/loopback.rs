@@ -0,0 +1,81 @@
+//! A real server and client talking over localhost: the client gets a seat, its shots
+//! reach the server and the server's actors reach the client.
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::{NetRole, StatePtr};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::networking;
+
+use std::time::{Duration, Instant};
+
+const SCREEN: u32 = 1080;
+const STEP: f32 = 1.0 / 144.0;
+/// Plenty for a handful of snapshots at net_setup.json's rate.
+const PATIENCE: Duration = Duration::from_secs(10);
+
+/// Steps both ends like their game loops would, until `done` holds or time runs out.
+fn run_until(server: &StatePtr, client: &StatePtr, mut done: impl FnMut(&StatePtr, &StatePtr) -> bool) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < PATIENCE {
+        {
+            let mut state = server.lock();
+            state.curr_time += STEP;
+            state.step_server(STEP);
+        }
+        {
+            let mut state = client.lock();
+            state.local_input.fire = true;
+            state.curr_time += STEP;
+            state.step_client(STEP);
+        }
+        if done(server, client) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    false
+}
+
+#[test]
+fn a_client_plays_on_a_real_server() {
+    let options = LaunchOptions { seed: Some(71), local_play: false, ..LaunchOptions::default() };
+    let mut server = StatePtr::headless(&options, SCREEN, SCREEN);
+    let planted = {
+        let mut state = server.lock();
+        let mut rock = Actor::create_rock(&state.tuning);
+        rock.pos = Vector2::new(300.0, -300.0);
+        rock.velocity = Vector2::new(0.0, 0.0);
+        state.rocks.push(rock);
+        state.issue_actor_ids();
+        state.rocks[0].id
+    };
+    let (snapshot_addr, input_addr) = networking::serve_headless(&mut server, "127.0.0.1", (0, 0)).expect("Failed to serve.");
+    assert_ne!(snapshot_addr.port(), 0);
+    assert_ne!(input_addr.port(), 0);
+
+    let options = LaunchOptions { role: NetRole::Client, local_play: false, ..LaunchOptions::default() };
+    let mut client = StatePtr::headless(&options, SCREEN, SCREEN);
+    let ports = (Some(snapshot_addr.port()), Some(input_addr.port()));
+    networking::connect_headless_client(&mut client, "127.0.0.1", ports).expect("Failed to connect.");
+    assert_eq!(client.lock().local_player_index, Some(0));
+
+    // The client holds fire, the server counts the shots that arrive with its inputs.
+    let shot_arrived = run_until(&server, &client, |server, _| {
+        let state = server.lock();
+        state.players.first().map_or(false, |p| p.stats.shots_fired > 0)
+            && state.shots.iter().any(|shot| shot.owner == Some(0))
+    });
+    assert!(shot_arrived, "no shot of the client's reached the server");
+
+    let synced = run_until(&server, &client, |server, client| {
+        let (server, client) = (server.lock(), client.lock());
+        let mine = client.rocks.iter().find(|rock| rock.id == planted);
+        client.players.len() == server.players.len()
+            && mine.map_or(false, |rock| (rock.pos - Vector2::new(300.0, -300.0)).norm() < 1.0)
+    });
+    assert!(synced, "the client never caught up with the server's players and rocks");
+
+    client.request_shutdown();
+    server.request_shutdown();
+}
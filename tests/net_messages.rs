@@ -0,0 +1,175 @@
+//! Every message on the wire comes back from bincode and JSON as it went in, and a JSON
+//! line that arrives in pieces is put back together.
+mod common;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use rust_blaster::actor::{Actor, Vec2Serial};
+use rust_blaster::codec::{BincodeCodec, Codec, DecodeError, JsonCodec, WireFormat};
+use rust_blaster::events::RestartReason;
+use rust_blaster::game_structs::{DebugCommand, InputState};
+use rust_blaster::input_timeline::HeldInput;
+use rust_blaster::net_structs::*;
+
+use std::io::{self, BufRead, Read};
+
+fn round_trips<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(sent: &T) {
+    let mut buf = Vec::new();
+    BincodeCodec.encode(sent, &mut buf);
+    assert_eq!(&BincodeCodec.decode::<T, _>(&mut &buf[..]).expect("Failed to deserialize."), sent);
+
+    let mut buf = Vec::new();
+    JsonCodec::default().encode(sent, &mut buf);
+    assert_eq!(buf.last(), Some(&b'\n'));
+    assert_eq!(&JsonCodec::default().decode::<T, _>(&mut &buf[..]).expect("Failed to deserialize."), sent);
+}
+
+fn input() -> NetClientInput {
+    let mut shot = Actor::create_shot(&rust_blaster::tuning::Tuning::default());
+    shot.pre_serialize();
+    NetClientInput {
+        seq: 41,
+        input_state: InputState { up: true, fire_pressed: true, ..InputState::default() },
+        final_position: Vec2Serial::from_floats(-12.5, 300.0),
+        shots_made: vec![shot],
+        debug_commands: vec![DebugCommand::ToggleClockFreeze, DebugCommand::ShiftClock(-5.0), DebugCommand::ClearRocks],
+        acked_event_seq: 7,
+        timeline: vec![HeldInput { input: InputState::default(), seconds: 0.016 }],
+        client_time: 12.75,
+    }
+}
+
+#[test]
+fn handshakes_round_trip() {
+    round_trips(&NetSnapshotHello::ours());
+    round_trips(&NetWireHello { wire_format: WireFormat::Json });
+    round_trips(&NetViewerHello { spectator: true });
+    let hello = NetPlayerHello { ship: 2, reconnect_token: std::u64::MAX, wire_format: WireFormat::Bincode, aim_assist: 0.35 };
+    round_trips(&hello);
+
+    let mut server = common::server(73, 2);
+    server.players[1].set_ship(1);
+    round_trips(&NetPlayerConnected::make(&server, 1, &hello, WireFormat::Json));
+    round_trips(&NetWelcome::make(&server, 1));
+    round_trips(&NetTuning::make(&server.tuning));
+    server.players.clear();
+    round_trips(&NetWelcome::make(&server, 0));
+}
+
+#[test]
+fn inputs_round_trip() {
+    round_trips(&input());
+    let empty = NetClientInput { shots_made: Vec::new(), debug_commands: Vec::new(), timeline: Vec::new(), ..input() };
+    round_trips(&empty);
+}
+
+#[test]
+fn snapshots_of_every_version_round_trip() {
+    let mut server = common::server(73, 2);
+    common::run(&mut server, 1.0);
+    server.soft_restart(RestartReason::AdminRestart);
+    common::run(&mut server, 0.5);
+    for version in MIN_SNAPSHOT_VERSION..=SNAPSHOT_VERSION {
+        server.snapshot.add_reader(version);
+    }
+    NetFromServer::publish(&mut server);
+
+    for version in MIN_SNAPSHOT_VERSION..=SNAPSHOT_VERSION {
+        let bytes = server.snapshot.bytes(version);
+        let decoded = VersionedSnapshot::decode(version, &mut BincodeCodec, &mut &bytes[..]).expect("Failed to deserialize.");
+
+        // Bincode back to the same bytes, JSON back to the same snapshot.
+        assert_eq!(NetFromServer::reencode(version, &bytes, &BincodeCodec).unwrap(), &bytes[..], "version {}", version);
+        let json = NetFromServer::reencode(version, &bytes, &JsonCodec::default()).unwrap();
+        let from_json = VersionedSnapshot::decode(version, &mut JsonCodec::default(), &mut &json[..]).expect("Failed to deserialize.");
+        assert_eq!(from_json, decoded, "version {}", version);
+    }
+}
+
+/// Hands out `chunks` one read at a time, an empty one standing for a read timeout.
+struct Trickle {
+    chunks: Vec<Vec<u8>>,
+    at: usize,
+}
+
+impl Trickle {
+    fn new(chunks: Vec<Vec<u8>>) -> Trickle {
+        Trickle { chunks: chunks.into_iter().rev().collect(), at: 0 }
+    }
+}
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Trickle {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.chunks.last().map_or(false, |chunk| chunk.is_empty()) {
+            self.chunks.pop();
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let at = self.at;
+        Ok(self.chunks.last().map_or(&[][..], |chunk| &chunk[at..]))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.at += amt;
+        if self.chunks.last().map_or(false, |chunk| self.at >= chunk.len()) {
+            self.chunks.pop();
+            self.at = 0;
+        }
+    }
+}
+
+#[test]
+fn a_json_line_cut_by_a_timeout_is_finished_by_the_next_read() {
+    let sent = input();
+    let mut line = Vec::new();
+    JsonCodec::default().encode(&sent, &mut line);
+
+    for cut in 1..line.len() {
+        let mut codec = JsonCodec::default();
+        let mut reader = Trickle::new(vec![line[..cut].to_vec(), Vec::new(), line[cut..].to_vec()]);
+        match codec.decode::<NetClientInput, _>(&mut reader) {
+            Err(DecodeError::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => (),
+            other => panic!("cut at {}: expected a timeout, got {:?}", cut, other),
+        }
+        let received: NetClientInput = codec.decode(&mut reader).expect("Failed to deserialize.");
+        assert_eq!(received, sent, "cut at {}", cut);
+    }
+}
+
+#[test]
+fn json_lines_are_read_one_at_a_time() {
+    let mut first = input();
+    first.seq = 1;
+    let mut second = input();
+    second.seq = 2;
+    let mut bytes = Vec::new();
+    let codec = JsonCodec::default();
+    codec.encode(&first, &mut bytes);
+    codec.encode(&second, &mut bytes);
+
+    // Both in one read, then the second split over three.
+    let split = bytes.len() - 10;
+    let mut reader = Trickle::new(vec![bytes[..split].to_vec(), Vec::new(), bytes[split..split + 5].to_vec(), bytes[split + 5..].to_vec()]);
+    let mut codec = JsonCodec::default();
+    assert_eq!(codec.decode::<NetClientInput, _>(&mut reader).unwrap(), first);
+    assert!(codec.decode::<NetClientInput, _>(&mut reader).is_err());
+    assert_eq!(codec.decode::<NetClientInput, _>(&mut reader).unwrap(), second);
+    // Then the stream ends.
+    match codec.decode::<NetClientInput, _>(&mut reader) {
+        Err(DecodeError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+        other => panic!("expected the end of the stream, got {:?}", other),
+    }
+}
@@ -0,0 +1,25 @@
+//! Exploding ships don't pick anything up.
+mod common;
+
+use common::{run, STEP};
+use rust_blaster::actor::Actor;
+
+#[test]
+fn dying_ships_leave_gems_and_power_ups_alone() {
+    let mut state = common::server(61, 1);
+    let pos = state.players[0].actor.pos;
+    state.players[0].dying_until = state.curr_time + 1.0;
+    let mut gem = Actor::create_gem(&state.tuning);
+    gem.pos = pos;
+    gem.score_value = 3;
+    let mut powerup = Actor::create_time_slow(&state.tuning);
+    powerup.pos = pos;
+    state.gems = vec![gem];
+    state.powerups = vec![powerup];
+    run(&mut state, STEP);
+
+    assert_eq!((state.gems.len(), state.powerups.len()), (1, 1));
+    assert_eq!(state.score, 0.0);
+    assert_eq!(state.time_slow_left, 0.0);
+    assert!(!state.play_sounds.play_pickup);
+}
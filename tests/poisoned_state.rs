@@ -0,0 +1,42 @@
+//! A panic while the state is locked pauses the game and cuts the network instead of
+//! taking every other thread down with it.
+mod common;
+
+use common::SCREEN;
+use rust_blaster::game_structs::StatePtr;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::NetFromServer;
+
+use std::sync::atomic::Ordering;
+
+#[test]
+fn the_game_keeps_running_after_a_panic_under_the_lock() {
+    let options = LaunchOptions { seed: Some(41), practice: true, ..LaunchOptions::default() };
+    let mut ptr = StatePtr::headless(&options, SCREEN, SCREEN);
+    let culprit = ptr.get_ref();
+    let panicked = std::thread::spawn(move || {
+        let mut state = culprit.lock();
+        state.players[99].active = true;
+    }).join();
+    assert!(panicked.is_err());
+    assert!(ptr.state.is_poisoned());
+
+    let cause = {
+        let state = ptr.lock();
+        assert!(state.paused_at.is_some());
+        state.recovered_from.clone().expect("no safe state after the panic")
+    };
+    assert!(cause.contains("tests/poisoned_state.rs"), "recovered from {}", cause);
+    assert!(ptr.shutdown.load(Ordering::Relaxed), "peers stay connected");
+
+    // Every thread can still lock and step the state, and the cause is only recorded once.
+    let other = ptr.get_ref();
+    std::thread::spawn(move || {
+        for _ in 0..60 {
+            common::step(&mut other.lock());
+        }
+    }).join().expect("the state stayed poisoned for other threads");
+    let mut state = ptr.lock();
+    NetFromServer::publish(&mut state);
+    assert_eq!(state.recovered_from, Some(cause));
+}
@@ -0,0 +1,56 @@
+//! Restarting after a game over keeps everyone seated, only the run starts over.
+mod common;
+
+use rust_blaster::events::RestartReason;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::launch_options::LaunchOptions;
+
+/// A server with a local player and two remote ones, a few seconds into the run.
+fn server() -> MainState {
+    let options = LaunchOptions { seed: Some(7), ..LaunchOptions::default() };
+    let mut state = common::server_with(&options, 2);
+    common::run(&mut state, 3.0);
+    state
+}
+
+fn seats(state: &MainState) -> Vec<(u32, bool)> {
+    state.players.iter().map(|p| (p.index, p.active)).collect()
+}
+
+#[test]
+fn soft_restart_keeps_the_players() {
+    let mut state = server();
+    let before = seats(&state);
+    let round = state.round;
+
+    state.soft_restart(RestartReason::AdminRestart);
+
+    assert_eq!(seats(&state), before);
+    assert_eq!(state.local_player_index, Some(0));
+    assert_eq!(state.round, round + 1);
+    assert_eq!(state.score, 0.0);
+    assert_eq!(state.difficulty_points, 0.0);
+    assert!(state.players.iter().all(|p| p.lives == state.game_setup.starting_lives));
+}
+
+#[test]
+fn hard_reset_keeps_the_players_and_drops_the_difficulty() {
+    let mut state = server();
+    let before = seats(&state);
+    let round = state.round;
+    state.difficulty_mult = 5.0;
+
+    state.hard_reset();
+
+    assert_eq!(seats(&state), before);
+    assert_eq!(state.local_player_index, Some(0));
+    assert_eq!(state.round, round + 1);
+    // Both remote players still double the launch difficulty.
+    assert_eq!(state.difficulty_mult, 4.0 * state.launch_difficulty);
+}
+
+#[test]
+fn launching_is_round_one() {
+    let state = common::server_with(&LaunchOptions::default(), 0);
+    assert_eq!(state.round, 1);
+}
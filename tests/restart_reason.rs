@@ -0,0 +1,63 @@
+//! Every run ends for a reason, and clients hear it.
+mod common;
+
+use common::STEP;
+use rust_blaster::codec::JsonCodec;
+use rust_blaster::events::{GameEvent, RestartReason};
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, SNAPSHOT_VERSION};
+use rust_blaster::strings::Strings;
+
+/// A refereeing server with two remote players.
+fn server() -> MainState {
+    common::server(47, 2)
+}
+
+/// The latest snapshot for a client on `version`, as JSON.
+fn snapshot_json(server: &mut MainState, version: u32) -> String {
+    server.snapshot.add_reader(version);
+    NetFromServer::publish(server);
+    let bytes = server.snapshot.bytes(version);
+    let json = NetFromServer::reencode(version, &bytes, &JsonCodec::default()).unwrap();
+    String::from_utf8(json).unwrap()
+}
+
+fn events(state: &MainState) -> Vec<GameEvent> {
+    state.event_log.unacked(None).into_iter().map(|record| record.event).collect()
+}
+
+#[test]
+fn the_player_who_died_ends_the_run() {
+    let mut state = server();
+    // P2's death animation on their last life just finished.
+    state.players[1].lives = 0;
+    state.players[1].dying_until = 0.5;
+    state.curr_time = 1.0;
+    state.step_server(STEP);
+
+    let reason = RestartReason::PlayerDeath { player: 1 };
+    assert!(events(&state).contains(&GameEvent::RunEnded { reason }), "{:?}", events(&state));
+    assert_eq!(reason.describe(&Strings::default()), "P2 was destroyed!");
+}
+
+#[test]
+fn a_reset_says_the_server_did_it() {
+    let mut state = server();
+    state.hard_reset();
+    let events = events(&state);
+    let reason = RestartReason::AdminRestart;
+    assert_eq!(&events[events.len() - 2..], &[
+        GameEvent::RunEnded { reason },
+        GameEvent::Restarted { round: 2, hard: true },
+    ]);
+    assert_eq!(reason.describe(&Strings::default()), "Server restarted the match");
+}
+
+#[test]
+fn clients_hear_the_reason_and_the_restart() {
+    let mut state = server();
+    state.soft_restart(RestartReason::AdminRestart);
+
+    let current = snapshot_json(&mut state, SNAPSHOT_VERSION);
+    assert!(current.contains("RunEnded") && current.contains("Restarted"), "{}", current);
+}
@@ -0,0 +1,72 @@
+//! Rocks that outstay `rock_lifetime` fade out and vanish, and clients see the fade.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::{receive, run, STEP};
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION};
+
+const OLD_X: f32 = -300.0;
+const FRESH_X: f32 = 300.0;
+
+/// A refereeing server with a resting rock half a second into its fade, and a fresh one.
+fn server() -> MainState {
+    let mut state = common::server(53, 0);
+    let lifetime = state.game_setup.rock_lifetime;
+    let mut old = Actor::create_rock(&state.tuning);
+    old.pos = Vector2::new(OLD_X, 0.0);
+    old.age = lifetime + 0.5;
+    let mut fresh = Actor::create_rock(&state.tuning);
+    fresh.pos = Vector2::new(FRESH_X, 0.0);
+    state.rocks = vec![old, fresh];
+    state
+}
+
+/// The resting rock placed at `x`, rocks spawned meanwhile are still falling from the top.
+fn rock_at(state: &MainState, x: f32) -> Option<&Actor> {
+    state.rocks.iter().find(|rock| (rock.pos.x - x).abs() < 1.0 && rock.pos.y.abs() < 1.0)
+}
+
+#[test]
+fn an_old_rock_fades_out_and_a_fresh_one_stays() {
+    let mut state = server();
+    run(&mut state, 0.25);
+    let fade = rock_at(&state, OLD_X).expect("gone before fading out").fade;
+    assert!(fade > 0.7 && fade < 0.8, "fade {}", fade);
+    assert_eq!(rock_at(&state, FRESH_X).unwrap().fade, 0.0);
+
+    run(&mut state, 0.5);
+    assert!(rock_at(&state, OLD_X).is_none());
+    assert!(rock_at(&state, FRESH_X).is_some());
+    // Expiring isn't destroying: no gem, no points.
+    assert!(state.gems.is_empty());
+    assert_eq!(state.score, 0.0);
+}
+
+#[test]
+fn no_lifetime_no_fading() {
+    let mut state = server();
+    state.game_setup.rock_lifetime = 0.0;
+    run(&mut state, 1.0);
+    assert_eq!(rock_at(&state, OLD_X).unwrap().fade, 0.0);
+}
+
+#[test]
+fn clients_see_the_fade() {
+    let mut state = server();
+    run(&mut state, STEP);
+    state.snapshot.add_reader(MIN_SNAPSHOT_VERSION);
+    NetFromServer::publish(&mut state);
+
+    let faded = |version: u32| {
+        let mut client = common::client();
+        receive(&state, &mut client, version);
+        rock_at(&client, OLD_X).unwrap().fade
+    };
+    let fade = rock_at(&state, OLD_X).unwrap().fade;
+    assert_eq!(faded(SNAPSHOT_VERSION), fade);
+    // Version 1 has no fades, rocks there just don't fade.
+    assert_eq!(faded(MIN_SNAPSHOT_VERSION), 0.0);
+}
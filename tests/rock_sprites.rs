@@ -0,0 +1,31 @@
+//! Rock sprites come from the run's seed and reach clients with the rocks.
+mod common;
+
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, SNAPSHOT_VERSION};
+
+fn sprites(state: &MainState) -> Vec<u8> {
+    state.rocks.iter().filter(|rock| !rock.kill).map(|rock| rock.sprite()).collect()
+}
+
+/// A few seconds of rocks from `seed`.
+fn server(seed: u64) -> MainState {
+    let mut state = common::server(seed, 1);
+    common::run(&mut state, 5.0);
+    assert!(state.rocks.len() > 1, "no rocks spawned");
+    state
+}
+
+#[test]
+fn the_same_seed_spawns_the_same_sprites() {
+    assert_eq!(sprites(&server(31)), sprites(&server(31)));
+}
+
+#[test]
+fn clients_draw_the_servers_sprites() {
+    let mut server = server(31);
+    NetFromServer::publish(&mut server);
+    let mut client = common::client();
+    common::receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(sprites(&client), sprites(&server));
+}
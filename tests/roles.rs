@@ -0,0 +1,87 @@
+//! Every role runs without a ship of its own where it has none: a server playing along,
+//! a refereeing server, a client that hasn't been seated yet and a spectator.
+mod common;
+
+use common::{receive, run, SCREEN, STEP};
+use rust_blaster::actor::Vec2Serial;
+use rust_blaster::game_structs::{MainState, NetRole};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::{NetClientInput, NetFromServer, SNAPSHOT_VERSION};
+
+fn headless(role: NetRole, local_play: bool) -> MainState {
+    let options = LaunchOptions { role, local_play, seed: Some(89), ..LaunchOptions::default() };
+    MainState::headless(&options, SCREEN, SCREEN)
+}
+
+#[test]
+fn a_server_steers_its_own_ship() {
+    let mut server = headless(NetRole::Server, true);
+    assert_eq!(server.local_player_index, Some(0));
+    assert_eq!(server.players.len(), 1);
+
+    let start = server.players[0].actor.pos;
+    server.local_input.up = true;
+    run(&mut server, 0.5);
+    assert_ne!(server.players[0].actor.pos, start);
+}
+
+#[test]
+fn a_refereeing_server_has_no_ship() {
+    let mut server = headless(NetRole::Server, false);
+    assert_eq!(server.local_player_index, None);
+    assert!(server.players.is_empty());
+
+    // Held keys have nobody to steer.
+    server.local_input.fire = true;
+    server.local_input.up = true;
+    run(&mut server, 0.5);
+    assert!(server.players.is_empty());
+    assert!(server.shots.is_empty());
+}
+
+#[test]
+fn a_client_sends_inputs_before_it_has_a_ship() {
+    let mut client = headless(NetRole::Client, false);
+    assert_eq!(client.local_player_index, None);
+    client.local_input.fire = true;
+    client.step_client(STEP);
+    let input = NetClientInput::make_from_state(&mut client, 0);
+    assert_eq!(input.final_position, Vec2Serial::default());
+    assert!(input.shots_made.is_empty());
+
+    // Seated, but the snapshot with its ship hasn't arrived.
+    client.local_player_index = Some(2);
+    client.step_client(STEP);
+    let input = NetClientInput::make_from_state(&mut client, 1);
+    assert_eq!(input.final_position, Vec2Serial::default());
+
+    // Once it has, the ship is there to report.
+    let mut server = common::server(89, 3);
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    client.local_input.up = true;
+    for _ in 0..20 {
+        client.step_client(STEP);
+    }
+    let input = NetClientInput::make_from_state(&mut client, 2);
+    assert_eq!(input.final_position, Vec2Serial::from_vec(&client.players[2].actor.pos));
+    assert_ne!(input.final_position, Vec2Serial::default());
+}
+
+#[test]
+fn a_spectator_only_watches() {
+    let mut server = common::server(89, 2);
+    NetFromServer::publish(&mut server);
+
+    let mut spectator = headless(NetRole::Spectator, false);
+    assert_eq!(spectator.local_player_index, None);
+    receive(&server, &mut spectator, SNAPSHOT_VERSION);
+    assert_eq!(spectator.players.len(), 2);
+
+    spectator.local_input.fire = true;
+    for _ in 0..10 {
+        spectator.step_client(STEP);
+    }
+    assert!(spectator.local_shots_made.is_empty());
+    assert_eq!(spectator.players.len(), 2);
+}
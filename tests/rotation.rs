@@ -0,0 +1,42 @@
+//! Turning towards the server's facing goes the shorter way around, across the wrap
+//! at PI when that's shorter.
+use std::f32::consts::PI;
+
+use rust_blaster::actor::angle_diff;
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+}
+
+#[test]
+fn across_the_wrap_is_a_small_step() {
+    // 3.1 and -3.1 are 0.083 apart through PI, not 6.2 through 0.
+    let step = 2.0 * PI - 6.2;
+    assert!(close(angle_diff(3.1, -3.1), step), "{}", angle_diff(3.1, -3.1));
+    assert!(close(angle_diff(-3.1, 3.1), -step), "{}", angle_diff(-3.1, 3.1));
+}
+
+#[test]
+fn a_full_turn_is_no_turn() {
+    assert!(close(angle_diff(0.0, 2.0 * PI), 0.0));
+    assert!(close(angle_diff(2.0 * PI, 0.0), 0.0));
+    assert!(close(angle_diff(1.0, 1.0 + 4.0 * PI), 0.0));
+    assert_eq!(angle_diff(0.5, 0.5), 0.0);
+}
+
+#[test]
+fn the_shorter_way_wins() {
+    // Mid-range it's the plain difference.
+    assert!(close(angle_diff(0.25, 1.0), 0.75));
+    assert!(close(angle_diff(1.0, 0.25), -0.75));
+    // 0.1 to 6.2 is a small step back, not most of a turn forward.
+    assert!(close(angle_diff(0.1, 6.2), 6.1 - 2.0 * PI));
+    assert!(close(angle_diff(6.2, 0.1), 2.0 * PI - 6.1));
+    for &(from, to) in &[(0.0, 3.0), (-2.0, 2.0), (5.0, -5.0), (0.1, 6.2), (3.1, -3.1)] {
+        let diff = angle_diff(from, to);
+        assert!(diff > -PI && diff <= PI, "{} to {}: {}", from, to, diff);
+    }
+    // Half a turn either way is as short, it comes out as PI.
+    assert!(close(angle_diff(0.0, PI), PI));
+    assert!(close(angle_diff(0.0, -PI), PI));
+}
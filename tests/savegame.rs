@@ -0,0 +1,70 @@
+//! A saved run comes back as it was left, down to what each rock is worth and how far
+//! it has faded.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::savegame::SaveGame;
+use rust_blaster::well::WellPull;
+
+/// An offline practice run, the only kind that can be saved.
+fn offline() -> MainState {
+    let options = LaunchOptions { seed: Some(67), practice: true, ..LaunchOptions::default() };
+    common::server_with(&options, 0)
+}
+
+/// Saves `state` to a file of its own and loads it into a fresh game.
+fn save_and_load(state: &mut MainState, name: &str) -> MainState {
+    let path = std::env::temp_dir().join(format!("rust_blaster_{}_{}.bin", name, std::process::id()));
+    let path = path.to_str().unwrap();
+    SaveGame::capture(state).write(path).expect("Failed to save.");
+    let save = SaveGame::read(path).expect("Failed to load.");
+    let _ = std::fs::remove_file(path);
+
+    let mut loaded = offline();
+    save.restore(&mut loaded);
+    loaded
+}
+
+#[test]
+fn rocks_and_gems_keep_their_worth() {
+    let mut state = offline();
+    let mut rock = Actor::create_rock(&state.tuning);
+    rock.pos = Vector2::new(100.0, 200.0);
+    rock.score_value = 3;
+    let mut gem = Actor::create_gem(&state.tuning);
+    gem.pos = Vector2::new(-100.0, 0.0);
+    gem.score_value = 2;
+    state.rocks = vec![rock];
+    state.gems = vec![gem];
+
+    let loaded = save_and_load(&mut state, "worth");
+    assert_eq!(loaded.rocks[0].score_value, 3);
+    assert_eq!(loaded.gems[0].score_value, 2);
+    assert_eq!(loaded.rocks[0].pos, Vector2::new(100.0, 200.0));
+}
+
+#[test]
+fn lifetimes_carry_on_after_loading() {
+    let mut state = offline();
+    let lifetime = state.game_setup.rock_lifetime;
+    let mut rock = Actor::create_rock(&state.tuning);
+    rock.age = lifetime + 0.1;
+    rock.fade = 0.4;
+    let mut shot = Actor::create_shot(&state.tuning);
+    shot.age = 0.25;
+    let mut well = Actor::create_well(WellPull::default(), &state.tuning);
+    well.age = 3.0;
+    state.rocks = vec![rock];
+    state.shots = vec![shot];
+    state.wells = vec![well];
+
+    let loaded = save_and_load(&mut state, "ages");
+    assert_eq!(loaded.rocks[0].age, lifetime + 0.1);
+    assert_eq!(loaded.rocks[0].fade, 0.4);
+    assert_eq!(loaded.shots[0].age, 0.25);
+    assert_eq!(loaded.wells[0].age, 3.0);
+}
@@ -0,0 +1,51 @@
+//! Ships on top of each other drift apart gently, unless one of them is still shielded.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::STEP;
+use rust_blaster::game_structs::MainState;
+
+/// A refereeing server with two ships a few pixels apart.
+fn server() -> MainState {
+    let mut state = common::server(4, 2);
+    state.players[0].actor.pos = Vector2::new(-2.0, -300.0);
+    state.players[1].actor.pos = Vector2::new(2.0, -300.0);
+    state
+}
+
+fn distance(state: &MainState) -> f32 {
+    (state.players[0].actor.pos - state.players[1].actor.pos).norm()
+}
+
+#[test]
+fn overlapping_ships_drift_apart_without_jumping() {
+    let mut state = server();
+    let touching = state.players[0].actor.bbox_size + state.players[1].actor.bbox_size;
+
+    for _ in 0..2 * 144 {
+        let before = state.players[0].actor.pos;
+        state.rocks.clear();
+        common::step(&mut state);
+        let moved = (state.players[0].actor.pos - before).norm();
+        assert!(moved <= 200.0 * STEP * 1.01, "a ship moved {} in one step", moved);
+    }
+
+    assert!(distance(&state) > touching - 0.5, "still {} apart", distance(&state));
+    // Pushed apart along the line between them, nobody got flung vertically.
+    assert!(state.players[0].actor.pos.x < -2.0);
+    assert!(state.players[1].actor.pos.x > 2.0);
+    assert!((state.players[0].actor.pos.y + 300.0).abs() < 0.01);
+}
+
+#[test]
+fn shielded_ships_stay_put() {
+    let mut state = server();
+    state.players[1].hit_flash = 1.0;
+
+    for _ in 0..72 {
+        state.rocks.clear();
+        common::step(&mut state);
+    }
+    assert_eq!(distance(&state), 4.0);
+}
@@ -0,0 +1,59 @@
+//! Shots a client submits belong to the player whose connection sent them, whatever the client claims.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::{Actor, Vec2Serial};
+use rust_blaster::codec::{BincodeCodec, Codec};
+use rust_blaster::game_structs::{InputState, MainState};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::NetClientInput;
+use rust_blaster::tuning::Tuning;
+
+use std::time::Instant;
+
+/// A server with a local player and two remote ones.
+fn server() -> MainState {
+    let options = LaunchOptions { seed: Some(5), ..LaunchOptions::default() };
+    common::server_with(&options, 2)
+}
+
+/// An input carrying one shot that claims to be P1's.
+fn input_claiming_p1() -> NetClientInput {
+    let mut shot = Actor::create_shot(&Tuning::default());
+    shot.pos = Vector2::new(10.0, -200.0);
+    shot.owner = Some(0);
+    shot.pre_serialize();
+    NetClientInput {
+        seq: 0,
+        input_state: InputState::default(),
+        final_position: Vec2Serial::default(),
+        shots_made: vec![shot],
+        debug_commands: Vec::new(),
+        acked_event_seq: 0,
+        timeline: Vec::new(),
+        client_time: 0.0,
+    }
+}
+
+#[test]
+fn submitted_shots_belong_to_the_sender() {
+    let mut state = server();
+    input_claiming_p1().update_main_state(2, Instant::now(), &mut state);
+
+    assert_eq!(state.shots.len(), 1);
+    assert_eq!(state.shots[0].owner, Some(2));
+    assert_eq!(state.players[2].stats.shots_fired, 1);
+    assert_eq!(state.players[0].stats.shots_fired, 0);
+}
+
+#[test]
+fn owners_are_stamped_after_the_wire_too() {
+    let mut buf = Vec::new();
+    BincodeCodec.encode(&input_claiming_p1(), &mut buf);
+    let received: NetClientInput = BincodeCodec.decode(&mut &buf[..]).expect("Failed to deserialize.");
+
+    let mut state = server();
+    received.update_main_state(2, Instant::now(), &mut state);
+    assert_eq!(state.shots[0].owner, Some(2));
+}
@@ -0,0 +1,26 @@
+//! Every player's shots sound a little different, and no two volleys exactly alike.
+use rust_blaster::sfx::{shot_pitch, VOLLEY_JITTER};
+
+#[test]
+fn the_first_players_get_distinct_pitches_within_ten_percent() {
+    let pitches: Vec<f32> = (0..5).map(|player| shot_pitch(player, 0.0)).collect();
+    assert_eq!(pitches[0], 1.0, "a lone player hears the sound as recorded");
+    for (i, a) in pitches.iter().enumerate() {
+        assert!((a - 1.0).abs() <= 0.1 + 1e-6, "P{} at {}", i + 1, a);
+        for b in &pitches[i + 1..] {
+            assert!((a - b).abs() > 0.01);
+        }
+    }
+    // Neighbours land on opposite sides of the base pitch.
+    assert!(pitches[1] > 1.0 && pitches[2] < 1.0);
+}
+
+#[test]
+fn jitter_stays_close_to_the_players_pitch() {
+    for player in 0..8 {
+        let base = shot_pitch(player, 0.0);
+        assert!((shot_pitch(player, 1.0) - base - VOLLEY_JITTER).abs() < 1e-6);
+        assert!((shot_pitch(player, -1.0) - base + VOLLEY_JITTER).abs() < 1e-6);
+        assert_eq!(shot_pitch(player, 5.0), shot_pitch(player, 1.0));
+    }
+}
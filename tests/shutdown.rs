@@ -0,0 +1,27 @@
+//! Quitting stops the server's listeners promptly, even when nobody is connecting.
+use rust_blaster::game_structs::StatePtr;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::networking;
+
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+/// The listeners poll for the flag a few times within this.
+const BOUND: Duration = Duration::from_secs(1);
+
+#[test]
+fn listeners_let_go_of_their_ports_after_shutdown() {
+    let options = LaunchOptions { seed: Some(83), local_play: false, ..LaunchOptions::default() };
+    let mut server = StatePtr::headless(&options, 1080, 1080);
+    let (snapshot_addr, input_addr) = networking::serve_headless(&mut server, "127.0.0.1", (0, 0)).expect("Failed to serve.");
+    assert!(TcpListener::bind(snapshot_addr).is_err(), "nothing is listening on {}", snapshot_addr);
+    assert!(TcpListener::bind(input_addr).is_err(), "nothing is listening on {}", input_addr);
+
+    server.request_shutdown();
+    let started = Instant::now();
+    // A listener is dropped once its accept loop returns, then its port is free again.
+    while TcpListener::bind(snapshot_addr).is_err() || TcpListener::bind(input_addr).is_err() {
+        assert!(started.elapsed() < BOUND, "still listening {:?} after the shutdown", started.elapsed());
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
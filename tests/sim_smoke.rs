@@ -0,0 +1,22 @@
+//! Runs a headless server through the library API and hands its snapshot to a client.
+mod common;
+
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::{NetFromServer, SNAPSHOT_VERSION};
+
+#[test]
+fn server_ticks_and_client_reads_its_snapshot() {
+    let options = LaunchOptions { seed: Some(7), ..LaunchOptions::default() };
+    let mut server = common::server_with(&options, 1);
+    common::run(&mut server, 2.0);
+
+    NetFromServer::publish(&mut server);
+    assert!(!server.snapshot.bytes(SNAPSHOT_VERSION).is_empty());
+
+    let mut client = common::client();
+    common::receive(&server, &mut client, SNAPSHOT_VERSION);
+
+    assert_eq!(client.players.len(), server.players.len());
+    assert_eq!(client.rocks.len(), server.rocks.len());
+    assert_eq!(client.score, server.score);
+}
@@ -0,0 +1,74 @@
+//! Every kind of actor in a snapshot lands in its own list on the client, and an actor
+//! that has no list there is counted instead of vanishing.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use rust_blaster::actor::{Actor, ActorType};
+use rust_blaster::codec::JsonCodec;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, VersionedSnapshot, SNAPSHOT_VERSION};
+use rust_blaster::well::WellPull;
+
+/// One actor of every kind, each at its own spot.
+fn server() -> MainState {
+    let mut state = common::server(37, 0);
+    let at = |mut actor: Actor, x: f32| {
+        actor.pos = Vector2::new(x, 100.0);
+        actor
+    };
+    state.rocks = vec![at(Actor::create_rock(&state.tuning), -200.0)];
+    state.shots = vec![at(Actor::create_shot(&state.tuning), -100.0)];
+    state.gems = vec![at(Actor::create_gem(&state.tuning), 0.0)];
+    state.powerups = vec![at(Actor::create_time_slow(&state.tuning), 100.0)];
+    state.wells = vec![at(Actor::create_well(WellPull::default(), &state.tuning), 200.0)];
+    NetFromServer::publish(&mut state);
+    state
+}
+
+/// Kind and x position of everything in each of the client's lists.
+fn lists(state: &MainState) -> Vec<Vec<(ActorType, f32)>> {
+    let kinds = |list: &[Actor]| -> Vec<(ActorType, f32)> { list.iter().map(|a| (a.tag.clone(), a.pos.x)).collect() };
+    vec![kinds(&state.rocks), kinds(&state.shots), kinds(&state.gems), kinds(&state.powerups), kinds(&state.wells)]
+}
+
+#[test]
+fn every_kind_lands_in_its_own_list() {
+    let server = server();
+    let mut client = common::client();
+    common::receive(&server, &mut client, SNAPSHOT_VERSION);
+
+    assert_eq!(lists(&client), lists(&server));
+    assert_eq!(client.net_stats.unexpected_actors, 0);
+}
+
+#[test]
+fn a_player_in_the_actor_list_is_counted_and_skipped() {
+    let server = server();
+    let bytes = server.snapshot.bytes(SNAPSHOT_VERSION);
+    let json = NetFromServer::reencode(SNAPSHOT_VERSION, &bytes, &JsonCodec::default()).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains("\"tag\":\"Rock\""));
+    let broken = json.replacen("\"tag\":\"Rock\"", "\"tag\":\"Player\"", 1);
+
+    let mut client = common::client();
+    VersionedSnapshot::decode(SNAPSHOT_VERSION, &mut JsonCodec::default(), &mut broken.as_bytes())
+        .expect("Failed to deserialize.")
+        .update_main_state(&mut client);
+
+    assert_eq!(client.net_stats.unexpected_actors, 1);
+    let mut expected = lists(&server);
+    expected[0].clear();
+    assert_eq!(lists(&client), expected);
+}
+
+#[test]
+fn a_server_never_mixes_up_the_lists() {
+    let result = std::panic::catch_unwind(|| {
+        let mut state = server();
+        let shot = Actor::create_shot(&state.tuning);
+        state.rocks.push(shot);
+        NetFromServer::publish(&mut state);
+    });
+    assert_eq!(result.is_err(), cfg!(debug_assertions));
+}
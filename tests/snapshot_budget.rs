@@ -0,0 +1,206 @@
+//! A snapshot over `max_actors` sends what matters most, far rocks take turns, and
+//! clients keep whatever a truncated snapshot leaves out.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::receive;
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::{NetFromServer, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION};
+use rust_blaster::well::WellPull;
+
+/// Farther than a view from the player at the origin, in a world three views wide.
+const FAR: f32 = 1400.0;
+
+/// A large world with one player at the origin.
+fn server() -> MainState {
+    let options = LaunchOptions { seed: Some(59), local_play: false, world_scale: 3.0, ..LaunchOptions::default() };
+    let mut state = common::server_with(&options, 1);
+    state.players[0].actor.pos = Vector2::new(0.0, 0.0);
+    state
+}
+
+fn rock(state: &MainState, x: f32, y: f32) -> Actor {
+    let mut rock = Actor::create_rock(&state.tuning);
+    rock.pos = Vector2::new(x, y);
+    rock.velocity = Vector2::new(0.0, 0.0);
+    rock
+}
+
+fn shot(state: &MainState, x: f32) -> Actor {
+    let mut shot = Actor::create_shot(&state.tuning);
+    shot.pos = Vector2::new(x, 50.0);
+    shot
+}
+
+/// Where the client shows its rocks, left to right.
+fn shown(list: &[Actor]) -> Vec<f32> {
+    let mut xs: Vec<f32> = list.iter().filter(|a| !a.kill).map(|a| a.pos.x.round()).collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    xs
+}
+
+#[test]
+fn near_rocks_go_first_then_shots() {
+    let mut server = server();
+    server.rocks = vec![
+        rock(&server, 400.0, 0.0),
+        rock(&server, 300.0, 0.0),
+        rock(&server, FAR, 0.0),
+        rock(&server, 200.0, 0.0),
+        rock(&server, 100.0, 0.0),
+    ];
+    server.shots = vec![shot(&server, -100.0), shot(&server, -200.0)];
+    // The player and four actors: one of them is kept for the far rock.
+    server.snapshot.max_actors = 5;
+    NetFromServer::publish(&mut server);
+
+    let mut client = common::client();
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.players.len(), 1);
+    assert_eq!(shown(&client.rocks), vec![100.0, 200.0, 300.0, FAR]);
+    assert!(shown(&client.shots).is_empty());
+    assert_eq!(client.net_stats.truncated_snapshots, 1);
+
+    // With room for one more, the first shot gets in before the farthest near rock.
+    server.snapshot.max_actors = 6;
+    server.rocks.truncate(4);
+    NetFromServer::publish(&mut server);
+    let mut client = common::client();
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(shown(&client.rocks), vec![200.0, 300.0, 400.0, FAR]);
+    assert_eq!(shown(&client.shots), vec![-100.0]);
+}
+
+#[test]
+fn far_rocks_take_turns() {
+    let mut server = server();
+    server.rocks = (0..4).map(|i| rock(&server, FAR + 50.0 * i as f32, 0.0)).collect();
+    server.snapshot.max_actors = 2;
+
+    let mut client = common::client();
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        NetFromServer::publish(&mut server);
+        receive(&server, &mut client, SNAPSHOT_VERSION);
+        seen.push(shown(&client.rocks).len());
+    }
+    // One new rock per snapshot, and after a full turn every rock has been sent.
+    assert_eq!(seen, vec![1, 2, 3, 4]);
+    let all: Vec<f32> = (0..4).map(|i| FAR + 50.0 * i as f32).collect();
+    assert_eq!(shown(&client.rocks), all);
+}
+
+#[test]
+fn left_out_rocks_are_kept_not_destroyed() {
+    let mut server = server();
+    server.rocks = vec![rock(&server, 100.0, 0.0), rock(&server, FAR, 0.0), rock(&server, FAR, 300.0)];
+    let mut client = common::client();
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.rocks.len(), 3);
+
+    // Only room for the near rock and one far one.
+    server.snapshot.max_actors = 3;
+    server.rocks[0].pos.x = 120.0;
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.rocks.len(), 3);
+    assert_eq!(shown(&client.rocks), vec![120.0, FAR, FAR]);
+
+    // Without the budget, a rock missing from the snapshot is gone.
+    server.snapshot.max_actors = 0;
+    server.rocks.pop();
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.rocks.len(), 2);
+}
+
+#[test]
+fn left_out_rocks_keep_who_they_are() {
+    let mut server = server();
+    server.rocks = vec![rock(&server, 100.0, 0.0), rock(&server, FAR, 0.0), rock(&server, FAR, 300.0), rock(&server, FAR, 600.0)];
+    let mut client = common::client();
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+
+    // A far rock is destroyed, and only one of the other two fits. The one left out
+    // mustn't take the place, or the look, of the one before it.
+    server.rocks.remove(1);
+    server.snapshot.max_actors = 3;
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.rocks.len(), 3);
+    for rock in &server.rocks {
+        let mine = client.rocks.iter().find(|r| r.id == rock.id).expect("a rock went missing");
+        assert_eq!(mine.pos.y.round(), rock.pos.y.round(), "rock {} moved", rock.id);
+    }
+    let ids: Vec<u32> = client.rocks.iter().map(|r| r.id).collect();
+    assert_eq!(ids, server.rocks.iter().map(|r| r.id).collect::<Vec<_>>());
+}
+
+fn well(state: &MainState, x: f32, reach: f32) -> Actor {
+    let mut well = Actor::create_well(WellPull { reach, ..WellPull::default() }, &state.tuning);
+    well.pos = Vector2::new(x, FAR);
+    well
+}
+
+#[test]
+fn left_out_wells_keep_their_pull() {
+    let mut server = server();
+    server.rocks = vec![rock(&server, 100.0, 0.0)];
+    server.wells = vec![well(&server, 200.0, 500.0)];
+    let mut client = common::client();
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+
+    // A well the client hasn't heard of comes before the one it knows, and only the rock fits.
+    let newer = well(&server, -200.0, 150.0);
+    server.wells.insert(0, newer);
+    server.snapshot.max_actors = 2;
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.wells.len(), 1);
+    assert_eq!(client.wells[0].id, server.wells[1].id);
+    assert_eq!(client.wells[0].pull.reach, 500.0);
+
+    // Once there's room, each well gets its own pull.
+    server.snapshot.max_actors = 0;
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    let pulls = |state: &MainState| -> Vec<(u32, f32)> { state.wells.iter().map(|w| (w.id, w.pull.reach)).collect() };
+    assert_eq!(pulls(&client), pulls(&server));
+}
+
+#[test]
+fn older_clients_get_every_actor() {
+    let mut server = server();
+    server.rocks = (0..6).map(|i| rock(&server, FAR + 10.0 * i as f32, 0.0)).collect();
+    server.snapshot.max_actors = 2;
+    // Version 1 can't leave actors out.
+    let older = MIN_SNAPSHOT_VERSION;
+    server.snapshot.add_reader(older);
+    NetFromServer::publish(&mut server);
+
+    let mut old_client = common::client();
+    receive(&server, &mut old_client, older);
+    assert_eq!(shown(&old_client.rocks).len(), 6);
+    assert_eq!(old_client.net_stats.truncated_snapshots, 0);
+}
+
+#[test]
+fn players_always_go_out() {
+    let mut server = server();
+    server.add_player();
+    server.add_player();
+    server.rocks = vec![rock(&server, 100.0, 0.0)];
+    server.snapshot.max_actors = 1;
+    NetFromServer::publish(&mut server);
+
+    let mut client = common::client();
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.players.len(), 3);
+    assert!(shown(&client.rocks).is_empty());
+}
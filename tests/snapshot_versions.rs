@@ -0,0 +1,102 @@
+//! Version 1, the layout from before snapshots were versioned, is still written for older
+//! clients and decodes with its own frozen types. A client reading it gets what it carries
+//! and keeps its own values for the rest.
+mod common;
+
+use ggez::graphics::Vector2;
+
+use common::receive;
+use rust_blaster::actor::Actor;
+use rust_blaster::codec::{BincodeCodec, Codec};
+use rust_blaster::game_structs::{MainState, DEFAULT_VOLLEY};
+use rust_blaster::net_structs::{NetFromServer, VersionedSnapshot, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION};
+use rust_blaster::patterns::Telegraph;
+use rust_blaster::snapshot_v1::{ActorTypeV1, NetFromServerV1};
+use rust_blaster::well::WellPull;
+
+const VOLLEY: u8 = 5;
+
+/// A server with one player firing a wider volley, two resting rocks, a shot, a gem, a well
+/// and a telegraph, published for both versions.
+fn published() -> MainState {
+    let mut state = common::server(79, 1);
+    state.players[0].volley = VOLLEY;
+    let mut rocks = Vec::new();
+    for &x in &[100.0, 200.0] {
+        let mut rock = Actor::create_rock(&state.tuning);
+        rock.pos = Vector2::new(x, 0.0);
+        rock.velocity = Vector2::new(0.0, 0.0);
+        rocks.push(rock);
+    }
+    state.rocks = rocks;
+    state.shots = vec![Actor::create_shot(&state.tuning)];
+    state.gems = vec![Actor::create_gem(&state.tuning)];
+    state.wells = vec![Actor::create_well(WellPull::default(), &state.tuning)];
+    state.score = 12.75;
+    state.telegraphs = vec![Telegraph { x: -50.0, time_left: 0.5 }];
+    state.snapshot.add_reader(MIN_SNAPSHOT_VERSION);
+    NetFromServer::publish(&mut state);
+    state
+}
+
+fn decode(server: &MainState, version: u32) -> VersionedSnapshot {
+    let bytes = server.snapshot.bytes(version);
+    VersionedSnapshot::decode(version, &mut BincodeCodec, &mut &bytes[..]).expect("Failed to deserialize.")
+}
+
+fn client_of(server: &MainState, version: u32) -> MainState {
+    let mut client = common::client();
+    receive(server, &mut client, version);
+    client
+}
+
+#[test]
+fn version_1_is_the_old_layout() {
+    let server = published();
+    match decode(&server, MIN_SNAPSHOT_VERSION) {
+        VersionedSnapshot::V1(_) => (),
+        other => panic!("version {} decoded as {:?}", MIN_SNAPSHOT_VERSION, other),
+    }
+
+    let bytes = server.snapshot.bytes(MIN_SNAPSHOT_VERSION);
+    let old: NetFromServerV1 = BincodeCodec.decode(&mut &bytes[..]).expect("Failed to deserialize.");
+    assert_eq!(old.players.len(), 1);
+    let tags: Vec<ActorTypeV1> = old.actors.iter().map(|actor| actor.tag).collect();
+    assert_eq!(tags, vec![ActorTypeV1::Rock, ActorTypeV1::Rock, ActorTypeV1::Shot]);
+    assert_eq!(old.actors[1].serial_interm.pos.x, 200.0);
+    assert_eq!(old.score, 12);
+}
+
+#[test]
+fn version_1_clients_keep_their_own_values_for_the_rest() {
+    let server = published();
+    let mut client = common::client();
+    client.world_width = 640.0;
+    client.difficulty_mult = 3.0;
+    receive(&server, &mut client, MIN_SNAPSHOT_VERSION);
+
+    assert_eq!(client.rocks.len(), 2);
+    assert_eq!(client.rocks[1].pos, Vector2::new(200.0, 0.0));
+    assert_eq!(client.shots.len(), 1);
+    assert!(client.gems.is_empty() && client.wells.is_empty() && client.telegraphs.is_empty());
+    assert_eq!(client.score, 12.0);
+    assert_eq!((client.world_width, client.difficulty_mult), (640.0, 3.0));
+    assert_eq!(client.players.len(), 1);
+    assert_eq!(client.players[0].volley, DEFAULT_VOLLEY);
+}
+
+#[test]
+fn the_current_version() {
+    let server = published();
+    match decode(&server, SNAPSHOT_VERSION) {
+        VersionedSnapshot::Current(_) => (),
+        other => panic!("version {} decoded as {:?}", SNAPSHOT_VERSION, other),
+    }
+    let client = client_of(&server, SNAPSHOT_VERSION);
+    assert_eq!(client.players[0].volley, VOLLEY);
+    assert_eq!((client.gems.len(), client.wells.len()), (1, 1));
+    assert_eq!(client.telegraphs, server.telegraphs);
+    let ids = |state: &MainState| -> Vec<u32> { state.rocks.iter().map(|rock| rock.id).collect() };
+    assert_eq!(ids(&client), ids(&server));
+    assert!(ids(&client).iter().all(|&id| id != 0));
+}
@@ -0,0 +1,95 @@
+//! On-screen text comes from a keyed table: every key the code asks for has English text,
+//! and a language file that lacks one shows the key instead of failing.
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::strings::{self, Strings, DEFAULT_STRINGS};
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Contents of every .rs file under `dir`.
+fn sources(dir: &Path, found: &mut Vec<(String, String)>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            sources(&path, found);
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            found.push((path.display().to_string(), fs::read_to_string(&path).unwrap()));
+        }
+    }
+}
+
+/// The quoted text following each occurrence of `start`, up to the closing quote.
+fn literals_after<'a>(source: &'a str, start: &str) -> Vec<&'a str> {
+    source.match_indices(start)
+        .filter_map(|(at, _)| {
+            let rest = &source[at + start.len()..];
+            rest.find('"').map(|end| &rest[..end])
+        })
+        .collect()
+}
+
+#[test]
+fn every_key_used_in_code_has_english_text() {
+    let mut files = Vec::new();
+    sources(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut files);
+    let prefixes: HashSet<&str> = DEFAULT_STRINGS.iter().map(|(key, _)| key.split('.').next().unwrap()).collect();
+
+    let is_key = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_lowercase() || c == '_' || c == '.');
+    let mut used = 0;
+    for (file, source) in &files {
+        let mut keys: Vec<String> = Vec::new();
+        keys.extend(literals_after(source, "tr(\"").into_iter().map(String::from));
+        keys.extend(literals_after(source, "tr_fmt(\"").into_iter().map(String::from));
+        // Keys picked first and looked up later, like the reasons saving is refused.
+        for prefix in &prefixes {
+            let rests = literals_after(source, &format!("\"{}.", prefix));
+            keys.extend(rests.into_iter().filter(|rest| is_key(*rest)).map(|rest| format!("{}.{}", prefix, rest)));
+        }
+        for key in keys {
+            used += 1;
+            assert!(strings::default_text(&key).is_some(), "{} uses {:?}, which has no English text", file, key);
+        }
+    }
+    assert!(used > DEFAULT_STRINGS.len() / 2, "only found {} keys, is the scan broken?", used);
+}
+
+#[test]
+fn keys_are_unique() {
+    let mut seen = HashSet::new();
+    for (key, _) in DEFAULT_STRINGS {
+        assert!(seen.insert(key), "{} is in the table twice", key);
+    }
+}
+
+#[test]
+fn missing_keys_show_their_name() {
+    let english = Strings::default();
+    assert_eq!(english.tr("hud.paused"), "Paused");
+    assert_eq!(english.tr("hud.no_such_text"), "hud.no_such_text");
+
+    let mut table = HashMap::new();
+    table.insert(String::from("hud.paused"), String::from("Pausiert"));
+    let german = Strings::language(table);
+    assert_eq!(german.tr("hud.paused"), "Pausiert");
+    // A language file doesn't fall back to English.
+    assert_eq!(german.tr("hud.debug"), "hud.debug");
+    assert_eq!(german.missing_keys().len(), DEFAULT_STRINGS.len() - 1);
+    assert!(english.missing_keys().is_empty());
+}
+
+#[test]
+fn placeholders_are_filled_in() {
+    let english = Strings::default();
+    assert_eq!(english.tr_fmt("hud.follow.rocks", &[("rocks", &12), ("graze", &3.0)]), "Rocks: 12  Graze: 3");
+    assert_eq!(english.tr_fmt("banner.milestone", &[("points", &50)]), "50 POINTS!");
+    // Missing values stay visible instead of vanishing.
+    assert_eq!(strings::fill("{a} and {b}", &[("a", &1)]), "1 and {b}");
+}
+
+#[test]
+fn lang_picks_a_language() {
+    let args = vec![String::from("--lang"), String::from("de")];
+    assert_eq!(LaunchOptions::parse(&args).lang, Some(String::from("de")));
+    assert_eq!(LaunchOptions::parse(&[]).lang, None);
+}
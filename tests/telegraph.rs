@@ -0,0 +1,92 @@
+//! Pattern rocks are announced at the top edge before they appear, and clients see the
+//! same warnings the server counts down.
+mod common;
+
+use common::{client, receive, run};
+use rust_blaster::game_structs::MainState;
+use rust_blaster::net_structs::{NetFromServer, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION};
+use rust_blaster::patterns::{self, SpawnQueue, Telegraph, MIN_TELEGRAPH_TIME, TELEGRAPH_TIME};
+
+const X: f32 = 123.0;
+
+fn server() -> MainState {
+    common::server(61, 0)
+}
+
+fn rocks_at_x(state: &MainState) -> usize {
+    state.rocks.iter().filter(|rock| (rock.pos.x - X).abs() < 0.01).count()
+}
+
+#[test]
+fn the_warning_shrinks_as_the_run_gets_harder() {
+    assert!((patterns::telegraph_time(0.0) - TELEGRAPH_TIME).abs() < 1e-6);
+    let mut last = TELEGRAPH_TIME;
+    for intensity in &[30.0, 90.0, 300.0, 600.0, 1500.0] {
+        let lead = patterns::telegraph_time(*intensity);
+        assert!(lead < last, "{}s at intensity {} isn't shorter than {}s", lead, intensity, last);
+        last = lead;
+    }
+    assert_eq!(patterns::telegraph_time(100_000.0), MIN_TELEGRAPH_TIME);
+}
+
+#[test]
+fn rocks_wait_for_their_telegraph() {
+    let mut queue = SpawnQueue::default();
+    queue.schedule(X, 0.0, 100.0, 100.0, 0.5);
+    assert!(queue.tick(0.3).is_empty());
+    assert_eq!(queue.telegraphs().len(), 1);
+    assert!((queue.telegraphs()[0].time_left - 0.2).abs() < 1e-5);
+
+    let due = queue.tick(0.25);
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].x, X);
+    assert!(queue.telegraphs().is_empty());
+}
+
+#[test]
+fn the_server_spawns_what_it_announced() {
+    let mut server = server();
+    server.spawn_queue.schedule(X, 0.0, 100.0, 100.0, 0.5);
+    run(&mut server, 0.4);
+    assert_eq!(rocks_at_x(&server), 0);
+    assert_eq!(server.telegraphs.len(), 1);
+    assert_eq!(server.telegraphs[0].x, X);
+
+    run(&mut server, 0.2);
+    assert_eq!(rocks_at_x(&server), 1);
+    assert!(server.telegraphs.is_empty());
+}
+
+#[test]
+fn restarting_drops_the_warnings() {
+    let mut server = server();
+    server.spawn_queue.schedule(X, 0.0, 100.0, 100.0, 0.5);
+    run(&mut server, 0.1);
+    server.hard_reset();
+    run(&mut server, 0.6);
+    assert_eq!(rocks_at_x(&server), 0);
+    assert!(server.telegraphs.is_empty());
+}
+
+#[test]
+fn clients_see_the_servers_telegraphs() {
+    let mut server = server();
+    server.telegraphs = vec![Telegraph { x: X, time_left: 0.4 }];
+    // Version 1 has no telegraphs.
+    let older = MIN_SNAPSHOT_VERSION;
+    server.snapshot.add_reader(older);
+    NetFromServer::publish(&mut server);
+
+    let mut client = client();
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    assert_eq!(client.telegraphs, server.telegraphs);
+    let mut old_client = self::client();
+    receive(&server, &mut old_client, older);
+    assert!(old_client.telegraphs.is_empty());
+
+    // Between snapshots the client counts them down itself and drops them when the rock is due.
+    client.step_client(0.3);
+    assert!((client.telegraphs[0].time_left - 0.1).abs() < 1e-5);
+    client.step_client(0.2);
+    assert!(client.telegraphs.is_empty());
+}
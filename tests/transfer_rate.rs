@@ -0,0 +1,59 @@
+//! The snapshot interval changes while the server runs, and clients hear about it.
+mod common;
+
+use common::receive;
+use rust_blaster::console::{self, ConsoleCommand};
+use rust_blaster::game_structs::{MainState, MAX_TRANSFER_MS, MIN_TRANSFER_MS};
+use rust_blaster::net_structs::{NetFromServer, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION};
+
+use std::sync::atomic::Ordering;
+
+fn server() -> MainState {
+    common::server(31, 0)
+}
+
+#[test]
+fn rate_commands_are_checked() {
+    assert_eq!(console::parse("rate 50"), Ok(ConsoleCommand::Rate(50)));
+    assert_eq!(console::parse("  rate   120 "), Ok(ConsoleCommand::Rate(120)));
+    assert!(console::parse(&format!("rate {}", MIN_TRANSFER_MS - 1)).is_err());
+    assert!(console::parse(&format!("rate {}", MAX_TRANSFER_MS + 1)).is_err());
+    assert!(console::parse("rate fast").is_err());
+    assert!(console::parse("rate").is_err());
+    assert!(console::parse("rate 50 60").is_err());
+    assert!(console::parse("speed 50").is_err());
+}
+
+#[test]
+fn the_rate_is_clamped_and_announced_once() {
+    let mut server = server();
+    assert_eq!(server.set_transfer_ms(5000), MAX_TRANSFER_MS);
+    assert_eq!(server.transfer_ms.load(Ordering::Relaxed), MAX_TRANSFER_MS);
+    let announced = server.event_log.last_seq();
+
+    // The same rate again changes nothing, so nothing is announced.
+    server.set_transfer_ms(MAX_TRANSFER_MS + 10);
+    assert_eq!(server.event_log.last_seq(), announced);
+
+    assert_eq!(server.set_transfer_ms(0), MIN_TRANSFER_MS);
+    assert_eq!(server.event_log.last_seq(), announced + 1);
+}
+
+#[test]
+fn clients_wait_longer_for_slower_snapshots() {
+    let mut server = server();
+    let mut client = common::client();
+    let mut old_client = common::client();
+    let older = MIN_SNAPSHOT_VERSION;
+    server.snapshot.add_reader(older);
+
+    server.set_transfer_ms(400);
+    NetFromServer::publish(&mut server);
+    receive(&server, &mut client, SNAPSHOT_VERSION);
+    receive(&server, &mut old_client, older);
+
+    assert_eq!(client.last_event_seq, server.event_log.last_seq());
+    assert!(client.extrapolation.max_extrapolation_ms >= 1200);
+    // Version 1 has no events, its clients keep waiting as long as they did.
+    assert_eq!(old_client.last_event_seq, 0);
+}
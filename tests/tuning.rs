@@ -0,0 +1,81 @@
+//! Speeds, fire rate and hitboxes come from the tuning, and clients play with the server's.
+mod common;
+
+use rust_blaster::codec::wire_config;
+use rust_blaster::game_structs::{InputState, MainState};
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::NetTuning;
+use rust_blaster::tuning::Tuning;
+
+/// A practice run with a local player holding `input`.
+fn server(tuning: Tuning, input: InputState) -> MainState {
+    let options = LaunchOptions { seed: Some(23), practice: true, ..LaunchOptions::default() };
+    let mut state = common::server_with(&options, 0);
+    state.set_tuning(tuning);
+    state.local_input = input;
+    state
+}
+
+/// Runs without rocks, so nothing gets in the ship's way.
+fn run(state: &mut MainState, seconds: f32) {
+    for _ in 0..(seconds / common::STEP).round() as u32 {
+        state.rocks.clear();
+        common::step(state);
+    }
+}
+
+#[test]
+fn ships_move_at_the_tuned_speed() {
+    let right = InputState { right: true, ..InputState::default() };
+    let mut slow = server(Tuning { player_speed: 200.0, ..Tuning::default() }, right.clone());
+    let mut fast = server(Tuning { player_speed: 400.0, ..Tuning::default() }, right);
+    let start = slow.players[0].actor.pos.x;
+    run(&mut slow, 0.5);
+    run(&mut fast, 0.5);
+
+    let slow_moved = slow.players[0].actor.pos.x - start;
+    let fast_moved = fast.players[0].actor.pos.x - start;
+    assert!((slow_moved - 100.0).abs() < 2.0, "moved {}", slow_moved);
+    assert!((fast_moved - 2.0 * slow_moved).abs() < 0.01);
+}
+
+#[test]
+fn a_longer_cooldown_fires_less() {
+    let fire = InputState { fire: true, ..InputState::default() };
+    let mut quick = server(Tuning::default(), fire.clone());
+    let mut slow = server(Tuning { shot_cooldown: 0.5, ..Tuning::default() }, fire);
+    run(&mut quick, 2.0);
+    run(&mut slow, 2.0);
+
+    let volleys = |state: &MainState| state.players[0].stats.shots_fired;
+    assert!(volleys(&quick) >= 9, "{} volleys", volleys(&quick));
+    assert!(volleys(&slow) >= 3 && volleys(&slow) <= 4, "{} volleys", volleys(&slow));
+}
+
+#[test]
+fn new_hitboxes_apply_to_ships_already_in_the_game() {
+    let mut state = server(Tuning::default(), InputState::default());
+    assert_eq!(state.players[0].actor.bbox_size, Tuning::default().player_bbox);
+    state.set_tuning(Tuning { player_bbox: 30.0, ..Tuning::default() });
+    assert_eq!(state.players[0].actor.bbox_size, 30.0);
+}
+
+#[test]
+fn clients_get_the_servers_tuning() {
+    let tuning = Tuning { player_speed: 650.0, shot_cooldown: 0.35, rock_bbox: 16.0, ..Tuning::default() };
+    let bytes = wire_config().serialize(&NetTuning::make(&tuning)).unwrap();
+    let received: NetTuning = wire_config().deserialize_from(&mut &bytes[..]).unwrap();
+    assert_eq!(received.tuning(), Ok(tuning));
+}
+
+#[test]
+fn fields_the_server_doesnt_know_refuse_the_join() {
+    let mut fields = Tuning::default().to_fields();
+    fields.retain(|(name, _)| name != "shot_cooldown");
+    assert_eq!(NetTuning { fields }.tuning(), Err(vec![String::from("shot_cooldown")]));
+
+    // Fields only the server has are its business.
+    let mut fields = Tuning::default().to_fields();
+    fields.push((String::from("boost_speed"), 900.0));
+    assert_eq!(NetTuning { fields }.tuning(), Ok(Tuning::default()));
+}
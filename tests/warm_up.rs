@@ -0,0 +1,79 @@
+//! A server can warm up while it waits for players, and only the run after that counts.
+mod common;
+
+use common::{run, STEP};
+use rust_blaster::actor::Actor;
+use rust_blaster::game_structs::{MainState, NetRole};
+use rust_blaster::launch_options::LaunchOptions;
+
+fn server(warm_up: bool) -> MainState {
+    let options = LaunchOptions { seed: Some(43), warm_up, ..LaunchOptions::default() };
+    common::server_with(&options, 0)
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn only_a_fresh_server_run_warms_up() {
+    assert!(LaunchOptions::parse(&args(&["--warm-up"])).warm_up);
+    let client = LaunchOptions::parse(&args(&["--warm-up", "c", "localhost"]));
+    assert_eq!(client.role, NetRole::Client);
+    assert!(!client.warm_up);
+    assert!(!LaunchOptions::parse(&args(&["--warm-up", "--daily"])).warm_up);
+    assert!(!LaunchOptions::parse(&args(&["--warm-up", "--record-input", "run.log"])).warm_up);
+}
+
+#[test]
+fn rocks_are_sparse_and_the_clock_stands_still() {
+    let mut warm = server(true);
+    let mut real = server(false);
+    // A game over would restart the run, only the spawning is compared.
+    real.practice = true;
+    run(&mut warm, 4.0);
+    run(&mut real, 4.0);
+
+    assert_eq!(warm.difficulty_points, 0.0);
+    assert!(real.difficulty_points > 0.0);
+    assert!(!warm.rocks.is_empty());
+    assert!(warm.rocks.len() < real.rocks.len(), "{} warm-up rocks, {} in a run", warm.rocks.len(), real.rocks.len());
+}
+
+#[test]
+fn hits_and_gems_dont_count() {
+    let mut state = server(true);
+    let lives = state.players[0].lives;
+    let mut rock = Actor::create_rock(&state.tuning);
+    rock.pos = state.players[0].actor.pos;
+    let mut gem = Actor::create_gem(&state.tuning);
+    gem.pos = state.players[0].actor.pos;
+    gem.score_value = 3;
+    state.rocks = vec![rock];
+    state.gems = vec![gem];
+    run(&mut state, STEP);
+
+    assert_eq!(state.players[0].lives, lives);
+    assert_eq!(state.players[0].deaths, 1);
+    assert_eq!(state.score, 0.0);
+    assert!(state.gems.is_empty());
+}
+
+#[test]
+fn ending_it_clears_the_field_for_the_real_run() {
+    let mut state = server(true);
+    run(&mut state, 3.0);
+    assert!(!state.rocks.is_empty());
+    let round = state.round;
+
+    state.end_warm_up();
+    assert!(!state.warm_up);
+    assert_eq!(state.round, round + 1);
+    assert!(state.rocks.iter().all(|rock| rock.kill));
+
+    run(&mut state, 2.0);
+    assert!(state.difficulty_points > 0.0);
+    // Only once.
+    state.end_warm_up();
+    assert_eq!(state.round, round + 1);
+}
@@ -0,0 +1,65 @@
+//! A player joining mid-run learns the game from the welcome, before any snapshot arrives.
+mod common;
+
+use rust_blaster::codec::{wire_config, WireFormat};
+use rust_blaster::events::RestartReason;
+use rust_blaster::game_structs::MainState;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::{NetPlayerConnected, NetPlayerHello, NetWelcome, SNAPSHOT_VERSION};
+
+/// A large hardcore world a while into its second round, with two remote players.
+fn server() -> MainState {
+    let options = LaunchOptions { seed: Some(17), hardcore: true, world_scale: 3.0, ..LaunchOptions::default() };
+    let mut state = common::server_with(&options, 2);
+    state.players[2].set_ship(2);
+    state.soft_restart(RestartReason::AdminRestart);
+    common::run(&mut state, 2.0);
+    state.difficulty_mult = 2.5;
+    state
+}
+
+/// What a server writes on the input stream after the player's hello.
+fn handshake_bytes(server: &MainState, player_index: usize) -> Vec<u8> {
+    let hello = NetPlayerHello { ship: 0, reconnect_token: 1, wire_format: WireFormat::Bincode, aim_assist: 0.0 };
+    let joined = NetPlayerConnected::make(server, player_index, &hello, WireFormat::Bincode);
+    let mut bytes = wire_config().serialize(&joined).unwrap();
+    bytes.extend(wire_config().serialize(&NetWelcome::make(server, player_index)).unwrap());
+    bytes
+}
+
+#[test]
+fn the_welcome_sets_up_the_game_before_the_first_snapshot() {
+    let server = server();
+    let bytes = handshake_bytes(&server, 2);
+
+    let mut client = common::client();
+    let mut reader = &bytes[..];
+    let joined: NetPlayerConnected = wire_config().deserialize_from(&mut reader).unwrap();
+    let welcome: NetWelcome = wire_config().deserialize_from(&mut reader).unwrap();
+    assert!(reader.is_empty());
+    assert_eq!(welcome.version, SNAPSHOT_VERSION);
+    welcome.update_main_state(&mut client);
+
+    assert_eq!(client.local_player_index, Some(joined.player_index));
+    assert_eq!((client.world_width, client.world_height), (server.world_width, server.world_height));
+    assert_eq!(client.difficulty_mult, 2.5);
+    assert_eq!(client.difficulty_points, server.difficulty_points);
+    assert!(client.hardcore);
+    assert_eq!(client.round, server.round);
+    assert_eq!(client.curr_time, server.curr_time);
+
+    let seats = |state: &MainState| -> Vec<(u32, u8, u8, bool)> {
+        state.players.iter().map(|p| (p.index, p.color_index, p.ship, p.active)).collect()
+    };
+    assert_eq!(seats(&client), seats(&server));
+}
+
+#[test]
+fn the_slot_comes_first() {
+    let server = server();
+    let bytes = handshake_bytes(&server, 1);
+
+    let joined: NetPlayerConnected = wire_config().deserialize_from(&mut &bytes[..]).unwrap();
+    assert_eq!(joined.player_index, 1);
+    assert!(joined.hardcore);
+}
@@ -0,0 +1,52 @@
+//! The window only changes how big things are drawn, the game plays out the same at any size.
+mod common;
+
+use rust_blaster::game_structs::{InputState, MainState, VIEW_SIZE};
+use rust_blaster::launch_options::LaunchOptions;
+
+/// A practice run with a local player, so nothing ends it early.
+fn server(window: (u32, u32)) -> MainState {
+    let options = LaunchOptions { seed: Some(21), practice: true, window, ..LaunchOptions::default() };
+    let mut state = MainState::headless(&options, window.0, window.1);
+    state.local_input = InputState { fire: true, left: true, ..InputState::default() };
+    state
+}
+
+/// Everything that moves, once every simulated second.
+fn trace(mut state: MainState) -> Vec<String> {
+    let mut lines = Vec::new();
+    for step in 0..20 * 144 {
+        common::step(&mut state);
+        if step % 144 == 0 {
+            let rocks: Vec<(f32, f32)> = state.rocks.iter().map(|r| (r.pos.x, r.pos.y)).collect();
+            let ship = state.players[0].actor.pos;
+            lines.push(format!("{} ({}, {}) {} {:?}", state.score, ship.x, ship.y, state.shots.len(), rocks));
+        }
+    }
+    lines
+}
+
+#[test]
+fn the_world_doesnt_follow_the_window() {
+    for &window in &[(VIEW_SIZE, VIEW_SIZE), (640, 360), (200, 1200)] {
+        let state = server(window);
+        assert_eq!((state.world_width, state.world_height), (VIEW_SIZE as f32, VIEW_SIZE as f32));
+    }
+}
+
+#[test]
+fn small_wide_windows_play_the_same_game() {
+    let square = trace(server((VIEW_SIZE, VIEW_SIZE)));
+    let wide = trace(server((640, 360)));
+    assert!(square.iter().any(|line| !line.ends_with("[]")), "no rocks ever spawned");
+    assert_eq!(square, wide);
+}
+
+#[test]
+fn window_sizes_parse() {
+    let args: Vec<String> = vec!["--window".into(), "640x360".into()];
+    assert_eq!(LaunchOptions::parse(&args).window, (640, 360));
+
+    let args: Vec<String> = vec!["--window".into(), "0x360".into()];
+    assert_eq!(LaunchOptions::parse(&args).window, (VIEW_SIZE, VIEW_SIZE));
+}
@@ -0,0 +1,237 @@
+//! The exact bytes a snapshot goes over the wire as. If this fails, the layout changed:
+//! either it was an accident, or it needs a new `SNAPSHOT_VERSION` and new bytes here.
+//! Messages also come back from both wire formats as they went in, and a cut off one is
+//! an error rather than a wrong message.
+use ggez::graphics::Vector2;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use rust_blaster::actor::{Actor, Vec2Serial};
+use rust_blaster::codec::{BincodeCodec, Codec, DecodeError, JsonCodec, MAX_MESSAGE_BYTES};
+use rust_blaster::game_structs::{DebugCommand, InputState, MainState};
+use rust_blaster::input_timeline::HeldInput;
+use rust_blaster::launch_options::LaunchOptions;
+use rust_blaster::net_structs::{NetClientInput, NetFromServer, SNAPSHOT_VERSION};
+use rust_blaster::patterns::Telegraph;
+use rust_blaster::tuning::Tuning;
+
+/// A server without players, a single rock and a single telegraph, every field set to a known value.
+fn canonical_state() -> MainState {
+    let options = LaunchOptions { seed: Some(1), local_play: false, ..LaunchOptions::default() };
+    let mut state = MainState::headless(&options, 1000, 800);
+
+    let mut rock = Actor::create_rock(&state.tuning);
+    rock.facing = 0.5;
+    rock.pos = Vector2::new(100.0, -200.0);
+    rock.velocity = Vector2::new(0.0, -150.0);
+    rock.score_value = 3;
+    rock.variant = 4;
+    state.rocks = vec![rock];
+
+    state.score = 12.0;
+    state.curr_time = 3.5;
+    state.world_width = 1000.0;
+    state.world_height = 800.0;
+    state.difficulty_mult = 1.0;
+    state.rock_time_scale = 1.0;
+    state.time_slow_left = 0.0;
+    state.connections = 0;
+    state.difficulty_points = 7.0;
+    state.telegraphs = vec![Telegraph { x: -250.0, time_left: 0.5 }];
+    state
+}
+
+const EMPTY: [u8; 8] = [0; 8];
+const ONE: [u8; 8] = [1, 0, 0, 0, 0, 0, 0, 0];
+
+fn expected() -> Vec<u8> {
+    let fields: &[&[u8]] = &[
+        &EMPTY, // players
+        &ONE, // actors
+        &[1, 0, 0, 0], // tag: Rock
+        &[0x00, 0x00, 0x00, 0x3f], // facing: 0.5
+        &[0x00, 0x00, 0xc8, 0x42], // pos.x: 100
+        &[0x00, 0x00, 0x48, 0xc3], // pos.y: -200
+        &[0x00, 0x00, 0x00, 0x00], // vel.x: 0
+        &[0x00, 0x00, 0x16, 0xc3], // vel.y: -150
+        &[0], // owner: none
+        &[0x00, 0x00, 0x00, 0x00], // fade: 0
+        &[3, 0, 0, 0], // score_value
+        &[4], // variant
+        &[0x00, 0x00, 0x70, 0x42], // pull.core: 60
+        &[0x00, 0x00, 0xaf, 0x43], // pull.reach: 350
+        &[0x00, 0x40, 0x1c, 0x45], // pull.shot_accel: 2500
+        &[0x00, 0x00, 0x82, 0x43], // pull.player_drift: 260
+        &[1, 0, 0, 0], // id: the first one issued
+        &[0x00, 0x00, 0x40, 0x41], // score: 12
+        &[0x00, 0x00, 0x60, 0x40], // server_time: 3.5
+        &EMPTY, // events
+        &[0x00, 0x00, 0x7a, 0x44], // world_width: 1000
+        &[0x00, 0x00, 0x48, 0x44], // world_height: 800
+        &[0x00, 0x00, 0x80, 0x3f], // difficulty_mult: 1
+        &[0x00, 0x00, 0x80, 0x3f], // rock_time_scale: 1
+        &[0x00, 0x00, 0x00, 0x00], // time_slow_left: 0
+        &[0, 0, 0, 0], // connections
+        &[0, 0, 0, 0], // spectators
+        &[0x00, 0x00, 0xe0, 0x40], // difficulty_points: 7
+        &[0], // truncated: no
+        &ONE, // telegraphs
+        &[0x00, 0x00, 0x7a, 0xc3], // x: -250
+        &[0x00, 0x00, 0x00, 0x3f], // time_left: 0.5
+        &EMPTY, // live_ids
+    ];
+    fields.concat()
+}
+
+#[test]
+fn snapshot_bytes_are_pinned() {
+    assert_eq!(SNAPSHOT_VERSION, 2, "new snapshot version, update the expected bytes for it");
+
+    let mut state = canonical_state();
+    NetFromServer::publish(&mut state);
+    assert_eq!(&state.snapshot.bytes(SNAPSHOT_VERSION)[..], &expected()[..]);
+}
+
+#[test]
+fn a_huge_length_prefix_is_refused() {
+    // A string claiming to be just over the limit, without the bytes to back it.
+    let mut bytes = (MAX_MESSAGE_BYTES + 1).to_le_bytes().to_vec();
+    bytes.extend_from_slice(&[b'a'; 16]);
+    match BincodeCodec.decode::<String, _>(&mut &bytes[..]) {
+        Err(DecodeError::Malformed(_)) => (),
+        other => panic!("expected the limit to refuse it, got {:?}", other),
+    }
+}
+
+#[test]
+fn oversized_messages_are_not_sent() {
+    let mut buf = vec![7];
+    BincodeCodec.encode(&vec![0u8; MAX_MESSAGE_BYTES as usize + 1], &mut buf);
+    assert_eq!(buf, vec![7]);
+}
+
+fn bincode_round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    let mut buf = Vec::new();
+    BincodeCodec.encode(value, &mut buf);
+    BincodeCodec.decode(&mut &buf[..]).expect("Failed to deserialize.")
+}
+
+fn json_round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    let mut buf = Vec::new();
+    JsonCodec::default().encode(value, &mut buf);
+    JsonCodec::default().decode(&mut &buf[..]).expect("Failed to deserialize.")
+}
+
+/// An input carrying `shots` shots and a held key for each of `seconds`.
+fn input(shots: Vec<Actor>, seconds: &[f32]) -> NetClientInput {
+    NetClientInput {
+        seq: std::u64::MAX,
+        input_state: InputState { fire: true, left: true, ..InputState::default() },
+        final_position: Vec2Serial::from_floats(0.0, 0.0),
+        shots_made: shots,
+        debug_commands: Vec::new(),
+        acked_event_seq: 0,
+        timeline: seconds.iter().map(|&seconds| HeldInput { input: InputState::default(), seconds }).collect(),
+        client_time: 0.0,
+    }
+}
+
+#[test]
+fn extreme_floats_come_back_exactly() {
+    use std::f32;
+    let floats = [f32::MAX, f32::MIN, f32::MIN_POSITIVE, f32::EPSILON, 1e-45, -0.0, 0.1];
+    let mut sent = input(Vec::new(), &floats);
+    sent.final_position = Vec2Serial::from_floats(f32::MAX, -f32::MIN_POSITIVE);
+    sent.debug_commands = vec![DebugCommand::ShiftClock(f32::MIN), DebugCommand::SpawnRock(Vec2Serial::from_floats(1e-45, -1e38))];
+    sent.client_time = 86_400.25;
+
+    for received in vec![bincode_round_trip(&sent), json_round_trip(&sent)] {
+        assert_eq!(received, sent);
+        let bits = |input: &NetClientInput| -> Vec<u32> { input.timeline.iter().map(|held| held.seconds.to_bits()).collect() };
+        // `==` can't tell -0 from 0.
+        assert_eq!(bits(&received), bits(&sent));
+    }
+}
+
+#[test]
+fn infinities_only_fit_in_bincode() {
+    let sent = input(Vec::new(), &[std::f32::INFINITY, std::f32::NEG_INFINITY]);
+    assert_eq!(bincode_round_trip(&sent), sent);
+    let nan = bincode_round_trip(&input(Vec::new(), &[std::f32::NAN]));
+    assert!(nan.timeline[0].seconds.is_nan());
+
+    // JSON writes them as null, which is refused instead of read as some number.
+    let mut buf = Vec::new();
+    JsonCodec::default().encode(&sent, &mut buf);
+    match JsonCodec::default().decode::<NetClientInput, _>(&mut &buf[..]) {
+        Err(DecodeError::Malformed(_)) => (),
+        other => panic!("expected an error, got {:?}", other),
+    }
+}
+
+#[test]
+fn empty_and_large_lists_come_back_whole() {
+    let tuning = Tuning::default();
+    let shots: Vec<Actor> = (0..5000).map(|i| {
+        let mut shot = Actor::create_shot(&tuning);
+        shot.facing = i as f32 * 0.001;
+        shot
+    }).collect();
+    let seconds: Vec<f32> = (0..5000).map(|i| i as f32 / 144.0).collect();
+
+    for sent in vec![input(Vec::new(), &[]), input(shots, &seconds)] {
+        assert_eq!(bincode_round_trip(&sent), sent);
+        assert_eq!(json_round_trip(&sent), sent);
+    }
+}
+
+#[test]
+fn actors_land_where_they_were_sent() {
+    let tuning = Tuning::default();
+    let mut shot = Actor::create_shot(&tuning);
+    shot.pos = Vector2::new(-1234.5, std::f32::MAX);
+    shot.velocity = Vector2::new(std::f32::MIN_POSITIVE, -800.25);
+    shot.facing = -std::f32::consts::PI;
+    shot.pre_serialize();
+    let sent = input(vec![shot.clone()], &[]);
+
+    for received in vec![bincode_round_trip(&sent), json_round_trip(&sent)] {
+        let mut landed = received.shots_made.into_iter().next().unwrap();
+        landed.post_deserialize(&tuning);
+        assert_eq!(landed.tag, shot.tag);
+        assert_eq!(landed.pos, shot.pos);
+        assert_eq!(landed.velocity, shot.velocity);
+        assert_eq!(landed.facing, shot.facing);
+        assert_eq!(landed.bbox_size, shot.bbox_size);
+    }
+}
+
+#[test]
+fn cut_off_messages_are_errors() {
+    let mut shot = Actor::create_shot(&Tuning::default());
+    shot.pre_serialize();
+    let sent = input(vec![shot], &[0.5, 0.25]);
+
+    let mut bytes = Vec::new();
+    BincodeCodec.encode(&sent, &mut bytes);
+    for cut in 0..bytes.len() {
+        let result = BincodeCodec.decode::<NetClientInput, _>(&mut &bytes[..cut]);
+        assert!(result.is_err(), "{} of {} bytes decoded", cut, bytes.len());
+    }
+
+    let mut json = Vec::new();
+    JsonCodec::default().encode(&sent, &mut json);
+    // The last byte is the newline.
+    for cut in 0..json.len() - 1 {
+        let result = JsonCodec::default().decode::<NetClientInput, _>(&mut &json[..cut]);
+        assert!(result.is_err(), "{} of {} bytes decoded", cut, json.len());
+
+        // A line that ends early is malformed, not waited on.
+        let mut line = json[..cut].to_vec();
+        line.push(b'\n');
+        match JsonCodec::default().decode::<NetClientInput, _>(&mut &line[..]) {
+            Err(DecodeError::Malformed(_)) => (),
+            other => panic!("{} of {} bytes: expected malformed, got {:?}", cut, json.len(), other),
+        }
+    }
+}